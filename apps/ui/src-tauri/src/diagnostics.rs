@@ -0,0 +1,9 @@
+use crate::engine_call;
+use bridge::{DhtStats, ErrorKind, RpcCommand, RpcError};
+
+// Advanced diagnostics: DHT routing table and lookup stats.
+#[tauri::command]
+pub async fn get_dht_stats() -> Result<DhtStats, RpcError> {
+    let result = engine_call(RpcCommand::GetDhtStats).await?;
+    serde_json::from_value(result).map_err(|e| RpcError::new(ErrorKind::Internal, e.to_string()))
+}
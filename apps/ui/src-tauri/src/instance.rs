@@ -0,0 +1,110 @@
+use bridge::{AddPreview, HANDOFF_PORT};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+// The running instance greets each connection with this line, so a second
+// one can tell it apart from some other program holding the port.
+const GREETING: &str = "AuroraTorrent handoff 1";
+// How long either side waits on the other
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Parsed magnet links / .torrent files handed to us on the command line or
+// by a second instance, waiting for the UI's add dialog to pick them up.
+#[derive(Default)]
+pub struct PendingAdds(pub Mutex<Vec<AddPreview>>);
+
+// Binding the handoff port doubles as the single-instance lock: it is
+// released by the OS when the process exits, so a crash can't leave a stale
+// lock behind like a lock file would.
+pub fn acquire() -> Option<TcpListener> {
+    TcpListener::bind(("127.0.0.1", HANDOFF_PORT)).ok()
+}
+
+// Sends our arguments to the running instance, one per line. False if
+// whatever holds the port didn't greet us like an instance would.
+pub fn forward(args: &[String]) -> std::io::Result<bool> {
+    let mut stream = TcpStream::connect_timeout(&(std::net::Ipv4Addr::LOCALHOST, HANDOFF_PORT).into(), TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut greeting = String::new();
+    let greeted = BufReader::new(&stream).take(GREETING.len() as u64 + 2).read_line(&mut greeting).is_ok();
+    if !greeted || greeting.trim_end() != GREETING {
+        return Ok(false);
+    }
+    for arg in args {
+        writeln!(stream, "{}", arg)?;
+    }
+    stream.flush()?;
+    Ok(true)
+}
+
+// Only things that look like something we can add are forwarded; the OS may
+// pass its own flags (e.g. -psn_ on older macOS).
+pub fn external_args() -> Vec<String> {
+    std::env::args()
+        .skip(1)
+        .filter(|a| a.starts_with("magnet:") || a.to_lowercase().ends_with(".torrent"))
+        .collect()
+}
+
+// Each connection gets its own thread and a read timeout, so a client that
+// connects and sends nothing can't hold up the next instance's handoff.
+pub fn listen(listener: TcpListener, app: AppHandle) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let app = app.clone();
+            std::thread::spawn(move || receive(stream, app));
+        }
+    });
+}
+
+fn receive(mut stream: TcpStream, app: AppHandle) {
+    let greeted = stream
+        .set_read_timeout(Some(TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+        .and_then(|_| writeln!(stream, "{}", GREETING));
+    if greeted.is_err() {
+        return;
+    }
+    let lines: Vec<String> = BufReader::new(stream)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    raise(&app);
+    for arg in lines {
+        deliver(&app, arg);
+    }
+}
+
+pub fn raise(app: &AppHandle) {
+    if let Some(window) = app.get_window("main") {
+        window.show().ok(); // may be hidden in the tray
+        window.unminimize().ok();
+        window.set_focus().ok();
+    }
+}
+
+pub fn deliver(app: &AppHandle, arg: String) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match aurora_engine::handle_external_add(&arg).await {
+            Ok(preview) => {
+                app.state::<PendingAdds>().0.lock().unwrap().push(preview.clone());
+                app.emit_all("external-add", preview).ok();
+            }
+            Err(e) => {
+                app.emit_all("external-add-error", format!("{}: {}", arg, e)).ok();
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn take_external_adds(pending: tauri::State<'_, PendingAdds>) -> Vec<AddPreview> {
+    std::mem::take(&mut *pending.0.lock().unwrap())
+}
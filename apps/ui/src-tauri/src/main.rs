@@ -1,56 +1,183 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::Manager;
-use engine;
-use bridge::{RpcRequest, RpcResponse, PORT};
+use tauri::{Manager, RunEvent, WindowEvent};
+use aurora_engine;
+use bridge::{ErrorKind, RpcCommand, RpcError, RpcRequest, RpcResponse, PORT};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
 use tokio::time::{timeout, Duration};
 
+mod diagnostics;
+mod instance;
+mod maintenance;
+mod power;
+mod settings;
+mod shell;
+mod tray;
+use instance::PendingAdds;
+
+// How long each step of a call may take, unless the caller says otherwise
+const RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Failures reach the UI as the engine's {kind, message} error object;
+// transport problems are reported as Unavailable.
 #[tauri::command]
-async fn rpc_request(request: String) -> Result<String, String> {
-    let timeout_duration = Duration::from_secs(5);
+async fn rpc_request(request: String) -> Result<String, RpcError> {
+    request_within(request, RPC_TIMEOUT).await
+}
+
+async fn request_within(request: String, timeout_duration: Duration) -> Result<String, RpcError> {
+    let unavailable = |e: std::io::Error| RpcError::new(ErrorKind::Unavailable, e.to_string());
+    let timed_out = |what: &str| RpcError::new(ErrorKind::Unavailable, format!("{} timed out", what));
 
     // Connect to the engine via TCP with timeout
     let mut stream = timeout(timeout_duration, TcpStream::connect(format!("127.0.0.1:{}", PORT)))
         .await
-        .map_err(|_| "Connection timed out".to_string())?
-        .map_err(|e| e.to_string())?;
+        .map_err(|_| timed_out("Connection"))?
+        .map_err(unavailable)?;
 
     // Write request with timeout
     timeout(timeout_duration, stream.write_all(request.as_bytes()))
         .await
-        .map_err(|_| "Write timed out".to_string())?
-        .map_err(|e| e.to_string())?;
+        .map_err(|_| timed_out("Write"))?
+        .map_err(unavailable)?;
 
     // Signal EOF to server so it processes and then closes the connection
-    stream.shutdown().await.map_err(|e| e.to_string())?;
+    stream.shutdown().await.map_err(unavailable)?;
 
     // Read response until EOF with timeout
     let mut buf = Vec::new();
     timeout(timeout_duration, stream.read_to_end(&mut buf))
         .await
-        .map_err(|_| "Read timed out".to_string())?
-        .map_err(|e| e.to_string())?;
+        .map_err(|_| timed_out("Read"))?
+        .map_err(unavailable)?;
 
     let response = String::from_utf8_lossy(&buf).to_string();
     Ok(response)
 }
 
+// Calls the engine from Rust code in the app, as opposed to the UI's raw
+// JSON requests.
+pub async fn engine_call(command: RpcCommand) -> Result<serde_json::Value, RpcError> {
+    engine_call_within(command, RPC_TIMEOUT).await
+}
+
+// For calls that take longer than most, such as Shutdown.
+pub async fn engine_call_within(command: RpcCommand, limit: Duration) -> Result<serde_json::Value, RpcError> {
+    let internal = |e: serde_json::Error| RpcError::new(ErrorKind::Internal, e.to_string());
+    let request = RpcRequest { jsonrpc: "2.0".into(), id: 0, command };
+    let request = serde_json::to_string(&request).map_err(internal)?;
+    let response = request_within(request, limit).await?;
+    let response: RpcResponse<serde_json::Value> = serde_json::from_str(&response).map_err(internal)?;
+    match (response.result, response.error) {
+        (_, Some(error)) => Err(error),
+        (Some(result), None) => Ok(result),
+        (None, None) => Err(RpcError::new(ErrorKind::Unavailable, "Empty response from engine")),
+    }
+}
+
 fn main() {
+    #[cfg(target_os = "macos")]
+    tauri_plugin_deep_link::prepare("com.auroratorrent.app");
+    let external = instance::external_args();
+
+    // Another instance already owns the engine and its ports: hand our
+    // magnet/.torrent over to it and get out of the way. Some other program
+    // on the handoff port just means going without the handoff.
+    let listener = match instance::acquire() {
+        Some(listener) => Some(listener),
+        None => match instance::forward(&external) {
+            Ok(true) => return,
+            Ok(false) => {
+                let port = bridge::HANDOFF_PORT;
+                eprintln!("Port {} is held by another program; running without the single-instance handoff", port);
+                None
+            }
+            Err(e) => {
+                eprintln!("Failed to reach running instance: {}", e);
+                return;
+            }
+        },
+    };
+
     tauri::Builder::default()
-        .setup(|app| {
+        .manage(PendingAdds::default())
+        .system_tray(tray::tray())
+        .on_system_tray_event(tray::on_event)
+        .setup(move |app| {
+            app.manage(settings::Settings::load(&app.handle()));
+            if let Some(listener) = listener {
+                instance::listen(listener, app.handle());
+            }
+            for arg in external {
+                instance::deliver(&app.handle(), arg);
+            }
+            // macOS hands the magnet links Info.plist registers us for to
+            // the app as Apple Events, not as arguments
+            #[cfg(target_os = "macos")]
+            {
+                let handle = app.handle();
+                let registered = tauri_plugin_deep_link::register("magnet", move |url| {
+                    instance::raise(&handle);
+                    instance::deliver(&handle, url);
+                });
+                if let Err(e) = registered {
+                    eprintln!("Failed to handle magnet links: {}", e);
+                }
+            }
+
             // Spawn the engine in a separate thread
             tauri::async_runtime::spawn(async {
-                if let Err(e) = engine::run().await {
+                if let Err(e) = aurora_engine::run().await {
                     eprintln!("Engine error: {}", e);
                 }
             });
+            power::watch_sleep();
+            tray::spawn_refresh(app.handle());
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![rpc_request])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        // Closing the window quits unless it goes to the tray; let the
+        // engine save and stop first
+        .on_window_event(|event| {
+            let window = event.window();
+            let settings = settings::current(&window.app_handle());
+            match event.event() {
+                WindowEvent::CloseRequested { api, .. } if settings.close_to_tray => {
+                    api.prevent_close();
+                    window.hide().ok();
+                }
+                WindowEvent::CloseRequested { api, .. } if !power::engine_stopped() => {
+                    api.prevent_close();
+                    power::shutdown_then_exit(window.app_handle());
+                }
+                WindowEvent::Resized(_) if settings.minimize_to_tray && window.is_minimized().unwrap_or(false) => {
+                    window.hide().ok();
+                }
+                _ => {}
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            rpc_request,
+            instance::take_external_adds,
+            shell::open_torrent_file,
+            shell::reveal_torrent_file,
+            diagnostics::get_dht_stats,
+            maintenance::reannounce_all,
+            maintenance::scrape_all,
+            settings::get_app_settings,
+            settings::set_app_settings,
+            tray::get_speed_summary
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::ExitRequested { api, .. } = event {
+                if !power::engine_stopped() {
+                    api.prevent_exit();
+                    power::shutdown_then_exit(app.clone());
+                }
+            }
+        });
 }
@@ -0,0 +1,24 @@
+use crate::engine_call;
+use bridge::{ErrorKind, RpcCommand, RpcError};
+
+// Troubleshooting commands for when swarms went quiet, e.g. after a network
+// change. Both run in the engine's background and return how many torrents
+// were queued; `category` narrows them to one category.
+
+#[tauri::command]
+pub async fn reannounce_all(category: Option<String>) -> Result<usize, RpcError> {
+    queued(RpcCommand::ReannounceAll { category }).await
+}
+
+#[tauri::command]
+pub async fn scrape_all(category: Option<String>) -> Result<usize, RpcError> {
+    queued(RpcCommand::ScrapeAll { category }).await
+}
+
+async fn queued(command: RpcCommand) -> Result<usize, RpcError> {
+    let result = engine_call(command).await?;
+    result["queued"]
+        .as_u64()
+        .map(|n| n as usize)
+        .ok_or_else(|| RpcError::new(ErrorKind::Internal, "Engine reply has no queued count"))
+}
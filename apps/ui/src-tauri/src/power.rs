@@ -0,0 +1,57 @@
+use crate::{engine_call, engine_call_within};
+use bridge::RpcCommand;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tauri::AppHandle;
+
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+// Wall clock running this far ahead of our sleep means the machine slept
+const SLEEP_GAP: Duration = Duration::from_secs(30);
+// Shutting down writes out buffered blocks and stops every torrent, which
+// on a slow disk takes far longer than an ordinary call
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(120);
+
+static STOPPING: AtomicBool = AtomicBool::new(false);
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+// Whether exiting can go ahead: only once the engine has shut down.
+pub fn engine_stopped() -> bool {
+    STOPPED.load(Ordering::SeqCst)
+}
+
+// Shuts the engine down (state saved, buffered blocks written, torrents
+// stopped) and then exits. A second call while that runs does nothing.
+pub fn shutdown_then_exit(app: AppHandle) {
+    if STOPPING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = engine_call_within(RpcCommand::Shutdown, SHUTDOWN_TIMEOUT).await {
+            eprintln!("Engine shutdown failed: {}", e);
+        }
+        STOPPED.store(true, Ordering::SeqCst);
+        app.exit(0);
+    });
+}
+
+// Tauri has no suspend/resume events, so sleep is noticed afterwards: this
+// thread's sleeps stop while the machine is suspended, and the wall clock
+// doesn't. The engine then re-announces so swarms pick up again.
+pub fn watch_sleep() {
+    std::thread::spawn(|| {
+        let mut last = SystemTime::now();
+        loop {
+            std::thread::sleep(CHECK_EVERY);
+            let now = SystemTime::now();
+            let elapsed = now.duration_since(last).unwrap_or_default();
+            last = now;
+            if elapsed > CHECK_EVERY + SLEEP_GAP {
+                tauri::async_runtime::spawn(async {
+                    if let Err(e) = engine_call(RpcCommand::ResumedFromSleep).await {
+                        eprintln!("Failed to tell the engine about waking up: {}", e);
+                    }
+                });
+            }
+        }
+    });
+}
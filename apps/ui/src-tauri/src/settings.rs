@@ -0,0 +1,63 @@
+use bridge::{ErrorKind, RpcError};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+const FILE: &str = "settings.json";
+
+// Window and tray behavior. These belong to the app rather than the
+// engine, so they live in the app's config dir instead of the engine
+// config.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AppSettings {
+    pub minimize_to_tray: bool,
+    pub close_to_tray: bool, // closing the window hides it; quit from the tray
+    pub speed_in_title: bool,
+}
+
+pub struct Settings {
+    path: Option<PathBuf>,
+    current: Mutex<AppSettings>,
+}
+
+impl Settings {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = app.path_resolver().app_config_dir().map(|dir| dir.join(FILE));
+        let current = path
+            .as_ref()
+            .and_then(|p| std::fs::read(p).ok())
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self { path, current: Mutex::new(current) }
+    }
+
+    pub fn get(&self) -> AppSettings {
+        self.current.lock().unwrap().clone()
+    }
+}
+
+// The current settings, or the defaults before setup has loaded them
+pub fn current(app: &AppHandle) -> AppSettings {
+    app.try_state::<Settings>().map(|s| s.get()).unwrap_or_default()
+}
+
+#[tauri::command]
+pub fn get_app_settings(settings: tauri::State<'_, Settings>) -> AppSettings {
+    settings.get()
+}
+
+#[tauri::command]
+pub fn set_app_settings(new: AppSettings, settings: tauri::State<'_, Settings>) -> Result<(), RpcError> {
+    let io = |e: std::io::Error| RpcError::new(ErrorKind::Io, e.to_string());
+    if let Some(path) = &settings.path {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(io)?;
+        }
+        let bytes = serde_json::to_vec_pretty(&new).map_err(|e| RpcError::new(ErrorKind::Internal, e.to_string()))?;
+        std::fs::write(path, bytes).map_err(io)?;
+    }
+    *settings.current.lock().unwrap() = new;
+    Ok(())
+}
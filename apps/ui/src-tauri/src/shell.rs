@@ -0,0 +1,86 @@
+use crate::engine_call;
+use bridge::{ErrorKind, RpcCommand, RpcError};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+async fn file_path(id: String, file_index: usize) -> Result<PathBuf, RpcError> {
+    let result = engine_call(RpcCommand::GetFilePath { id, file_index }).await?;
+    result["path"]
+        .as_str()
+        .map(PathBuf::from)
+        .ok_or_else(|| RpcError::new(ErrorKind::Internal, "Engine returned no path"))
+}
+
+// Opens a torrent's file with the default application for its type.
+#[tauri::command]
+pub async fn open_torrent_file(id: String, file_index: usize) -> Result<(), RpcError> {
+    let path = file_path(id, file_index).await?;
+    if !path.exists() {
+        return Err(RpcError::new(
+            ErrorKind::NotFound,
+            format!("{} has not been downloaded yet", path.display()),
+        ));
+    }
+    spawn(open_command(&path))
+}
+
+// Shows the file selected in the system file manager. Falls back to the
+// closest existing folder while the file hasn't been created yet.
+#[tauri::command]
+pub async fn reveal_torrent_file(id: String, file_index: usize) -> Result<(), RpcError> {
+    let path = file_path(id, file_index).await?;
+    if path.exists() {
+        return spawn(reveal_command(&path));
+    }
+    let folder = path
+        .ancestors()
+        .find(|p| p.is_dir())
+        .ok_or_else(|| RpcError::new(ErrorKind::NotFound, format!("No folder exists for {}", path.display())))?;
+    spawn(open_command(folder))
+}
+
+fn spawn(mut command: Command) -> Result<(), RpcError> {
+    command.spawn().map(|_| ()).map_err(|e| RpcError::new(ErrorKind::Io, e.to_string()))
+}
+
+#[cfg(target_os = "windows")]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(path);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg(path);
+    command
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn open_command(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(path);
+    command
+}
+
+#[cfg(target_os = "windows")]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(format!("/select,{}", path.display()));
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path);
+    command
+}
+
+// There's no portable "select this file" on Linux file managers, so open
+// the containing folder.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn reveal_command(path: &Path) -> Command {
+    open_command(path.parent().unwrap_or(path))
+}
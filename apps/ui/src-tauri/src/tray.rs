@@ -0,0 +1,153 @@
+use crate::{engine_call, power, settings};
+use bridge::{ErrorKind, RpcCommand, RpcError, SessionSummary, SpeedSummary};
+use tauri::{
+    AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem,
+    SystemTraySubmenu,
+};
+use tokio::time::{sleep, Duration};
+
+const TITLE: &str = "AuroraTorrent";
+const REFRESH: Duration = Duration::from_secs(2);
+const RECENT: usize = 5;
+
+pub fn tray() -> SystemTray {
+    SystemTray::new().with_menu(menu(None)).with_tooltip(TITLE)
+}
+
+// Menu item ids say what a click does, so the handler needs no state
+fn menu(summary: Option<&SessionSummary>) -> SystemTrayMenu {
+    let speeds = summary.map_or_else(|| "Engine starting...".to_string(), speed_line);
+    let (pause_id, pause_title) = match summary {
+        Some(s) if s.session_paused => ("resume-all", "Resume all"),
+        _ => ("pause-all", "Pause all"),
+    };
+    let alt_on = summary.is_some_and(|s| s.alt_speed);
+    let mut alt = CustomMenuItem::new(if alt_on { "alt-speed-off" } else { "alt-speed-on" }, "Alternative speed limits");
+    alt.selected = alt_on;
+
+    let mut recent = SystemTrayMenu::new();
+    let torrents = summary.map(|s| s.recent.as_slice()).unwrap_or_default();
+    for torrent in torrents {
+        let title = format!("{} ({:.0}%)", torrent.name, torrent.progress * 100.0);
+        recent = recent.add_item(CustomMenuItem::new(format!("open:{}", torrent.id), title));
+    }
+    if torrents.is_empty() {
+        recent = recent.add_item(CustomMenuItem::new("no-recent", "No torrents").disabled());
+    }
+
+    SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new("speeds", speeds).disabled())
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new("show", format!("Show {}", TITLE)))
+        .add_item(CustomMenuItem::new(pause_id, pause_title))
+        .add_item(alt)
+        .add_submenu(SystemTraySubmenu::new("Recent torrents", recent))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new("quit", "Quit"))
+}
+
+pub fn on_event(app: &AppHandle, event: SystemTrayEvent) {
+    match event {
+        SystemTrayEvent::LeftClick { .. } => show(app),
+        SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
+            "show" => show(app),
+            "quit" => power::shutdown_then_exit(app.clone()),
+            "pause-all" => command(RpcCommand::PauseSession),
+            "resume-all" => command(RpcCommand::ResumeSession),
+            "alt-speed-on" => command(RpcCommand::SetAltSpeed { enabled: true }),
+            "alt-speed-off" => command(RpcCommand::SetAltSpeed { enabled: false }),
+            id => {
+                if let Some(torrent) = id.strip_prefix("open:") {
+                    show(app);
+                    app.emit_all("open-torrent", torrent.to_string()).ok();
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+pub fn show(app: &AppHandle) {
+    if let Some(window) = app.get_window("main") {
+        window.show().ok();
+        window.unminimize().ok();
+        window.set_focus().ok();
+    }
+}
+
+// The next refresh shows the result
+fn command(command: RpcCommand) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = engine_call(command).await {
+            eprintln!("Tray action failed: {}", e);
+        }
+    });
+}
+
+// Session rates and torrent counts for the title bar; cheap to poll often
+#[tauri::command]
+pub async fn get_speed_summary() -> Result<SpeedSummary, RpcError> {
+    let result = engine_call(RpcCommand::GetSpeedSummary).await?;
+    serde_json::from_value(result).map_err(|e| RpcError::new(ErrorKind::Internal, e.to_string()))
+}
+
+// Keeps the tray's speeds, menu and (with `speed_in_title`) the window
+// title current. The menu is only rebuilt when something in it other than
+// the speeds changed.
+pub fn spawn_refresh(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut shown_menu = None;
+        let mut shown_title = TITLE.to_string();
+        loop {
+            sleep(REFRESH).await;
+            let Ok(result) = engine_call(RpcCommand::GetSessionSummary { recent: RECENT }).await else {
+                continue;
+            };
+            let Ok(summary) = serde_json::from_value::<SessionSummary>(result) else { continue };
+            let speeds = speed_line(&summary);
+
+            let tray = app.tray_handle();
+            let menu_key = menu_key(&summary);
+            if shown_menu.as_ref() != Some(&menu_key) {
+                tray.set_menu(menu(Some(&summary))).ok();
+                shown_menu = Some(menu_key);
+            } else {
+                tray.get_item("speeds").set_title(&speeds).ok();
+            }
+            tray.set_tooltip(&format!("{}\n{}", TITLE, speeds)).ok();
+
+            let title = if settings::current(&app).speed_in_title {
+                format!("{} - {}", speeds, TITLE)
+            } else {
+                TITLE.to_string()
+            };
+            if title != shown_title {
+                if let Some(window) = app.get_window("main") {
+                    window.set_title(&title).ok();
+                }
+                shown_title = title;
+            }
+        }
+    });
+}
+
+fn menu_key(summary: &SessionSummary) -> (bool, bool, Vec<(String, String, u32)>) {
+    let recent = summary
+        .recent
+        .iter()
+        .map(|t| (t.id.clone(), t.name.clone(), (t.progress * 100.0) as u32))
+        .collect();
+    (summary.session_paused, summary.alt_speed, recent)
+}
+
+fn speed_line(summary: &SessionSummary) -> String {
+    format!("⇣ {}  ⇡ {}", format_speed(summary.download_speed), format_speed(summary.upload_speed))
+}
+
+fn format_speed(bytes_per_sec: u64) -> String {
+    match bytes_per_sec {
+        b if b >= 1024 * 1024 => format!("{:.1} MB/s", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.0} kB/s", b as f64 / 1024.0),
+        b => format!("{} B/s", b),
+    }
+}
@@ -1,12 +1,16 @@
-import { useState, useEffect } from 'react';
+import { useState, useEffect, useRef } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 import Sidebar from './components/Sidebar';
 import LibraryGrid from './components/LibraryGrid';
 import NowPlayingFooter from './components/NowPlayingFooter';
 import VideoPlayer from './components/VideoPlayer';
 import SettingsModal from './components/SettingsModal';
 import TorrentDetails from './components/TorrentDetails';
+import AddTorrentDialog from './components/AddTorrentDialog';
 import { sendRpc } from './rpc';
-import { Torrent } from './types';
+import { setLocale, t } from './messages';
+import { AddPreview, EventRecord, HandshakeInfo, Torrent } from './types';
 
 function App() {
     const [view, setView] = useState('home');
@@ -15,12 +19,40 @@ function App() {
     const [activeStreamUrl, setActiveStreamUrl] = useState<string | null>(null);
     const [showSettings, setShowSettings] = useState(false);
     const [selectedTorrent, setSelectedTorrent] = useState<Torrent | null>(null);
+    const [pendingAdds, setPendingAdds] = useState<AddPreview[]>([]);
+    const [sessionPaused, setSessionPaused] = useState(false);
+    const [notices, setNotices] = useState<string[]>([]);
+    const lastEventSeq = useRef(0);
+    const torrentsRef = useRef<Torrent[]>([]);
+
+    // The engine settles the locale: Config.locale if set, else ours
+    useEffect(() => {
+        sendRpc('Handshake', { locale: navigator.language }).then(resp => {
+            if (resp && resp.result) {
+                setLocale((resp.result as HandshakeInfo).locale);
+            }
+        });
+    }, []);
 
     useEffect(() => {
         const interval = setInterval(async () => {
+            const status = await sendRpc('GetSessionStatus');
+            if (status && status.result) {
+                setSessionPaused(status.result.paused);
+            }
+            const events = await sendRpc('GetEvents', { since: lastEventSeq.current });
+            if (events && events.result) {
+                for (const record of events.result as EventRecord[]) {
+                    lastEventSeq.current = record.seq;
+                    if (record.event.type === 'TorrentStalled') {
+                        setNotices(prev => [...prev, t(record.message)]);
+                    }
+                }
+            }
             const resp = await sendRpc('ListTorrents');
             if (resp && resp.result) {
                 setTorrents(resp.result);
+                torrentsRef.current = resp.result;
 
                 // Check if any torrent is streaming and update activeStreamUrl if needed
                 // For now, we rely on the user clicking "Play" to set the URL, 
@@ -30,6 +62,36 @@ function App() {
         return () => clearInterval(interval);
     }, []);
 
+    // Magnet links / .torrent files opened from the OS or a second app launch
+    useEffect(() => {
+        const takeExternal = async () => {
+            const pending = await invoke<AddPreview[]>('take_external_adds');
+            setPendingAdds(prev => [...prev, ...pending]);
+        };
+        takeExternal();
+        const unlisten = listen('external-add', takeExternal);
+        const unlistenError = listen<string>('external-add-error', e => alert("Could not open: " + e.payload));
+        // Picked from the tray's recent torrents
+        const unlistenOpen = listen<string>('open-torrent', e => {
+            const torrent = torrentsRef.current.find(t => t.id === e.payload);
+            if (torrent) {
+                setSelectedTorrent(torrent);
+            }
+        });
+        return () => {
+            unlisten.then(f => f());
+            unlistenError.then(f => f());
+            unlistenOpen.then(f => f());
+        };
+    }, []);
+
+    const toggleSessionPause = async () => {
+        const resp = await sendRpc(sessionPaused ? 'ResumeSession' : 'PauseSession');
+        if (resp && resp.result) {
+            setSessionPaused(!sessionPaused);
+        }
+    };
+
     const handleStreamStart = async (id: string) => {
         const resp = await sendRpc('StreamTorrent', { id });
         if (resp && resp.result && resp.result.url) {
@@ -49,6 +111,9 @@ function App() {
                 />
             )}
             {showSettings && <SettingsModal onClose={() => setShowSettings(false)} />}
+            {pendingAdds.length > 0 && (
+                <AddTorrentDialog preview={pendingAdds[0]} onClose={() => setPendingAdds(prev => prev.slice(1))} />
+            )}
             {selectedTorrent && <TorrentDetails torrent={selectedTorrent} onClose={() => setSelectedTorrent(null)} />}
             <div className="flex flex-1 overflow-hidden">
                 <Sidebar currentView={view} setView={setView} />
@@ -59,10 +124,23 @@ function App() {
                             <button className="bg-black/40 rounded-full p-2 px-3 hover:bg-black/60 transition"><span>&gt;</span></button>
                         </div>
                         <div className="flex gap-4">
+                            <button
+                                onClick={toggleSessionPause}
+                                className={`text-sm font-bold transition ${sessionPaused ? 'text-spotify-green' : 'text-spotify-grey hover:text-white'}`}
+                            >
+                                {sessionPaused ? 'Resume All Traffic' : 'Stop All Traffic'}
+                            </button>
                             <button onClick={() => setShowSettings(true)} className="text-sm font-bold text-spotify-grey hover:text-white transition">Settings</button>
                         </div>
                     </header>
 
+                    {notices.map((notice, i) => (
+                        <div key={i} className="flex justify-between items-center bg-spotify-light rounded-md px-4 py-2 mb-2 text-sm">
+                            <span>{notice}</span>
+                            <button onClick={() => setNotices(prev => prev.filter((_, j) => j !== i))} className="text-spotify-grey hover:text-white">Dismiss</button>
+                        </div>
+                    ))}
+
                     {view === 'library' && <LibraryGrid torrents={torrents} onStream={handleStreamStart} />}
                 </main>
                 <div className="w-72 bg-black p-4 hidden lg:block">
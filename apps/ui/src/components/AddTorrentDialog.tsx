@@ -0,0 +1,104 @@
+import { useEffect, useState } from 'react';
+import { open } from '@tauri-apps/api/dialog';
+import { X } from 'lucide-react';
+import { sendRpc } from '../rpc';
+import { AddPreview, SwarmHealth } from '../types';
+
+interface AddTorrentDialogProps {
+    preview: AddPreview;
+    onClose: () => void;
+}
+
+export default function AddTorrentDialog({ preview: initial, onClose }: AddTorrentDialogProps) {
+    const [preview, setPreview] = useState<AddPreview>(initial);
+    const [health, setHealth] = useState<SwarmHealth | null>(null);
+    const [startAt, setStartAt] = useState(''); // datetime-local value, empty = now
+
+    // Magnets only carry a hash; fetch the file list from peers
+    useEffect(() => {
+        if (initial.total_size != null) return;
+        let cancelled = false;
+        sendRpc('ResolveMagnet', { magnet: initial.source }).then(res => {
+            if (!cancelled && res?.result) setPreview(res.result);
+        });
+        return () => { cancelled = true; };
+    }, [initial]);
+
+    useEffect(() => {
+        let cancelled = false;
+        sendRpc('PreviewSwarm', { source: preview.source }).then(res => {
+            if (!cancelled && res?.result) setHealth(res.result);
+        });
+        return () => { cancelled = true; };
+    }, [preview.source]);
+
+    const add = async (seedPath?: string) => {
+        const start_at = startAt && !seedPath ? Math.floor(new Date(startAt).getTime() / 1000) : null;
+        const res = await sendRpc('AddTorrent', { magnet: preview.source, seed_path: seedPath ?? null, start_at });
+        if (!res || !res.result) {
+            alert(res?.error?.message ?? "Failed to add torrent.");
+        }
+        onClose();
+    };
+
+    // Re-seeding data that's already on disk: verified, never downloaded
+    const seedExisting = async () => {
+        const folder = await open({ directory: true, title: 'Folder containing the torrent\'s files' });
+        if (typeof folder === 'string') {
+            await add(folder);
+        }
+    };
+
+    return (
+        <div className="fixed inset-0 bg-black/80 z-[100] flex items-center justify-center p-4">
+            <div className="bg-spotify-dark w-full max-w-lg rounded-lg p-6 relative shadow-2xl border border-spotify-light">
+                <button onClick={onClose} className="absolute top-4 right-4 text-spotify-grey hover:text-white">
+                    <X size={24} />
+                </button>
+
+                <h2 className="text-2xl font-bold mb-2 break-all">{preview.name ?? preview.info_hash}</h2>
+                <div className="text-sm text-spotify-grey mb-4">
+                    {preview.total_size != null
+                        ? `${(preview.total_size / 1024 / 1024).toFixed(1)} MB • ${preview.files.length} files`
+                        : 'Fetching metadata from peers…'}
+                    {' • '}{preview.trackers.length} trackers
+                </div>
+                <div className="text-sm text-spotify-grey mb-4">
+                    {health
+                        ? `${health.seeders ?? '?'} seeds • ${health.leechers ?? '?'} leechers` +
+                          (health.dht_peers != null ? ` • ${health.dht_peers} DHT peers` : '')
+                        : 'Checking swarm health…'}
+                </div>
+
+                {preview.files.length > 0 && (
+                    <div className="max-h-60 overflow-y-auto mb-4 space-y-1">
+                        {preview.files.map((f, i) => (
+                            <div key={i} className="flex justify-between text-sm">
+                                <span className="truncate mr-4">{f.name}</span>
+                                <span className="text-spotify-grey shrink-0">{(f.size / 1024 / 1024).toFixed(1)} MB</span>
+                            </div>
+                        ))}
+                    </div>
+                )}
+
+                <label className="flex items-center gap-2 text-sm text-spotify-grey mb-4">
+                    Start at
+                    <input
+                        type="datetime-local"
+                        value={startAt}
+                        onChange={e => setStartAt(e.target.value)}
+                        className="bg-spotify-light text-white rounded px-2 py-1"
+                    />
+                </label>
+
+                <div className="flex justify-end gap-4">
+                    <button onClick={onClose} className="text-sm font-bold text-spotify-grey hover:text-white">Cancel</button>
+                    {preview.files.length > 0 && (
+                        <button onClick={seedExisting} className="text-sm font-bold text-spotify-grey hover:text-white">Seed Existing Data…</button>
+                    )}
+                    <button onClick={() => add()} className="bg-spotify-green text-black font-bold rounded-full px-6 py-2 hover:scale-105 transition">Add</button>
+                </div>
+            </div>
+        </div>
+    );
+}
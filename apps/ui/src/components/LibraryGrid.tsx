@@ -1,13 +1,18 @@
-import { Play } from 'lucide-react';
+import { AlertTriangle, Play } from 'lucide-react';
 import { sendRpc } from '../rpc';
+import { statusLabel } from '../messages';
 import { Torrent } from '../types';
 
 export default function LibraryGrid({ torrents, onStream, onSelect }: { torrents: Torrent[], onStream?: (id: string) => void, onSelect?: (t: Torrent) => void }) {
     const addTorrent = async () => {
-        const magnet = prompt("Enter Magnet Link:");
+        const magnet = prompt("Enter Magnet Link or info hash:")?.trim();
         if (magnet) {
             try {
-                const res = await sendRpc('AddTorrent', { magnet });
+                // A bare 40-char hex or 32-char base32 hash is looked up on the DHT
+                const bareHash = /^([0-9a-f]{40}|[a-z2-7]{32})$/i.test(magnet);
+                const res = bareHash
+                    ? await sendRpc('AddByInfoHash', { info_hash: magnet })
+                    : await sendRpc('AddTorrent', { magnet });
                 if (res && res.result) {
                     alert("Torrent added successfully!");
                 } else {
@@ -56,8 +61,11 @@ export default function LibraryGrid({ torrents, onStream, onSelect }: { torrents
                                 <Play fill="black" className="text-black ml-1" size={20} />
                             </button>
                         </div>
-                        <h3 className="font-bold truncate mb-1">{t.name}</h3>
-                        <p className="text-sm text-spotify-grey line-clamp-2">{t.status} • {Math.round(t.progress * 100)}%</p>
+                        <h3 className="font-bold truncate mb-1 flex items-center gap-1">
+                            {t.tracker_warning && <AlertTriangle size={14} className="text-yellow-400 shrink-0" />}
+                            <span className="truncate">{t.name}</span>
+                        </h3>
+                        <p className="text-sm text-spotify-grey line-clamp-2">{statusLabel(t.status)} • {Math.round(t.progress * 100)}%</p>
                     </div>
                 ))}
             </div>
@@ -1,4 +1,5 @@
 import { Play, Pause, SkipBack, SkipForward, Settings, ListMusic, MonitorSpeaker, Volume2 } from 'lucide-react';
+import { statusLabel } from '../messages';
 
 export default function NowPlayingFooter({ torrents, speedUnit, setSpeedUnit }: { torrents: any[], speedUnit: 'MB/s' | 'kB/s', setSpeedUnit: (u: 'MB/s' | 'kB/s') => void }) {
     const active = torrents.find(t => t.status === 'Downloading' || t.status === 'Seeding' || t.status === 'Streaming') || torrents[0];
@@ -20,7 +21,7 @@ export default function NowPlayingFooter({ torrents, speedUnit, setSpeedUnit }:
                         <div className="w-14 h-14 bg-gradient-to-br from-green-400 to-blue-500 rounded"></div>
                         <div>
                             <div className="font-sm hover:underline cursor-pointer">{active.name}</div>
-                            <div className="text-xs text-spotify-grey">{active.status}</div>
+                            <div className="text-xs text-spotify-grey">{statusLabel(active.status)}</div>
                         </div>
                     </>
                 ) : (
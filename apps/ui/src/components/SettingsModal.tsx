@@ -1,6 +1,8 @@
 import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
 import { X, Save } from 'lucide-react';
 import { sendRpc } from '../rpc';
+import { AppSettings } from '../types';
 
 interface SettingsModalProps {
     onClose: () => void;
@@ -10,6 +12,11 @@ export default function SettingsModal({ onClose }: SettingsModalProps) {
     const [downloadPath, setDownloadPath] = useState('');
     const [maxDownloadSpeed, setMaxDownloadSpeed] = useState(0);
     const [maxUploadSpeed, setMaxUploadSpeed] = useState(0);
+    const [appSettings, setAppSettings] = useState<AppSettings>({
+        minimize_to_tray: false,
+        close_to_tray: false,
+        speed_in_title: false,
+    });
     const [loading, setLoading] = useState(true);
 
     useEffect(() => {
@@ -21,6 +28,7 @@ export default function SettingsModal({ onClose }: SettingsModalProps) {
                     setMaxDownloadSpeed(resp.result.max_download_speed);
                     setMaxUploadSpeed(resp.result.max_upload_speed);
                 }
+                setAppSettings(await invoke<AppSettings>('get_app_settings'));
             } catch (error) {
                 console.error('Failed to fetch settings:', error);
             } finally {
@@ -36,6 +44,11 @@ export default function SettingsModal({ onClose }: SettingsModalProps) {
             max_download_speed: maxDownloadSpeed,
             max_upload_speed: maxUploadSpeed,
         });
+        try {
+            await invoke('set_app_settings', { new: appSettings });
+        } catch (error) {
+            console.error('Failed to save app settings:', error);
+        }
         onClose();
     };
 
@@ -82,6 +95,33 @@ export default function SettingsModal({ onClose }: SettingsModalProps) {
                             className="w-full bg-black border border-spotify-light rounded p-2 text-white focus:border-spotify-green focus:outline-none"
                         />
                     </div>
+
+                    <label className="flex items-center gap-2 text-sm font-bold text-spotify-grey">
+                        <input
+                            type="checkbox"
+                            checked={appSettings.minimize_to_tray}
+                            onChange={(e) => setAppSettings({ ...appSettings, minimize_to_tray: e.target.checked })}
+                        />
+                        Minimize to the system tray
+                    </label>
+
+                    <label className="flex items-center gap-2 text-sm font-bold text-spotify-grey">
+                        <input
+                            type="checkbox"
+                            checked={appSettings.close_to_tray}
+                            onChange={(e) => setAppSettings({ ...appSettings, close_to_tray: e.target.checked })}
+                        />
+                        Keep running in the tray when the window is closed
+                    </label>
+
+                    <label className="flex items-center gap-2 text-sm font-bold text-spotify-grey">
+                        <input
+                            type="checkbox"
+                            checked={appSettings.speed_in_title}
+                            onChange={(e) => setAppSettings({ ...appSettings, speed_in_title: e.target.checked })}
+                        />
+                        Show transfer speeds in the window title
+                    </label>
                 </div>
 
                 <div className="mt-8 flex justify-end">
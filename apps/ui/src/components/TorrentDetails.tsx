@@ -1,6 +1,9 @@
 import { useState } from 'react';
-import { X, File, Users, Server } from 'lucide-react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { X, File, Users, Server, ExternalLink, FolderOpen } from 'lucide-react';
 import { Torrent, FileInfo, PeerInfo, TrackerInfo } from '../types';
+import { errorMessage } from '../rpc';
+import { formatBytes, pauseReasonLabel, statusLabel, trackerStatusLabel } from '../messages';
 
 interface TorrentDetailsProps {
     torrent: Torrent;
@@ -10,6 +13,14 @@ interface TorrentDetailsProps {
 export default function TorrentDetails({ torrent, onClose }: TorrentDetailsProps) {
     const [activeTab, setActiveTab] = useState<'files' | 'peers' | 'trackers'>('files');
 
+    const fileAction = async (command: 'open_torrent_file' | 'reveal_torrent_file', fileIndex: number) => {
+        try {
+            await invoke(command, { id: torrent.id, fileIndex });
+        } catch (e) {
+            alert(errorMessage(e));
+        }
+    };
+
     return (
         <div className="fixed inset-0 bg-black/80 z-[90] flex items-center justify-center p-4">
             <div className="bg-spotify-dark w-full max-w-4xl h-[80vh] rounded-lg p-6 relative shadow-2xl border border-spotify-light flex flex-col">
@@ -19,7 +30,8 @@ export default function TorrentDetails({ torrent, onClose }: TorrentDetailsProps
 
                 <h2 className="text-2xl font-bold mb-2">{torrent.name}</h2>
                 <div className="text-sm text-spotify-grey mb-6">
-                    {((torrent.total_size ?? 0) / 1024 / 1024).toFixed(1)} MB • {torrent.status}
+                    {formatBytes(torrent.total_size ?? 0)} • {statusLabel(torrent.status)}
+                    {torrent.pause_reason && ` (${pauseReasonLabel(torrent.pause_reason)})`}
                 </div>
 
                 <div className="flex gap-6 border-b border-spotify-light mb-4">
@@ -51,14 +63,23 @@ export default function TorrentDetails({ torrent, onClose }: TorrentDetailsProps
                                     <th className="py-2">Name</th>
                                     <th className="py-2 text-right">Size</th>
                                     <th className="py-2 text-right">Progress</th>
+                                    <th className="py-2"></th>
                                 </tr>
                             </thead>
                             <tbody>
                                 {torrent.files?.map((file: FileInfo, i: number) => (
                                     <tr key={i} className="hover:bg-white/5">
                                         <td className="py-2 text-white">{file.name}</td>
-                                        <td className="py-2 text-right">{((file.size ?? 0) / 1024 / 1024).toFixed(1)} MB</td>
+                                        <td className="py-2 text-right">{formatBytes(file.size ?? 0)}</td>
                                         <td className="py-2 text-right">{((file.progress ?? 0) * 100).toFixed(0)}%</td>
+                                        <td className="py-2 text-right whitespace-nowrap">
+                                            <button onClick={() => fileAction('open_torrent_file', i)} title="Open" className="hover:text-white ml-3">
+                                                <ExternalLink size={14} />
+                                            </button>
+                                            <button onClick={() => fileAction('reveal_torrent_file', i)} title="Show in folder" className="hover:text-white ml-3">
+                                                <FolderOpen size={14} />
+                                            </button>
+                                        </td>
                                     </tr>
                                 ))}
                             </tbody>
@@ -99,8 +120,11 @@ export default function TorrentDetails({ torrent, onClose }: TorrentDetailsProps
                             <tbody>
                                 {torrent.trackers?.map((tracker: TrackerInfo, i: number) => (
                                     <tr key={i} className="hover:bg-white/5">
-                                        <td className="py-2 text-white truncate max-w-md">{tracker.url}</td>
-                                        <td className="py-2 text-right text-spotify-green">{tracker.status}</td>
+                                        <td className="py-2 text-white truncate max-w-md">
+                                            {tracker.url}
+                                            {tracker.message && <div className="text-xs text-yellow-400 truncate">{tracker.message}</div>}
+                                        </td>
+                                        <td className="py-2 text-right text-spotify-green">{trackerStatusLabel(tracker.status)}</td>
                                     </tr>
                                 ))}
                             </tbody>
@@ -1,4 +1,6 @@
+import { useEffect, useRef, useState } from 'react';
 import { X } from 'lucide-react';
+import { StreamStatus } from '../types';
 
 interface VideoPlayerProps {
     streamUrl: string;
@@ -6,6 +8,33 @@ interface VideoPlayerProps {
 }
 
 export default function VideoPlayer({ streamUrl, onClose }: VideoPlayerProps) {
+    const videoRef = useRef<HTMLVideoElement>(null);
+    const [status, setStatus] = useState<StreamStatus | null>(null);
+
+    // Buffer bar: what the torrent has of the file, polled from the engine
+    useEffect(() => {
+        const statusUrl = streamUrl.replace('/stream/', '/stream-status/');
+        const poll = async () => {
+            const video = videoRef.current;
+            let query = '';
+            if (video && video.duration > 0 && status) {
+                // Byte offset of the playhead, assuming a roughly constant bitrate
+                query = `?offset=${Math.floor((video.currentTime / video.duration) * status.file_size)}`;
+            }
+            try {
+                const res = await fetch(statusUrl + query);
+                if (res.ok) setStatus(await res.json());
+            } catch {
+                // Engine not reachable; keep the last status
+            }
+        };
+        poll();
+        const interval = setInterval(poll, 1000);
+        return () => clearInterval(interval);
+    }, [streamUrl, status?.file_size]);
+
+    const percent = (n: number) => (status && status.file_size > 0 ? (n / status.file_size) * 100 : 0);
+
     return (
         <div className="fixed inset-0 bg-black/90 z-[100] flex items-center justify-center p-8">
             <button
@@ -14,15 +43,42 @@ export default function VideoPlayer({ streamUrl, onClose }: VideoPlayerProps) {
             >
                 <X size={32} />
             </button>
-            <div className="w-full max-w-5xl aspect-video bg-black rounded-lg overflow-hidden shadow-2xl border border-spotify-grey/20">
-                <video
-                    src={streamUrl}
-                    controls
-                    autoPlay
-                    className="w-full h-full object-contain"
-                >
-                    Your browser does not support the video tag.
-                </video>
+            <div className="w-full max-w-5xl">
+                <div className="aspect-video bg-black rounded-lg overflow-hidden shadow-2xl border border-spotify-grey/20">
+                    <video
+                        ref={videoRef}
+                        src={streamUrl}
+                        controls
+                        autoPlay
+                        className="w-full h-full object-contain"
+                    >
+                        Your browser does not support the video tag.
+                    </video>
+                </div>
+                {status && (
+                    <div className="mt-2">
+                        <div className="relative h-1.5 bg-spotify-grey/30 rounded overflow-hidden">
+                            {status.prefetch && (
+                                <div
+                                    className="absolute h-full bg-spotify-green/30"
+                                    style={{ left: `${percent(status.prefetch.start)}%`, width: `${percent(status.prefetch.end - status.prefetch.start)}%` }}
+                                />
+                            )}
+                            {status.buffered.map((r) => (
+                                <div
+                                    key={r.start}
+                                    className="absolute h-full bg-spotify-green"
+                                    style={{ left: `${percent(r.start)}%`, width: `${percent(r.end - r.start)}%` }}
+                                />
+                            ))}
+                        </div>
+                        {status.eta_secs !== null && status.eta_secs > 0 && (
+                            <p className="text-xs text-spotify-grey mt-1">
+                                Buffering… about {Math.ceil(status.eta_secs)}s at {(status.download_speed / 1024 / 1024).toFixed(1)} MB/s
+                            </p>
+                        )}
+                    </div>
+                )}
             </div>
         </div>
     );
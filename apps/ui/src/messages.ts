@@ -0,0 +1,102 @@
+import { Message } from './types';
+
+// English text for the engine's message keys. Other languages add a
+// catalog here keyed by their language subtag; missing keys fall back to
+// English, then to the key itself.
+const en: Record<string, string> = {
+    'status.Checking': 'Checking',
+    'status.Downloading': 'Downloading',
+    'status.Stalled': 'Stalled',
+    'status.Seeding': 'Seeding',
+    'status.Paused': 'Paused',
+    'status.Corrupted': 'Corrupted',
+    'status.Streaming': 'Streaming',
+    'status.Unknown': 'Unknown',
+
+    'pause_reason.LowDiskSpace': 'low disk space',
+    'pause_reason.InsufficientDiskSpace': 'not enough disk space',
+    'pause_reason.HighCpu': 'high CPU usage',
+    'pause_reason.SessionPaused': 'all paused',
+    'pause_reason.Scheduled': 'scheduled',
+    'pause_reason.TrackerLimit': 'tracker limit',
+    'pause_reason.ShareLimit': 'seeding goal reached',
+    'pause_reason.Checking': 'checking',
+    'pause_reason.Retention': 'moving to archive',
+    'pause_reason.DataCap': 'data cap',
+    'pause_reason.Queued': 'queued',
+    'pause_reason.User': 'by you',
+
+    'tracker_status.Working': 'Working',
+    'tracker_status.NotWorking': 'Not working',
+    'tracker_status.Unknown': 'Not scraped',
+
+    'event.torrent_corrupted': '{name} failed verification: {bad_pieces} bad pieces.',
+    'event.torrent_paused': 'Torrent paused ({reason}).',
+    'event.torrent_resumed': 'Torrent resumed.',
+    'event.network_changed': 'Network changed.',
+    'event.torrent_stalled': '{name} has stalled: no seeds and no progress.',
+    'event.torrent_unstalled': 'Torrent is moving again.',
+    'event.share_limit_reached': '{name} reached its seeding goal.',
+    'event.data_cap_warning': '{used:size} of the {cap:size} monthly data cap used.',
+    'event.data_cap_reached': 'Monthly data cap of {cap:size} reached.',
+    'event.update_available': 'AuroraTorrent {version} is available.',
+    'event.job_done': '{name}: finished.',
+    'event.job_failed': '{name}: {error}',
+    'event.job_cancelled': '{name}: cancelled.',
+    'event.retention_archived': '{name} was moved to {destination} after seeding.',
+    'event.retention_deleted': '{name} and its files were deleted after seeding.',
+
+    'error.not_found': '{detail} not found',
+    'error.invalid_input': 'Invalid input: {detail}',
+    'error.io': '{detail}',
+    'error.credentials': 'Credential store error: {detail}',
+    'error.unsupported': '{detail} is not supported',
+    'error.unavailable': '{detail}',
+    'error.internal': '{detail}',
+};
+
+const catalogs: Record<string, Record<string, string>> = { en };
+
+let locale = navigator.language || 'en';
+
+// Set from the engine's handshake so Config.locale wins over the system
+export const setLocale = (tag: string) => {
+    locale = tag;
+};
+
+export const currentLocale = () => locale;
+
+// "{name}" is the parameter as is, "{used:size}" a byte count and
+// "{at:date}" unix seconds, both formatted for the locale.
+export const t = (message: Message | string): string => {
+    const { key, params = {} } = typeof message === 'string' ? { key: message, params: {} } : message;
+    const catalog = catalogs[locale.split(/[-_]/)[0]] ?? {};
+    const template = catalog[key] ?? en[key] ?? key;
+    return template.replace(/\{(\w+)(?::(\w+))?\}/g, (_, name: string, format?: string) => {
+        const value = params[name] ?? '';
+        if (format === 'size') return formatBytes(Number(value));
+        if (format === 'date') return formatDate(Number(value));
+        return value;
+    });
+};
+
+export const statusLabel = (status: string) => t(`status.${status}`);
+
+export const pauseReasonLabel = (reason: string) => t(`pause_reason.${reason}`);
+
+export const trackerStatusLabel = (status: string) => t(`tracker_status.${status}`);
+
+export const formatBytes = (bytes: number, digits = 1) => {
+    const units = ['B', 'kB', 'MB', 'GB', 'TB'];
+    let value = bytes;
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+        value /= 1024;
+        unit++;
+    }
+    const number = new Intl.NumberFormat(locale, { maximumFractionDigits: unit === 0 ? 0 : digits }).format(value);
+    return `${number} ${units[unit]}`;
+};
+
+export const formatDate = (unixSeconds: number) =>
+    new Intl.DateTimeFormat(locale, { dateStyle: 'medium', timeStyle: 'short' }).format(new Date(unixSeconds * 1000));
@@ -0,0 +1,24 @@
+import { PieceStates } from './types';
+
+// Unpacks PieceStates into one flag per piece. Null for an encoding this
+// build doesn't know yet.
+export const decodePieces = ({ encoding, len, data }: PieceStates): boolean[] | null => {
+    const have = new Array<boolean>(len).fill(false);
+    if (encoding === 'RunLength') {
+        let at = 0;
+        data.split(',').forEach((run, i) => {
+            const end = Math.min(len, at + Number(run));
+            if (i % 2 === 1) have.fill(true, at, end);
+            at = end;
+        });
+        return have;
+    }
+    if (encoding === 'Bitfield') {
+        const bytes = atob(data);
+        for (let i = 0; i < len; i++) {
+            have[i] = ((bytes.charCodeAt(i >> 3) >> (7 - (i & 7))) & 1) === 1;
+        }
+        return have;
+    }
+    return null;
+};
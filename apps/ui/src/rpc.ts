@@ -1,4 +1,6 @@
 import { invoke } from '@tauri-apps/api/tauri';
+import { t } from './messages';
+import { RpcError } from './types';
 
 let requestId = 0;
 
@@ -17,7 +19,15 @@ export const sendRpc = async (method: string, params: any = {}) => {
         const response = await invoke('rpc_request', { request: JSON.stringify(request) });
         return JSON.parse(response as string);
     } catch (e) {
-        console.error("RPC Error:", e);
+        console.error("RPC Error:", errorMessage(e));
         return null;
     }
 };
+
+export const errorMessage = (e: unknown): string => {
+    const error = e as RpcError;
+    if (error && error.localized) {
+        return t(error.localized);
+    }
+    return error && typeof error.message === 'string' ? error.message : String(e);
+};
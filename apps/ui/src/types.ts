@@ -1,7 +1,22 @@
+export type FilePriority = 'Skip' | 'Low' | 'Normal' | 'High';
+
 export interface FileInfo {
     name: string;
     size: number;
     progress: number;
+    downloaded: number;
+    priority: FilePriority;
+    disk_name: string | null;
+}
+
+export interface FileTreeNode {
+    name: string;
+    path: string;
+    size: number;
+    progress: number;
+    priority: FilePriority;
+    file_index: number | null;
+    children: FileTreeNode[];
 }
 
 export interface PeerInfo {
@@ -11,20 +26,370 @@ export interface PeerInfo {
     up_speed: number;
 }
 
+// Reply to GetPieceStates; with `cells`, one entry per run of pieces
+export interface TorrentPieces {
+    num_pieces: number;
+    piece_states: PieceStates; // decode with decodePieces
+}
+
+export type PieceEncoding = 'Bitfield' | 'RunLength';
+
+export interface PieceStates {
+    encoding: PieceEncoding;
+    len: number;
+    data: string;
+}
+
+export type TrackerStatus = 'Working' | 'NotWorking' | 'Unknown';
+
 export interface TrackerInfo {
     url: string;
-    status: string;
+    status: TrackerStatus;
+    message: string | null;
+    seeders: number | null;
+    leechers: number | null;
 }
 
+export type TorrentStatus = 'Checking' | 'Downloading' | 'Stalled' | 'Seeding' | 'Paused' | 'Corrupted' | 'Unknown';
+
 export interface Torrent {
     id: string;
     name: string;
-    status: string;
+    status: TorrentStatus;
     progress: number;
     download_speed: number;
     upload_speed: number;
     total_size: number;
+    file_count: number;
     files: FileInfo[];
     peers: PeerInfo[];
     trackers: TrackerInfo[];
+    tags: string[];
+    category: string | null;
+    ratio: number;
+    ratio_limit: number | null;
+    comment: string | null;
+    created_by: string | null;
+    amount_left: number;
+    downloaded_session: number;
+    uploaded_session: number;
+    queue_position: number | null; // download queue while unfinished, else seed queue; 0 = first
+    added_on: number; // unix seconds
+    eta_secs: number | null;
+    mode: TorrentMode;
+    tracker_warning: boolean; // a tracker's last scrape failed or sent a warning
+    pause_reason: PauseReason | null;
+}
+
+export type PauseReason =
+    | 'LowDiskSpace'
+    | 'InsufficientDiskSpace'
+    | 'HighCpu'
+    | 'SessionPaused'
+    | 'Scheduled'
+    | 'TrackerLimit'
+    | 'ShareLimit'
+    | 'Checking'
+    | 'Retention'
+    | 'DataCap'
+    | 'Queued'
+    | 'User';
+
+// Auto managed torrents wait for a queue slot, force started ones don't,
+// paused ones stay stopped
+export type TorrentMode = 'AutoManaged' | 'ForceStarted' | 'Paused';
+
+export type QueueMove = 'Up' | 'Down' | 'Top' | 'Bottom';
+
+// Reply to GetTorrentQueues: torrent ids, first in line first
+export interface TorrentQueues {
+    downloads: string[];
+    seeds: string[];
+}
+
+export type TorrentSort = 'Name' | 'Size' | 'Progress' | 'Ratio' | 'AddedOn' | 'Eta';
+export type SortOrder = 'Ascending' | 'Descending';
+
+export interface TorrentFilter {
+    status?: TorrentStatus;
+    category?: string;
+    tag?: string;
+    search?: string;
+}
+
+// Reply to ListTorrentsPage
+export interface TorrentPage {
+    total: number;
+    offset: number;
+    torrents: Torrent[];
+}
+
+export interface BannedPeer {
+    ip: string;
+    reason: string;
+    created_at: number;
+    expires_at: number | null; // null = permanent
+}
+
+// Settings for every torrent on a tracker domain (or its subdomains)
+export interface TrackerLimit {
+    domain: string;
+    max_active: number | null;
+    min_announce_interval_secs: number | null;
+    default_category: string | null;
+}
+
+// Reply to ListTags; `parent` nests a tag under another in the sidebar
+export interface TagInfo {
+    name: string;
+    color: string | null; // "#rrggbb"
+    parent: string | null;
+}
+
+// Auto-tagging rule; every condition that is set must match
+export interface TagRule {
+    id: number;
+    name: string;
+    enabled: boolean;
+    tracker_domain: string | null;
+    name_regex: string | null;
+    extensions: string[];
+    min_size: number | null;
+    max_size: number | null;
+    tags: string[];
+    category: string | null;
+    start_time: string | null; // "HH:MM" UTC
+}
+
+export type RetentionAction = 'Archive' | 'Delete';
+
+// Per category: what happens to content some days after its seeding goal was met
+export interface RetentionRule {
+    category: string;
+    after_days: number;
+    action: RetentionAction;
+    archive_dir: string;
+}
+
+// Reply to PreviewRetention
+export interface RetentionCandidate {
+    id: string;
+    name: string;
+    category: string;
+    action: RetentionAction;
+    path: string;
+    destination: string | null;
+    goal_met_at: number; // unix seconds
+    due_at: number;
+    due: boolean;
+}
+
+export interface AddPreview {
+    source: string;
+    info_hash: string;
+    name: string | null;
+    total_size: number | null;
+    files: FileInfo[];
+    trackers: string[];
+}
+
+export interface TrackerScrape {
+    url: string;
+    seeders: number | null;
+    leechers: number | null;
+    completed: number | null;
+    error: string | null;
+    warning: string | null;
+}
+
+export interface SwarmHealth {
+    info_hash: string;
+    seeders: number | null;
+    leechers: number | null;
+    dht_peers: number | null;
+    trackers: TrackerScrape[];
+}
+
+export interface CodecInfo {
+    name: string;
+    kind: 'video' | 'audio' | 'subtitle' | 'data';
+    decode: boolean;
+    encode: boolean;
+}
+
+export interface TranscoderInfo {
+    available: boolean;
+    path: string;
+    version: string | null;
+    codecs: CodecInfo[];
+    hwaccels: string[];
+    error: string | null;
+}
+
+// A data check that is running (position null) or waiting its turn
+export interface CheckQueueEntry {
+    id: string;
+    name: string;
+    position: number | null;
+}
+
+export type JobKind = 'InitialCheck' | 'SeedCheck' | 'SpotCheck' | 'CreateTorrent';
+export type JobState = 'Queued' | 'Running' | 'Done' | 'Failed' | 'Cancelled';
+
+// Reply to ListJobs, oldest first; finished jobs stay listed for a while
+export interface JobInfo {
+    id: number;
+    kind: JobKind;
+    torrent_id: string | null;
+    name: string;
+    state: JobState;
+    progress: number; // 0 to 1
+    error: string | null;
+    result: string | null;
+    started_at: number; // unix seconds
+    finished_at: number | null;
+}
+
+export type TaskState = 'Running' | 'Restarting' | 'Failed' | 'Stopped';
+
+// Reply to GetTaskHealth: the engine's background loops
+export interface TaskHealth {
+    name: string;
+    critical: boolean; // restarted after a panic
+    state: TaskState;
+    restarts: number;
+    last_panic: string | null;
+    last_panic_at: number | null; // unix seconds
+}
+
+export interface ByteRange {
+    start: number;
+    end: number;
+}
+
+export interface StreamStatus {
+    file_size: number;
+    buffered: ByteRange[];
+    prefetch: ByteRange | null;
+    download_speed: number;
+    offset: number | null;
+    eta_secs: number | null;
+}
+
+export interface DhtStats {
+    enabled: boolean;
+    nodes: number;
+    outstanding_requests: number;
+    lookups: number;
+    lookups_last_minute: number;
+}
+
+export interface TrackerSite {
+    domain: string;
+    title: string | null;
+    has_icon: boolean; // GET http://127.0.0.1:3000/tracker-icon/<domain>
+}
+
+export interface DailyUsage {
+    date: number; // unix seconds at midnight UTC
+    downloaded: number;
+    uploaded: number;
+}
+
+// Window and tray behavior, saved by the app rather than the engine
+export interface AppSettings {
+    minimize_to_tray: boolean;
+    close_to_tray: boolean;
+    speed_in_title: boolean;
+}
+
+export interface UpdateInfo {
+    current_version: string;
+    latest_version: string | null;
+    update_available: boolean;
+    release_url: string | null;
+    notes: string | null;
+    checked_at: number; // unix seconds, 0 = never
+}
+
+export interface SpeedSummary {
+    download_speed: number;
+    upload_speed: number;
+    downloading: number;
+    seeding: number;
+    paused: number;
+}
+
+export interface SessionSummary {
+    download_speed: number;
+    upload_speed: number;
+    session_paused: boolean;
+    alt_speed: boolean;
+    recent: RecentTorrent[]; // most recently added first
+}
+
+export interface RecentTorrent {
+    id: string;
+    name: string;
+    progress: number;
+}
+
+export interface DataUsage {
+    days: DailyUsage[];
+    period_start: number;
+    period_used: number;
+    cap: number; // 0 = no cap
+    capped: boolean;
+}
+
+export interface AddParams {
+    category?: string | null;
+    tags?: string[];
+    save_path?: string | null;
+    paused?: boolean; // only sticks with auto_managed off
+    auto_managed?: boolean; // default true
+}
+
+export interface Profile {
+    name: string;
+    download_path: string;
+    max_download_speed: number;
+    max_upload_speed: number;
+    active: boolean;
+}
+
+export interface CreatedTorrent {
+    info_hash: string;
+    path: string;
+    piece_size: number;
+    piece_count: number;
+    total_size: number;
+}
+
+export type ErrorKind = 'NotFound' | 'InvalidInput' | 'Io' | 'Credentials' | 'Unsupported' | 'Unavailable' | 'Internal';
+
+// Returned in an RPC response's `error` and thrown by Tauri commands
+export interface RpcError {
+    kind: ErrorKind;
+    message: string; // English
+    localized?: Message;
+}
+
+// Text to show, as a key into messages.ts and its parameters
+export interface Message {
+    key: string;
+    params?: Record<string, string>;
+}
+
+export interface HandshakeInfo {
+    engine_version: string;
+    locale: string;
+}
+
+// Only the events the UI reacts to are typed out
+export interface EventRecord {
+    seq: number;
+    timestamp: number;
+    event: { type: string; data: any };
+    message: Message;
 }
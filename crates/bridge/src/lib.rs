@@ -1,10 +1,36 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "method", content = "params")]
 pub enum RpcCommand {
-    AddTorrent { magnet: String },
+    AddTorrent {
+        magnet: String,
+        // Existing content folder: verify it and seed without downloading
+        #[serde(default)]
+        seed_path: Option<String>,
+        // Unix time to start at; added paused until then
+        #[serde(default)]
+        start_at: Option<u64>,
+        // Don't verify files already in the download folder
+        #[serde(default)]
+        skip_checking: bool,
+    },
+    // 40 hex or 32 base32 chars; peers and metadata come from the DHT
+    AddByInfoHash { info_hash: String },
     ListTorrents,
+    // One sorted, filtered page of ListTorrents
+    ListTorrentsPage {
+        #[serde(default)]
+        offset: usize,
+        limit: usize,
+        #[serde(default)]
+        sort_by: TorrentSort,
+        #[serde(default)]
+        order: SortOrder,
+        #[serde(default)]
+        filter: TorrentFilter,
+    },
     StartTorrent { id: String },
     PauseTorrent { id: String },
     RemoveTorrent { id: String },
@@ -14,7 +40,122 @@ pub enum RpcCommand {
         download_path: Option<String>,
         max_download_speed: Option<u64>,
         max_upload_speed: Option<u64>,
+        #[serde(default)]
+        ffmpeg_path: Option<String>,
+        #[serde(default)]
+        locale: Option<String>,
+    },
+    GetEvents { since: u64 },
+    ListTrackerCredentials,
+    SetTrackerPasskey { domain: String, passkey: String },
+    RemoveTrackerPasskey { domain: String },
+    PauseSession,
+    ResumeSession,
+    Shutdown, // before the app exits; torrents stop for good
+    GetSessionSummary { recent: usize },
+    GetSpeedSummary,
+    GetVersionInfo,
+    CheckUpdates,
+    // First call from a UI: its own locale in, the one to show text in out
+    Handshake {
+        #[serde(default)]
+        locale: Option<String>,
+    },
+    SetAltSpeed { enabled: bool },
+    ResumedFromSleep,
+    GetSessionStatus,
+    GetTorrentFiles { id: String, offset: usize, limit: usize },
+    // `cells` groups the pieces into that many runs, for a bar that's narrower than the piece count
+    GetPieceStates { id: String, #[serde(default)] cells: Option<usize> },
+    GetFileTree { id: String },
+    SetFilePriority { id: String, file_indices: Vec<usize>, priority: FilePriority },
+    SetFolderPriority { id: String, path: String, priority: FilePriority },
+    GetFilePath { id: String, file_index: usize },
+    // Finish files one after another; `order` lists file indices, None = as listed
+    SetDownloadOrder { id: String, enabled: bool, order: Option<Vec<usize>> },
+    // Magnet link, .torrent path or bare info hash; nothing is added
+    PreviewSwarm { source: String },
+    // Fetch a magnet's file list from peers without adding it
+    ResolveMagnet { magnet: String },
+    GetDhtStats,
+    // Zip of config, torrents, peers, trackers, events and logs for a bug report
+    DumpDiagnostics { output: String },
+    GetTaskHealth,
+    GetCheckQueue,
+    CancelCheck { id: String },
+    MoveCheck { id: String, position: usize },
+    ListJobs,
+    // Running or queued; a queued data check also leaves the check queue
+    CancelJob { id: u64 },
+    GetTorrentQueues,
+    // Unfinished torrents move in the download queue, finished ones in the seed queue
+    MoveInDownloadQueue { id: String, direction: QueueMove },
+    MoveInSeedQueue { id: String, direction: QueueMove },
+    // StartTorrent sets AutoManaged and PauseTorrent Paused
+    SetTorrentMode { id: String, mode: TorrentMode },
+    // Re-runs detection when `refresh` is set instead of using the cached result
+    GetTranscoderInfo { #[serde(default)] refresh: bool },
+    ScheduleStart { id: String, start_at: u64 },
+    CancelScheduledStart { id: String },
+    ListScheduledStarts,
+    ListTagRules,
+    AddTagRule { rule: TagRule },
+    UpdateTagRule { rule: TagRule },
+    RemoveTagRule { id: u64 },
+    SetTorrentTags { id: String, tags: Vec<String>, category: Option<String> },
+    ListTags,
+    SetTag { tag: TagInfo },
+    // Also takes the tag off every torrent; tags under it move up a level
+    RemoveTag { name: String },
+    ListTrackerLimits,
+    SetTrackerLimit { limit: TrackerLimit },
+    RemoveTrackerLimit { domain: String },
+    ListRetentionRules,
+    SetRetentionRule { rule: RetentionRule },
+    RemoveRetentionRule { category: String },
+    // What the retention rules would do, without doing it
+    PreviewRetention,
+    // `duration_secs` None is permanent. The session only loads its blocklist
+    // when it starts, so a ban or unban takes effect from the next start and
+    // peers already connected stay; the reply says "takes_effect": "next_start"
+    BanPeer { ip: String, reason: String, duration_secs: Option<u64> },
+    UnbanPeer { ip: String },
+    ListBannedPeers,
+    SetRatioLimit { id: String, ratio: Option<f64> }, // None = use the category's or global target
+    SetCategoryRatioLimit { category: String, ratio: Option<f64> },
+    GetCategoryRatioLimits,
+    // None leaves a field as it is, "" removes it
+    EditTorrentMetadata { id: String, comment: Option<String>, created_by: Option<String> },
+    // Restarts each torrent to announce it, which drops its peer connections
+    ReannounceAll { category: Option<String> }, // None = every torrent
+    ScrapeAll { category: Option<String> },
+    CreateTorrent {
+        source: String, // file or folder
+        output: String, // where to write the .torrent
+        trackers: Vec<String>,
+        private: bool,
+        piece_size: Option<u64>, // None = picked from the total size
     },
+    ExportAsNewTorrent { id: String, output: String, trackers: Vec<String>, private: bool },
+    GetDataUsage,
+    GetTrackerSites,
+    AddFromLink {
+        link: String, // magnet, .torrent URL, or a download page linking to one
+        #[serde(default)]
+        params: AddParams,
+    },
+    ListProfiles,
+    CreateProfile { profile: Profile },
+    SwitchProfile { name: String }, // every other command goes to the active profile
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FilePriority {
+    Skip,
+    Low,
+    #[default]
+    Normal,
+    High,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,6 +163,216 @@ pub struct FileInfo {
     pub name: String,
     pub size: u64,
     pub progress: f64,
+    #[serde(default)]
+    pub downloaded: u64, // verified bytes of this file
+    #[serde(default)]
+    pub priority: FilePriority,
+    // Its name on disk when that isn't `name`: renamed so it doesn't collide
+    // with another of the torrent's files (see paths::unique in the engine)
+    #[serde(default)]
+    pub disk_name: Option<String>,
+}
+
+// Folder view of a torrent's files. Folders aggregate size and progress over
+// everything below them; `priority` is the one covering the most bytes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileTreeNode {
+    pub name: String,
+    pub path: String, // "/"-separated, relative to the torrent root
+    pub size: u64,
+    pub progress: f64,
+    pub priority: FilePriority,
+    pub file_index: Option<usize>, // set on files, None on folders
+    pub children: Vec<FileTreeNode>,
+}
+
+// One page of GetTorrentFiles
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilePage {
+    pub total: usize,
+    pub offset: usize,
+    pub files: Vec<FileInfo>,
+}
+
+// Reply to GetPieceStates: which pieces we have. Grouped into runs, a run
+// is had if all its pieces are. librqbit doesn't expose which pieces its
+// peers have, so there is no per-piece availability.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TorrentPieces {
+    pub num_pieces: usize,
+    pub piece_states: PieceStates,
+}
+
+// Which pieces we have, packed so a 100k-piece torrent isn't 100k JSON
+// booleans. `encoding` says how to read `data`; new encodings get a new
+// variant, so a UI can tell one it doesn't know.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PieceStates {
+    pub encoding: PieceEncoding,
+    pub len: usize,
+    pub data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PieceEncoding {
+    // Base64 of the bitfield as on the peer wire: piece 0 is the high bit of
+    // the first byte
+    Bitfield,
+    // Comma-separated run lengths, alternating missing and had, starting
+    // with missing ("0,40,2" = 40 had, then 2 missing)
+    RunLength,
+}
+
+impl PieceStates {
+    // Whichever encoding comes out shorter: run lengths for mostly complete
+    // or mostly empty torrents, the bitfield for scattered ones.
+    pub fn encode(have: &[bool]) -> Self {
+        let mut runs = Vec::new();
+        let mut current = false;
+        let mut run = 0usize;
+        for &piece in have {
+            if piece != current {
+                runs.push(run.to_string());
+                current = piece;
+                run = 0;
+            }
+            run += 1;
+        }
+        runs.push(run.to_string());
+        let run_length = runs.join(",");
+        let bitfield_len = have.len().div_ceil(8).div_ceil(3) * 4;
+        if run_length.len() <= bitfield_len {
+            return Self { encoding: PieceEncoding::RunLength, len: have.len(), data: run_length };
+        }
+        let mut bytes = vec![0u8; have.len().div_ceil(8)];
+        for (i, _) in have.iter().enumerate().filter(|(_, h)| **h) {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+        Self { encoding: PieceEncoding::Bitfield, len: have.len(), data: base64(&bytes) }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CodecInfo {
+    pub name: String,
+    pub kind: String, // "video", "audio", "subtitle" or "data"
+    pub decode: bool,
+    pub encode: bool,
+}
+
+// What the FFmpeg binary the engine would transcode with can do
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TranscoderInfo {
+    pub available: bool,
+    pub path: String,
+    pub version: Option<String>,
+    pub codecs: Vec<CodecInfo>,
+    pub hwaccels: Vec<String>, // e.g. "cuda", "vaapi", "videotoolbox"
+    pub error: Option<String>, // why it isn't available
+}
+
+// A data check that is running (no position) or waiting its turn
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckQueueEntry {
+    pub id: String,
+    pub name: String,
+    pub position: Option<usize>, // 0 = next to run
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    InitialCheck, // the session checking a torrent it adds or restores
+    SeedCheck,    // data a torrent was added to seed from
+    SpotCheck,    // sampled pieces of a long-running seed (auto recheck)
+    CreateTorrent,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Queued, // waiting for a turn, e.g. in the check queue
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+// A long operation the engine is running or recently finished
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobInfo {
+    pub id: u64,
+    pub kind: JobKind,
+    pub torrent_id: Option<String>,
+    pub name: String,
+    pub state: JobState,
+    pub progress: f64, // 0.0 to 1.0
+    pub error: Option<String>,
+    pub result: Option<String>, // e.g. the created .torrent's path
+    pub started_at: u64,        // unix seconds
+    pub finished_at: Option<u64>,
+}
+
+// How long Client::start took, for telling a slow start apart from a hang.
+// Restoring the saved torrents happens while the session opens.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct StartupTimings {
+    pub session_ms: u64, // opening the session, restoring its torrents
+    pub state_ms: u64,   // loading the engine's own state files
+    pub restore_ms: u64, // starting or holding the restored torrents
+    pub total_ms: u64,
+    pub torrents: usize, // restored by the session
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    Restarting, // panicked, waiting out the backoff
+    Failed,     // panicked and not restarted
+    Stopped,    // returned on its own
+}
+
+// One of the engine's background loops (queue, schedules, data cap...)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskHealth {
+    pub name: String,
+    pub critical: bool, // restarted after a panic
+    pub state: TaskState,
+    pub restarts: u32,
+    pub last_panic: Option<String>,
+    pub last_panic_at: Option<u64>, // unix seconds
+}
+
+// Byte range within a file, end exclusive
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+// Buffer state of a file being played, for the player's buffer bar
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamStatus {
+    pub file_size: u64,
+    pub buffered: Vec<ByteRange>, // verified data, merged and in order
+    pub prefetch: Option<ByteRange>, // None when nothing is playing the file
+    pub download_speed: u64,
+    pub offset: Option<u64>,
+    pub eta_secs: Option<f64>, // until `offset` is playable; None if it can't be estimated
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,10 +383,121 @@ pub struct PeerInfo {
     pub up_speed: u64,
 }
 
+// Serialized as the variant name, the same strings the status used to be.
+// Names from older engines are read as their current variant and anything
+// unrecognized as Unknown.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TrackerStatus {
+    Working,
+    #[serde(alias = "Error")]
+    NotWorking, // the last scrape failed
+    #[serde(other)]
+    Unknown, // not scraped yet
+}
+
+impl TrackerStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TrackerStatus::Working => "Working",
+            TrackerStatus::NotWorking => "NotWorking",
+            TrackerStatus::Unknown => "Unknown",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TrackerInfo {
     pub url: String,
-    pub status: String,
+    pub status: TrackerStatus,
+    #[serde(default)]
+    pub message: Option<String>, // last warning the tracker sent us
+    #[serde(default)]
+    pub seeders: Option<u32>, // from the last bulk scrape
+    #[serde(default)]
+    pub leechers: Option<u32>,
+}
+
+// Parsed details of a magnet/.torrent shown before it is added
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AddPreview {
+    pub source: String, // what to pass back as AddTorrent's `magnet`
+    pub info_hash: String,
+    pub name: Option<String>,
+    pub total_size: Option<u64>, // unknown for magnets until metadata arrives
+    pub files: Vec<FileInfo>,
+    pub trackers: Vec<String>,
+}
+
+// Display details of a tracker's site. The icon is served by the streaming
+// server at /tracker-icon/<domain>.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrackerSite {
+    pub domain: String,
+    pub title: Option<String>,
+    pub has_icon: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrackerScrape {
+    pub url: String,
+    pub seeders: Option<u32>,
+    pub leechers: Option<u32>,
+    pub completed: Option<u32>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub warning: Option<String>,
+}
+
+// Expected health of a swarm, from tracker scrapes and a DHT lookup.
+// Trackers overlap, so the totals are the best single tracker, not a sum.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SwarmHealth {
+    pub info_hash: String,
+    pub seeders: Option<u32>,
+    pub leechers: Option<u32>,
+    pub dht_peers: Option<u32>, // None when DHT is off
+    pub trackers: Vec<TrackerScrape>,
+}
+
+// DHT diagnostics. Lookup counts cover the engine's own lookups (swarm
+// previews); the session's announces aren't counted.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DhtStats {
+    pub enabled: bool,
+    pub nodes: usize, // routing table size
+    pub outstanding_requests: usize,
+    pub lookups: u64,
+    pub lookups_last_minute: u32,
+}
+
+// Serialized as the variant name, the same strings the status used to be.
+// "Active" and "Pending" from older engines read as Downloading and Paused.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TorrentStatus {
+    Checking, // verifying data already on disk
+    #[serde(alias = "Active")]
+    Downloading,
+    Stalled, // no progress for a while
+    Seeding,
+    #[serde(alias = "Pending")]
+    Paused, // held by the engine for any reason
+    Corrupted,
+    #[serde(other)]
+    Unknown,
+}
+
+impl TorrentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TorrentStatus::Checking => "Checking",
+            TorrentStatus::Downloading => "Downloading",
+            TorrentStatus::Stalled => "Stalled",
+            TorrentStatus::Seeding => "Seeding",
+            TorrentStatus::Paused => "Paused",
+            TorrentStatus::Corrupted => "Corrupted",
+            TorrentStatus::Unknown => "Unknown",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -43,13 +505,437 @@ pub struct TorrentState {
     pub id: String,
     pub name: String,
     pub progress: f64,
-    pub status: String, // "Downloading", "Seeding", "Paused"
+    pub status: TorrentStatus,
     pub download_speed: u64,
     pub upload_speed: u64,
     pub total_size: u64,
-    pub files: Vec<FileInfo>,
+    pub file_count: usize,
+    pub files: Vec<FileInfo>, // empty when file_count is large, use GetTorrentFiles
     pub peers: Vec<PeerInfo>,
     pub trackers: Vec<TrackerInfo>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub ratio: f64, // uploaded since it was added over the size we have
+    #[serde(default)]
+    pub ratio_limit: Option<f64>, // the target that applies: torrent, category or global
+    #[serde(default)]
+    pub comment: Option<String>, // from the .torrent file
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub amount_left: u64, // bytes of wanted files still missing
+    #[serde(default)]
+    pub downloaded_session: u64, // since the engine started
+    #[serde(default)]
+    pub uploaded_session: u64,
+    #[serde(default)]
+    pub queue_position: Option<usize>, // in the download queue while unfinished, else the seed queue; 0 = first
+    #[serde(default)]
+    pub added_on: u64, // unix seconds
+    #[serde(default)]
+    pub eta_secs: Option<u64>, // until the wanted files are complete; None while nothing is coming in
+    #[serde(default)]
+    pub mode: TorrentMode,
+    #[serde(default)]
+    pub tracker_warning: bool, // a tracker's last scrape failed or sent a warning
+    #[serde(default)]
+    pub pause_reason: Option<PauseReason>, // why it's paused; User is the only one the engine never lifts
+}
+
+// How a torrent gets started. Auto managed torrents run when their download
+// or seed queue gives them a slot, force started ones regardless of the
+// queues, and paused ones not until they are set to something else. The
+// engine can still hold any of them for other reasons (disk space, data cap).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TorrentMode {
+    #[default]
+    AutoManaged,
+    ForceStarted,
+    Paused,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum QueueMove {
+    Up,
+    Down,
+    Top,
+    Bottom,
+}
+
+// Torrent ids in queue order; the first ones get the active slots
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TorrentQueues {
+    pub downloads: Vec<String>,
+    pub seeds: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TorrentSort {
+    #[default]
+    Name,
+    Size,
+    Progress,
+    Ratio,
+    AddedOn,
+    Eta, // unknown ETAs sort last either way
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+// Every field that is set has to match
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TorrentFilter {
+    pub status: Option<TorrentStatus>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    pub search: Option<String>, // case-insensitive, in the name
+}
+
+// One page of ListTorrentsPage
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TorrentPage {
+    pub total: usize, // torrents matching the filter
+    pub offset: usize,
+    pub torrents: Vec<TorrentState>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BannedPeer {
+    pub ip: std::net::IpAddr,
+    pub reason: String,
+    pub created_at: u64,
+    pub expires_at: Option<u64>, // unix time, None = permanent
+}
+
+// Settings for every torrent on a tracker domain (or its subdomains)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TrackerLimit {
+    pub domain: String,
+    pub max_active: Option<usize>, // running torrents; the rest wait paused
+    pub min_announce_interval_secs: Option<u64>, // spacing of the engine's own re-announces
+    pub default_category: Option<String>, // for new torrents no tagging rule categorized
+}
+
+// A tag's look in the sidebar. Torrents carry tags by name; `parent` nests
+// this one under another (e.g. "anime" under "tv") for grouping only.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct TagInfo {
+    pub name: String,
+    pub color: Option<String>, // "#rrggbb"
+    pub parent: Option<String>,
+}
+
+// Applied to a torrent added from a link. A category or tags replace what
+// auto-tagging would pick.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AddParams {
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub save_path: Option<String>, // used like the download folder
+    pub paused: bool,       // don't start it right away
+    pub auto_managed: bool, // leave starting and stopping to the queues
+}
+
+impl Default for AddParams {
+    fn default() -> Self {
+        Self { category: None, tags: Vec::new(), save_path: None, paused: false, auto_managed: true }
+    }
+}
+
+impl AddParams {
+    // An auto managed torrent added paused still starts once its queue has
+    // room, so `paused` only sticks when it isn't auto managed.
+    pub fn mode(&self) -> TorrentMode {
+        match (self.paused, self.auto_managed) {
+            (_, true) => TorrentMode::AutoManaged,
+            (false, false) => TorrentMode::ForceStarted,
+            (true, false) => TorrentMode::Paused,
+        }
+    }
+}
+
+// A named engine context with its own download folder, torrents, state
+// and speed limits. "default" is the engine's own config.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub download_path: String,
+    #[serde(default)]
+    pub max_download_speed: u64, // bytes per second, 0 = unlimited
+    #[serde(default)]
+    pub max_upload_speed: u64,
+    #[serde(default)]
+    pub active: bool, // set in ListProfiles replies
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreatedTorrent {
+    pub info_hash: String,
+    pub path: String, // the .torrent written
+    pub piece_size: u64,
+    pub piece_count: u32,
+    pub total_size: u64,
+}
+
+// Labels new torrents automatically. Every condition that is set must
+// match; a torrent matching several rules gets all their tags and the
+// category of the first.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TagRule {
+    pub id: u64, // assigned by the engine
+    pub name: String,
+    pub enabled: bool,
+    pub tracker_domain: Option<String>, // also matches subdomains
+    pub name_regex: Option<String>,     // case-insensitive
+    pub extensions: Vec<String>,        // any file with one of these
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub start_time: Option<String>, // "HH:MM" UTC: matching torrents wait paused until then
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    LowDiskSpace,          // free space on the download volume fell below the floor
+    InsufficientDiskSpace, // not enough room left for this torrent's remaining data
+    HighCpu,
+    SessionPaused, // global "stop all traffic" switch
+    Scheduled,     // waiting for its scheduled start time
+    TrackerLimit,  // its tracker already has the maximum number of active torrents
+    ShareLimit,    // done seeding
+    Checking,      // verifying data that was already on disk when it was added
+    Retention,     // its content is being moved to a retention rule's archive folder
+    DataCap,       // the monthly data cap was reached
+    Queued,        // its download or seed queue already has the maximum number of active torrents
+    User,          // set to TorrentMode::Paused
+}
+
+// What happens when the monthly data cap is reached
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataCapAction {
+    #[default]
+    Pause,
+    AltLimits, // switch to the alternative speed limits
+}
+
+// Bytes exchanged with peers outside the local network on one UTC day
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DailyUsage {
+    pub date: u64, // unix seconds at midnight UTC
+    pub downloaded: u64,
+    pub uploaded: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateInfo {
+    pub current_version: String,
+    pub latest_version: Option<String>, // None until a check succeeded
+    pub update_available: bool,
+    pub release_url: Option<String>,
+    pub notes: Option<String>,
+    pub checked_at: u64, // unix seconds, 0 = never
+}
+
+// Session-wide rates and torrent counts, refreshed once a second
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct SpeedSummary {
+    pub download_speed: u64,
+    pub upload_speed: u64,
+    pub downloading: usize,
+    pub seeding: usize,
+    pub paused: usize, // held by the engine for any reason
+}
+
+// Session totals and the latest torrents, for the tray
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionSummary {
+    pub download_speed: u64,
+    pub upload_speed: u64,
+    pub session_paused: bool,
+    pub alt_speed: bool, // alternative speed limits switched on
+    pub recent: Vec<RecentTorrent>, // most recently added first
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecentTorrent {
+    pub id: String,
+    pub name: String,
+    pub progress: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataUsage {
+    pub days: Vec<DailyUsage>, // oldest first
+    pub period_start: u64,     // start of the current billing month
+    pub period_used: u64,      // both directions since then
+    pub cap: u64,              // 0 = no cap
+    pub capped: bool,
+}
+
+// What happens to a torrent that reached its seeding goal
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShareLimitAction {
+    #[default]
+    Pause,
+    Remove,
+    RemoveWithFiles,
+}
+
+// What a retention rule does with a torrent's content once it has met its
+// seeding goal and the rule's days have passed. The torrent leaves the
+// session either way.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetentionAction {
+    #[default]
+    Archive, // move the content into `archive_dir`
+    Delete,
+}
+
+// One per category
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RetentionRule {
+    pub category: String,
+    pub after_days: u32, // counted from when the seeding goal was met
+    pub action: RetentionAction,
+    pub archive_dir: String,
+}
+
+// Reply to PreviewRetention: a torrent a rule will act on, due or not
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetentionCandidate {
+    pub id: String,
+    pub name: String,
+    pub category: String,
+    pub action: RetentionAction,
+    pub path: String,                // the torrent's folder, or its file
+    pub destination: Option<String>, // where Archive moves it
+    pub goal_met_at: u64,            // unix seconds
+    pub due_at: u64,
+    pub due: bool, // acted on in the next pass
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "data")]
+pub enum EngineEvent {
+    TorrentCorrupted { id: String, name: String, bad_pieces: Vec<u32> },
+    TorrentPaused { id: String, reason: PauseReason },
+    TorrentResumed { id: String, reason: PauseReason },
+    NetworkChanged { local_ip: Option<String>, external_ip: Option<String>, listen_port: Option<u16> },
+    TorrentStalled { id: String, name: String }, // no seeds and no progress for a while
+    TorrentUnstalled { id: String },
+    ShareLimitReached { id: String, name: String, action: ShareLimitAction },
+    DataCapWarning { used: u64, cap: u64 },
+    DataCapReached { used: u64, cap: u64, action: DataCapAction },
+    UpdateAvailable { version: String, url: Option<String> },
+    JobFinished { job: JobInfo }, // done, failed or cancelled
+    RetentionApplied { id: String, name: String, action: RetentionAction, destination: Option<String> },
+}
+
+impl EngineEvent {
+    // The notification text for this event, for the UI to look up
+    pub fn message(&self) -> Message {
+        match self {
+            EngineEvent::TorrentCorrupted { id, name, bad_pieces } => Message::new("event.torrent_corrupted")
+                .with("id", id)
+                .with("name", name)
+                .with("bad_pieces", bad_pieces.len()),
+            EngineEvent::TorrentPaused { id, reason } => {
+                Message::new("event.torrent_paused").with("id", id).with("reason", format!("{:?}", reason))
+            }
+            EngineEvent::TorrentResumed { id, reason } => {
+                Message::new("event.torrent_resumed").with("id", id).with("reason", format!("{:?}", reason))
+            }
+            EngineEvent::NetworkChanged { local_ip, external_ip, listen_port } => Message::new("event.network_changed")
+                .with("local_ip", local_ip.as_deref().unwrap_or_default())
+                .with("external_ip", external_ip.as_deref().unwrap_or_default())
+                .with("listen_port", listen_port.map(|p| p.to_string()).unwrap_or_default()),
+            EngineEvent::TorrentStalled { id, name } => {
+                Message::new("event.torrent_stalled").with("id", id).with("name", name)
+            }
+            EngineEvent::TorrentUnstalled { id } => Message::new("event.torrent_unstalled").with("id", id),
+            EngineEvent::ShareLimitReached { id, name, action } => Message::new("event.share_limit_reached")
+                .with("id", id)
+                .with("name", name)
+                .with("action", format!("{:?}", action)),
+            EngineEvent::DataCapWarning { used, cap } => {
+                Message::new("event.data_cap_warning").with("used", used).with("cap", cap)
+            }
+            EngineEvent::DataCapReached { used, cap, action } => Message::new("event.data_cap_reached")
+                .with("used", used)
+                .with("cap", cap)
+                .with("action", format!("{:?}", action)),
+            EngineEvent::UpdateAvailable { version, url } => Message::new("event.update_available")
+                .with("version", version)
+                .with("url", url.as_deref().unwrap_or_default()),
+            EngineEvent::JobFinished { job } => Message::new(match job.state {
+                JobState::Failed => "event.job_failed",
+                JobState::Cancelled => "event.job_cancelled",
+                _ => "event.job_done",
+            })
+            .with("id", job.id)
+            .with("kind", format!("{:?}", job.kind))
+            .with("name", &job.name)
+            .with("error", job.error.as_deref().unwrap_or_default()),
+            EngineEvent::RetentionApplied { id, name, action, destination } => Message::new(match action {
+                RetentionAction::Archive => "event.retention_archived",
+                RetentionAction::Delete => "event.retention_deleted",
+            })
+            .with("id", id)
+            .with("name", name)
+            .with("destination", destination.as_deref().unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventRecord {
+    pub seq: u64,
+    pub timestamp: u64, // unix seconds
+    pub event: EngineEvent,
+    #[serde(default)]
+    pub message: Message,
+}
+
+// User-facing text as a key plus named parameters, so the UI can show it in
+// its own language. Sizes are plain byte counts and times unix seconds; the
+// UI formats them for the locale.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Message {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+impl Message {
+    pub fn new(key: impl Into<String>) -> Self {
+        Self { key: key.into(), params: BTreeMap::new() }
+    }
+
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HandshakeInfo {
+    pub engine_version: String,
+    pub locale: String, // `Config.locale` if set, else the UI's own, else "en"
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,7 +951,79 @@ pub struct RpcResponse<T> {
     pub jsonrpc: String,
     pub id: u64,
     pub result: Option<T>,
-    pub error: Option<String>,
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotFound,     // unknown torrent, file...
+    InvalidInput, // malformed request or out-of-range argument
+    Io,           // disk or socket error
+    Credentials,  // OS keyring refused
+    Unsupported,  // method not implemented
+    Unavailable,  // engine not reachable
+    Internal,
 }
 
+// Error as it crosses RPC, HTTP and Tauri IPC
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RpcError {
+    pub kind: ErrorKind,
+    pub message: String, // English, for logs and clients that don't localize
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub localized: Option<Message>,
+}
+
+impl RpcError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into(), localized: None }
+    }
+
+    pub fn with_message(mut self, message: Message) -> Self {
+        self.localized = Some(message);
+        self
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
 pub const PORT: u16 = 4000;
+// Single-instance lock and argument handoff between app launches
+pub const HANDOFF_PORT: u16 = 4001;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piece_states_use_run_lengths_when_shorter() {
+        let mut have = vec![true; 40];
+        have.extend([false; 2]);
+        let states = PieceStates::encode(&have);
+        assert_eq!((states.encoding, states.len, states.data.as_str()), (PieceEncoding::RunLength, 42, "0,40,2"));
+        assert_eq!(PieceStates::encode(&[false; 3]).data, "3");
+        assert_eq!(PieceStates::encode(&[]).data, "");
+    }
+
+    #[test]
+    fn piece_states_use_the_bitfield_when_scattered() {
+        let have: Vec<bool> = (0..64).map(|i| i % 2 == 0).collect();
+        let states = PieceStates::encode(&have);
+        assert_eq!((states.encoding, states.len, states.data.as_str()), (PieceEncoding::Bitfield, 64, "qqqqqqqqqqo="));
+    }
+
+    #[test]
+    fn base64_pads() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+}
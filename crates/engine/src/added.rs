@@ -0,0 +1,52 @@
+use crate::autosave::Dirty;
+use crate::persist;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const FILE: &str = "added_on.json";
+
+// When each torrent was added, in unix seconds, saved to the state dir by
+// the autosave task. Torrents the session restored from before this was
+// kept count as added when they are first seen.
+#[derive(Clone)]
+pub struct AddedTimes {
+    path: PathBuf,
+    times: Arc<Mutex<HashMap<String, u64>>>,
+    dirty: Dirty,
+}
+
+impl AddedTimes {
+    pub fn load(state_dir: &Path, dirty: Dirty) -> Self {
+        let path = state_dir.join(FILE);
+        Self { times: Arc::new(Mutex::new(persist::load(&path))), path, dirty }
+    }
+
+    // When `id` was added, recording now if it's new
+    pub fn get_or_record(&self, id: &str) -> u64 {
+        let mut times = self.times.lock().unwrap();
+        if let Some(&at) = times.get(id) {
+            return at;
+        }
+        let now = persist::now();
+        times.insert(id.to_string(), now);
+        self.dirty.mark();
+        now
+    }
+
+    pub fn forget(&self, id: &str) {
+        if self.times.lock().unwrap().remove(id).is_some() {
+            self.dirty.mark();
+        }
+    }
+
+    pub fn save_if_dirty(&self) {
+        if !self.dirty.take() {
+            return;
+        }
+        if let Err(e) = persist::save(&self.path, &*self.times.lock().unwrap()) {
+            warn!("Failed to save added times: {}", e);
+        }
+    }
+}
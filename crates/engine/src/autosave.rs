@@ -0,0 +1,79 @@
+use crate::AppState;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+// Background saving for state files that change in bursts or all the time.
+// Stores mark themselves dirty instead of writing on every change; the task
+// below writes the dirty ones once a burst settles (`autosave_debounce_ms`
+// without another change) and otherwise every `autosave_interval_secs`.
+#[derive(Clone, Default)]
+pub struct Autosave {
+    wake: Arc<Notify>,
+}
+
+impl Autosave {
+    // A dirty flag for one store
+    pub fn dirty(&self) -> Dirty {
+        Dirty { flag: Arc::default(), wake: self.wake.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct Dirty {
+    flag: Arc<AtomicBool>,
+    wake: Arc<Notify>,
+}
+
+impl Dirty {
+    // Saved once changes stop coming
+    pub fn mark(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+        self.wake.notify_one();
+    }
+
+    // Saved on the next interval; for counters that change constantly
+    pub fn mark_periodic(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    // True once per batch of changes
+    pub fn take(&self) -> bool {
+        self.flag.swap(false, Ordering::Relaxed)
+    }
+}
+
+// Writes every store with unsaved changes
+pub fn save_dirty(state: &AppState) {
+    state.added.save_if_dirty();
+    state.data_meter.save_if_dirty();
+    state.transfers.save_if_dirty();
+}
+
+pub async fn run(state: AppState) {
+    loop {
+        let (interval, debounce) = {
+            let config = state.config.lock().unwrap();
+            (
+                Duration::from_secs(config.autosave_interval_secs.max(1)),
+                Duration::from_millis(config.autosave_debounce_ms),
+            )
+        };
+        let deadline = Instant::now() + interval;
+        tokio::select! {
+            _ = state.autosave.wake.notified() => {
+                // Wait for the burst to end, but not past the interval
+                loop {
+                    let until = (Instant::now() + debounce).min(deadline);
+                    if tokio::time::timeout_at(until, state.autosave.wake.notified()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            _ = tokio::time::sleep_until(deadline) => {}
+        }
+        save_dirty(&state);
+    }
+}
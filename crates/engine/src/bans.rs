@@ -0,0 +1,71 @@
+use crate::persist::{self, now};
+use anyhow::Result;
+use bridge::BannedPeer;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const FILE: &str = "bans.json";
+const BLOCKLIST: &str = "blocklist.txt";
+
+// Banned peer IPs, optionally expiring, saved to the state dir. The session
+// only reads its blocklist when it opens, so the bans in force then are
+// handed to it as one (see blocklist): a ban placed or lifted later takes
+// effect from the next start, and one expiring in between lasts until then.
+#[derive(Clone)]
+pub struct Bans {
+    path: PathBuf,
+    entries: Arc<Mutex<Vec<BannedPeer>>>,
+}
+
+impl Bans {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self { entries: Arc::new(Mutex::new(persist::load(&path))), path }
+    }
+
+    // Replaces an existing ban of the same IP.
+    pub fn add(&self, ban: BannedPeer) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|b| b.ip != ban.ip);
+        entries.push(ban);
+        persist::save(&self.path, &*entries)
+    }
+
+    pub fn remove(&self, ip: IpAddr) -> Result<bool> {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|b| b.ip != ip);
+        if entries.len() == before {
+            return Ok(false);
+        }
+        persist::save(&self.path, &*entries)?;
+        Ok(true)
+    }
+
+    // Bans still in force; expired ones are dropped on the way.
+    pub fn active(&self) -> Vec<BannedPeer> {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        let now = now();
+        entries.retain(|b| b.expires_at.is_none_or(|t| t > now));
+        if entries.len() != before {
+            let _ = persist::save(&self.path, &*entries);
+        }
+        entries.clone()
+    }
+
+    // Writes the bans in force as a blocklist ("name:first-last" ranges)
+    // next to the bans file, for the session to load. None if there are
+    // none: the session refuses an empty blocklist.
+    pub fn blocklist(&self, state_dir: &Path) -> Result<Option<PathBuf>> {
+        let active = self.active();
+        if active.is_empty() {
+            return Ok(None);
+        }
+        let lines: String = active.iter().map(|b| format!("ban:{}-{}\n", b.ip, b.ip)).collect();
+        let path = state_dir.join(BLOCKLIST);
+        std::fs::write(&path, lines)?;
+        Ok(Some(path))
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+
+// Deeper than any real metainfo or tracker response; without a limit a
+// body of nested lists from a tracker would overflow the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(d) => d.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<String> {
+        self.as_bytes().map(|b| String::from_utf8_lossy(b).into_owned())
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+pub fn decode(data: &[u8]) -> Result<Value> {
+    let (value, rest) = parse(data, 0)?;
+    if !rest.is_empty() {
+        bail!("{} trailing bytes after bencoded value", rest.len());
+    }
+    Ok(value)
+}
+
+// The top-level dictionary's entries with each value's bytes exactly as
+// they appear in `data`, for rewriting some keys without re-encoding others.
+pub fn raw_entries(data: &[u8]) -> Result<Vec<(Vec<u8>, &[u8])>> {
+    if data.first() != Some(&b'd') {
+        bail!("not a bencoded dictionary");
+    }
+    let mut rest = &data[1..];
+    let mut entries = Vec::new();
+    while rest.first() != Some(&b'e') {
+        if rest.is_empty() {
+            bail!("unterminated dictionary");
+        }
+        let (key, r) = parse(rest, 1)?;
+        let Value::Bytes(key) = key else { bail!("dictionary key is not a string") };
+        let (_, after) = parse(r, 1)?;
+        entries.push((key, &r[..r.len() - after.len()]));
+        rest = after;
+    }
+    if rest.len() > 1 {
+        bail!("{} trailing bytes after bencoded value", rest.len() - 1);
+    }
+    Ok(entries)
+}
+
+fn parse(data: &[u8], depth: usize) -> Result<(Value, &[u8])> {
+    if depth > MAX_DEPTH {
+        bail!("bencode nested more than {} levels deep", MAX_DEPTH);
+    }
+    match data.first() {
+        Some(b'i') => {
+            let end = data.iter().position(|&c| c == b'e').ok_or_else(|| anyhow!("unterminated integer"))?;
+            let n = std::str::from_utf8(&data[1..end])?.parse()?;
+            Ok((Value::Int(n), &data[end + 1..]))
+        }
+        Some(b'l') => {
+            let mut rest = &data[1..];
+            let mut items = Vec::new();
+            while rest.first() != Some(&b'e') {
+                if rest.is_empty() {
+                    bail!("unterminated list");
+                }
+                let (item, r) = parse(rest, depth + 1)?;
+                items.push(item);
+                rest = r;
+            }
+            Ok((Value::List(items), &rest[1..]))
+        }
+        Some(b'd') => {
+            let mut rest = &data[1..];
+            let mut dict = BTreeMap::new();
+            while rest.first() != Some(&b'e') {
+                if rest.is_empty() {
+                    bail!("unterminated dictionary");
+                }
+                let (key, r) = parse(rest, depth + 1)?;
+                let Value::Bytes(key) = key else { bail!("dictionary key is not a string") };
+                let (value, r) = parse(r, depth + 1)?;
+                dict.insert(key, value);
+                rest = r;
+            }
+            Ok((Value::Dict(dict), &rest[1..]))
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let colon = data.iter().position(|&c| c == b':').ok_or_else(|| anyhow!("missing string length"))?;
+            let len: usize = std::str::from_utf8(&data[..colon])?.parse()?;
+            let start = colon + 1;
+            let end = start
+                .checked_add(len)
+                .filter(|&end| end <= data.len())
+                .ok_or_else(|| anyhow!("string runs past end of input"))?;
+            Ok((Value::Bytes(data[start..end].to_vec()), &data[end..]))
+        }
+        _ => bail!("invalid bencode"),
+    }
+}
+
+// Dictionaries are BTreeMaps, so keys come out sorted as the spec requires
+// and re-encoding a canonical info dict reproduces the original bytes.
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write(value, &mut out);
+    out
+}
+
+fn write(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Int(i) => out.extend_from_slice(format!("i{}e", i).as_bytes()),
+        Value::Bytes(b) => {
+            out.extend_from_slice(format!("{}:", b.len()).as_bytes());
+            out.extend_from_slice(b);
+        }
+        Value::List(items) => {
+            out.push(b'l');
+            for item in items {
+                write(item, out);
+            }
+            out.push(b'e');
+        }
+        Value::Dict(dict) => {
+            out.push(b'd');
+            for (k, v) in dict {
+                write(&Value::Bytes(k.clone()), out);
+                write(v, out);
+            }
+            out.push(b'e');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let data = b"d4:infod6:lengthi5e4:name3:abce4:listl1:ai-3eee";
+        let value = decode(data).unwrap();
+        assert_eq!(value.get("info").and_then(|i| i.get("name")).and_then(Value::as_str).as_deref(), Some("abc"));
+        assert_eq!(encode(&value), data);
+    }
+
+    #[test]
+    fn rejects_lengths_past_the_end() {
+        assert!(decode(b"5:abc").is_err());
+        assert!(decode(b"18446744073709551615:a").is_err());
+        assert!(decode(b"18446744073709551616:a").is_err());
+        assert!(decode(b"l18446744073709551615:ae").is_err());
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let mut data = vec![b'l'; 100_000];
+        data.extend(vec![b'e'; 100_000]);
+        assert!(decode(&data).is_err());
+        let ok = [vec![b'l'; MAX_DEPTH], vec![b'e'; MAX_DEPTH]].concat();
+        assert!(decode(&ok).is_ok());
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        for data in [&b"i12"[..], b"l1:a", b"d1:a", b"d1:ai1e", b"", b"x", b"3"] {
+            assert!(decode(data).is_err(), "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn raw_entries_keep_the_original_bytes() {
+        let entries = raw_entries(b"d1:bi01e1:ali1eee").unwrap();
+        assert_eq!(entries, vec![(b"b".to_vec(), &b"i01e"[..]), (b"a".to_vec(), &b"li1ee"[..])]);
+    }
+}
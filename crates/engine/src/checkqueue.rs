@@ -0,0 +1,126 @@
+use crate::config::Config;
+use bridge::CheckQueueEntry;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+// Every full or spot check of a torrent's data goes through this queue, so
+// several torrents rechecking at once don't thrash the disk. At most
+// `max_concurrent_checks` run; the rest wait in order and can be cancelled
+// or moved while they wait.
+#[derive(Clone)]
+pub struct CheckQueue {
+    config: Arc<Mutex<Config>>,
+    entries: Arc<Mutex<Vec<Entry>>>, // running first, then waiting in order
+    changed: Arc<Notify>,
+}
+
+struct Entry {
+    id: String,
+    name: String,
+    running: bool,
+}
+
+// Held while a check runs; dropping it frees the slot.
+pub struct CheckSlot {
+    queue: CheckQueue,
+    id: String,
+}
+
+impl Drop for CheckSlot {
+    fn drop(&mut self) {
+        self.queue.entries.lock().unwrap().retain(|e| !(e.running && e.id == self.id));
+        self.queue.changed.notify_waiters();
+    }
+}
+
+struct Waiting<'a> {
+    queue: &'a CheckQueue,
+    id: &'a str,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.queue.cancel(self.id);
+    }
+}
+
+impl CheckQueue {
+    pub fn new(config: Arc<Mutex<Config>>) -> Self {
+        Self { config, entries: Arc::default(), changed: Arc::default() }
+    }
+
+    // Waits for a turn to check `id`. None if the check was cancelled while
+    // waiting.
+    pub async fn enter(&self, id: &str, name: &str) -> Option<CheckSlot> {
+        self.entries.lock().unwrap().push(Entry { id: id.to_string(), name: name.to_string(), running: false });
+        self.changed.notify_waiters();
+        // Leaves the queue if the caller stops waiting
+        let _waiting = Waiting { queue: self, id };
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            {
+                let limit = self.config.lock().unwrap().max_concurrent_checks.max(1);
+                let mut entries = self.entries.lock().unwrap();
+                let running = entries.iter().filter(|e| e.running).count();
+                let first_waiting = entries.iter().position(|e| !e.running);
+                match entries.iter().position(|e| !e.running && e.id == id) {
+                    None => return None,
+                    Some(i) if running < limit && Some(i) == first_waiting => {
+                        entries[i].running = true;
+                        return Some(CheckSlot { queue: self.clone(), id: id.to_string() });
+                    }
+                    Some(_) => {}
+                }
+            }
+            changed.await;
+        }
+    }
+
+    // Drops a waiting check. Checks that are already running aren't stopped.
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|e| e.running || e.id != id);
+        let removed = entries.len() != before;
+        drop(entries);
+        if removed {
+            self.changed.notify_waiters();
+        }
+        removed
+    }
+
+    // Moves a waiting check to `position` among the waiting ones (0 = next).
+    pub fn move_to(&self, id: &str, position: usize) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(from) = entries.iter().position(|e| !e.running && e.id == id) else {
+            return false;
+        };
+        let entry = entries.remove(from);
+        let running = entries.iter().filter(|e| e.running).count();
+        let to = (running + position).min(entries.len());
+        entries.insert(to, entry);
+        drop(entries);
+        self.changed.notify_waiters();
+        true
+    }
+
+    pub fn entries(&self) -> Vec<CheckQueueEntry> {
+        let entries = self.entries.lock().unwrap();
+        let mut waiting = 0;
+        entries
+            .iter()
+            .map(|e| CheckQueueEntry {
+                id: e.id.clone(),
+                name: e.name.clone(),
+                position: if e.running {
+                    None
+                } else {
+                    waiting += 1;
+                    Some(waiting - 1)
+                },
+            })
+            .collect()
+    }
+}
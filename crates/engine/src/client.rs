@@ -0,0 +1,1892 @@
+use crate::added::AddedTimes;
+use crate::autosave::{self, Autosave};
+use crate::bans::Bans;
+use crate::bencode;
+use crate::checkqueue::CheckQueue;
+use crate::config::{self, Config};
+use crate::create;
+use crate::credentials::{self, CredentialStore};
+use crate::datacap::{self, DataMeter};
+use crate::dht::DhtLookups;
+use crate::diagnostics;
+use crate::error::EngineError;
+use crate::events::EventBus;
+use crate::files::{self, FileCache};
+use crate::identity::Identity;
+use crate::jobs::Jobs;
+use crate::maintenance::{self, Pacer};
+use crate::metainfo::{self, Metainfo};
+use crate::modes::Modes;
+use crate::paths;
+use crate::queue::{self, Queues};
+use crate::persist;
+use crate::retention::{self, Retention};
+use crate::roots::ContentRoots;
+use crate::resolve::{self, Resolved};
+use crate::schedule::{self, Schedules};
+use crate::seedgoal::{self, SeedGoals};
+use crate::speed::{self, SpeedMeter};
+use crate::storage::DiskStorageFactory;
+use crate::stream::{self, FileReader, Prefetcher};
+use crate::supervisor::{self, Tasks};
+use crate::tagging::{Candidate, Labels, Tagging};
+use crate::trackerlimits::{self, TrackerLimits};
+use crate::trackersites::TrackerSites;
+use crate::transcoder::Transcoder;
+use crate::torrent::{self, TorrentExt, TorrentInfo};
+use crate::transfer::Transfers;
+use crate::updates::{self, Updates};
+use crate::verify::{self, PieceLayout};
+use crate::writeorder::WriteBudget;
+use crate::{
+    configwatch, connection, external, netwatch, ordering, peers, ratelimit, recheck, resources, scrape,
+    stalled, writeorder, AppState,
+};
+use bridge::{
+    AddParams, AddPreview, BannedPeer, CheckQueueEntry, CreatedTorrent, DataUsage, DhtStats, EventRecord, FileInfo,
+    FilePage, FilePriority, FileTreeNode, HandshakeInfo, JobInfo, JobKind, JobState, PauseReason,
+    PieceStates, QueueMove, RecentTorrent, RetentionCandidate,
+    RetentionRule, SessionSummary, SortOrder, SpeedSummary, StartupTimings, StreamStatus,
+    SwarmHealth, TagInfo, TagRule, TaskHealth, TorrentFilter, TorrentMode, TorrentPage, TorrentPieces, TorrentQueues,
+    TorrentSort, TorrentState, TorrentStatus, TrackerInfo, TrackerLimit, TrackerSite, TrackerStatus, TranscoderInfo,
+    UpdateInfo,
+};
+use futures::StreamExt;
+use librqbit::{
+    AddTorrent, AddTorrentOptions, AddTorrentResponse, ListOnlyResponse, ManagedTorrent, Session, SessionOptions,
+    TorrentStatsState,
+};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, watch};
+use tracing::{info, warn};
+
+// Torrents restored and initialized at once. Initializing checks whatever
+// data of the torrent is on disk.
+const INIT_CONCURRENCY: usize = 16;
+
+// Ports the session tries in turn for incoming peer connections; without
+// one it only connects out.
+const LISTEN_PORTS: std::ops::Range<u16> = 4240..4260;
+
+/// Handle to a running engine.
+///
+/// This is what the desktop app's RPC server is built on, and what an
+/// embedding application should use instead of talking RPC. Cloning is
+/// cheap; every clone drives the same session.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), aurora_engine::EngineError> {
+/// let client = aurora_engine::Client::start(aurora_engine::Config::default()).await?;
+/// let id = client.add_torrent("magnet:?xt=urn:btih:...").await?;
+/// for torrent in client.list_torrents() {
+///     println!("{} {:.1}%", torrent.name, torrent.progress * 100.0);
+/// }
+/// # let _ = id;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Client {
+    pub(crate) state: AppState,
+}
+
+impl Client {
+    /// Creates the download directory, opens a session with `config`, adds
+    /// back the torrents saved in its state dir and starts the engine's
+    /// background tasks (recheck, resource guard, network watch, file
+    /// ordering, stall detection).
+    pub async fn start(config: Config) -> Result<Client, EngineError> {
+        let started = Instant::now();
+        tokio::fs::create_dir_all(&config.download_path).await?;
+
+        let download_path = config.download_path.clone();
+        let state_dir = config.state_dir.clone();
+        let config = Arc::new(Mutex::new(config));
+        let autosave = Autosave::default();
+        let data_meter = DataMeter::load(Path::new(&state_dir), autosave.dirty());
+        let speed = SpeedMeter::new();
+        let bans = Bans::load(Path::new(&state_dir));
+        let blocklist = match bans.blocklist(Path::new(&state_dir)) {
+            Ok(path) => path.and_then(|p| reqwest::Url::from_file_path(p).ok()).map(String::from),
+            Err(e) => {
+                warn!("Failed to write the blocklist of banned peers: {}", e);
+                None
+            }
+        };
+        let peer_opts = connection::peer_options(&config.lock().unwrap().connection);
+        let identity = Identity::new(&config.lock().unwrap().peer_id_prefix)
+            .map_err(|e| EngineError::InvalidInput(e.to_string()))?;
+        info!("Peer id prefix {}", config.lock().unwrap().peer_id_prefix);
+        let content_roots = ContentRoots::load(Path::new(&state_dir));
+        let write_budget = WriteBudget::default();
+        let disk = DiskStorageFactory::new(config.clone(), content_roots.clone());
+        let ratelimits = ratelimit::limits(&config.lock().unwrap(), false);
+        let opening = Instant::now();
+        let session = Session::new_with_opts(
+            download_path.into(),
+            SessionOptions {
+                ratelimits,
+                blocklist_url: blocklist,
+                peer_opts: Some(peer_opts),
+                peer_id: Some(identity.peer_id()),
+                default_storage_factory: Some(writeorder::storage_factory(disk, config.clone(), write_budget.clone())),
+                // The engine saves and restores its torrents itself (see
+                // restore)
+                persistence: None,
+                concurrent_init_limit: Some(INIT_CONCURRENCY),
+                listen_port_range: Some(LISTEN_PORTS),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let session_ms = opening.elapsed().as_millis() as u64;
+        let loading = Instant::now();
+        let events = EventBus::new();
+        let state = AppState {
+            dht: DhtLookups::spawn(session.clone()),
+            prefetch: Prefetcher::default(),
+            checks: CheckQueue::new(config.clone()),
+            jobs: Jobs::new(events.clone()),
+            transcoder: Transcoder::default(),
+            schedules: Schedules::load(Path::new(&state_dir)),
+            tagging: Tagging::load(Path::new(&state_dir)),
+            tracker_limits: TrackerLimits::load(Path::new(&state_dir)),
+            retention: Retention::load(Path::new(&state_dir)),
+            bans,
+            seed_goals: SeedGoals::load(Path::new(&state_dir)),
+            transfers: Transfers::load(Path::new(&state_dir), autosave.dirty()),
+            write_budget,
+            data_meter,
+            tracker_sites: TrackerSites::load(Path::new(&state_dir)),
+            metainfo: Metainfo::load(Path::new(&state_dir)),
+            pacer: Pacer::default(),
+            speed,
+            updates: Updates::load(Path::new(&state_dir)),
+            scrapes: Arc::default(),
+            added: AddedTimes::load(Path::new(&state_dir), autosave.dirty()),
+            autosave,
+            queues: Queues::load(Path::new(&state_dir)),
+            modes: Modes::load(Path::new(&state_dir)),
+            identity,
+            tasks: Tasks::default(),
+            startup: Arc::default(),
+            session,
+            config,
+            events,
+            corrupted: Arc::new(Mutex::new(HashSet::new())),
+            engine_paused: Arc::new(Mutex::new(HashMap::new())),
+            credentials: Arc::new(CredentialStore::default()),
+            trackers: Arc::new(Mutex::new(HashMap::new())),
+            tracker_warnings: Arc::new(Mutex::new(HashMap::new())),
+            udp_trackers: scrape::UdpTrackers::default(),
+            session_paused: Arc::new(AtomicBool::new(false)),
+            session_paused_over: Arc::new(Mutex::new(HashMap::new())),
+            files: FileCache::load(Path::new(&state_dir)),
+            file_orders: ordering::FileOrders::load(Path::new(&state_dir)),
+            stalled: Arc::new(Mutex::new(HashSet::new())),
+            content_roots,
+        };
+        let state_ms = loading.elapsed().as_millis() as u64;
+
+        let restoring = Instant::now();
+        let client = Client { state };
+        let torrents = client.restore().await?;
+        let state = client.state;
+
+        let startup = StartupTimings {
+            session_ms,
+            state_ms,
+            restore_ms: restoring.elapsed().as_millis() as u64,
+            total_ms: started.elapsed().as_millis() as u64,
+            torrents,
+        };
+        info!(
+            "Started in {} ms with {} torrents (session {} ms, state files {} ms, restore {} ms)",
+            startup.total_ms, startup.torrents, startup.session_ms, startup.state_ms, startup.restore_ms
+        );
+        state.startup.set(startup).ok();
+
+        // Critical loops enforce limits or save state and are restarted if they panic
+        supervisor::spawn(&state, "recheck", true, recheck::run);
+        supervisor::spawn(&state, "resources", true, resources::run);
+        supervisor::spawn(&state, "netwatch", true, netwatch::run);
+        supervisor::spawn(&state, "ordering", true, ordering::run);
+        supervisor::spawn(&state, "stalled", false, stalled::run);
+        supervisor::spawn(&state, "schedule", true, schedule::run);
+        supervisor::spawn(&state, "trackerlimits", true, trackerlimits::run);
+        supervisor::spawn(&state, "seedgoal", true, seedgoal::run);
+        supervisor::spawn(&state, "retention", true, retention::run);
+        supervisor::spawn(&state, "datacap", true, datacap::run);
+        supervisor::spawn(&state, "ratelimit", true, ratelimit::run);
+        supervisor::spawn(&state, "speed", true, speed::run); // counts toward the data cap
+        supervisor::spawn(&state, "updates", false, updates::run);
+        supervisor::spawn(&state, "autosave", true, autosave::run);
+        supervisor::spawn(&state, "writeorder", true, writeorder::run);
+        supervisor::spawn(&state, "queue", true, queue::run);
+
+        Ok(Client { state })
+    }
+
+    /// Adds a magnet link or .torrent path and returns its info hash.
+    ///
+    /// Announce URLs carrying a `{passkey}` placeholder are filled in from
+    /// the credential store before the first announce. If the session is
+    /// paused the torrent is added paused.
+    ///
+    /// If some of its files are already in the download folder, they are
+    /// checked first (status "Checking") and only missing or bad pieces
+    /// are downloaded.
+    pub async fn add_torrent(&self, source: &str) -> Result<String, EngineError> {
+        self.add_over_existing(source, false, None, TorrentMode::AutoManaged).await
+    }
+
+    /// Adds a torrent known only by its info hash (40 hex or 32 base32
+    /// characters), like a magnet link without trackers: peers come from
+    /// the DHT, which also has to supply the metadata. Fails if the session
+    /// runs without DHT.
+    pub async fn add_by_info_hash(&self, info_hash: &str) -> Result<String, EngineError> {
+        let info_hash =
+            external::normalize_info_hash(info_hash.trim()).map_err(|e| EngineError::InvalidInput(e.to_string()))?;
+        if self.state.session.get_dht().is_none() {
+            return Err(EngineError::Unavailable("DHT is off; a bare info hash can only be looked up there".into()));
+        }
+        self.add_torrent(&format!("magnet:?xt=urn:btih:{}", info_hash)).await
+    }
+
+    /// Like [`Client::add_torrent`], for a torrent none of whose files are
+    /// in the download folder yet. The session checks whatever data it
+    /// finds, so with some of them there this fails with
+    /// [`EngineError::Unsupported`] instead of adding the torrent.
+    pub async fn add_torrent_skip_checking(&self, source: &str) -> Result<String, EngineError> {
+        self.add_over_existing(source, true, None, TorrentMode::AutoManaged).await
+    }
+
+    /// Adds a torrent from a download link as search providers hand them
+    /// out: a magnet link, a .torrent URL, or a page in front of either,
+    /// followed through a few redirects or pages. Otherwise like
+    /// [`Client::add_torrent`], with `params` applied on top; see
+    /// [`AddParams::mode`] for how `paused` and `auto_managed` combine.
+    pub async fn add_from_link(&self, link: &str, params: AddParams) -> Result<String, EngineError> {
+        let state = &self.state;
+        let resolved = resolve::resolve(link, &state.user_agent())
+            .await
+            .map_err(|e| EngineError::Unavailable(format!("Couldn't get the torrent from the link: {:#}", e)))?;
+
+        let mode = params.mode();
+        let folder = params.save_path.as_deref().map(Path::new);
+        if let Some(folder) = folder {
+            tokio::fs::create_dir_all(folder).await?;
+        }
+        let id = match resolved {
+            Resolved::Magnet(magnet) => self.add_over_existing(&magnet, false, folder, mode).await?,
+            Resolved::Torrent(bytes) => {
+                // The session reads .torrent files from disk
+                let file = tempfile::Builder::new().suffix(".torrent").tempfile()?;
+                std::fs::write(file.path(), bytes)?;
+                self.add_over_existing(&file.path().to_string_lossy(), false, folder, mode).await?
+            }
+        };
+
+        if params.category.is_some() || !params.tags.is_empty() {
+            let mut labels = state.tagging.labels(&id);
+            if params.category.is_some() {
+                labels.category = params.category;
+            }
+            if !params.tags.is_empty() {
+                labels.tags = params.tags;
+            }
+            state.tagging.set_labels(&id, labels)?;
+        }
+        Ok(id)
+    }
+
+    /// Adds a torrent that stays paused until `start_at` (unix seconds). A
+    /// time in the past starts it right away.
+    pub async fn add_torrent_scheduled(&self, source: &str, start_at: u64) -> Result<String, EngineError> {
+        if start_at <= persist::now() {
+            return self.add_torrent(source).await;
+        }
+        let options = AddTorrentOptions { paused: true, ..Default::default() };
+        let handle = self.add(source, options).await?;
+        let id = handle.info_hash().as_string();
+        self.state.schedules.set(&id, start_at)?;
+        self.state.hold(&id, PauseReason::Scheduled);
+        Ok(id)
+    }
+
+    /// Pauses a torrent until `start_at` (unix seconds), replacing any
+    /// earlier schedule for it.
+    pub async fn schedule_start(&self, id: &str, start_at: u64) -> Result<(), EngineError> {
+        let handle = self.handle(id)?;
+        let state = &self.state;
+        if let Some(reason) = state.engine_paused.lock().unwrap().get(id) {
+            if *reason != PauseReason::Scheduled && *reason != PauseReason::SessionPaused {
+                return Err(EngineError::Unavailable(format!("{} is paused ({:?})", id, reason)));
+            }
+        }
+        state.schedules.set(id, start_at)?;
+        if !state.engine_paused.lock().unwrap().contains_key(id) {
+            state.session.pause(&handle).await?;
+            state.hold(id, PauseReason::Scheduled);
+        }
+        Ok(())
+    }
+
+    /// Drops a torrent's scheduled start and starts it now (or when the
+    /// session is resumed).
+    pub async fn cancel_scheduled_start(&self, id: &str) -> Result<(), EngineError> {
+        let handle = self.handle(id)?;
+        let state = &self.state;
+        if !state.schedules.remove(id)? {
+            return Err(EngineError::NotFound(format!("Scheduled start of {}", id)));
+        }
+        Ok(state.release(&handle, PauseReason::Scheduled).await?)
+    }
+
+    /// Torrent id -> scheduled start time (unix seconds).
+    pub fn scheduled_starts(&self) -> HashMap<String, u64> {
+        self.state.schedules.all()
+    }
+
+    /// Adds a torrent whose data already exists in `content_dir` (the folder
+    /// holding the torrent's files) and seeds it from there. Every piece is
+    /// verified first; the torrent only starts if all of them match, so it
+    /// never downloads. The check runs before the session gets the
+    /// torrent, so on a mismatch it's never added and nothing on disk is
+    /// touched.
+    pub async fn add_torrent_for_seeding(&self, source: &str, content_dir: &Path) -> Result<String, EngineError> {
+        if !content_dir.is_dir() {
+            return Err(EngineError::NotFound(format!("Folder {}", content_dir.display())));
+        }
+        // Checked before the session has the torrent: it would set the
+        // files' lengths after its own check, mismatched or not
+        let listed = self.list(source).await?;
+        let id = listed.info_hash.as_string();
+        let info = TorrentInfo::from_info(&listed.info).map_err(|e| EngineError::InvalidInput(e.to_string()))?;
+        let state = &self.state;
+        let layout = PieceLayout::new(content_dir, &info)?;
+        let job = state.jobs.start(JobKind::SeedCheck, Some(&id), &info.name);
+        let Some(slot) = state.checks.enter(&id, &info.name).await else {
+            job.finish_cancelled();
+            return Err(EngineError::Unavailable(format!("Check of {} was cancelled", id)));
+        };
+        job.running();
+        info!("Verifying {} against {}", id, content_dir.display());
+        let checked = verify::check_all(&layout, &info, Some(&job)).await;
+        drop(slot);
+        let Some(bad) = checked else {
+            job.finish(Ok(None));
+            return Err(EngineError::Unavailable(format!("Check of {} was cancelled", id)));
+        };
+        if !bad.is_empty() {
+            let error = format!(
+                "{} of {} pieces don't match the data in {}",
+                bad.len(),
+                layout.num_pieces(),
+                content_dir.display()
+            );
+            job.finish(Err(error.clone()));
+            return Err(EngineError::InvalidInput(error));
+        }
+        job.finish(Ok(None));
+
+        // The storage finds the folder when the session adds the torrent
+        state.content_roots.set(&id, content_dir)?;
+        let options = AddTorrentOptions {
+            paused: true,
+            output_folder: Some(content_dir.to_string_lossy().into_owned()),
+            overwrite: true, // use the files that are there
+            ..Default::default()
+        };
+        let handle = match self.add_from(source, AddTorrent::from_bytes(listed.torrent_bytes), options).await {
+            Ok(handle) => handle,
+            Err(e) => {
+                state.content_roots.forget(&id);
+                return Err(e);
+            }
+        };
+        self.start_added(&handle).await?;
+        Ok(id)
+    }
+
+    // `folder` takes the place of the download folder: a multi-file
+    // torrent gets a folder named after it there, like it would in the
+    // download folder
+    async fn add_over_existing(
+        &self,
+        source: &str,
+        skip_checking: bool,
+        folder: Option<&Path>,
+        mode: TorrentMode,
+    ) -> Result<String, EngineError> {
+        let state = &self.state;
+        let options = AddTorrentOptions {
+            paused: true,
+            overwrite: true,
+            output_folder: folder.map(|f| f.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        // The storage looks up a content folder of the torrent's own when
+        // the session adds it, so that's saved first; skip_checking looks
+        // for data that would be checked. Both need the torrent's id and
+        // files up front.
+        let handle = if folder.is_some() || skip_checking {
+            let listed = self.list(source).await?;
+            let id = listed.info_hash.as_string();
+            let info = TorrentInfo::from_info(&listed.info).map_err(|e| EngineError::InvalidInput(e.to_string()))?;
+            let root = match folder {
+                Some(folder) => files::content_root(&folder.to_string_lossy(), &info),
+                None => state.content_root(&id, &info),
+            };
+            if skip_checking {
+                let existing = files::disk_paths(&root, &info)?
+                    .into_iter()
+                    .any(|path| path.metadata().is_ok_and(|m| m.len() > 0));
+                if existing {
+                    return Err(EngineError::Unsupported(format!(
+                        "Files of {} are already in {}; the session checks them when it adds the torrent",
+                        id,
+                        root.display()
+                    )));
+                }
+            }
+            if folder.is_some() {
+                state.content_roots.set(&id, &root)?;
+            }
+            self.add_from(source, AddTorrent::from_bytes(listed.torrent_bytes), options).await?
+        } else {
+            self.add(source, options).await?
+        };
+        let id = handle.info_hash().as_string();
+        state.modes.set(&id, mode)?;
+        // The session checks data already on disk while it initializes the
+        // torrent; start_added holds it as Checking until that's done
+        self.start_added(&handle).await?;
+        Ok(id)
+    }
+
+    // Adds back every torrent with a stored .torrent (see Metainfo), up to
+    // INIT_CONCURRENCY at once. The session's own persistence isn't used:
+    // it only takes librqbit's filesystem storage, and it would write the
+    // trackers with passkeys filled in to disk. Without its saved
+    // have-bitfields the session checks each torrent's data again. They are
+    // added paused and started like new ones, unless the engine should
+    // still hold them (see start_added); holds for other reasons come back
+    // with the tasks that set them. Returns how many there are.
+    async fn restore(&self) -> Result<usize, EngineError> {
+        let restored: Vec<Arc<ManagedTorrent>> = futures::stream::iter(self.state.metainfo.ids())
+            .map(|id| async move {
+                let restored = self.restore_torrent(&id).await;
+                if let Err(e) = &restored {
+                    warn!("Failed to restore {}: {}", id, e);
+                }
+                restored.ok()
+            })
+            .buffer_unordered(INIT_CONCURRENCY)
+            .filter_map(|handle| async { handle })
+            .collect()
+            .await;
+        for handle in &restored {
+            self.start_added(handle).await?;
+        }
+        Ok(restored.len())
+    }
+
+    // Adds a saved torrent with the files it had selected
+    async fn restore_torrent(&self, id: &str) -> Result<Arc<ManagedTorrent>, EngineError> {
+        let state = &self.state;
+        let bytes = state.metainfo.get(id).ok_or_else(|| EngineError::torrent_not_found(id))?;
+        let preview = external::parse_torrent(&bytes).map_err(|e| EngineError::InvalidInput(e.to_string()))?;
+        let mut files = preview.files;
+        for (file, priority) in files.iter_mut().zip(state.files.priorities(id)) {
+            file.priority = priority;
+        }
+        let window = state.config.lock().unwrap().ordered_files_window;
+        let only = ordering::selection(&files, state.file_orders.get(id).as_deref(), window);
+        let options = AddTorrentOptions {
+            paused: true,
+            overwrite: true, // its files are there already
+            only_files: (only.len() < files.len()).then(|| only.into_iter().collect()),
+            ..Default::default()
+        };
+        self.add_to_session(AddTorrent::from_bytes(bytes), &preview.trackers, options).await
+    }
+
+    // Starts a torrent that was added paused, unless it was added in the
+    // Paused mode, was paused at its share limit, the session is paused or
+    // a rule scheduled it for later.
+    // While the session is still checking it (existing data, or a restored
+    // torrent's saved bitfield), it is held as Checking and started once
+    // the check is done.
+    async fn start_added(&self, handle: &Arc<ManagedTorrent>) -> Result<(), EngineError> {
+        let state = &self.state;
+        let id = handle.info_hash().as_string();
+        if state.schedules.all().get(&id).is_some_and(|&at| at > persist::now()) {
+            state.hold(&id, PauseReason::Scheduled);
+        } else if state.modes.get(&id) == TorrentMode::Paused {
+            state.hold(&id, PauseReason::User);
+        } else if state.seed_goals.is_reached(&id) {
+            state.hold(&id, PauseReason::ShareLimit);
+        } else if state.is_session_paused() {
+            state.engine_paused.lock().unwrap().insert(id, PauseReason::SessionPaused);
+        } else if matches!(handle.stats().state, TorrentStatsState::Initializing) {
+            state.hold(&id, PauseReason::Checking);
+            recheck::initial(state.clone(), handle.clone());
+        } else {
+            state.session.unpause(handle).await?;
+        }
+        Ok(())
+    }
+
+    // Swaps a torrent in the session for the same one from other .torrent
+    // bytes, e.g. with other trackers. Everything the engine keeps about it
+    // stays; its data is checked again, and a running torrent restarts
+    // after that.
+    async fn readd(&self, handle: &Arc<ManagedTorrent>, bytes: Vec<u8>) -> Result<(), EngineError> {
+        let state = &self.state;
+        let id = handle.info_hash().as_string();
+        let running = !handle.is_paused();
+        let options = AddTorrentOptions {
+            paused: true,
+            overwrite: true,
+            only_files: handle.only_files(),
+            ..Default::default()
+        };
+        state.session.delete(handle.info_hash().into(), false).await?;
+        let added = state.session.add_torrent(AddTorrent::from_bytes(bytes), Some(options)).await?;
+        let handle = added.into_handle().ok_or_else(|| EngineError::Internal(anyhow::anyhow!("torrent was only listed")))?;
+        if running {
+            state.hold(&id, PauseReason::Checking);
+            recheck::initial(state.clone(), handle);
+        }
+        Ok(())
+    }
+
+    // Takes back a torrent the engine can't use, leaving the data alone.
+    async fn forget(&self, handle: &ManagedTorrent) -> Result<(), EngineError> {
+        Ok(self.state.remove(handle, false).await?)
+    }
+
+    // Adds to the session and fills in private tracker passkeys before the
+    // first announce.
+    async fn add(&self, source: &str, options: AddTorrentOptions) -> Result<Arc<ManagedTorrent>, EngineError> {
+        let torrent = load(source).await?;
+        self.add_from(source, torrent, options).await
+    }
+
+    // Parses a source without adding it, fetching a magnet link's metadata
+    // first. For adds that need the info hash or the files up front.
+    async fn list(&self, source: &str) -> Result<ListOnlyResponse, EngineError> {
+        let options = AddTorrentOptions { list_only: true, ..Default::default() };
+        match self.state.session.add_torrent(load(source).await?, Some(options)).await? {
+            AddTorrentResponse::ListOnly(listed) => Ok(listed),
+            AddTorrentResponse::AlreadyManaged(_, handle) | AddTorrentResponse::Added(_, handle) => {
+                Err(EngineError::InvalidInput(format!("{} is already added", handle.info_hash().as_string())))
+            }
+        }
+    }
+
+    // `source` is what `torrent` was loaded from: its magnet link or
+    // .torrent file
+    async fn add_from(
+        &self,
+        source: &str,
+        torrent: AddTorrent<'_>,
+        options: AddTorrentOptions,
+    ) -> Result<Arc<ManagedTorrent>, EngineError> {
+        let state = &self.state;
+        let source = source.trim();
+        let bytes = match &torrent {
+            AddTorrent::TorrentFileBytes(bytes) => Some(bytes.to_vec()),
+            AddTorrent::Url(_) => None,
+        };
+        let announce: Vec<String> = crate::handle_external_add(source)
+            .await
+            .map(|p| p.trackers)
+            .unwrap_or_default();
+        let handle = self.add_to_session(torrent, &announce, options).await?;
+        let id = handle.info_hash().as_string();
+        let info = handle.info();
+        let disk_names = match files::disk_names(&info) {
+            Ok(names) => names,
+            Err(e) => {
+                self.forget(&handle).await?;
+                return Err(EngineError::InvalidInput(e.to_string()));
+            }
+        };
+
+        let files = info.files();
+        for (file, disk_name) in files.iter().zip(disk_names) {
+            if paths::renamed(&file.name, &disk_name) {
+                warn!("{}: {} collides with another file, saving it as {}", id, file.name, disk_name);
+            }
+        }
+        let candidate = Candidate {
+            name: &info.name,
+            total_size: info.total_size,
+            files: files.iter().map(|f| f.name.as_str()).collect(),
+            trackers: &announce,
+        };
+        // Auto-tagging; a torrent added again keeps the labels it has
+        if state.tagging.labels(&id) == Labels::default() {
+            let mut labels = state.tagging.evaluate(&candidate);
+            if labels.category.is_none() {
+                labels.category = state.tracker_limits.default_category(&announce);
+            }
+            if labels != Labels::default() {
+                info!("Tagged {} with {:?}", id, labels);
+                if let Err(e) = state.tagging.set_labels(&id, labels) {
+                    warn!("Failed to save tags of {}: {}", id, e);
+                }
+            }
+            // Rules can hold new torrents back until a time of day, e.g. so
+            // overnight batches don't compete with daytime downloads
+            if let Some(minute) = state.tagging.start_time(&candidate) {
+                if !state.schedules.all().contains_key(&id) {
+                    let start_at = schedule::next_at(minute, persist::now());
+                    info!("{} waits for its rule's start time ({})", id, start_at);
+                    state.schedules.set(&id, start_at)?;
+                }
+            }
+        }
+        // Keep the .torrent as added, with the trackers it was added with:
+        // it's what the torrent is restored from, and where edits to its
+        // comment and creator go. A magnet link's is the one the session
+        // built from the metadata.
+        let bytes = bytes
+            .or_else(|| handle.metadata.load().as_ref().map(|m| metainfo::from_info(&m.info_bytes, &announce)))
+            .ok_or_else(|| anyhow::anyhow!("the session has no metadata"));
+        if let Err(e) = bytes.and_then(|bytes| state.metainfo.store(&id, &bytes)) {
+            self.forget(&handle).await?;
+            return Err(EngineError::Internal(anyhow::anyhow!("Failed to keep the .torrent of {}: {}", id, e)));
+        }
+        state.added.get_or_record(&id);
+        Ok(handle)
+    }
+
+    // Adds to the session with private tracker passkeys filled in before
+    // the first announce. The session keeps a torrent's trackers as it was
+    // added with them, so they go into the magnet link or .torrent it gets.
+    // `announce` is the torrent's trackers as given.
+    async fn add_to_session(
+        &self,
+        torrent: AddTorrent<'_>,
+        announce: &[String],
+        options: AddTorrentOptions,
+    ) -> Result<Arc<ManagedTorrent>, EngineError> {
+        let state = &self.state;
+        let mut resolved = Vec::with_capacity(announce.len());
+        for url in announce {
+            resolved.push(state.credentials.apply(url).await);
+        }
+        let passkeys: HashMap<&str, &str> =
+            announce.iter().zip(&resolved).filter(|(a, r)| a != r).map(|(a, r)| (a.as_str(), r.as_str())).collect();
+        let with_passkeys = |url: &str| passkeys.get(url).map_or_else(|| url.to_string(), |r| r.to_string());
+        let torrent = match torrent {
+            torrent if passkeys.is_empty() => torrent,
+            AddTorrent::TorrentFileBytes(bytes) => AddTorrent::from_bytes(
+                metainfo::map_trackers(&bytes, with_passkeys).map_err(|e| EngineError::InvalidInput(e.to_string()))?,
+            ),
+            AddTorrent::Url(url) => AddTorrent::from_url(external::map_magnet_trackers(&url, with_passkeys)),
+        };
+        // Unsafe file names fail the storage (see paths::check)
+        let added = state.session.add_torrent(torrent, Some(options)).await.map_err(|e| {
+            match e.downcast_ref::<paths::UnsafeName>() {
+                Some(unsafe_name) => EngineError::InvalidInput(unsafe_name.to_string()),
+                None => e.into(),
+            }
+        })?;
+        let handle = added.into_handle().ok_or_else(|| EngineError::Internal(anyhow::anyhow!("torrent was only listed")))?;
+        state.trackers.lock().unwrap().insert(handle.info_hash().as_string(), resolved);
+        Ok(handle)
+    }
+
+    /// Scrapes the trackers of a magnet link, .torrent path or bare info hash
+    /// and asks the DHT for peers, without adding anything. Takes a few
+    /// seconds; trackers that fail are reported individually.
+    pub async fn preview_swarm(&self, source: &str) -> Result<SwarmHealth, EngineError> {
+        let state = &self.state;
+        if state.is_session_paused() {
+            return Err(EngineError::Unavailable("Session is paused".into()));
+        }
+        let (info_hash, trackers) = match external::normalize_info_hash(source.trim()) {
+            Ok(hash) => (hash, vec![]),
+            Err(_) => {
+                let preview = crate::handle_external_add(source)
+                    .await
+                    .map_err(|e| EngineError::InvalidInput(e.to_string()))?;
+                (preview.info_hash, preview.trackers)
+            }
+        };
+        let raw: [u8; 20] = hex::decode(&info_hash)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| EngineError::InvalidInput(format!("bad info hash {}", info_hash)))?;
+
+        let user_agent = &state.user_agent();
+        let scrapes = futures::future::join_all(
+            trackers.iter().map(|url| maintenance::scrape_tracker(state, url, &raw, user_agent)),
+        );
+        let (trackers, dht_peers) = tokio::join!(scrapes, state.dht.peer_count(&info_hash));
+
+        Ok(SwarmHealth {
+            info_hash,
+            seeders: trackers.iter().filter_map(|t| t.seeders).max(),
+            leechers: trackers.iter().filter_map(|t| t.leechers).max(),
+            dht_peers,
+            trackers,
+        })
+    }
+
+    /// Fetches a magnet's metadata from peers (found through its trackers
+    /// or, for trackerless magnets, the DHT) without adding it, and returns
+    /// the preview with its files filled in. The info dictionary is checked
+    /// against the magnet's info hash.
+    pub async fn resolve_magnet(&self, magnet: &str) -> Result<AddPreview, EngineError> {
+        let magnet = magnet.trim();
+        let preview = external::parse_magnet(magnet).map_err(|e| EngineError::InvalidInput(e.to_string()))?;
+        let state = &self.state;
+        if state.is_session_paused() {
+            return Err(EngineError::Unavailable("Session is paused".into()));
+        }
+        let timeout = Duration::from_secs(state.config.lock().unwrap().metadata_timeout_secs);
+        let listed = tokio::time::timeout(timeout, self.list(magnet))
+            .await
+            .map_err(|_| EngineError::Unavailable(format!("No peer sent the metadata within {:?}", timeout)))??;
+        // The session hands the metadata back as a .torrent around the info
+        // dictionary it received
+        let (_, info_bytes) = bencode::raw_entries(&listed.torrent_bytes)?
+            .into_iter()
+            .find(|(key, _)| key == b"info")
+            .ok_or_else(|| anyhow::anyhow!("listed torrent has no info dictionary"))?;
+        Ok(external::with_metadata(preview, info_bytes)?)
+    }
+
+    /// Snapshot of every torrent in the session. Torrents with more than
+    /// `inline_file_limit` files come back with an empty `files` list; use
+    /// [`Client::torrent_files`] for those.
+    pub fn list_torrents(&self) -> Vec<TorrentState> {
+        let state = &self.state;
+        let corrupted = state.corrupted.lock().unwrap().clone();
+        let engine_paused = state.engine_paused.lock().unwrap().clone();
+        let stalled = state.stalled.lock().unwrap().clone();
+        let (inline_file_limit, global_ratio) = {
+            let config = state.config.lock().unwrap();
+            (config.inline_file_limit, config.ratio_limit)
+        };
+        let mut torrents = Vec::new();
+
+        for handle in state.torrents() {
+            let info = handle.info();
+            let stats = handle.stats();
+            let id = handle.info_hash().as_string();
+
+            // Huge torrents only report a count; the UI pages through
+            // GetTorrentFiles instead.
+            let all_files = state.files.get(&id, &handle);
+            let with_progress = files::with_progress(&all_files, 0..all_files.len(), &stats.file_progress);
+            let amount_left = with_progress
+                .iter()
+                .filter(|f| f.priority != FilePriority::Skip)
+                .map(|f| f.size.saturating_sub(f.downloaded))
+                .sum();
+            let files = if all_files.len() <= inline_file_limit { with_progress } else { vec![] };
+            state.transfers.record(&id, &handle);
+            let (downloaded_session, uploaded_session) = state.transfers.session_totals(&id);
+            let trackers = state.trackers.lock().unwrap().get(&id).cloned().unwrap_or_default();
+            let scrapes = state.scrapes.lock().unwrap().get(&id).cloned().unwrap_or_default();
+            let labels = state.tagging.labels(&id);
+            let ratio = seedgoal::ratio(state, &id, &handle);
+            let ratio_limit = state.seed_goals.ratio_limit(&id, labels.category.as_deref(), global_ratio);
+            let meta = state.metainfo.fields(&id);
+            let added_on = state.added.get_or_record(&id);
+            let queue_position = state.queues.position(&id);
+            let mode = state.modes.get(&id);
+            let tracker_warning = scrapes.iter().any(|s| s.error.is_some() || s.warning.is_some());
+            let pause_reason = engine_paused.get(&id).copied();
+            let download_speed = torrent::download_speed(&stats);
+            let eta_secs = match (amount_left, download_speed) {
+                (0, _) => Some(0),
+                (_, 0) => None,
+                (left, speed) => Some(left.div_ceil(speed)),
+            };
+            let status = if corrupted.contains(&id) {
+                TorrentStatus::Corrupted
+            } else if engine_paused.get(&id) == Some(&PauseReason::Checking)
+                || matches!(stats.state, TorrentStatsState::Initializing)
+            {
+                TorrentStatus::Checking
+            } else if engine_paused.contains_key(&id) {
+                TorrentStatus::Paused
+            } else if stats.finished {
+                TorrentStatus::Seeding
+            } else if stalled.contains(&id) {
+                TorrentStatus::Stalled
+            } else {
+                TorrentStatus::Downloading
+            };
+
+            torrents.push(TorrentState {
+                id,
+                name: info.name.clone(),
+                progress: torrent::progress(&stats),
+                status,
+                download_speed,
+                upload_speed: torrent::upload_speed(&stats),
+                total_size: info.total_size,
+                file_count: all_files.len(),
+                files,
+                peers: peers::connected(&handle),
+                trackers: trackers
+                    .iter()
+                    .map(|url| {
+                        let url = state.credentials.mask(url);
+                        let message = state.tracker_warnings.lock().unwrap().get(&url).cloned();
+                        let scrape = scrapes.iter().find(|s| s.url == url);
+                        TrackerInfo {
+                            seeders: scrape.and_then(|s| s.seeders),
+                            leechers: scrape.and_then(|s| s.leechers),
+                            status: match scrape {
+                                Some(s) if s.error.is_some() => TrackerStatus::NotWorking,
+                                Some(_) => TrackerStatus::Working,
+                                None => TrackerStatus::Unknown,
+                            },
+                            url,
+                            message,
+                        }
+                    })
+                    .collect(),
+                tags: labels.tags,
+                category: labels.category,
+                ratio,
+                ratio_limit,
+                comment: meta.comment,
+                created_by: meta.created_by,
+                amount_left,
+                downloaded_session,
+                uploaded_session,
+                queue_position,
+                added_on,
+                eta_secs,
+                mode,
+                tracker_warning,
+                pause_reason,
+            });
+        }
+        let live: Vec<String> = torrents.iter().map(|t| t.id.clone()).collect();
+        state.files.retain(&live);
+        torrents
+    }
+
+    /// Up to `limit` torrents matching `filter`, sorted by `sort_by`,
+    /// starting at `offset`. `total` counts every match, for a virtualized
+    /// list or Web API client paging through the rest.
+    pub fn list_torrents_page(
+        &self,
+        offset: usize,
+        limit: usize,
+        sort_by: TorrentSort,
+        order: SortOrder,
+        filter: &TorrentFilter,
+    ) -> TorrentPage {
+        let search = filter.search.as_deref().map(str::to_lowercase).filter(|s| !s.is_empty());
+        let mut torrents: Vec<TorrentState> = self
+            .list_torrents()
+            .into_iter()
+            .filter(|t| filter.status.is_none_or(|s| t.status == s))
+            .filter(|t| filter.category.is_none() || t.category == filter.category)
+            .filter(|t| filter.tag.as_ref().is_none_or(|tag| t.tags.contains(tag)))
+            .filter(|t| search.as_ref().is_none_or(|s| t.name.to_lowercase().contains(s)))
+            .collect();
+        sort_torrents(&mut torrents, sort_by, order);
+        let total = torrents.len();
+        let torrents = torrents.into_iter().skip(offset).take(limit).collect();
+        TorrentPage { total, offset, torrents }
+    }
+
+    /// Which pieces of torrent `id` we have, for the bar under the progress
+    /// bar. With `cells`, the pieces are grouped into that many runs, had if
+    /// every piece in it is. librqbit doesn't expose its peers' bitfields,
+    /// so there are no per-piece peer counts.
+    pub fn piece_states(&self, id: &str, cells: Option<usize>) -> Result<TorrentPieces, EngineError> {
+        let handle = self.handle(id)?;
+        let have = self.state.have_pieces(&handle);
+        let piece_states = PieceStates::encode(&torrent::cells(&have, cells.unwrap_or(0)));
+        Ok(TorrentPieces { num_pieces: have.len(), piece_states })
+    }
+
+    /// Up to `limit` files of torrent `id` starting at `offset`, with progress.
+    pub fn torrent_files(&self, id: &str, offset: usize, limit: usize) -> Result<FilePage, EngineError> {
+        let handle = self.handle(id)?;
+        let files = self.state.files.get(id, &handle);
+        let end = offset.saturating_add(limit).min(files.len());
+        Ok(FilePage {
+            total: files.len(),
+            offset,
+            files: files::with_progress(&files, offset.min(end)..end, &handle.stats().file_progress),
+        })
+    }
+
+    /// The torrent's files as a folder tree with per-folder aggregates.
+    pub fn file_tree(&self, id: &str) -> Result<FileTreeNode, EngineError> {
+        let handle = self.handle(id)?;
+        let info = handle.info();
+        let files = self.state.files.get(id, &handle);
+        let files = files::with_progress(&files, 0..files.len(), &handle.stats().file_progress);
+        Ok(files::build_tree(&info.name, &files))
+    }
+
+    /// Sets the priority of the given files and returns how many were changed.
+    /// [`FilePriority::Skip`] stops them downloading.
+    pub async fn set_file_priority(
+        &self,
+        id: &str,
+        file_indices: Vec<usize>,
+        priority: FilePriority,
+    ) -> Result<usize, EngineError> {
+        self.set_priority(id, |_| file_indices, priority).await
+    }
+
+    /// Sets the priority of every file at or below `path` ("" for the whole
+    /// torrent) and returns how many files that covered.
+    pub async fn set_folder_priority(&self, id: &str, path: &str, priority: FilePriority) -> Result<usize, EngineError> {
+        self.set_priority(id, |files| files::indices_under(files, path), priority).await
+    }
+
+    /// Where file `file_index` of torrent `id` is (or will be) on disk.
+    pub fn file_path(&self, id: &str, file_index: usize) -> Result<PathBuf, EngineError> {
+        let handle = self.handle(id)?;
+        let info = handle.info();
+        files::absolute_path(&self.state.content_root(id, &info), &info, file_index)
+            .ok_or_else(|| EngineError::NotFound(format!("File {} of torrent {}", file_index, id)))
+    }
+
+    /// Opens a file for playback from byte `first` to `last` (inclusive,
+    /// end of file if `None`). Reads wait for missing pieces, and the
+    /// session fetches the pieces ahead of the player first.
+    pub async fn open_stream(
+        &self,
+        id: &str,
+        file_index: usize,
+        first: u64,
+        last: Option<u64>,
+    ) -> Result<FileReader, EngineError> {
+        let handle = self.handle(id)?;
+        FileReader::open(self.state.prefetch.clone(), handle, id, file_index, first, last).await
+    }
+
+    /// Buffered ranges and prefetch window of a file being played, and an
+    /// estimate of when `offset` will be playable.
+    pub fn stream_status(&self, id: &str, file_index: usize, offset: Option<u64>) -> Result<StreamStatus, EngineError> {
+        let handle = self.handle(id)?;
+        let have = self.state.have_pieces(&handle);
+        stream::status(&self.state.prefetch, &handle, &have, id, file_index, offset)
+    }
+
+    /// Hashes a file or folder and writes a .torrent for it to `output`.
+    /// `piece_size` must be a power of two and is clamped to 16 KiB–128 MiB;
+    /// `None` picks one from the total size. The result has the piece size
+    /// and count actually used.
+    pub async fn create_torrent(
+        &self,
+        source: &Path,
+        output: &Path,
+        trackers: Vec<String>,
+        private: bool,
+        piece_size: Option<u64>,
+    ) -> Result<CreatedTorrent, EngineError> {
+        if piece_size.is_some_and(|size| !size.is_power_of_two()) {
+            return Err(EngineError::InvalidInput(format!(
+                "piece size must be a power of two between {} and {} bytes",
+                create::MIN_PIECE_SIZE,
+                create::MAX_PIECE_SIZE
+            )));
+        }
+        if !source.exists() {
+            return Err(EngineError::NotFound(source.display().to_string()));
+        }
+        let name = source.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let job = self.state.jobs.start(JobKind::CreateTorrent, None, &name);
+        job.running();
+        let created = create::create(source.to_path_buf(), output, &trackers, private, piece_size, job.clone()).await;
+        match &created {
+            Ok(created) => job.finish(Ok(Some(created.path.clone()))),
+            Err(e) => job.finish(Err(format!("{:#}", e))),
+        }
+        let created = created?;
+        info!("Created {} ({} pieces of {} bytes)", created.path, created.piece_count, created.piece_size);
+        Ok(created)
+    }
+
+    /// Writes a .torrent for torrent `id`'s content with a new tracker list
+    /// and private flag, reusing the session's piece hashes instead of
+    /// hashing the data again. Only for complete torrents. Other than the
+    /// flag the info dict is the same, so with an unchanged `private` the
+    /// info hash is too.
+    pub async fn export_as_new_torrent(
+        &self,
+        id: &str,
+        output: &Path,
+        trackers: Vec<String>,
+        private: bool,
+    ) -> Result<CreatedTorrent, EngineError> {
+        let handle = self.handle(id)?;
+        if !self.state.have_pieces(&handle).iter().all(|have| *have) {
+            return Err(EngineError::Unavailable(format!("Torrent {} is not complete", id)));
+        }
+        let info = handle.info();
+        let (content, pieces) = create::from_session(&info)?;
+        let created = create::write(output, &content, info.piece_length, pieces, &trackers, private).await?;
+        info!("Exported {} as {} ({})", id, created.path, created.info_hash);
+        Ok(created)
+    }
+
+    /// Re-announces every running torrent, or only those in `category`, to
+    /// pick up peers again after e.g. a network change. Runs in the
+    /// background with each tracker getting at most one announce a second;
+    /// returns how many torrents were queued.
+    ///
+    /// The session only announces a torrent when it starts, so each one is
+    /// paused and unpaused: its peer connections are dropped and built up
+    /// again from the fresh announce.
+    pub fn reannounce_all(&self, category: Option<&str>) -> Result<usize, EngineError> {
+        if self.state.is_session_paused() {
+            return Err(EngineError::Unavailable("Session is paused".into()));
+        }
+        Ok(maintenance::reannounce_all(&self.state, category))
+    }
+
+    /// Scrapes every tracker of every torrent, or of those in `category`.
+    /// Runs in the background, paced like [`Client::reannounce_all`]; the
+    /// seeder and leecher counts show up in the torrents' tracker lists as
+    /// they come in. Returns how many torrents were queued.
+    pub fn scrape_all(&self, category: Option<&str>) -> Result<usize, EngineError> {
+        if self.state.is_session_paused() {
+            return Err(EngineError::Unavailable("Session is paused".into()));
+        }
+        Ok(maintenance::scrape_all(&self.state, category, self.state.user_agent()))
+    }
+
+    /// Sets the comment and "created by" of torrent `id`'s .torrent file and
+    /// returns the new values. `None` leaves a field as it is and an empty
+    /// string removes it. The info dict is kept byte for byte, so the info
+    /// hash doesn't change.
+    pub fn edit_torrent_metadata(
+        &self,
+        id: &str,
+        comment: Option<String>,
+        created_by: Option<String>,
+    ) -> Result<(Option<String>, Option<String>), EngineError> {
+        self.handle(id)?;
+        let state = &self.state;
+        let bytes = state
+            .metainfo
+            .get(id)
+            .ok_or_else(|| EngineError::Unavailable(format!("The .torrent of {} is not available", id)))?;
+        let fields = state.metainfo.edit(id, &bytes, comment.as_deref(), created_by.as_deref())?;
+        info!("Edited the metadata of {}", id);
+        Ok((fields.comment, fields.created_by))
+    }
+
+    /// Long operations (data checks, torrent creation) running, waiting or
+    /// recently finished, oldest first, with their progress.
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.state.jobs.list()
+    }
+
+    /// Stops a running or queued job; it ends as Cancelled. A cancelled
+    /// initial check starts the torrent with nothing verified, and a
+    /// cancelled seeding check takes the torrent back out again.
+    pub fn cancel_job(&self, id: u64) -> Result<(), EngineError> {
+        let job = self.state.jobs.cancel(id).ok_or_else(|| EngineError::NotFound(format!("Unfinished job {}", id)))?;
+        // A check still waiting for its turn only notices once it leaves the queue
+        if job.state == JobState::Queued {
+            if let Some(torrent_id) = &job.torrent_id {
+                self.state.checks.cancel(torrent_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Data checks that are running or waiting for a turn, running first.
+    pub fn check_queue(&self) -> Vec<CheckQueueEntry> {
+        self.state.checks.entries()
+    }
+
+    /// Drops a torrent's waiting check. A running check can't be cancelled.
+    pub fn cancel_check(&self, id: &str) -> Result<(), EngineError> {
+        if !self.state.checks.cancel(id) {
+            return Err(EngineError::NotFound(format!("Waiting check of {}", id)));
+        }
+        Ok(())
+    }
+
+    /// The download and seed queues in order. Only enforced while
+    /// `queue.download_queue_enabled` or `queue.upload_queue_enabled` is on.
+    pub fn torrent_queues(&self) -> TorrentQueues {
+        self.state.queues.all()
+    }
+
+    /// Moves an unfinished torrent within the download queue.
+    pub fn move_in_download_queue(&self, id: &str, direction: QueueMove) -> Result<(), EngineError> {
+        self.move_in_queue(id, false, direction)
+    }
+
+    /// Moves a finished torrent within the seed queue.
+    pub fn move_in_seed_queue(&self, id: &str, direction: QueueMove) -> Result<(), EngineError> {
+        self.move_in_queue(id, true, direction)
+    }
+
+    /// Switches a torrent between auto managed, force started and paused.
+    /// A paused torrent stops now and stays stopped through engine restarts;
+    /// a force started one leaves the queues' active slots to the others.
+    /// Holds for other reasons (disk space, data cap, a session pause) still
+    /// apply, and a torrent set to Paused during one stays paused after it.
+    pub async fn set_torrent_mode(&self, id: &str, mode: TorrentMode) -> Result<(), EngineError> {
+        let handle = self.handle(id)?;
+        let state = &self.state;
+        state.modes.set(id, mode)?;
+        let current = state.engine_paused.lock().unwrap().get(id).copied();
+        match (mode, current) {
+            (TorrentMode::Paused, None) => {
+                state.session.pause(&handle).await?;
+                state.hold(id, PauseReason::User);
+            }
+            // The queue only releases the torrents it manages
+            (TorrentMode::Paused, Some(PauseReason::Queued)) => state.hold(id, PauseReason::User),
+            (TorrentMode::Paused, Some(_)) => {}
+            (_, Some(PauseReason::User)) => state.release(&handle, PauseReason::User).await?,
+            (TorrentMode::ForceStarted, Some(PauseReason::Queued)) => state.release(&handle, PauseReason::Queued).await?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn move_in_queue(&self, id: &str, seeds: bool, direction: QueueMove) -> Result<(), EngineError> {
+        self.handle(id)?;
+        if !self.state.queues.move_torrent(id, seeds, direction)? {
+            let queue = if seeds { "seed" } else { "download" };
+            return Err(EngineError::NotFound(format!("Torrent {} in the {} queue", id, queue)));
+        }
+        Ok(())
+    }
+
+    /// Moves a torrent's waiting check to `position` in the queue (0 = next).
+    pub fn move_check(&self, id: &str, position: usize) -> Result<(), EngineError> {
+        if !self.state.checks.move_to(id, position) {
+            return Err(EngineError::NotFound(format!("Waiting check of {}", id)));
+        }
+        Ok(())
+    }
+
+    /// Sets the upload ratio at which this torrent stops seeding (see
+    /// `share_limit_action`), overriding its category's and the global
+    /// target. 0 seeds without a ratio limit; `None` goes back to the
+    /// category's or global target.
+    pub fn set_ratio_limit(&self, id: &str, ratio: Option<f64>) -> Result<(), EngineError> {
+        self.handle(id)?;
+        check_ratio(ratio)?;
+        Ok(self.state.seed_goals.set_torrent_limit(id, ratio)?)
+    }
+
+    /// Sets the ratio target for torrents in `category`, used unless the
+    /// torrent has its own. 0 means no limit; `None` falls back to the
+    /// global `ratio_limit`.
+    pub fn set_category_ratio_limit(&self, category: &str, ratio: Option<f64>) -> Result<(), EngineError> {
+        check_ratio(ratio)?;
+        Ok(self.state.seed_goals.set_category_limit(category, ratio)?)
+    }
+
+    pub fn category_ratio_limits(&self) -> HashMap<String, f64> {
+        self.state.seed_goals.category_limits()
+    }
+
+    /// Downloads the torrent's files one at a time in `order` (file index
+    /// order if `None`), or turns that off again when `enabled` is false.
+    pub async fn set_download_order(
+        &self,
+        id: &str,
+        enabled: bool,
+        order: Option<Vec<usize>>,
+    ) -> Result<(), EngineError> {
+        let handle = self.handle(id)?;
+        if let Some(order) = &order {
+            check_indices(order, handle.info().files().len())?;
+        }
+        let order = enabled.then(|| ordering::normalize(order, handle.info().files().len()));
+        self.state.file_orders.set(id, order)?;
+        self.apply_file_selection(id, &handle).await
+    }
+
+    /// Pauses every torrent until [`Client::resume_session`].
+    pub async fn pause_session(&self) {
+        self.state.pause_session().await
+    }
+
+    /// Undoes [`Client::pause_session`]. Torrents that were already paused
+    /// by the engine for another reason stay paused.
+    pub async fn resume_session(&self) {
+        self.state.resume_session().await
+    }
+
+    /// Stops the engine before the process exits: saves state files with
+    /// unsaved changes, such as the data usage count and transfer totals,
+    /// then stops every torrent, which writes out buffered blocks. The
+    /// client can't run torrents afterwards.
+    pub async fn shutdown(&self) {
+        info!("Shutting down");
+        for handle in self.state.torrents() {
+            self.state.transfers.record(&handle.info_hash().as_string(), &handle);
+        }
+        self.state.data_meter.flush();
+        autosave::save_dirty(&self.state);
+        self.state.session.stop().await;
+    }
+
+    /// Call after the machine woke from sleep. Peer connections and tracker
+    /// state from before are likely stale, so running torrents re-announce
+    /// (paced as in [`Client::reannounce_all`]).
+    pub fn resumed_from_sleep(&self) {
+        if self.state.is_session_paused() {
+            return;
+        }
+        let queued = maintenance::reannounce_all(&self.state, None);
+        info!("Woke from sleep, re-announcing {} torrents", queued);
+    }
+
+    pub fn is_session_paused(&self) -> bool {
+        self.state.is_session_paused()
+    }
+
+    /// Bytes of downloaded blocks held in memory by ordered writes, waiting
+    /// to go to disk.
+    pub fn write_buffer_bytes(&self) -> u64 {
+        self.state.write_budget.used()
+    }
+
+    /// DHT routing table size, or 0 when DHT is off.
+    pub fn dht_nodes(&self) -> usize {
+        self.state.dht.node_count()
+    }
+
+    /// Routing table and lookup diagnostics for the DHT.
+    pub fn dht_stats(&self) -> DhtStats {
+        self.state.dht.stats()
+    }
+
+    /// Writes a zip for bug reports to `output`: the config without private
+    /// tracker domains, every torrent with its peers and trackers, recent
+    /// events and log lines, and running jobs and queues. Passkeys in
+    /// tracker URLs, whether in the path or the query, are redacted
+    /// everywhere, log lines included, and the home folder is shown as `~`.
+    pub fn dump_diagnostics(&self, output: &Path) -> Result<(), EngineError> {
+        diagnostics::write(&self.state, self.list_torrents(), output)?;
+        info!("Wrote diagnostics to {}", output.display());
+        Ok(())
+    }
+
+    /// How long the engine took to start and how many torrents it restored.
+    pub fn startup_timings(&self) -> StartupTimings {
+        self.state.startup.get().copied().unwrap_or_default()
+    }
+
+    /// The engine's background loops and whether they are running. A loop
+    /// that panics is logged; critical ones are restarted with a backoff.
+    pub fn task_health(&self) -> Vec<TaskHealth> {
+        self.state.tasks.list()
+    }
+
+    /// Engine events with a sequence number greater than `since`, oldest
+    /// first. Only the most recent events are kept.
+    pub fn events_since(&self, since: u64) -> Vec<EventRecord> {
+        self.state.events.since(since)
+    }
+
+    /// Live engine events as they are emitted. A receiver that falls more
+    /// than the history length behind gets `RecvError::Lagged` and can catch
+    /// up with [`Client::events_since`].
+    pub fn subscribe(&self) -> broadcast::Receiver<EventRecord> {
+        self.state.events.subscribe()
+    }
+
+    /// Events after `since` followed by live ones as they happen, without
+    /// gaps or repeats. Drop the receiver to stop.
+    pub fn follow_events(&self, since: u64) -> mpsc::Receiver<EventRecord> {
+        self.state.events.follow(since)
+    }
+
+    pub fn config(&self) -> Config {
+        self.state.config.lock().unwrap().clone()
+    }
+
+    /// Settles the locale a UI shows its text in: `Config.locale` when it's
+    /// set, otherwise the `offered` locale the UI found on its own system,
+    /// otherwise English.
+    pub fn handshake(&self, offered: Option<&str>) -> HandshakeInfo {
+        let configured = self.state.config.lock().unwrap().locale.clone();
+        let locale = [Some(configured.as_str()), offered]
+            .into_iter()
+            .flatten()
+            .find(|l| config::valid_locale(l))
+            .unwrap_or("en")
+            .to_string();
+        HandshakeInfo { engine_version: updates::CURRENT_VERSION.to_string(), locale }
+    }
+
+    /// The running version and what the last update check found, without
+    /// checking.
+    pub fn version_info(&self) -> UpdateInfo {
+        self.state.updates.info()
+    }
+
+    /// Asks the release feed (`update_feed_url`) for the newest version now.
+    pub async fn check_updates(&self) -> Result<UpdateInfo, EngineError> {
+        let feed = self.state.config.lock().unwrap().update_feed_url.clone();
+        if feed.is_empty() {
+            return Err(EngineError::Unavailable("No update feed is configured".into()));
+        }
+        self.state
+            .updates
+            .check(&feed, &self.state.user_agent())
+            .await
+            .map_err(|e| EngineError::Unavailable(format!("Update check failed: {:#}", e)))
+    }
+
+    /// Switches the alternative speed limits (`alt_download_speed` and
+    /// `alt_upload_speed`) on or off.
+    pub fn set_alt_speed(&self, enabled: bool) {
+        info!("Alternative speed limits {}", if enabled { "on" } else { "off" });
+        self.state.config.lock().unwrap().alt_speed_enabled = enabled;
+    }
+
+    /// Session-wide rates and how many torrents are downloading, seeding or
+    /// paused, as of the last second. Cheap enough to poll many times a
+    /// second; [`Client::follow_speed`] pushes it instead.
+    pub fn speed_summary(&self) -> SpeedSummary {
+        self.state.speed.latest()
+    }
+
+    /// Each new [`Client::speed_summary`], once a second while it changes.
+    pub fn follow_speed(&self) -> watch::Receiver<SpeedSummary> {
+        self.state.speed.subscribe()
+    }
+
+    /// Totals across the session plus the `recent` most recently added
+    /// torrents, for the tray.
+    pub fn session_summary(&self, recent: usize) -> SessionSummary {
+        // The session lists torrents in the order they were added
+        let recent = self
+            .state
+            .torrents()
+            .iter()
+            .rev()
+            .take(recent)
+            .map(|handle| RecentTorrent {
+                id: handle.info_hash().as_string(),
+                name: handle.info().name.clone(),
+                progress: torrent::progress(&handle.stats()),
+            })
+            .collect();
+        let speed = self.state.speed.latest();
+        SessionSummary {
+            download_speed: speed.download_speed,
+            upload_speed: speed.upload_speed,
+            session_paused: self.state.is_session_paused(),
+            alt_speed: self.state.config.lock().unwrap().alt_speed_enabled,
+            recent,
+        }
+    }
+
+    /// Changes the running config in place.
+    pub fn update_config(&self, update: impl FnOnce(&mut Config)) {
+        update(&mut self.state.config.lock().unwrap());
+    }
+
+    /// Applies changes to a JSON config file as it is edited. A change that
+    /// doesn't parse or validate is logged and the current settings stay.
+    /// `download_path`, `state_dir`, `grpc_listen_addr` and
+    /// `http_listen_addr` only change on the next start.
+    pub fn watch_config_file(&self, path: PathBuf) {
+        supervisor::spawn(&self.state, "configwatch", true, move |state| configwatch::run(state, path.clone()));
+    }
+
+    /// Bytes exchanged with peers outside the local network per day, and
+    /// how much of this billing month's data cap is used.
+    pub fn data_usage(&self) -> DataUsage {
+        let (billing_day, cap) = {
+            let config = self.state.config.lock().unwrap();
+            (config.data_cap_billing_day, config.monthly_data_cap)
+        };
+        self.state.data_meter.usage(billing_day, cap)
+    }
+
+    /// Title and icon of the site behind each tracker domain in use. Sites
+    /// not fetched yet (or due for a refresh) are fetched in the background
+    /// unless `fetch_tracker_sites` is off, and fill in on a later call.
+    pub fn tracker_sites(&self) -> Vec<TrackerSite> {
+        let mut domains: Vec<String> = {
+            let trackers = self.state.trackers.lock().unwrap();
+            trackers.values().flatten().filter_map(|url| credentials::tracker_domain(url)).collect()
+        };
+        domains.sort();
+        domains.dedup();
+        if !self.state.config.lock().unwrap().fetch_tracker_sites {
+            return self.state.tracker_sites.get(&domains, None);
+        }
+        self.state.tracker_sites.get(&domains, Some(&self.state.user_agent()))
+    }
+
+    /// A tracker site's cached icon and its content type.
+    pub fn tracker_icon(&self, domain: &str) -> Result<(Vec<u8>, String), EngineError> {
+        self.state.tracker_sites.icon(domain).ok_or_else(|| EngineError::NotFound(format!("Icon of {}", domain)))
+    }
+
+    /// Whether FFmpeg is usable and which codecs and hardware accelerations
+    /// it has. Detection is cached for a few minutes; `refresh` forces a new
+    /// one, e.g. after the user installs FFmpeg.
+    pub async fn transcoder_info(&self, refresh: bool) -> TranscoderInfo {
+        let path = self.state.config.lock().unwrap().ffmpeg_path.clone();
+        self.state.transcoder.info(&path, refresh).await
+    }
+
+    /// Auto-tagging rules, in the order they are applied.
+    pub fn tag_rules(&self) -> Vec<TagRule> {
+        self.state.tagging.rules()
+    }
+
+    /// Adds an auto-tagging rule for torrents added from now on and returns
+    /// its id.
+    pub fn add_tag_rule(&self, rule: TagRule) -> Result<u64, EngineError> {
+        self.state.tagging.add_rule(rule).map_err(|e| EngineError::InvalidInput(e.to_string()))
+    }
+
+    /// Replaces the rule with the same id.
+    pub fn update_tag_rule(&self, rule: TagRule) -> Result<(), EngineError> {
+        let id = rule.id;
+        match self.state.tagging.update_rule(rule) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(EngineError::NotFound(format!("Tag rule {}", id))),
+            Err(e) => Err(EngineError::InvalidInput(e.to_string())),
+        }
+    }
+
+    pub fn remove_tag_rule(&self, id: u64) -> Result<(), EngineError> {
+        if !self.state.tagging.remove_rule(id)? {
+            return Err(EngineError::NotFound(format!("Tag rule {}", id)));
+        }
+        Ok(())
+    }
+
+    /// Every tag, with its color and parent. Tags only found on torrents
+    /// are listed top-level without a color.
+    pub fn tags(&self) -> Vec<TagInfo> {
+        self.state.tagging.tags()
+    }
+
+    /// Sets a tag's color and parent, defining the tag if it's new.
+    pub fn set_tag(&self, tag: TagInfo) -> Result<(), EngineError> {
+        self.state.tagging.set_tag(tag).map_err(|e| EngineError::InvalidInput(e.to_string()))
+    }
+
+    /// Deletes a tag and takes it off every torrent. Tags nested under it
+    /// move up to its parent.
+    pub fn remove_tag(&self, name: &str) -> Result<(), EngineError> {
+        if !self.state.tagging.remove_tag(name)? {
+            return Err(EngineError::NotFound(format!("Tag {}", name)));
+        }
+        Ok(())
+    }
+
+    /// Replaces a torrent's tags and category.
+    pub fn set_torrent_tags(&self, id: &str, tags: Vec<String>, category: Option<String>) -> Result<(), EngineError> {
+        self.handle(id)?;
+        Ok(self.state.tagging.set_labels(id, Labels { tags, category })?)
+    }
+
+    /// Bans a peer IP from every torrent, for `duration_secs` or for good.
+    /// The session only loads its blocklist when it starts, so the ban
+    /// takes effect from the next start, and current connections stay open.
+    pub fn ban_peer(&self, ip: &str, reason: &str, duration_secs: Option<u64>) -> Result<(), EngineError> {
+        let ip = parse_ip(ip)?;
+        let now = persist::now();
+        self.state.bans.add(BannedPeer {
+            ip,
+            reason: reason.to_string(),
+            created_at: now,
+            expires_at: duration_secs.map(|d| now + d),
+        })?;
+        info!("Banned {} from the next start on", ip);
+        Ok(())
+    }
+
+    /// Lifts a ban, from the next start on.
+    pub fn unban_peer(&self, ip: &str) -> Result<(), EngineError> {
+        let ip = parse_ip(ip)?;
+        if !self.state.bans.remove(ip)? {
+            return Err(EngineError::NotFound(format!("Ban of {}", ip)));
+        }
+        Ok(())
+    }
+
+    /// Bans in force, with their reasons.
+    pub fn banned_peers(&self) -> Vec<BannedPeer> {
+        self.state.bans.active()
+    }
+
+    /// Per-tracker-domain settings.
+    pub fn tracker_limits(&self) -> Vec<TrackerLimit> {
+        self.state.tracker_limits.all()
+    }
+
+    /// Adds or replaces the settings for `limit.domain`. Active torrent caps
+    /// are enforced within a few seconds.
+    pub fn set_tracker_limit(&self, limit: TrackerLimit) -> Result<(), EngineError> {
+        self.state.tracker_limits.set(limit).map_err(|e| EngineError::InvalidInput(e.to_string()))
+    }
+
+    /// Drops a tracker domain's settings. Torrents it was holding back are
+    /// resumed on the next pass.
+    pub fn remove_tracker_limit(&self, domain: &str) -> Result<(), EngineError> {
+        if !self.state.tracker_limits.remove(domain)? {
+            return Err(EngineError::NotFound(format!("Tracker limit for {}", domain)));
+        }
+        Ok(())
+    }
+
+    /// Per-category rules for archiving or deleting content after seeding.
+    pub fn retention_rules(&self) -> Vec<RetentionRule> {
+        self.state.retention.rules()
+    }
+
+    /// Adds or replaces the rule for `rule.category`. Torrents in the
+    /// category are archived or deleted `after_days` after they meet their
+    /// ratio limit; torrents without one are never touched. Checked every few minutes.
+    pub fn set_retention_rule(&self, rule: RetentionRule) -> Result<(), EngineError> {
+        self.state.retention.set_rule(rule).map_err(|e| EngineError::InvalidInput(e.to_string()))
+    }
+
+    pub fn remove_retention_rule(&self, category: &str) -> Result<(), EngineError> {
+        if !self.state.retention.remove_rule(category)? {
+            return Err(EngineError::NotFound(format!("Retention rule for {}", category)));
+        }
+        Ok(())
+    }
+
+    /// Every torrent a retention rule will act on, soonest first, with where
+    /// its content is and where it would go. Nothing is changed; those
+    /// marked `due` are handled in the next pass.
+    pub fn preview_retention(&self) -> Vec<RetentionCandidate> {
+        retention::preview(&self.state)
+    }
+
+    /// Tracker domains that have a passkey in the OS keyring.
+    pub fn tracker_credential_domains(&self) -> Vec<String> {
+        self.state.config.lock().unwrap().tracker_credential_domains.clone()
+    }
+
+    /// Stores the passkey for a tracker domain and swaps it into the
+    /// announce URLs of every torrent using that tracker. The session can't
+    /// change a running torrent's trackers, so those torrents are added
+    /// again, keeping their data, which is checked again. Returns the ids of
+    /// the torrents that were updated.
+    pub async fn set_tracker_passkey(&self, domain: &str, passkey: &str) -> Result<Vec<String>, EngineError> {
+        let state = &self.state;
+        let domain = domain.to_lowercase();
+        let old = state.credentials.get(&domain).await;
+        state
+            .credentials
+            .set(&domain, passkey)
+            .map_err(|e| EngineError::Credentials(e.to_string()))?;
+        {
+            let mut config = state.config.lock().unwrap();
+            if !config.tracker_credential_domains.contains(&domain) {
+                config.tracker_credential_domains.push(domain.clone());
+            }
+        }
+
+        // Rotate the passkey in every torrent announcing to this tracker
+        let rotate = |url: &str| match credentials::tracker_domain(url) {
+            Some(d) if d == domain => credentials::rotate(url, old.as_deref(), passkey),
+            _ => url.to_string(),
+        };
+        let mut changed = Vec::new();
+        {
+            let mut trackers = state.trackers.lock().unwrap();
+            for handle in state.torrents() {
+                let id = handle.info_hash().as_string();
+                let Some(urls) = trackers.get_mut(&id) else { continue };
+                let rotated: Vec<String> = urls.iter().map(|u| rotate(u)).collect();
+                if rotated != *urls {
+                    *urls = rotated;
+                    changed.push(handle);
+                }
+            }
+        }
+        let mut updated = Vec::new();
+        for handle in changed {
+            let id = handle.info_hash().as_string();
+            let Some(bytes) = handle.metadata.load().as_ref().map(|m| m.torrent_bytes.clone()) else { continue };
+            let bytes = metainfo::map_trackers(&bytes, rotate)?;
+            self.readd(&handle, bytes).await?;
+            updated.push(id);
+        }
+        Ok(updated)
+    }
+
+    /// Deletes the stored passkey for a tracker domain.
+    pub fn remove_tracker_passkey(&self, domain: &str) -> Result<(), EngineError> {
+        let domain = domain.to_lowercase();
+        self.state
+            .credentials
+            .remove(&domain)
+            .map_err(|e| EngineError::Credentials(e.to_string()))?;
+        self.state.config.lock().unwrap().tracker_credential_domains.retain(|d| *d != domain);
+        Ok(())
+    }
+
+    fn handle(&self, id: &str) -> Result<Arc<ManagedTorrent>, EngineError> {
+        self.state.torrent(id).ok_or_else(|| EngineError::torrent_not_found(id))
+    }
+
+    // Skipped files are deselected in the session; the other tiers are kept
+    // for the UI and ordering but all download.
+    async fn set_priority(
+        &self,
+        id: &str,
+        select: impl FnOnce(&[FileInfo]) -> Vec<usize>,
+        priority: FilePriority,
+    ) -> Result<usize, EngineError> {
+        let handle = self.handle(id)?;
+        let files = self.state.files.get(id, &handle);
+        let indices = select(&files);
+        check_indices(&indices, files.len())?;
+        self.state.files.set_priority(id, files.len(), &indices, priority)?;
+        self.apply_file_selection(id, &handle).await?;
+        Ok(indices.len())
+    }
+
+    async fn apply_file_selection(&self, id: &str, handle: &Arc<ManagedTorrent>) -> Result<(), EngineError> {
+        let state = &self.state;
+        let cached = state.files.get(id, handle);
+        let current = files::with_progress(&cached, 0..cached.len(), &handle.stats().file_progress);
+        let order = state.file_orders.get(id);
+        let window = state.config.lock().unwrap().ordered_files_window;
+        let only = ordering::selection(&current, order.as_deref(), window);
+        Ok(state.select_files(handle, &only).await?)
+    }
+}
+
+fn check_indices(indices: &[usize], file_count: usize) -> Result<(), EngineError> {
+    match indices.iter().find(|&&i| i >= file_count) {
+        Some(i) => Err(EngineError::InvalidInput(format!(
+            "file index {} out of range, torrent has {} files",
+            i, file_count
+        ))),
+        None => Ok(()),
+    }
+}
+
+// What the session adds a source as: magnet links as they are, .torrent
+// files read here so it isn't left reading them on the runtime
+async fn load(source: &str) -> Result<AddTorrent<'_>, EngineError> {
+    let source = source.trim();
+    if source.is_empty() {
+        return Err(EngineError::InvalidInput("empty magnet link or path".into()));
+    }
+    if source.starts_with("magnet:") {
+        return Ok(AddTorrent::from_url(source));
+    }
+    Ok(AddTorrent::from_bytes(tokio::fs::read(external::torrent_path(source)).await?))
+}
+
+fn parse_ip(ip: &str) -> Result<IpAddr, EngineError> {
+    ip.trim().parse().map_err(|_| EngineError::InvalidInput(format!("bad IP address {}", ip)))
+}
+
+fn check_ratio(ratio: Option<f64>) -> Result<(), EngineError> {
+    match ratio {
+        Some(r) if r.is_nan() || r < 0.0 => Err(EngineError::InvalidInput(format!("ratio {} is negative", r))),
+        _ => Ok(()),
+    }
+}
+
+// Ties keep the session's order. Torrents without an ETA go last in either
+// order, so the ones about to finish stay at the top.
+fn sort_torrents(torrents: &mut [TorrentState], sort_by: TorrentSort, order: SortOrder) {
+    let by = |a: &TorrentState, b: &TorrentState| match sort_by {
+        TorrentSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        TorrentSort::Size => a.total_size.cmp(&b.total_size),
+        TorrentSort::Progress => a.progress.total_cmp(&b.progress),
+        TorrentSort::Ratio => a.ratio.total_cmp(&b.ratio),
+        TorrentSort::AddedOn => a.added_on.cmp(&b.added_on),
+        TorrentSort::Eta => a.eta_secs.cmp(&b.eta_secs),
+    };
+    torrents.sort_by(|a, b| {
+        if sort_by == TorrentSort::Eta && a.eta_secs.is_none() != b.eta_secs.is_none() {
+            return a.eta_secs.is_none().cmp(&b.eta_secs.is_none());
+        }
+        match order {
+            SortOrder::Ascending => by(a, b),
+            SortOrder::Descending => by(b, a),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge::RetentionAction;
+
+    // A client with its download and state folders under `dir`
+    async fn start(dir: &Path, update: impl FnOnce(&mut Config)) -> Client {
+        let mut config = Config {
+            download_path: dir.join("downloads").to_string_lossy().into_owned(),
+            state_dir: dir.join("state").to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+        update(&mut config);
+        Client::start(config).await.unwrap()
+    }
+
+    // A two-file album in `folder` and its .torrent in `dir`
+    async fn album(client: &Client, folder: &Path, dir: &Path, trackers: Vec<String>) -> PathBuf {
+        let source = folder.join("album");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("1.flac"), vec![1; 40_000]).unwrap();
+        std::fs::write(source.join("2.flac"), vec![2; 40_000]).unwrap();
+        let output = dir.join("album.torrent");
+        client.create_torrent(&source, &output, trackers, false, None).await.unwrap();
+        output
+    }
+
+    // Files can only be selected once the session has checked the data
+    async fn initialized(client: &Client, id: &str) {
+        let handle = client.handle(id).unwrap();
+        let checked = async {
+            while matches!(handle.stats().state, TorrentStatsState::Initializing) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), checked).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn torrents_come_back_after_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = start(dir.path(), |_| {}).await;
+        let torrent = album(&client, dir.path(), dir.path(), vec!["http://tracker.example/announce".into()]).await;
+        let id = client.add_torrent(&torrent.to_string_lossy()).await.unwrap();
+        initialized(&client, &id).await;
+        client.set_file_priority(&id, vec![1], FilePriority::Skip).await.unwrap();
+        client.shutdown().await;
+
+        let client = start(dir.path(), |_| {}).await;
+        let ids: Vec<String> = client.list_torrents().into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, [id.as_str()]);
+        let files = client.torrent_files(&id, 0, 10).unwrap().files;
+        assert_eq!(files[1].priority, FilePriority::Skip);
+        assert_eq!(client.handle(&id).unwrap().only_files(), Some(vec![0]));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn ordered_writes_keep_data_across_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let ordered = |config: &mut Config| config.ordered_writes = true;
+        let client = start(dir.path(), ordered).await;
+        let downloads = dir.path().join("downloads");
+        let torrent = album(&client, &downloads, dir.path(), Vec::new()).await;
+        let id = client.add_torrent(&torrent.to_string_lossy()).await.unwrap();
+        initialized(&client, &id).await;
+        client.shutdown().await;
+
+        let client = start(dir.path(), ordered).await;
+        initialized(&client, &id).await;
+        let restored = client.list_torrents().into_iter().find(|t| t.id == id).unwrap();
+        assert_eq!(restored.progress, 1.0);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn passkeys_stay_out_of_the_state_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let tracker = "http://tracker.example/{passkey}/announce";
+        let with_passkey = vec!["http://tracker.example/secret/announce".to_string()];
+        let client = start(dir.path(), |_| {}).await;
+        client.state.credentials.remember("tracker.example", "secret");
+        let torrent = album(&client, dir.path(), dir.path(), vec![tracker.into()]).await;
+        let id = client.add_torrent(&torrent.to_string_lossy()).await.unwrap();
+        assert_eq!(client.state.trackers.lock().unwrap()[&id], with_passkey);
+        client.shutdown().await;
+
+        let state_dir = dir.path().join("state");
+        let mut dirs = vec![state_dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    let bytes = std::fs::read(&path).unwrap();
+                    assert!(!bytes.windows(6).any(|w| w == b"secret"), "passkey in {}", path.display());
+                }
+            }
+        }
+
+        // Restored torrents get their trackers back, filled in from a
+        // keyring this one doesn't have
+        let client = start(dir.path(), |_| {}).await;
+        assert_eq!(client.state.trackers.lock().unwrap()[&id], [tracker]);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn download_order_outlives_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = start(dir.path(), |_| {}).await;
+        let torrent = album(&client, dir.path(), dir.path(), Vec::new()).await;
+        let id = client.add_torrent(&torrent.to_string_lossy()).await.unwrap();
+        initialized(&client, &id).await;
+        client.set_download_order(&id, true, Some(vec![1])).await.unwrap();
+        assert_eq!(client.handle(&id).unwrap().only_files(), Some(vec![1]));
+        client.shutdown().await;
+
+        let client = start(dir.path(), |_| {}).await;
+        assert_eq!(client.state.file_orders.get(&id), Some(vec![1, 0]));
+        assert_eq!(client.handle(&id).unwrap().only_files(), Some(vec![1]));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn retention_deletes_torrents_with_their_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = start(dir.path(), |_| {}).await;
+        let downloads = dir.path().join("downloads");
+        let torrent = album(&client, &downloads, dir.path(), Vec::new()).await;
+        let id = client.add_torrent(&torrent.to_string_lossy()).await.unwrap();
+        initialized(&client, &id).await;
+        let rule = RetentionRule {
+            category: "music".into(),
+            after_days: 0,
+            action: RetentionAction::Delete,
+            archive_dir: String::new(),
+        };
+        client.set_retention_rule(rule).unwrap();
+        client.set_torrent_tags(&id, Vec::new(), Some("music".into())).unwrap();
+        client.state.retention.goal_met(&id);
+        assert!(client.preview_retention()[0].due);
+
+        retention::apply_due(&client.state).await;
+        assert!(client.handle(&id).is_err());
+        assert!(!downloads.join("album").exists());
+        assert!(downloads.exists());
+        client.shutdown().await;
+    }
+
+}
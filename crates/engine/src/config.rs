@@ -1,18 +1,265 @@
+use crate::identity::{self, Identity};
+use anyhow::{anyhow, Context, Result};
+use bridge::{DataCapAction, ShareLimitAction};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     pub download_path: String,
+    pub state_dir: String, // engine state files (schedules, ...)
     pub max_download_speed: u64, // bytes per second
     pub max_upload_speed: u64,   // bytes per second
+    pub auto_recheck_enabled: bool,
+    pub auto_recheck_interval_secs: u64,
+    pub auto_recheck_pieces: usize,     // pieces sampled per torrent per pass
+    pub auto_recheck_read_limit: u64,   // bytes per second, 0 = unlimited
+    pub max_concurrent_checks: usize,   // torrents verified at once, across rechecks and seeding adds
+    pub min_free_disk_space: u64,       // bytes, 0 = disabled
+    pub max_cpu_percent: f32,           // 0 = disabled
+    pub resource_check_interval_secs: u64,
+    pub tracker_credential_domains: Vec<String>, // passkeys themselves are in the OS keyring
+    pub peer_id_prefix: String, // e.g. "-AT0100-"; also sets the user agent, applied at the next start
+    pub fetch_tracker_sites: bool, // fetch tracker home pages for their titles and icons
+    pub reannounce_on_network_change: bool,
+    pub external_ip_check_url: String, // empty = only watch the local interface
+    pub external_ip_check_interval_secs: u64,
+    pub inline_file_limit: usize, // larger torrents omit `files` from ListTorrents
+    pub ordered_files_window: usize, // files fetched at once for torrents with a download order
+    pub stall_timeout_secs: u64, // no seeds and no progress for this long = stalled, 0 = off
+    pub stall_reannounce: bool,  // re-announce stalled torrents once per timeout
+    pub metadata_timeout_secs: u64, // how long ResolveMagnet waits for peers to send metadata
+    pub ratio_limit: f64, // stop seeding at this upload ratio unless a category or torrent sets its own, 0 = off
+    pub share_limit_action: ShareLimitAction,
+    pub monthly_data_cap: u64, // bytes both ways to peers per billing month, 0 = off
+    pub data_cap_warn_percent: u32,
+    pub data_cap_billing_day: u32, // 1-28, UTC
+    pub data_cap_action: DataCapAction,
+    pub alt_speed_enabled: bool, // use the alternative limits below now, cap or not
+    pub alt_download_speed: u64, // bytes per second with alternative limits on, 0 = unlimited
+    pub alt_upload_speed: u64,
+    pub ffmpeg_path: String, // empty = "ffmpeg" on PATH
+    pub ordered_writes: bool, // filesystem storage: buffer blocks and write them in disk order (for HDDs)
+    pub ordered_write_buffer: u64, // bytes held per torrent before flushing
+    pub ordered_write_budget: u64, // bytes held across all torrents; writes flush when it's reached
+    pub check_for_updates: bool,
+    pub update_feed_url: String, // GitHub "latest release" API URL or a {version, url, notes} JSON document
+    pub web_ui_path: String, // folder served at the HTTP server's root (built UI, VueTorrent...), empty = off
+    pub http_listen_addr: String, // streaming server, API and web UI; the desktop app's player expects 127.0.0.1:3000
+    pub http_token: String, // asked of clients on other hosts (Bearer or ?token=); needed to listen off loopback
+    pub grpc_listen_addr: String, // e.g. "127.0.0.1:50051", empty = off; needs the `grpc` feature
+    pub locale: String, // BCP 47 tag such as "de-DE" for the UI's text, empty = the UI's own
+    pub autosave_interval_secs: u64, // longest a changed state file waits to be written
+    pub autosave_debounce_ms: u64,   // quiet time after a burst of changes before writing
+    pub connection: ConnectionSettings,
+    pub queue: QueueSettings,
+}
+
+// Active torrent limits. Unfinished torrents queue for download slots and
+// finished ones for seed slots, each in their own order; a queue that is off
+// lets every torrent run.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct QueueSettings {
+    pub download_queue_enabled: bool,
+    pub max_active_downloads: usize,
+    pub upload_queue_enabled: bool,
+    pub max_active_uploads: usize, // seeding torrents
+    pub seed_rotation_secs: u64,   // swap active and waiting seeds this often, 0 = off; needs the upload queue
+    pub seed_rotation_count: usize, // seeds swapped per rotation
+}
+
+impl Default for QueueSettings {
+    fn default() -> Self {
+        Self {
+            download_queue_enabled: false,
+            max_active_downloads: 3,
+            upload_queue_enabled: false,
+            max_active_uploads: 5,
+            seed_rotation_secs: 0,
+            seed_rotation_count: 1,
+        }
+    }
+}
+
+// Peer connection timeouts and keep-alives, applied at the next start. Any
+// preset but Custom replaces the values below with ones tuned for that kind
+// of network.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    pub preset: ConnectionPreset,
+    pub connect_timeout_secs: u64, // to establish a connection and finish the handshake
+    pub read_timeout_secs: u64,    // nothing at all from a peer, not even a keep-alive: disconnect
+    pub keep_alive_secs: u64,      // how often we send keep-alives to quiet peers
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionPreset {
+    #[default]
+    Custom,
+    Fast,     // wired or fiber: give up on unresponsive peers quickly
+    Slow,     // satellite, mobile data: allow for long round trips
+    Unstable, // flaky Wi-Fi, NAT that forgets mappings: keep-alive often, drop dead peers early
+}
+
+impl Default for ConnectionSettings {
+    fn default() -> Self {
+        Self {
+            preset: ConnectionPreset::Custom,
+            connect_timeout_secs: 10,
+            read_timeout_secs: 300,
+            keep_alive_secs: 120,
+        }
+    }
+}
+
+impl ConnectionSettings {
+    // The values in effect: the preset's, or the configured ones for Custom
+    pub fn effective(&self) -> ConnectionSettings {
+        let (connect, read, keep_alive) = match self.preset {
+            ConnectionPreset::Custom => return self.clone(),
+            ConnectionPreset::Fast => (5, 180, 120),
+            ConnectionPreset::Slow => (30, 600, 120),
+            ConnectionPreset::Unstable => (20, 120, 45),
+        };
+        ConnectionSettings {
+            preset: self.preset,
+            connect_timeout_secs: connect,
+            read_timeout_secs: read,
+            keep_alive_secs: keep_alive,
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             download_path: "downloads".to_string(),
+            state_dir: "state".to_string(),
             max_download_speed: 0, // 0 = unlimited
             max_upload_speed: 0,
+            auto_recheck_enabled: false,
+            auto_recheck_interval_secs: 24 * 60 * 60,
+            auto_recheck_pieces: 16,
+            auto_recheck_read_limit: 8 * 1024 * 1024,
+            max_concurrent_checks: 1,
+            min_free_disk_space: 512 * 1024 * 1024,
+            max_cpu_percent: 0.0,
+            resource_check_interval_secs: 10,
+            tracker_credential_domains: Vec::new(),
+            peer_id_prefix: identity::default_prefix(),
+            fetch_tracker_sites: true,
+            reannounce_on_network_change: true,
+            external_ip_check_url: "https://api.ipify.org".to_string(),
+            external_ip_check_interval_secs: 5 * 60,
+            inline_file_limit: 500,
+            ordered_files_window: 1,
+            stall_timeout_secs: 30 * 60,
+            stall_reannounce: true,
+            metadata_timeout_secs: 60,
+            ratio_limit: 0.0,
+            share_limit_action: ShareLimitAction::Pause,
+            monthly_data_cap: 0,
+            data_cap_warn_percent: 90,
+            data_cap_billing_day: 1,
+            data_cap_action: DataCapAction::Pause,
+            alt_speed_enabled: false,
+            alt_download_speed: 0,
+            alt_upload_speed: 0,
+            ffmpeg_path: String::new(),
+            ordered_writes: false,
+            ordered_write_buffer: 64 * 1024 * 1024,
+            ordered_write_budget: 256 * 1024 * 1024,
+            check_for_updates: true,
+            update_feed_url: "https://api.github.com/repos/master-moose/AuroraTorrent/releases/latest".to_string(),
+            web_ui_path: String::new(),
+            http_listen_addr: "127.0.0.1:3000".to_string(),
+            http_token: String::new(),
+            grpc_listen_addr: String::new(),
+            locale: String::new(),
+            autosave_interval_secs: 60,
+            autosave_debounce_ms: 2000,
+            connection: ConnectionSettings::default(),
+            queue: QueueSettings::default(),
         }
     }
 }
+
+impl Config {
+    // A JSON config file; settings it leaves out keep their defaults.
+    pub fn load(path: &Path) -> Result<Config> {
+        let bytes = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let config: Config = serde_json::from_slice(&bytes).with_context(|| format!("parsing {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Values that parse but can't work
+    pub fn validate(&self) -> Result<()> {
+        if self.download_path.trim().is_empty() {
+            return Err(anyhow!("download_path is empty"));
+        }
+        if self.state_dir.trim().is_empty() {
+            return Err(anyhow!("state_dir is empty"));
+        }
+        if !(0.0..=100.0).contains(&self.max_cpu_percent) {
+            return Err(anyhow!("max_cpu_percent must be between 0 and 100"));
+        }
+        if !(1..=28).contains(&self.data_cap_billing_day) {
+            return Err(anyhow!("data_cap_billing_day must be between 1 and 28"));
+        }
+        if self.data_cap_warn_percent > 100 {
+            return Err(anyhow!("data_cap_warn_percent must be at most 100"));
+        }
+        if self.ratio_limit.is_nan() || self.ratio_limit < 0.0 {
+            return Err(anyhow!("ratio_limit must not be negative"));
+        }
+        if self.max_concurrent_checks == 0 {
+            return Err(anyhow!("max_concurrent_checks must be at least 1"));
+        }
+        if self.resource_check_interval_secs == 0
+            || self.external_ip_check_interval_secs == 0
+            || self.autosave_interval_secs == 0
+        {
+            return Err(anyhow!("check intervals must be at least 1 second"));
+        }
+        Identity::new(&self.peer_id_prefix)?;
+        if !self.external_ip_check_url.is_empty() && reqwest::Url::parse(&self.external_ip_check_url).is_err() {
+            return Err(anyhow!("external_ip_check_url is not a URL"));
+        }
+        if !self.update_feed_url.is_empty() && reqwest::Url::parse(&self.update_feed_url).is_err() {
+            return Err(anyhow!("update_feed_url is not a URL"));
+        }
+        let http_addr: std::net::SocketAddr =
+            self.http_listen_addr.parse().map_err(|_| anyhow!("http_listen_addr is not an address:port"))?;
+        if !http_addr.ip().is_loopback() && self.http_token.is_empty() {
+            return Err(anyhow!("http_token is needed to listen on {}", http_addr));
+        }
+        if !self.grpc_listen_addr.is_empty() && self.grpc_listen_addr.parse::<std::net::SocketAddr>().is_err() {
+            return Err(anyhow!("grpc_listen_addr is not an address:port"));
+        }
+        let connection = &self.connection;
+        if [connection.connect_timeout_secs, connection.read_timeout_secs, connection.keep_alive_secs].contains(&0) {
+            return Err(anyhow!("connection timeouts must be at least 1 second"));
+        }
+        if connection.keep_alive_secs >= connection.read_timeout_secs {
+            return Err(anyhow!("connection.keep_alive_secs must be less than read_timeout_secs"));
+        }
+        if !self.locale.is_empty() && !valid_locale(&self.locale) {
+            return Err(anyhow!("locale is not a language tag"));
+        }
+        Ok(())
+    }
+}
+
+// "en", "pt-BR", "zh_Hant_TW": letters and digits in parts of up to 8,
+// starting with a 2-3 letter language.
+pub fn valid_locale(tag: &str) -> bool {
+    let mut parts = tag.split(['-', '_']);
+    let language = parts.next().unwrap_or("");
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|p| (1..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()))
+}
@@ -0,0 +1,57 @@
+use crate::config::Config;
+use crate::AppState;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+const TICK: Duration = Duration::from_secs(2);
+
+// Applies edits to the config file without a restart. Everything the engine
+// reads from the live config (speed limits, queue sizes, check and seeding
+// settings, tool paths) takes effect right away. Settings fixed at startup,
+// including the download folder the session and existing torrents use, keep
+// their running values until the next start. A file that doesn't parse or validate is logged and skipped, and
+// the engine keeps its current settings until the file is fixed.
+pub async fn run(state: AppState, path: PathBuf) {
+    let mut seen = modified(&path);
+    loop {
+        tokio::time::sleep(TICK).await;
+        let current = modified(&path);
+        if current == seen {
+            continue;
+        }
+        seen = current;
+        if current.is_none() {
+            warn!("Config file {} is gone; keeping the current settings", path.display());
+            continue;
+        }
+
+        let mut loaded = match Config::load(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Not applying config file {}: {:#}", path.display(), e);
+                continue;
+            }
+        };
+        let mut config = state.config.lock().unwrap();
+        let restart = [
+            ("download_path", loaded.download_path != config.download_path),
+            ("state_dir", loaded.state_dir != config.state_dir),
+            ("grpc_listen_addr", loaded.grpc_listen_addr != config.grpc_listen_addr),
+            ("http_listen_addr", loaded.http_listen_addr != config.http_listen_addr),
+        ];
+        for (name, _) in restart.iter().filter(|(_, changed)| *changed) {
+            warn!("Config file changes {}; that takes effect after a restart", name);
+        }
+        loaded.download_path = config.download_path.clone();
+        loaded.state_dir = config.state_dir.clone();
+        loaded.grpc_listen_addr = config.grpc_listen_addr.clone();
+        loaded.http_listen_addr = config.http_listen_addr.clone();
+        *config = loaded;
+        info!("Applied config file {}", path.display());
+    }
+}
+
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
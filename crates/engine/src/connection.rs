@@ -0,0 +1,14 @@
+use crate::config::ConnectionSettings;
+use librqbit::PeerConnectionOptions;
+use std::time::Duration;
+
+// The session takes these when it starts and has no way to change them
+// afterwards.
+pub fn peer_options(settings: &ConnectionSettings) -> PeerConnectionOptions {
+    let settings = settings.effective();
+    PeerConnectionOptions {
+        connect_timeout: Some(Duration::from_secs(settings.connect_timeout_secs)),
+        read_write_timeout: Some(Duration::from_secs(settings.read_timeout_secs)),
+        keep_alive_interval: Some(Duration::from_secs(settings.keep_alive_secs)),
+    }
+}
@@ -0,0 +1,215 @@
+use crate::AppState;
+use bridge::{EngineEvent, PauseReason, TorrentMode};
+use crate::files;
+use crate::persist;
+use crate::torrent::{self, TorrentExt, TorrentInfo};
+use librqbit::dht::Id20;
+use librqbit::{Api, ManagedTorrent};
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+impl AppState {
+    pub fn torrent(&self, id: &str) -> Option<Arc<ManagedTorrent>> {
+        let info_hash = id.parse::<Id20>().ok()?;
+        self.session.get(info_hash.into())
+    }
+
+    pub fn torrents(&self) -> Vec<Arc<ManagedTorrent>> {
+        self.session.with_torrents(|torrents| torrents.map(|(_, handle)| handle.clone()).collect())
+    }
+
+    // Which pieces have been downloaded and checked, all false while the
+    // torrent is still initializing.
+    pub fn have_pieces(&self, handle: &ManagedTorrent) -> Vec<bool> {
+        let num_pieces = handle.info().num_pieces();
+        let api = Api::new(self.session.clone(), None);
+        match api.api_dump_haves(handle.info_hash().into()) {
+            Ok(dump) => torrent::parse_haves(&dump, num_pieces),
+            Err(_) => vec![false; num_pieces],
+        }
+    }
+
+    // User agent for the engine's own HTTP requests
+    pub fn user_agent(&self) -> String {
+        self.identity.user_agent()
+    }
+
+    // Where the torrent's files are (see ContentRoots::resolve)
+    pub fn content_root(&self, id: &str, info: &TorrentInfo) -> PathBuf {
+        let download_path = self.config.lock().unwrap().download_path.clone();
+        self.content_roots.resolve(id, &download_path, info)
+    }
+
+    // Re-announces a running torrent (trackers and DHT) unless one of its
+    // trackers has a minimum announce interval that hasn't passed yet. The
+    // session only announces when a torrent starts, so this restarts it,
+    // which drops its peer connections; held or paused torrents are left
+    // alone.
+    pub async fn reannounce(&self, handle: &Arc<ManagedTorrent>) {
+        let id = handle.info_hash().as_string();
+        if handle.is_paused() || self.engine_paused.lock().unwrap().contains_key(&id) {
+            return;
+        }
+        let trackers = self.trackers.lock().unwrap().get(&id).cloned().unwrap_or_default();
+        if !self.tracker_limits.may_announce(&trackers) {
+            info!("Skipping re-announce of {}: tracker announce interval", id);
+            return;
+        }
+        let restarted = match self.session.pause(handle).await {
+            Ok(()) => self.session.unpause(handle).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = restarted {
+            warn!("Failed to re-announce {}: {:#}", id, e);
+        }
+    }
+
+    // Takes the torrent out of the session and drops what the engine kept
+    // about it.
+    pub async fn remove(&self, handle: &ManagedTorrent, delete_files: bool) -> anyhow::Result<()> {
+        let id = handle.info_hash().as_string();
+        self.session.delete(handle.info_hash().into(), delete_files).await?;
+        self.content_roots.forget(&id);
+        self.trackers.lock().unwrap().remove(&id);
+        self.engine_paused.lock().unwrap().remove(&id);
+        self.seed_goals.forget(&id);
+        self.retention.forget(&id);
+        self.transfers.forget(&id);
+        self.metainfo.remove(&id);
+        self.scrapes.lock().unwrap().remove(&id);
+        self.added.forget(&id);
+        self.modes.forget(&id);
+        self.files.forget(&id);
+        self.schedules.forget(&id);
+        self.tagging.forget(&id);
+        self.file_orders.forget(&id);
+        Ok(())
+    }
+
+    // Narrows or widens the files the session downloads. A finished torrent
+    // has stopped requesting pieces, so files selected after that would
+    // never arrive; restarting it makes the session work out what is
+    // missing and go back to downloading.
+    pub async fn select_files(&self, handle: &Arc<ManagedTorrent>, only: &HashSet<usize>) -> anyhow::Result<()> {
+        let id = handle.info_hash().as_string();
+        let finished = handle.stats().finished;
+        self.session.update_only_files(handle, only).await?;
+        if !finished || self.engine_paused.lock().unwrap().contains_key(&id) {
+            return Ok(()); // resuming restarts it anyway
+        }
+        let cached = self.files.get(&id, handle);
+        let current = files::with_progress(&cached, 0..cached.len(), &handle.stats().file_progress);
+        if only.iter().all(|&i| current.get(i).is_none_or(|f| f.downloaded >= f.size)) {
+            return Ok(());
+        }
+        info!("Downloading newly selected files of finished torrent {}", id);
+        self.session.pause(handle).await?;
+        self.session.unpause(handle).await?;
+        Ok(())
+    }
+
+    // Marks a just-paused torrent as held for `reason`. Under a session pause
+    // it is remembered underneath, for resume to hand back.
+    pub fn hold(&self, id: &str, reason: PauseReason) {
+        if self.is_session_paused() {
+            self.engine_paused.lock().unwrap().insert(id.to_string(), PauseReason::SessionPaused);
+            self.session_paused_over.lock().unwrap().insert(id.to_string(), reason);
+        } else {
+            self.engine_paused.lock().unwrap().insert(id.to_string(), reason);
+            self.events.emit(EngineEvent::TorrentPaused { id: id.to_string(), reason });
+        }
+    }
+
+    // Ends a hold(). Under a session pause the torrent then starts with the
+    // session.
+    pub async fn release(&self, handle: &Arc<ManagedTorrent>, reason: PauseReason) -> anyhow::Result<()> {
+        let id = handle.info_hash().as_string();
+        if self.session_paused_over.lock().unwrap().get(&id) == Some(&reason) {
+            self.session_paused_over.lock().unwrap().remove(&id);
+        } else if self.engine_paused.lock().unwrap().get(&id) == Some(&reason) {
+            // A pause set through the API, or else a scheduled start that
+            // isn't due yet, takes over the hold
+            let scheduled = self.schedules.all().get(&id).is_some_and(|&at| at > persist::now());
+            let next = if self.modes.get(&id) == TorrentMode::Paused {
+                PauseReason::User
+            } else {
+                PauseReason::Scheduled
+            };
+            if (next == PauseReason::User || scheduled) && reason != next {
+                self.engine_paused.lock().unwrap().insert(id.clone(), next);
+                self.events.emit(EngineEvent::TorrentPaused { id, reason: next });
+                return Ok(());
+            }
+            self.session.unpause(handle).await?;
+            self.engine_paused.lock().unwrap().remove(&id);
+            self.events.emit(EngineEvent::TorrentResumed { id, reason });
+        }
+        Ok(())
+    }
+
+    pub fn is_session_paused(&self) -> bool {
+        self.session_paused.load(Ordering::SeqCst)
+    }
+
+    // Global "stop all traffic": pauses every torrent in one go. While set,
+    // the background tasks leave torrents alone and skip re-announces, so
+    // nothing talks to the network until resume_session().
+    pub async fn pause_session(&self) {
+        if self.session_paused.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        info!("Pausing session");
+
+        let reason = PauseReason::SessionPaused;
+        for handle in self.torrents() {
+            let id = handle.info_hash().as_string();
+            if let Err(e) = self.session.pause(&handle).await {
+                warn!("Failed to pause {}: {}", id, e);
+                continue;
+            }
+            // Remember what it was paused for before so resume can hand it
+            // back to whichever task owned it.
+            let previous = self.engine_paused.lock().unwrap().insert(id.clone(), reason);
+            if let Some(previous) = previous {
+                self.session_paused_over.lock().unwrap().insert(id.clone(), previous);
+            }
+            self.events.emit(EngineEvent::TorrentPaused { id, reason });
+        }
+    }
+
+    pub async fn resume_session(&self) {
+        if !self.session_paused.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        info!("Resuming session");
+
+        let reason = PauseReason::SessionPaused;
+        let previous = std::mem::take(&mut *self.session_paused_over.lock().unwrap());
+        for handle in self.torrents() {
+            let id = handle.info_hash().as_string();
+            if self.engine_paused.lock().unwrap().get(&id) != Some(&reason) {
+                continue;
+            }
+            // Was already paused by another task: stays paused, for that reason
+            if let Some(&earlier) = previous.get(&id) {
+                self.engine_paused.lock().unwrap().insert(id, earlier);
+                continue;
+            }
+            // Set to Paused while the session was
+            if self.modes.get(&id) == TorrentMode::Paused {
+                self.engine_paused.lock().unwrap().insert(id.clone(), PauseReason::User);
+                self.events.emit(EngineEvent::TorrentPaused { id, reason: PauseReason::User });
+                continue;
+            }
+            if let Err(e) = self.session.unpause(&handle).await {
+                warn!("Failed to resume {}: {}", id, e);
+                continue;
+            }
+            self.engine_paused.lock().unwrap().remove(&id);
+            self.events.emit(EngineEvent::TorrentResumed { id, reason });
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::bencode::{self, Value};
+use crate::jobs::Job;
+use crate::persist;
+use anyhow::{anyhow, bail, Result};
+use bridge::CreatedTorrent;
+use crate::torrent::TorrentInfo;
+use sha1::{Digest, Sha1};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub const MIN_PIECE_SIZE: u64 = 16 * 1024;
+pub const MAX_PIECE_SIZE: u64 = 128 * 1024 * 1024;
+// Auto piece size aims for about this many pieces: few enough to keep the
+// .torrent small, enough that a bad piece doesn't cost much to refetch.
+const TARGET_PIECES: u64 = 1500;
+
+// Files to put in a torrent, as paths relative to the source folder.
+pub struct Content {
+    pub name: String,
+    pub files: Vec<(Vec<String>, u64)>,
+    pub single_file: bool,
+}
+
+impl Content {
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|(_, len)| len).sum()
+    }
+}
+
+// Power of two between MIN_PIECE_SIZE and MAX_PIECE_SIZE. Without a
+// request, one that gives about TARGET_PIECES pieces.
+pub fn piece_size(requested: Option<u64>, total_size: u64) -> u64 {
+    requested
+        .unwrap_or(total_size / TARGET_PIECES)
+        .next_power_of_two()
+        .clamp(MIN_PIECE_SIZE, MAX_PIECE_SIZE)
+}
+
+pub fn piece_count(total_size: u64, piece_size: u64) -> u32 {
+    total_size.div_ceil(piece_size) as u32
+}
+
+// Hashes `source` (a file or a folder) and writes a .torrent for it.
+pub async fn create(
+    source: PathBuf,
+    output: &Path,
+    trackers: &[String],
+    private: bool,
+    requested_piece_size: Option<u64>,
+    job: Job,
+) -> Result<CreatedTorrent> {
+    let (content, piece_size, pieces) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let content = scan(&source)?;
+        let piece_size = piece_size(requested_piece_size, content.total_size());
+        let pieces = hash_pieces(&source, &content, piece_size, &job)?;
+        Ok((content, piece_size, pieces))
+    })
+    .await??;
+    write(output, &content, piece_size, pieces, trackers, private).await
+}
+
+// A session torrent's content and piece hashes, for writing it out as a new
+// torrent without reading the data again.
+pub fn from_session(info: &TorrentInfo) -> Result<(Content, Vec<u8>)> {
+    let files = info.files();
+    let content = Content {
+        name: info.name.clone(),
+        single_file: files.len() == 1,
+        files: files.iter().map(|f| (f.name.split('/').map(String::from).collect(), f.len)).collect(),
+    };
+    let count = piece_count(content.total_size(), info.piece_length);
+    let mut pieces = Vec::with_capacity(count as usize * 20);
+    for index in 0..count {
+        let hash = info.piece_hash(index).ok_or_else(|| anyhow!("no hash for piece {}", index))?;
+        pieces.extend_from_slice(&hash);
+    }
+    Ok((content, pieces))
+}
+
+pub async fn write(
+    output: &Path,
+    content: &Content,
+    piece_size: u64,
+    pieces: Vec<u8>,
+    trackers: &[String],
+    private: bool,
+) -> Result<CreatedTorrent> {
+    let info = info_dict(content, piece_size, pieces, private);
+    let info_hash = hex::encode(Sha1::digest(bencode::encode(&info)));
+    tokio::fs::write(output, torrent_file(info, trackers)).await?;
+    let total_size = content.total_size();
+    Ok(CreatedTorrent {
+        info_hash,
+        path: output.to_string_lossy().into_owned(),
+        piece_size,
+        piece_count: piece_count(total_size, piece_size),
+        total_size,
+    })
+}
+
+fn scan(source: &Path) -> Result<Content> {
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| anyhow!("{} has no file name", source.display()))?;
+    let meta = std::fs::metadata(source)?;
+    if meta.is_file() {
+        return Ok(Content { files: vec![(vec![name.clone()], meta.len())], name, single_file: true });
+    }
+    let mut files = Vec::new();
+    walk(source, &mut Vec::new(), &mut files)?;
+    if files.is_empty() {
+        bail!("{} has no files", source.display());
+    }
+    Ok(Content { name, files, single_file: false })
+}
+
+// Sorted so the same folder always gives the same torrent.
+fn walk(dir: &Path, prefix: &mut Vec<String>, files: &mut Vec<(Vec<String>, u64)>) -> Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let meta = entry.metadata()?;
+        prefix.push(name);
+        if meta.is_dir() {
+            walk(&entry.path(), prefix, files)?;
+        } else if meta.is_file() {
+            files.push((prefix.clone(), meta.len()));
+        }
+        prefix.pop();
+    }
+    Ok(())
+}
+
+// Pieces run across file boundaries, as if the files were one stream.
+fn hash_pieces(source: &Path, content: &Content, piece_size: u64, job: &Job) -> Result<Vec<u8>> {
+    let total = content.total_size();
+    let mut pieces = Vec::with_capacity(piece_count(content.total_size(), piece_size) as usize * 20);
+    let mut buf = vec![0u8; piece_size as usize];
+    let mut filled = 0;
+    for (path, _) in &content.files {
+        let path = if content.single_file { source.to_path_buf() } else { source.join(path.iter().collect::<PathBuf>()) };
+        let mut file = File::open(path)?;
+        loop {
+            let n = file.read(&mut buf[filled..])?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+            if filled == buf.len() {
+                pieces.extend_from_slice(&Sha1::digest(&buf));
+                filled = 0;
+                if job.is_cancelled() {
+                    bail!("cancelled");
+                }
+                job.progress(pieces.len() as u64 / 20 * piece_size, total);
+            }
+        }
+    }
+    if filled > 0 {
+        pieces.extend_from_slice(&Sha1::digest(&buf[..filled]));
+    }
+    Ok(pieces)
+}
+
+pub fn info_dict(content: &Content, piece_size: u64, pieces: Vec<u8>, private: bool) -> Value {
+    let mut info = BTreeMap::new();
+    info.insert(b"name".to_vec(), Value::Bytes(content.name.clone().into_bytes()));
+    info.insert(b"piece length".to_vec(), Value::Int(piece_size as i64));
+    info.insert(b"pieces".to_vec(), Value::Bytes(pieces));
+    if private {
+        info.insert(b"private".to_vec(), Value::Int(1));
+    }
+    if content.single_file {
+        info.insert(b"length".to_vec(), Value::Int(content.total_size() as i64));
+    } else {
+        let files = content
+            .files
+            .iter()
+            .map(|(path, len)| {
+                let mut file = BTreeMap::new();
+                file.insert(b"length".to_vec(), Value::Int(*len as i64));
+                let path = path.iter().map(|p| Value::Bytes(p.clone().into_bytes())).collect();
+                file.insert(b"path".to_vec(), Value::List(path));
+                Value::Dict(file)
+            })
+            .collect();
+        info.insert(b"files".to_vec(), Value::List(files));
+    }
+    Value::Dict(info)
+}
+
+// Each tracker gets its own tier, tried in the order given.
+pub fn torrent_file(info: Value, trackers: &[String]) -> Vec<u8> {
+    let mut root = BTreeMap::new();
+    if let Some(first) = trackers.first() {
+        root.insert(b"announce".to_vec(), Value::Bytes(first.clone().into_bytes()));
+    }
+    if trackers.len() > 1 {
+        let tiers = trackers.iter().map(|t| Value::List(vec![Value::Bytes(t.clone().into_bytes())])).collect();
+        root.insert(b"announce-list".to_vec(), Value::List(tiers));
+    }
+    root.insert(b"created by".to_vec(), Value::Bytes(b"AuroraTorrent".to_vec()));
+    root.insert(b"creation date".to_vec(), Value::Int(persist::now() as i64));
+    root.insert(b"info".to_vec(), info);
+    bencode::encode(&Value::Dict(root))
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Passkeys live in the OS keyring (Keychain, Credential Manager, Secret
+// Service), one entry per tracker domain. Only the domain names are kept in
+// the config so they can be listed.
+const SERVICE: &str = "AuroraTorrent";
+// Announce URLs can carry this placeholder instead of a real passkey.
+pub const PASSKEY_PLACEHOLDER: &str = "{passkey}";
+
+pub fn tracker_domain(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(|h| h.to_lowercase())
+}
+
+#[derive(Default)]
+pub struct CredentialStore {
+    // Keyring lookups can hit D-Bus or prompt the user, so keep what we've read
+    cache: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl CredentialStore {
+    // The keyring is read off the runtime, once per domain
+    pub async fn get(&self, domain: &str) -> Option<String> {
+        if let Some(passkey) = self.cached(domain) {
+            return passkey;
+        }
+        let entry = domain.to_string();
+        let passkey = tokio::task::spawn_blocking(move || keyring::Entry::new(SERVICE, &entry).ok()?.get_password().ok())
+            .await
+            .ok()
+            .flatten();
+        self.cache.lock().unwrap().insert(domain.to_string(), passkey.clone());
+        passkey
+    }
+
+    fn cached(&self, domain: &str) -> Option<Option<String>> {
+        self.cache.lock().unwrap().get(domain).cloned()
+    }
+
+    pub fn set(&self, domain: &str, passkey: &str) -> Result<()> {
+        keyring::Entry::new(SERVICE, domain)?.set_password(passkey)?;
+        self.cache.lock().unwrap().insert(domain.to_string(), Some(passkey.to_string()));
+        Ok(())
+    }
+
+    pub fn remove(&self, domain: &str) -> Result<()> {
+        match keyring::Entry::new(SERVICE, domain)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.cache.lock().unwrap().insert(domain.to_string(), None);
+        Ok(())
+    }
+
+    // Fills in the stored passkey for the URL's domain in place of the
+    // placeholder.
+    pub async fn apply(&self, url: &str) -> String {
+        let Some(domain) = tracker_domain(url) else { return url.to_string() };
+        match self.get(&domain).await {
+            Some(passkey) => swap(url, PASSKEY_PLACEHOLDER, &passkey),
+            None => url.to_string(),
+        }
+    }
+
+    // A passkey as if read from the keyring, which tests can't use
+    #[cfg(test)]
+    pub fn remember(&self, domain: &str, passkey: &str) {
+        self.cache.lock().unwrap().insert(domain.to_string(), Some(passkey.to_string()));
+    }
+
+    // For display: never show a stored passkey in the UI. Only passkeys
+    // already read are masked, but a URL only carries one once apply (or a
+    // rotation) has read it.
+    pub fn mask(&self, url: &str) -> String {
+        match tracker_domain(url).and_then(|d| self.cached(&d).flatten()) {
+            Some(passkey) if !passkey.is_empty() => swap(url, &passkey, PASSKEY_PLACEHOLDER),
+            _ => url.to_string(),
+        }
+    }
+}
+
+// Swaps a rotated passkey in an announce URL.
+pub fn rotate(url: &str, old: Option<&str>, new: &str) -> String {
+    let url = swap(url, PASSKEY_PLACEHOLDER, new);
+    match old.filter(|o| !o.is_empty()) {
+        Some(old) => swap(&url, old, new),
+        None => url,
+    }
+}
+
+// Replaces `from` where it is a whole path segment or query value of the
+// URL, and nowhere else: a short passkey can just as well turn up in the
+// host, the tracker's path or another parameter.
+fn swap(url: &str, from: &str, to: &str) -> String {
+    let is_from = |part: &str| part == from || urlencoding::decode(part).is_ok_and(|p| p == from);
+    let (url, fragment) = url.split_at(url.find('#').unwrap_or(url.len()));
+    let (url, query) = match url.split_once('?') {
+        Some((url, query)) => (url, Some(query)),
+        None => (url, None),
+    };
+    let path_start = match url.find("://") {
+        Some(i) => url[i + 3..].find('/').map_or(url.len(), |j| i + 3 + j),
+        None => 0,
+    };
+    let (origin, path) = url.split_at(path_start);
+
+    let mut out = origin.to_string();
+    let segments: Vec<&str> = path.split('/').map(|s| if is_from(s) { to } else { s }).collect();
+    out.push_str(&segments.join("/"));
+    if let Some(query) = query {
+        let pairs: Vec<String> = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, value)) if is_from(value) => format!("{}={}", key, to),
+                _ => pair.to_string(),
+            })
+            .collect();
+        out.push('?');
+        out.push_str(&pairs.join("&"));
+    }
+    out.push_str(fragment);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swaps_only_whole_segments_and_values() {
+        let url = "https://ab.example/ab/announce?passkey=ab&x=abc#ab";
+        assert_eq!(swap(url, "ab", "cd"), "https://ab.example/cd/announce?passkey=cd&x=abc#ab");
+        assert_eq!(
+            rotate("https://t.example/announce.php?passkey={passkey}", None, "k1"),
+            "https://t.example/announce.php?passkey=k1"
+        );
+        assert_eq!(rotate("https://t.example/%7Bpasskey%7D/announce", None, "k1"), "https://t.example/k1/announce");
+        assert_eq!(rotate("udp://t.example:80/k1k1/k1", Some("k1"), "k2"), "udp://t.example:80/k1k1/k2");
+    }
+}
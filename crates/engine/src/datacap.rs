@@ -0,0 +1,242 @@
+use crate::autosave::Dirty;
+use crate::{persist, AppState};
+use bridge::{DailyUsage, DataCapAction, DataUsage, EngineEvent, PauseReason};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const TICK: Duration = Duration::from_secs(10);
+const FILE: &str = "data_usage.json";
+const KEEP_DAYS: usize = 400;
+const DAY: u64 = 24 * 60 * 60;
+
+// Bytes exchanged with peers, per UTC day. The speed task hands over the
+// session's counts once a second; the task below folds them into today's
+// entry, and the autosave task saves them to the state dir. The session
+// counts peers on the local network like any other.
+#[derive(Clone)]
+pub struct DataMeter {
+    path: PathBuf,
+    dirty: Dirty,
+    downloaded: Arc<AtomicU64>, // since the last fold
+    uploaded: Arc<AtomicU64>,
+    days: Arc<Mutex<Vec<DailyUsage>>>, // oldest first
+    capped: Arc<AtomicBool>,
+}
+
+impl DataMeter {
+    pub fn load(state_dir: &Path, dirty: Dirty) -> Self {
+        let path = state_dir.join(FILE);
+        Self {
+            days: Arc::new(Mutex::new(persist::load(&path))),
+            path,
+            dirty,
+            downloaded: Arc::default(),
+            uploaded: Arc::default(),
+            capped: Arc::default(),
+        }
+    }
+
+    pub fn add(&self, downloaded: u64, uploaded: u64) {
+        self.downloaded.fetch_add(downloaded, Ordering::Relaxed);
+        self.uploaded.fetch_add(uploaded, Ordering::Relaxed);
+    }
+
+    // Whether this month's cap was reached
+    pub fn capped(&self) -> bool {
+        self.capped.load(Ordering::Relaxed)
+    }
+
+    fn fold(&self, now: u64) {
+        let downloaded = self.downloaded.swap(0, Ordering::Relaxed);
+        let uploaded = self.uploaded.swap(0, Ordering::Relaxed);
+        if downloaded == 0 && uploaded == 0 {
+            return;
+        }
+        let date = now - now % DAY;
+        let mut days = self.days.lock().unwrap();
+        if days.last().is_none_or(|d| d.date != date) {
+            days.push(DailyUsage { date, downloaded: 0, uploaded: 0 });
+        }
+        let today = days.last_mut().unwrap();
+        today.downloaded += downloaded;
+        today.uploaded += uploaded;
+        let excess = days.len().saturating_sub(KEEP_DAYS);
+        days.drain(..excess);
+        self.dirty.mark_periodic();
+    }
+
+    // Counts what came in since the last tick, so the next save has it
+    pub fn flush(&self) {
+        self.fold(persist::now());
+    }
+
+    pub fn save_if_dirty(&self) {
+        if !self.dirty.take() {
+            return;
+        }
+        if let Err(e) = persist::save(&self.path, &*self.days.lock().unwrap()) {
+            warn!("Failed to save data usage: {}", e);
+        }
+    }
+
+    // Both directions since `start`
+    fn used_since(&self, start: u64) -> u64 {
+        let days = self.days.lock().unwrap();
+        days.iter().filter(|d| d.date >= start).map(|d| d.downloaded + d.uploaded).sum()
+    }
+
+    pub fn usage(&self, billing_day: u32, cap: u64) -> DataUsage {
+        let period_start = period_start(persist::now(), billing_day);
+        DataUsage {
+            days: self.days.lock().unwrap().clone(),
+            period_start,
+            period_used: self.used_since(period_start),
+            cap,
+            capped: self.capped(),
+        }
+    }
+}
+
+// Start of the billing month `now` is in: the last `billing_day` (1-28) of
+// a month at or before today, as unix seconds at UTC midnight.
+fn period_start(now: u64, billing_day: u32) -> u64 {
+    let billing_day = billing_day.clamp(1, 28);
+    let (year, month, day) = civil_from_days((now / DAY) as i64);
+    let (year, month) = match (day >= billing_day, month) {
+        (true, _) => (year, month),
+        (false, 1) => (year - 1, 12),
+        (false, _) => (year, month - 1),
+    };
+    days_from_civil(year, month, billing_day) as u64 * DAY
+}
+
+// Days since 1970-01-01 <-> proleptic Gregorian (year, month, day)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// Enforces `monthly_data_cap`: warns once per billing month at
+// `data_cap_warn_percent`, and at the cap either pauses every torrent or
+// switches to the alternative speed limits (see ratelimit) until the next
+// billing day.
+pub async fn run(state: AppState) {
+    let meter = state.data_meter.clone();
+    let mut warned_period = None;
+    loop {
+        tokio::time::sleep(TICK).await;
+        let now = persist::now();
+        meter.fold(now);
+
+        let (cap, warn_percent, billing_day, action) = {
+            let config = state.config.lock().unwrap();
+            let cap = config.monthly_data_cap;
+            (cap, config.data_cap_warn_percent, config.data_cap_billing_day, config.data_cap_action)
+        };
+        let period = period_start(now, billing_day);
+        let used = meter.used_since(period);
+        let over = cap > 0 && used >= cap;
+
+        if cap > 0 && !over && warned_period != Some(period) && used >= cap / 100 * warn_percent as u64 {
+            warned_period = Some(period);
+            warn!("Data cap: {} of {} bytes used this month", used, cap);
+            state.events.emit(EngineEvent::DataCapWarning { used, cap });
+        }
+        if over && !meter.capped.swap(true, Ordering::Relaxed) {
+            info!("Data cap of {} bytes reached: {:?}", cap, action);
+            state.events.emit(EngineEvent::DataCapReached { used, cap, action });
+        } else if !over && meter.capped.swap(false, Ordering::Relaxed) {
+            info!("New billing month, data cap lifted");
+        }
+
+        if state.is_session_paused() {
+            continue;
+        }
+        let reason = PauseReason::DataCap;
+        let hold = over && action == DataCapAction::Pause;
+        for handle in state.torrents() {
+            let id = handle.info_hash().as_string();
+            let current = state.engine_paused.lock().unwrap().get(&id).copied();
+            match (current, hold) {
+                (None, true) => {
+                    if let Err(e) = state.session.pause(&handle).await {
+                        warn!("Failed to pause {}: {}", id, e);
+                        continue;
+                    }
+                    state.engine_paused.lock().unwrap().insert(id.clone(), reason);
+                    state.events.emit(EngineEvent::TorrentPaused { id, reason });
+                }
+                (Some(r), false) if r == reason => {
+                    if let Err(e) = state.release(&handle, reason).await {
+                        warn!("Failed to resume {}: {}", id, e);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i64, month: u32, day: u32) -> u64 {
+        days_from_civil(year, month, day) as u64 * DAY
+    }
+
+    #[test]
+    fn dates_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+        // 2100 isn't a leap year, 2000 is
+        assert_eq!(civil_from_days(days_from_civil(2100, 2, 28) + 1), (2100, 3, 1));
+        assert_eq!(civil_from_days(days_from_civil(2000, 2, 28) + 1), (2000, 2, 29));
+        for days in -800..800 {
+            let (year, month, day) = civil_from_days(days * 37);
+            assert_eq!(days_from_civil(year, month, day), days * 37);
+        }
+    }
+
+    #[test]
+    fn periods_roll_over_months_and_years() {
+        // on or after the billing day: this month
+        assert_eq!(period_start(at(2026, 5, 15) + 3600, 15), at(2026, 5, 15));
+        assert_eq!(period_start(at(2026, 5, 20), 15), at(2026, 5, 15));
+        // before it: last month, or last year's December in January
+        assert_eq!(period_start(at(2026, 5, 14) + DAY - 1, 15), at(2026, 4, 15));
+        assert_eq!(period_start(at(2026, 1, 3), 10), at(2025, 12, 10));
+        assert_eq!(period_start(at(2026, 1, 1), 1), at(2026, 1, 1));
+    }
+
+    #[test]
+    fn late_billing_days_count_as_the_28th() {
+        // every month has a 28th, leap or not
+        assert_eq!(period_start(at(2026, 3, 1), 31), at(2026, 2, 28));
+        assert_eq!(period_start(at(2024, 2, 29), 30), at(2024, 2, 28));
+        assert_eq!(period_start(at(2024, 2, 27), 29), at(2024, 1, 28));
+        assert_eq!(period_start(at(2026, 7, 9), 0), at(2026, 7, 1));
+    }
+}
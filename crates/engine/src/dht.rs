@@ -1,9 +1,126 @@
-use anyhow::Result;
-use mainline::Dht;
-
-pub async fn init_dht() -> Result<Dht> {
-    // Initialize the Mainline DHT
-    let dht = Dht::client()?;
-    println!("DHT Initialized");
-    Ok(dht)
+use bridge::DhtStats;
+use futures::StreamExt;
+use librqbit::dht::Id20;
+use librqbit::Session;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::time::Instant;
+
+// How long to collect peers for one lookup
+const LOOKUP_WINDOW: Duration = Duration::from_secs(8);
+// Lookups in flight at once, and the minimum gap between starting two, so a
+// page of search results can't flood the routing table with get_peers.
+const MAX_LOOKUPS: usize = 4;
+const LOOKUP_SPACING: Duration = Duration::from_millis(250);
+
+struct Lookup {
+    info_hash: Id20,
+    reply: oneshot::Sender<Option<u32>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    lookups: u64,
+    started: VecDeque<Instant>, // starts within the last minute
+}
+
+// The engine's own DHT lookups (swarm previews) go through this task rather
+// than each caller driving the session's DHT directly. Requests queue on a
+// channel; a lookup whose caller has gone away is dropped before it starts
+// or cancelled while it runs.
+#[derive(Clone)]
+pub struct DhtLookups {
+    tx: mpsc::Sender<Lookup>,
+    session: Arc<Session>,
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl DhtLookups {
+    pub fn spawn(session: Arc<Session>) -> Self {
+        let (tx, mut rx) = mpsc::channel::<Lookup>(64);
+        let counters = Arc::new(Mutex::new(Counters::default()));
+        let lookups = Self { tx, session: session.clone(), counters: counters.clone() };
+        tokio::spawn(async move {
+            let slots = Arc::new(Semaphore::new(MAX_LOOKUPS));
+            let mut next_start = Instant::now();
+            while let Some(lookup) = rx.recv().await {
+                tokio::time::sleep_until(next_start).await;
+                let Ok(permit) = slots.clone().acquire_owned().await else { return };
+                if lookup.reply.is_closed() {
+                    continue;
+                }
+                next_start = Instant::now() + LOOKUP_SPACING;
+
+                let session = session.clone();
+                let counters = counters.clone();
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    let mut reply = lookup.reply;
+                    tokio::select! {
+                        count = peer_count(&session, &counters, lookup.info_hash) => {
+                            let _ = reply.send(count);
+                        }
+                        _ = reply.closed() => {}
+                    }
+                });
+            }
+        });
+        lookups
+    }
+
+    // Distinct peers the DHT returns for the hash within a short window.
+    // None when DHT is off or the hash is malformed.
+    pub async fn peer_count(&self, info_hash: &str) -> Option<u32> {
+        let info_hash = info_hash.parse::<Id20>().ok()?;
+        let (reply, rx) = oneshot::channel();
+        self.tx.send(Lookup { info_hash, reply }).await.ok()?;
+        rx.await.ok().flatten()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.session.get_dht().map_or(0, |dht| dht.stats().routing_table_size)
+    }
+
+    pub fn stats(&self) -> DhtStats {
+        let Some(dht) = self.session.get_dht() else {
+            return DhtStats::default();
+        };
+        let table = dht.stats();
+        let mut counters = self.counters.lock().unwrap();
+        prune(&mut counters.started);
+        DhtStats {
+            enabled: true,
+            nodes: table.routing_table_size,
+            outstanding_requests: table.outstanding_requests,
+            lookups: counters.lookups,
+            lookups_last_minute: counters.started.len() as u32,
+        }
+    }
+}
+
+fn prune(started: &mut VecDeque<Instant>) {
+    while started.front().is_some_and(|t| t.elapsed() > Duration::from_secs(60)) {
+        started.pop_front();
+    }
+}
+
+async fn peer_count(session: &Session, counters: &Mutex<Counters>, info_hash: Id20) -> Option<u32> {
+    let dht = session.get_dht()?;
+    let mut peers = dht.get_peers(info_hash, None);
+    {
+        let mut counters = counters.lock().unwrap();
+        counters.lookups += 1;
+        counters.started.push_back(Instant::now());
+        prune(&mut counters.started);
+    }
+    let mut seen = HashSet::new();
+    let _ = tokio::time::timeout(LOOKUP_WINDOW, async {
+        while let Some(addr) = peers.next().await {
+            seen.insert(addr);
+        }
+    })
+    .await;
+    Some(seen.len() as u32)
 }
@@ -0,0 +1,145 @@
+use crate::{logs, persist, AppState};
+use anyhow::Result;
+use bridge::TorrentState;
+use std::collections::BTreeMap;
+use std::io::Write;
+use regex::{Captures, Regex};
+use std::path::Path;
+use std::sync::OnceLock;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+// A zip for bug reports with what is otherwise spread over a dozen RPCs: the
+// config, the torrents with their peer and tracker tables apart, recent
+// events and log lines, and the background work and its health. Passkeys
+// in any URL are redacted (see redact_urls), the config loses the private
+// tracker list, and the user's home folder shows as "~" throughout.
+pub fn write(state: &AppState, torrents: Vec<TorrentState>, output: &Path) -> Result<()> {
+    let mut config = state.config.lock().unwrap().clone();
+    config.tracker_credential_domains.clear();
+
+    let mut peers = BTreeMap::new();
+    let mut trackers = BTreeMap::new();
+    let torrents: Vec<TorrentState> = torrents
+        .into_iter()
+        .map(|mut torrent| {
+            peers.insert(torrent.id.clone(), std::mem::take(&mut torrent.peers));
+            trackers.insert(torrent.id.clone(), std::mem::take(&mut torrent.trackers));
+            torrent
+        })
+        .collect();
+
+    let summary = serde_json::json!({
+        "created_at": persist::now(),
+        "version": env!("CARGO_PKG_VERSION"),
+        "user_agent": state.identity.user_agent(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "session_paused": state.is_session_paused(),
+        "engine_paused": *state.engine_paused.lock().unwrap(),
+        "dht": state.dht.stats(),
+        "write_buffer_bytes": state.write_budget.used(),
+        "startup": state.startup.get(),
+    });
+    let tasks = serde_json::json!({
+        "background": state.tasks.list(),
+        "jobs": state.jobs.list(),
+        "check_queue": state.checks.entries(),
+        "queues": state.queues.all(),
+    });
+
+    let entries = [
+        ("summary.json", serde_json::to_string_pretty(&summary)?),
+        ("config.json", serde_json::to_string_pretty(&config)?),
+        ("torrents.json", serde_json::to_string_pretty(&torrents)?),
+        ("peers.json", serde_json::to_string_pretty(&peers)?),
+        ("trackers.json", serde_json::to_string_pretty(&trackers)?),
+        ("events.json", serde_json::to_string_pretty(&state.events.since(0))?),
+        ("tasks.json", serde_json::to_string_pretty(&tasks)?),
+        ("engine.log", logs::recent().join("\n")),
+    ];
+
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut zip = ZipWriter::new(std::fs::File::create(output)?);
+    for (name, text) in entries {
+        zip.start_file(name, SimpleFileOptions::default())?;
+        zip.write_all(scrub_home(&redact_urls(&text)).as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+const REDACTED: &str = "REDACTED";
+// Query parameters trackers take passkeys in
+const SECRET_PARAMS: &[&str] = &["passkey", "authkey", "pid", "key", "torrent_pass"];
+
+// Only passkeys in the keyring are masked in the engine's tracker URLs; a
+// private .torrent's own announce URLs carry theirs in the path or query,
+// and so do events and log lines that quote them.
+fn redact_urls(text: &str) -> String {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    let url = URL.get_or_init(|| Regex::new(r#"(?i)\b(?:https?|udp)://[^\s"'<>]+"#).unwrap());
+    url.replace_all(text, |c: &Captures| redact_url(&c[0])).into_owned()
+}
+
+// "/<passkey>/announce", "/announce/<passkey>" and "?passkey=..." alike
+fn redact_url(url: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some((base, query)) => (base, Some(query)),
+        None => (url, None),
+    };
+    let path_start = base.find("://").and_then(|i| base[i + 3..].find('/').map(|j| i + 3 + j)).unwrap_or(base.len());
+    let (host, path) = base.split_at(path_start);
+    let path: Vec<&str> = path.split('/').map(|segment| if is_key(segment) { REDACTED } else { segment }).collect();
+    let mut out = format!("{}{}", host, path.join("/"));
+    if let Some(query) = query {
+        let params: Vec<String> = query
+            .split('&')
+            .map(|param| match param.split_once('=') {
+                Some((name, _)) if SECRET_PARAMS.contains(&name.to_ascii_lowercase().as_str()) => {
+                    format!("{}={}", name, REDACTED)
+                }
+                _ => param.to_string(),
+            })
+            .collect();
+        out.push('?');
+        out.push_str(&params.join("&"));
+    }
+    out
+}
+
+// Passkeys are long runs of letters and digits; path words like "announce"
+// have no digits
+fn is_key(segment: &str) -> bool {
+    segment.len() >= 16
+        && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && segment.chars().any(|c| c.is_ascii_digit())
+}
+
+// Both as written and JSON-escaped, for Windows paths
+fn scrub_home(text: &str) -> String {
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).unwrap_or_default();
+    if home.len() < 2 {
+        return text.to_string();
+    }
+    let escaped = home.replace('\\', "\\\\");
+    text.replace(&escaped, "~").replace(&home, "~")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_passkeys_in_urls() {
+        let text = r#"{"url":"https://t.example/0123456789abcdef0123/announce"} udp://t.example:80/announce?passkey=s3cr3t&x=1
+            see http://t.example/announce.php?uid=7&PID=abc next"#;
+        let redacted = redact_urls(text);
+        assert!(redacted.contains(r#""https://t.example/REDACTED/announce""#));
+        assert!(redacted.contains("udp://t.example:80/announce?passkey=REDACTED&x=1\n"));
+        assert!(redacted.contains("http://t.example/announce.php?uid=7&PID=REDACTED next"));
+        assert_eq!(redact_urls("https://t.example/announce"), "https://t.example/announce");
+    }
+}
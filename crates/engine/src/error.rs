@@ -0,0 +1,78 @@
+use bridge::{ErrorKind, Message, RpcError};
+use thiserror::Error;
+
+/// Errors returned by [`Client`](crate::Client) operations.
+///
+/// Each variant maps to an [`ErrorKind`] so callers on the other side of
+/// RPC, HTTP or Tauri IPC can tell a missing torrent from a bad argument
+/// without parsing the message.
+#[derive(Debug, Error)]
+pub enum EngineError {
+    /// The torrent or file named in the message doesn't exist.
+    #[error("{0} not found")]
+    NotFound(String),
+    /// An argument was malformed or out of range.
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The OS keyring refused to store or delete a passkey.
+    #[error("Credential store error: {0}")]
+    Credentials(String),
+    #[error("{0} is not supported")]
+    Unsupported(String),
+    /// The engine can't do this right now, e.g. the session is paused.
+    #[error("{0}")]
+    Unavailable(String),
+    /// Anything else, usually an error from the session itself.
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl EngineError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            EngineError::NotFound(_) => ErrorKind::NotFound,
+            EngineError::InvalidInput(_) => ErrorKind::InvalidInput,
+            EngineError::Io(_) => ErrorKind::Io,
+            EngineError::Credentials(_) => ErrorKind::Credentials,
+            EngineError::Unsupported(_) => ErrorKind::Unsupported,
+            EngineError::Unavailable(_) => ErrorKind::Unavailable,
+            EngineError::Internal(_) => ErrorKind::Internal,
+        }
+    }
+
+    /// The error as a message key ("error.not_found", ...) with the detail
+    /// text as its `detail` parameter, for UIs that show it localized.
+    pub fn message(&self) -> Message {
+        let key = match self.kind() {
+            ErrorKind::NotFound => "error.not_found",
+            ErrorKind::InvalidInput => "error.invalid_input",
+            ErrorKind::Io => "error.io",
+            ErrorKind::Credentials => "error.credentials",
+            ErrorKind::Unsupported => "error.unsupported",
+            ErrorKind::Unavailable => "error.unavailable",
+            ErrorKind::Internal => "error.internal",
+        };
+        let detail = match self {
+            EngineError::NotFound(s)
+            | EngineError::InvalidInput(s)
+            | EngineError::Credentials(s)
+            | EngineError::Unsupported(s)
+            | EngineError::Unavailable(s) => s.clone(),
+            EngineError::Io(e) => e.to_string(),
+            EngineError::Internal(e) => format!("{:#}", e),
+        };
+        Message::new(key).with("detail", detail)
+    }
+
+    pub(crate) fn torrent_not_found(id: &str) -> Self {
+        EngineError::NotFound(format!("Torrent {}", id))
+    }
+}
+
+impl From<EngineError> for RpcError {
+    fn from(e: EngineError) -> Self {
+        RpcError::new(e.kind(), e.to_string()).with_message(e.message())
+    }
+}
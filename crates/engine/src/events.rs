@@ -0,0 +1,98 @@
+use bridge::{EngineEvent, EventRecord};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+
+const HISTORY_LEN: usize = 256;
+
+// Fan-out for engine notifications. Live subscribers get events through the
+// broadcast channel; pollers (the UI over RPC) read the bounded history.
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<EventRecord>,
+    history: Arc<Mutex<History>>,
+}
+
+struct History {
+    next_seq: u64,
+    records: VecDeque<EventRecord>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(HISTORY_LEN);
+        Self {
+            tx,
+            history: Arc::new(Mutex::new(History {
+                next_seq: 1,
+                records: VecDeque::with_capacity(HISTORY_LEN),
+            })),
+        }
+    }
+
+    pub fn emit(&self, event: EngineEvent) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let record = {
+            let mut history = self.history.lock().unwrap();
+            let message = event.message();
+            let record = EventRecord { seq: history.next_seq, timestamp, event, message };
+            history.next_seq += 1;
+            if history.records.len() == HISTORY_LEN {
+                history.records.pop_front();
+            }
+            history.records.push_back(record.clone());
+            record
+        };
+
+        // No receivers is fine, the history still has it.
+        let _ = self.tx.send(record);
+    }
+
+    // Live events from now on. Subscribe before reading `since` and skip
+    // anything already seen by seq so nothing falls in between.
+    pub fn subscribe(&self) -> broadcast::Receiver<EventRecord> {
+        self.tx.subscribe()
+    }
+
+    pub fn since(&self, seq: u64) -> Vec<EventRecord> {
+        let history = self.history.lock().unwrap();
+        history.records.iter().filter(|r| r.seq > seq).cloned().collect()
+    }
+
+    // Buffered events after `since`, then live ones, in order and without
+    // repeats. Ends when the receiver is dropped.
+    pub fn follow(&self, since: u64) -> mpsc::Receiver<EventRecord> {
+        let mut live = self.subscribe();
+        let backlog = self.since(since);
+        let bus = self.clone();
+        let (tx, rx) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            let mut last = since;
+            let mut records = backlog;
+            loop {
+                for record in records {
+                    if record.seq <= last {
+                        continue;
+                    }
+                    last = record.seq;
+                    if tx.send(record).await.is_err() {
+                        return;
+                    }
+                }
+                records = match live.recv().await {
+                    Ok(record) => vec![record],
+                    // Fell behind the channel: catch up from the history
+                    Err(broadcast::error::RecvError::Lagged(_)) => bus.since(last),
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+            }
+        });
+        rx
+    }
+}
@@ -0,0 +1,244 @@
+use crate::bencode::{self, Value};
+use crate::paths;
+use anyhow::{anyhow, bail, Result};
+use bridge::{AddPreview, FileInfo, FilePriority};
+use sha1::{Digest, Sha1};
+
+// Turns a magnet link or .torrent path handed to us by the OS into the
+// details the add dialog shows, without adding anything to the session.
+pub async fn handle_external_add(uri: &str) -> Result<AddPreview> {
+    let uri = uri.trim();
+    if uri.starts_with("magnet:") {
+        return parse_magnet(uri);
+    }
+
+    let path = torrent_path(uri);
+    let bytes = tokio::fs::read(&path).await?;
+    let mut preview = parse_torrent(&bytes)?;
+    preview.source = path;
+    Ok(preview)
+}
+
+// The .torrent file a non-magnet source names; the OS hands these over as
+// file:// URLs.
+pub fn torrent_path(uri: &str) -> String {
+    let path = uri.trim();
+    let path = path.strip_prefix("file://").unwrap_or(path);
+    urlencoding::decode(path).map(|p| p.into_owned()).unwrap_or_else(|_| path.to_string())
+}
+
+pub fn parse_magnet(uri: &str) -> Result<AddPreview> {
+    let query = uri.strip_prefix("magnet:?").ok_or_else(|| anyhow!("not a magnet link"))?;
+
+    let mut info_hash = None;
+    let mut name = None;
+    let mut trackers = Vec::new();
+    for pair in query.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = urlencoding::decode(&value.replace('+', " "))?.into_owned();
+        match key {
+            "xt" => {
+                if let Some(hash) = value.strip_prefix("urn:btih:") {
+                    info_hash = Some(normalize_info_hash(hash)?);
+                }
+            }
+            "dn" => name = Some(value),
+            "tr" => trackers.push(value),
+            _ => {}
+        }
+    }
+
+    Ok(AddPreview {
+        source: uri.to_string(),
+        info_hash: info_hash.ok_or_else(|| anyhow!("magnet link has no btih info hash"))?,
+        name,
+        total_size: None,
+        files: vec![],
+        trackers,
+    })
+}
+
+// Passes the magnet link's trackers ("tr") through `f`, leaving the rest
+// of it as it is
+pub fn map_magnet_trackers(uri: &str, f: impl Fn(&str) -> String) -> String {
+    let Some(query) = uri.strip_prefix("magnet:?") else { return uri.to_string() };
+    let pairs: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some(("tr", value)) => match urlencoding::decode(&value.replace('+', " ")) {
+                Ok(url) => format!("tr={}", urlencoding::encode(&f(&url))),
+                Err(_) => pair.to_string(),
+            },
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("magnet:?{}", pairs.join("&"))
+}
+
+pub fn parse_torrent(bytes: &[u8]) -> Result<AddPreview> {
+    let root = bencode::decode(bytes)?;
+    let info = root.get("info").ok_or_else(|| anyhow!("torrent has no info dictionary"))?;
+    // The hash is of the info dict's bytes as they are in the file, which
+    // re-encoding only reproduces when they were canonical
+    let (_, info_bytes) = bencode::raw_entries(bytes)?
+        .into_iter()
+        .find(|(key, _)| key == b"info")
+        .ok_or_else(|| anyhow!("torrent has no info dictionary"))?;
+    let info_hash = hex::encode(Sha1::digest(info_bytes));
+    let (name, files) = describe_info(info)?;
+
+    let mut trackers: Vec<String> = root.get("announce").and_then(Value::as_str).into_iter().collect();
+    if let Some(tiers) = root.get("announce-list").and_then(Value::as_list) {
+        for url in tiers.iter().filter_map(Value::as_list).flatten().filter_map(Value::as_str) {
+            if !trackers.contains(&url) {
+                trackers.push(url);
+            }
+        }
+    }
+
+    Ok(AddPreview {
+        source: String::new(),
+        info_hash,
+        name,
+        total_size: Some(total_size(&files)?),
+        files,
+        trackers,
+    })
+}
+
+// Fills in a magnet's preview from the info dictionary fetched from peers,
+// after checking it really is the torrent the magnet names.
+pub fn with_metadata(mut preview: AddPreview, info_bytes: &[u8]) -> Result<AddPreview> {
+    let digest = hex::encode(Sha1::digest(info_bytes));
+    if digest != preview.info_hash {
+        bail!("metadata hashes to {}, expected {}", digest, preview.info_hash);
+    }
+    let info = bencode::decode(info_bytes)?;
+    let pieces = info.get("pieces").and_then(Value::as_bytes).unwrap_or_default();
+    let piece_length = info.get("piece length").and_then(Value::as_int).unwrap_or(0);
+    if pieces.is_empty() || pieces.len() % 20 != 0 || piece_length <= 0 {
+        bail!("metadata is not a valid info dictionary");
+    }
+    let (name, files) = describe_info(&info)?;
+    preview.name = name.or(preview.name);
+    preview.total_size = Some(total_size(&files)?);
+    preview.files = files;
+    Ok(preview)
+}
+
+// So the preview shows which files will be renamed before the torrent is
+// added, and a torrent with unsafe names is turned down already here
+fn with_disk_names(mut files: Vec<FileInfo>) -> Result<Vec<FileInfo>> {
+    let disk_names = paths::unique(&files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>())?;
+    for (file, disk_name) in files.iter_mut().zip(disk_names) {
+        if paths::renamed(&file.name, &disk_name) {
+            file.disk_name = Some(disk_name);
+        }
+    }
+    Ok(files)
+}
+
+fn describe_info(info: &Value) -> Result<(Option<String>, Vec<FileInfo>)> {
+    let name = info.get("name").and_then(Value::as_str);
+    let files: Vec<FileInfo> = match info.get("files").and_then(Value::as_list) {
+        Some(list) => list
+            .iter()
+            .map(|f| {
+                Ok(FileInfo {
+                    name: f
+                        .get("path")
+                        .and_then(Value::as_list)
+                        .map(|parts| parts.iter().filter_map(Value::as_str).collect::<Vec<_>>().join("/"))
+                        .unwrap_or_default(),
+                    size: length(f)?,
+                    progress: 0.0,
+                    downloaded: 0,
+                    priority: FilePriority::default(),
+                    disk_name: None,
+                })
+            })
+            .collect::<Result<_>>()?,
+        None => vec![FileInfo {
+            name: name.clone().unwrap_or_default(),
+            size: length(info)?,
+            progress: 0.0,
+            downloaded: 0,
+            priority: FilePriority::default(),
+            disk_name: None,
+        }],
+    };
+    if let (Some(name), true) = (&name, files.len() > 1) {
+        paths::check(name)?;
+    }
+    Ok((name, with_disk_names(files)?))
+}
+
+fn length(entry: &Value) -> Result<u64> {
+    let length = entry.get("length").and_then(Value::as_int).ok_or_else(|| anyhow!("a file has no length"))?;
+    u64::try_from(length).map_err(|_| anyhow!("a file has a negative length"))
+}
+
+fn total_size(files: &[FileInfo]) -> Result<u64> {
+    files
+        .iter()
+        .try_fold(0u64, |total, f| total.checked_add(f.size))
+        .ok_or_else(|| anyhow!("the files add up to more than 2^64 bytes"))
+}
+
+// Magnets carry the hash as 40 hex chars or 32 base32 chars.
+pub fn normalize_info_hash(hash: &str) -> Result<String> {
+    match hash.len() {
+        40 if hash.chars().all(|c| c.is_ascii_hexdigit()) => Ok(hash.to_lowercase()),
+        32 => {
+            let mut bits = 0u64;
+            let mut nbits = 0;
+            let mut out = Vec::with_capacity(20);
+            for c in hash.to_ascii_uppercase().bytes() {
+                let v = match c {
+                    b'A'..=b'Z' => c - b'A',
+                    b'2'..=b'7' => c - b'2' + 26,
+                    _ => bail!("invalid base32 info hash"),
+                };
+                bits = (bits << 5) | v as u64;
+                nbits += 5;
+                if nbits >= 8 {
+                    nbits -= 8;
+                    out.push((bits >> nbits) as u8);
+                    bits &= (1 << nbits) - 1;
+                }
+            }
+            Ok(hex::encode(out))
+        }
+        _ => bail!("invalid info hash length"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_the_info_dict_as_written() {
+        // Keys out of order and an integer with a leading zero
+        let info = b"d4:name1:a6:lengthi01e12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let torrent = [&b"d4:info"[..], info, b"e"].concat();
+        let preview = parse_torrent(&torrent).unwrap();
+        assert_eq!(preview.info_hash, hex::encode(Sha1::digest(info)));
+        assert_eq!(preview.name.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn refuses_impossible_lengths() {
+        let torrent = |info: &[u8]| [&b"d4:info"[..], info, b"e"].concat();
+        let negative = b"d6:lengthi-1e4:name1:a12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        assert!(parse_torrent(&torrent(negative)).is_err());
+        let file = |name: &str| format!("d6:lengthi{}e4:pathl1:{}ee", i64::MAX, name);
+        let huge = format!(
+            "d5:filesl{}{}{}e4:name1:x12:piece lengthi16384e6:pieces20:aaaaaaaaaaaaaaaaaaaae",
+            file("a"),
+            file("b"),
+            file("c")
+        );
+        assert!(parse_torrent(&torrent(huge.as_bytes())).is_err());
+    }
+}
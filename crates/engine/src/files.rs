@@ -0,0 +1,266 @@
+use crate::paths;
+use crate::persist;
+use anyhow::Result;
+use bridge::{FileInfo, FilePriority, FileTreeNode};
+use crate::torrent::{TorrentExt, TorrentInfo};
+use librqbit::ManagedTorrent;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const PRIORITIES_FILE: &str = "file_priorities.json";
+
+// Building the bridged file list walks the whole metainfo, which is costly
+// for torrents with tens of thousands of files and happens on every poll.
+// The list is built once per torrent and shared until something that
+// changes it (priority, rename) invalidates it. Priorities are saved to the
+// state dir, so skipped files stay skipped after a restart.
+#[derive(Clone)]
+pub struct FileCache {
+    path: PathBuf,
+    entries: Arc<Mutex<HashMap<String, Arc<Vec<FileInfo>>>>>,
+    priorities: Arc<Mutex<HashMap<String, Vec<FilePriority>>>>,
+}
+
+impl FileCache {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(PRIORITIES_FILE);
+        Self { priorities: Arc::new(Mutex::new(persist::load(&path))), entries: Arc::default(), path }
+    }
+
+    // Set priorities by file index; files past the end have the default
+    pub fn priorities(&self, id: &str) -> Vec<FilePriority> {
+        self.priorities.lock().unwrap().get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn get(&self, id: &str, handle: &ManagedTorrent) -> Arc<Vec<FileInfo>> {
+        if let Some(files) = self.entries.lock().unwrap().get(id) {
+            return files.clone();
+        }
+
+        let priorities = self.priorities(id);
+        let info = handle.info();
+        // Torrents with unsafe names never make it into the session
+        let disk_names = disk_names(&info).unwrap_or_default();
+        let files: Arc<Vec<FileInfo>> = Arc::new(
+            info.files()
+                .iter()
+                .enumerate()
+                .map(|(i, f)| FileInfo {
+                    name: f.name.clone(),
+                    size: f.len,
+                    progress: 0.0,
+                    downloaded: 0,
+                    priority: priorities.get(i).copied().unwrap_or_default(),
+                    disk_name: disk_names.get(i).filter(|d| paths::renamed(&f.name, d)).cloned(),
+                })
+                .collect(),
+        );
+        self.entries.lock().unwrap().insert(id.to_string(), files.clone());
+        files
+    }
+
+    pub fn invalidate(&self, id: &str) {
+        self.entries.lock().unwrap().remove(id);
+    }
+
+    pub fn set_priority(&self, id: &str, file_count: usize, indices: &[usize], priority: FilePriority) -> Result<()> {
+        let saved = {
+            let mut priorities = self.priorities.lock().unwrap();
+            let list = priorities.entry(id.to_string()).or_default();
+            list.resize(file_count, FilePriority::default());
+            for &i in indices.iter().filter(|&&i| i < file_count) {
+                list[i] = priority;
+            }
+            persist::save(&self.path, &*priorities)
+        };
+        self.invalidate(id);
+        saved
+    }
+
+    // Drops cached lists of torrents no longer in the session.
+    pub fn retain(&self, live: &[String]) {
+        self.entries.lock().unwrap().retain(|id, _| live.contains(id));
+    }
+
+    pub fn forget(&self, id: &str) {
+        self.invalidate(id);
+        let mut priorities = self.priorities.lock().unwrap();
+        if priorities.remove(id).is_some() {
+            if let Err(e) = persist::save(&self.path, &*priorities) {
+                warn!("Failed to save file priorities: {}", e);
+            }
+        }
+    }
+}
+
+// Multi-file torrents live under a directory named after the torrent,
+// single-file torrents directly in the download directory.
+pub fn content_root(download_path: &str, info: &TorrentInfo) -> PathBuf {
+    if info.files().len() > 1 {
+        paths::on_disk(&Path::new(download_path).join(&info.name))
+    } else {
+        PathBuf::from(download_path)
+    }
+}
+
+pub fn absolute_path(root: &Path, info: &TorrentInfo, file_index: usize) -> Option<PathBuf> {
+    disk_paths(root, info).ok()?.into_iter().nth(file_index)
+}
+
+// Every file's path, in file index order. The storage the session writes
+// through maps files the same way (see storage::DiskStorageFactory).
+pub fn disk_paths(root: &Path, info: &TorrentInfo) -> Result<Vec<PathBuf>> {
+    Ok(disk_names(info)?.iter().map(|name| on_disk(root, name)).collect())
+}
+
+// File names with collisions renamed away (see paths::unique). Fails for a
+// torrent whose names, or whose own folder name, would leave its folder.
+pub fn disk_names(info: &TorrentInfo) -> Result<Vec<String>> {
+    let files = info.files();
+    if files.len() > 1 {
+        paths::check(&info.name)?;
+    }
+    paths::unique(&files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>())
+}
+
+pub fn on_disk(root: &Path, name: &str) -> PathBuf {
+    let path = root.join(name.split('/').collect::<PathBuf>());
+    paths::on_disk(&std::path::absolute(&path).unwrap_or(path))
+}
+
+// Copies `range` of the cached list with progress filled in from the
+// session's per-file byte counts (`TorrentStats::file_progress`), which are
+// empty until the torrent has initialized.
+pub fn with_progress(files: &[FileInfo], range: Range<usize>, file_progress: &[u64]) -> Vec<FileInfo> {
+    files
+        .iter()
+        .enumerate()
+        .skip(range.start)
+        .take(range.len())
+        .map(|(i, f)| {
+            let downloaded = file_progress.get(i).copied().unwrap_or(0).min(f.size);
+            FileInfo {
+                downloaded,
+                progress: if f.size > 0 { downloaded as f64 / f.size as f64 } else { 1.0 },
+                ..f.clone()
+            }
+        })
+        .collect()
+}
+
+// Indices of every file at or below `path` ("" is the whole torrent).
+pub fn indices_under(files: &[FileInfo], path: &str) -> Vec<usize> {
+    let path = path.trim_matches('/');
+    files
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| {
+            path.is_empty()
+                || f.name == path
+                || f.name.strip_prefix(path).is_some_and(|rest| rest.starts_with('/'))
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[derive(Default)]
+struct Folder {
+    folders: BTreeMap<String, Folder>,
+    files: Vec<usize>,
+}
+
+pub fn build_tree(name: &str, files: &[FileInfo]) -> FileTreeNode {
+    let mut root = Folder::default();
+    for (i, f) in files.iter().enumerate() {
+        let mut parts: Vec<&str> = f.name.split('/').filter(|p| !p.is_empty()).collect();
+        parts.pop();
+        let mut folder = &mut root;
+        for part in parts {
+            folder = folder.folders.entry(part.to_string()).or_default();
+        }
+        folder.files.push(i);
+    }
+    to_node(name, "", &root, files)
+}
+
+fn to_node(name: &str, path: &str, folder: &Folder, files: &[FileInfo]) -> FileTreeNode {
+    let mut children: Vec<FileTreeNode> = folder
+        .folders
+        .iter()
+        .map(|(child, sub)| {
+            let child_path = if path.is_empty() { child.clone() } else { format!("{}/{}", path, child) };
+            to_node(child, &child_path, sub, files)
+        })
+        .collect();
+
+    for &i in &folder.files {
+        let f = &files[i];
+        children.push(FileTreeNode {
+            name: f.name.rsplit('/').next().unwrap_or(&f.name).to_string(),
+            path: f.name.clone(),
+            size: f.size,
+            progress: f.progress,
+            priority: f.priority,
+            file_index: Some(i),
+            children: vec![],
+        });
+    }
+
+    let size: u64 = children.iter().map(|c| c.size).sum();
+    let done: f64 = children.iter().map(|c| c.progress * c.size as f64).sum();
+
+    let mut by_priority: HashMap<FilePriority, u64> = HashMap::new();
+    for c in &children {
+        *by_priority.entry(c.priority).or_default() += c.size;
+    }
+    let priority = by_priority
+        .into_iter()
+        .max_by_key(|&(p, bytes)| (bytes, p as u8))
+        .map(|(p, _)| p)
+        .unwrap_or_default();
+
+    FileTreeNode {
+        name: name.to_string(),
+        path: path.to_string(),
+        size,
+        progress: if size > 0 { done / size as f64 } else { 1.0 },
+        priority,
+        file_index: None,
+        children,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> FileInfo {
+        FileInfo {
+            name: name.to_string(),
+            size,
+            progress: 0.0,
+            downloaded: 0,
+            priority: FilePriority::default(),
+            disk_name: None,
+        }
+    }
+
+    #[test]
+    fn with_progress_uses_per_file_bytes() {
+        let files = [file("a", 10), file("b", 20), file("c", 0)];
+        let listed = with_progress(&files, 1..3, &[10, 5, 0]);
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].downloaded, 5);
+        assert_eq!(listed[0].progress, 0.25);
+        assert_eq!(listed[1].progress, 1.0);
+    }
+
+    #[test]
+    fn with_progress_before_the_torrent_is_initialized() {
+        let files = [file("a", 10)];
+        assert_eq!(with_progress(&files, 0..1, &[])[0].downloaded, 0);
+    }
+}
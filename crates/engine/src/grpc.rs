@@ -0,0 +1,182 @@
+use crate::client::Client;
+use crate::profiles::Profiles;
+use crate::error::EngineError;
+use bridge::{ErrorKind, FileInfo, FilePriority, TorrentState};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+
+mod proto {
+    tonic::include_proto!("aurora");
+}
+
+use proto::aurora_server::{Aurora, AuroraServer};
+
+pub fn spawn(profiles: Profiles, addr: SocketAddr) {
+    tokio::spawn(async move {
+        info!("gRPC server listening on {}", addr);
+        let service = AuroraServer::new(Service { profiles });
+        if let Err(e) = tonic::transport::Server::builder().add_service(service).serve(addr).await {
+            error!("gRPC server failed: {}", e);
+        }
+    });
+}
+
+struct Service {
+    profiles: Profiles,
+}
+
+impl Service {
+    // Calls go to the active profile
+    fn client(&self) -> Client {
+        self.profiles.active()
+    }
+}
+
+impl From<EngineError> for Status {
+    fn from(e: EngineError) -> Self {
+        let message = e.to_string();
+        match e.kind() {
+            ErrorKind::NotFound => Status::not_found(message),
+            ErrorKind::InvalidInput => Status::invalid_argument(message),
+            ErrorKind::Unsupported => Status::unimplemented(message),
+            ErrorKind::Unavailable => Status::unavailable(message),
+            ErrorKind::Io | ErrorKind::Credentials | ErrorKind::Internal => Status::internal(message),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Aurora for Service {
+    async fn add_torrent(
+        &self,
+        request: Request<proto::AddTorrentRequest>,
+    ) -> Result<Response<proto::AddTorrentReply>, Status> {
+        let id = self.client().add_torrent(&request.into_inner().source).await?;
+        Ok(Response::new(proto::AddTorrentReply { id }))
+    }
+
+    async fn list_torrents(
+        &self,
+        _request: Request<proto::ListTorrentsRequest>,
+    ) -> Result<Response<proto::ListTorrentsReply>, Status> {
+        let torrents = self.client().list_torrents().into_iter().map(torrent).collect();
+        Ok(Response::new(proto::ListTorrentsReply { torrents }))
+    }
+
+    async fn get_torrent_files(
+        &self,
+        request: Request<proto::GetTorrentFilesRequest>,
+    ) -> Result<Response<proto::GetTorrentFilesReply>, Status> {
+        let request = request.into_inner();
+        let page = self.client().torrent_files(&request.id, request.offset as usize, request.limit as usize)?;
+        Ok(Response::new(proto::GetTorrentFilesReply {
+            total: page.total as u64,
+            files: page
+                .files
+                .iter()
+                .enumerate()
+                .map(|(i, f)| file(page.offset + i, f))
+                .collect(),
+        }))
+    }
+
+    async fn set_file_priority(
+        &self,
+        request: Request<proto::SetFilePriorityRequest>,
+    ) -> Result<Response<proto::SetFilePriorityReply>, Status> {
+        let request = request.into_inner();
+        let priority = match request.priority() {
+            proto::FilePriority::Skip => FilePriority::Skip,
+            proto::FilePriority::Low => FilePriority::Low,
+            proto::FilePriority::Normal => FilePriority::Normal,
+            proto::FilePriority::High => FilePriority::High,
+        };
+        let indices = request.file_indices.iter().map(|&i| i as usize).collect();
+        let files = self.client().set_file_priority(&request.id, indices, priority).await?;
+        Ok(Response::new(proto::SetFilePriorityReply { files: files as u64 }))
+    }
+
+    async fn set_download_order(
+        &self,
+        request: Request<proto::SetDownloadOrderRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let request = request.into_inner();
+        let order = if request.order.is_empty() {
+            None
+        } else {
+            Some(request.order.iter().map(|&i| i as usize).collect())
+        };
+        self.client().set_download_order(&request.id, request.enabled, order).await?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn pause_session(&self, _request: Request<proto::Empty>) -> Result<Response<proto::SessionStatus>, Status> {
+        self.client().pause_session().await;
+        Ok(Response::new(proto::SessionStatus { paused: true }))
+    }
+
+    async fn resume_session(&self, _request: Request<proto::Empty>) -> Result<Response<proto::SessionStatus>, Status> {
+        self.client().resume_session().await;
+        Ok(Response::new(proto::SessionStatus { paused: false }))
+    }
+
+    async fn get_session_status(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::SessionStatus>, Status> {
+        Ok(Response::new(proto::SessionStatus { paused: self.client().is_session_paused() }))
+    }
+
+    type StreamEventsStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+    async fn stream_events(
+        &self,
+        request: Request<proto::StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let events = self.client().follow_events(request.into_inner().since);
+        let stream = ReceiverStream::new(events).map(|record| event(&record)).map(Ok);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+fn torrent(t: TorrentState) -> proto::Torrent {
+    proto::Torrent {
+        id: t.id,
+        name: t.name,
+        progress: t.progress,
+        status: t.status.as_str().to_string(),
+        download_speed: t.download_speed,
+        upload_speed: t.upload_speed,
+        total_size: t.total_size,
+        file_count: t.file_count as u64,
+    }
+}
+
+fn file(index: usize, f: &FileInfo) -> proto::File {
+    let priority = match f.priority {
+        FilePriority::Skip => proto::FilePriority::Skip,
+        FilePriority::Low => proto::FilePriority::Low,
+        FilePriority::Normal => proto::FilePriority::Normal,
+        FilePriority::High => proto::FilePriority::High,
+    };
+    proto::File {
+        index: index as u64,
+        name: f.name.clone(),
+        size: f.size,
+        downloaded: f.downloaded,
+        progress: f.progress,
+        priority: priority as i32,
+    }
+}
+
+fn event(record: &bridge::EventRecord) -> proto::Event {
+    proto::Event {
+        seq: record.seq,
+        timestamp: record.timestamp,
+        json: serde_json::to_string(&record.event).unwrap_or_default(),
+    }
+}
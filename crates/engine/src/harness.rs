@@ -0,0 +1,360 @@
+//! End-to-end transfers between engine sessions on one machine, for
+//! integration tests. Built with the `test-harness` feature.
+//!
+//! Every peer is a librqbit session with DHT and trackers off that only
+//! knows the other peers it is given, storing torrents in memory, so
+//! nothing touches the disk or leaves the machine. Peers reach each other
+//! only through a TCP proxy per pair, which simulates slow or unreliable
+//! peers by applying their [`Conditions`] to every connection and every
+//! byte between them.
+
+use crate::create;
+use crate::external;
+use crate::torrent::{TorrentExt, TorrentInfo};
+use crate::verify::{self, PieceLayout};
+use anyhow::{anyhow, Result};
+use librqbit::storage::{BoxStorageFactory, StorageFactory, StorageFactoryExt, TorrentStorage};
+use librqbit::{
+    AddTorrent, AddTorrentOptions, ManagedTorrent, ManagedTorrentShared, Session, SessionOptions, TorrentMetadata,
+};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+
+type Files = Arc<Mutex<Vec<Vec<u8>>>>;
+
+// Where the sessions listen; each takes the first free port
+const LISTEN_PORTS: std::ops::Range<u16> = 42000..43000;
+const CHUNK: usize = 16 * 1024;
+
+/// Keeps each torrent's files in memory, keyed by torrent name. Content put
+/// there before the torrent is added is what the session finds "on disk".
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    torrents: Arc<Mutex<HashMap<String, Files>>>,
+}
+
+impl MemoryStorage {
+    /// Sets the content of a torrent's files, in metainfo order. Put there
+    /// before the torrent is added, the session's initial check finds it.
+    pub fn put(&self, torrent: &str, files: Vec<Vec<u8>>) {
+        *self.files(torrent).lock().unwrap() = files;
+    }
+
+    /// The content of a torrent's files, if the torrent was stored here.
+    pub fn get(&self, torrent: &str) -> Option<Vec<Vec<u8>>> {
+        self.torrents.lock().unwrap().get(torrent).map(|f| f.lock().unwrap().clone())
+    }
+
+    fn files(&self, torrent: &str) -> Files {
+        self.torrents.lock().unwrap().entry(torrent.to_string()).or_default().clone()
+    }
+}
+
+impl StorageFactory for MemoryStorage {
+    type Storage = MemoryFiles;
+
+    fn create(&self, _shared: &ManagedTorrentShared, metadata: &TorrentMetadata) -> Result<MemoryFiles> {
+        Ok(MemoryFiles(self.files(&TorrentInfo::new(metadata).name)))
+    }
+
+    fn clone_box(&self) -> BoxStorageFactory {
+        self.clone().boxed()
+    }
+}
+
+/// One torrent's files in a [`MemoryStorage`], for the session's I/O and
+/// the engine's own piece reads alike.
+pub struct MemoryFiles(Files);
+
+impl TorrentStorage for MemoryFiles {
+    fn init(&mut self, _shared: &ManagedTorrentShared, _metadata: &TorrentMetadata) -> Result<()> {
+        Ok(())
+    }
+
+    fn pread_exact(&self, file_id: usize, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let files = self.0.lock().unwrap();
+        let data = files.get(file_id).ok_or_else(|| anyhow!("no file {}", file_id))?;
+        let range = offset as usize..offset as usize + buf.len();
+        buf.copy_from_slice(data.get(range).ok_or_else(|| anyhow!("read past the end of file {}", file_id))?);
+        Ok(())
+    }
+
+    fn pwrite_all(&self, file_id: usize, offset: u64, buf: &[u8]) -> Result<()> {
+        let mut files = self.0.lock().unwrap();
+        if files.len() <= file_id {
+            files.resize(file_id + 1, Vec::new());
+        }
+        let data = &mut files[file_id];
+        let end = offset as usize + buf.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset as usize..end].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn remove_file(&self, file_id: usize, _filename: &Path) -> Result<()> {
+        if let Some(data) = self.0.lock().unwrap().get_mut(file_id) {
+            data.clear();
+        }
+        Ok(())
+    }
+
+    fn remove_directory_if_empty(&self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    fn ensure_file_length(&self, file_id: usize, length: u64) -> Result<()> {
+        let mut files = self.0.lock().unwrap();
+        if files.len() <= file_id {
+            files.resize(file_id + 1, Vec::new());
+        }
+        files[file_id].resize(length as usize, 0);
+        Ok(())
+    }
+
+    fn take(&self) -> Result<Box<dyn TorrentStorage>> {
+        Ok(Box::new(MemoryFiles(self.0.clone())))
+    }
+}
+
+/// Network conditions a peer sees on all of its connections. Changes take
+/// effect from the next chunk of data or the next connection.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Conditions {
+    /// Added to everything sent or received, one way.
+    pub latency: Duration,
+    /// Bytes per second, each way; 0 is unlimited.
+    pub max_speed: u64,
+    /// Turns away new connections, incoming and outgoing.
+    pub refuse_connections: bool,
+}
+
+#[derive(Default)]
+struct Link {
+    conditions: Mutex<Conditions>,
+    upload: Pace,
+    download: Pace,
+    connections: Mutex<Vec<AbortHandle>>,
+}
+
+impl Link {
+    fn conditions(&self) -> Conditions {
+        *self.conditions.lock().unwrap()
+    }
+}
+
+// When the peer's link is next free in one direction
+#[derive(Default)]
+struct Pace(Mutex<Option<Instant>>);
+
+impl Pace {
+    // When `bytes` will have gone through at `rate` bytes per second, after
+    // what is already on its way; None when unlimited.
+    fn reserve(&self, bytes: usize, rate: u64) -> Option<Instant> {
+        if rate == 0 {
+            return None;
+        }
+        let mut free = self.0.lock().unwrap();
+        let start = free.map_or(Instant::now(), |at| at.max(Instant::now()));
+        let done = start + Duration::from_secs_f64(bytes as f64 / rate as f64);
+        *free = Some(done);
+        Some(done)
+    }
+}
+
+// Listens on a port of its own for `from`'s connections to `to`, and
+// forwards each one to `to`'s session unless either peer refuses it.
+async fn proxy(from: Arc<Link>, to: Arc<Link>, target: SocketAddr) -> Result<SocketAddr> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((incoming, _)) = listener.accept().await {
+            if from.conditions().refuse_connections || to.conditions().refuse_connections {
+                continue; // dropping it closes it
+            }
+            let Ok(outgoing) = TcpStream::connect(target).await else { continue };
+            let (from_read, from_write) = incoming.into_split();
+            let (to_read, to_write) = outgoing.into_split();
+            let (sender, receiver) = (from.clone(), to.clone());
+            let connection = tokio::spawn(async move {
+                tokio::join!(
+                    relay(from_read, to_write, sender.clone(), receiver.clone()),
+                    relay(to_read, from_write, receiver, sender),
+                );
+            });
+            for link in [&from, &to] {
+                let mut connections = link.connections.lock().unwrap();
+                connections.retain(|c| !c.is_finished());
+                connections.push(connection.abort_handle());
+            }
+        }
+    });
+    Ok(addr)
+}
+
+// One direction of a proxied connection: what `sender` sends, held back by
+// both peers' latency from when it was read and paced to their speeds.
+// Reading goes on while earlier chunks wait, so latency doesn't also cut
+// the throughput.
+async fn relay(mut from: OwnedReadHalf, mut to: OwnedWriteHalf, sender: Arc<Link>, receiver: Arc<Link>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(Instant, Vec<u8>)>();
+    let reading = async move {
+        let mut buf = vec![0; CHUNK];
+        while let Ok(n @ 1..) = from.read(&mut buf).await {
+            if tx.send((Instant::now(), buf[..n].to_vec())).is_err() {
+                return;
+            }
+        }
+    };
+    let writing = async move {
+        while let Some((read_at, chunk)) = rx.recv().await {
+            let (out, into) = (sender.conditions(), receiver.conditions());
+            tokio::time::sleep_until(read_at + out.latency + into.latency).await;
+            let sent = sender.upload.reserve(chunk.len(), out.max_speed);
+            let received = receiver.download.reserve(chunk.len(), into.max_speed);
+            if let Some(at) = sent.max(received) {
+                tokio::time::sleep_until(at).await;
+            }
+            if to.write_all(&chunk).await.is_err() {
+                return;
+            }
+        }
+        let _ = to.shutdown().await;
+    };
+    tokio::join!(reading, writing);
+}
+
+/// Writes a .torrent with `files` ("file0", "file1"... under `name`, or
+/// just `name` for one file) for sharing with [`Swarm::share`].
+pub fn write_torrent(path: &Path, name: &str, files: &[Vec<u8>], piece_size: u64) -> Result<()> {
+    let content = create::Content {
+        name: name.to_string(),
+        files: files.iter().enumerate().map(|(i, f)| (vec![format!("file{}", i)], f.len() as u64)).collect(),
+        single_file: files.len() == 1,
+    };
+    let data = files.concat();
+    let pieces = data.chunks(piece_size as usize).flat_map(|piece| Sha1::digest(piece).to_vec()).collect();
+    let info = create::info_dict(&content, piece_size, pieces, false);
+    std::fs::write(path, create::torrent_file(info, &[]))?;
+    Ok(())
+}
+
+/// One session in a [`Swarm`]. `addr` is where the session listens; other
+/// peers reach it through their proxies.
+pub struct Peer {
+    pub session: Arc<Session>,
+    pub storage: MemoryStorage,
+    pub addr: SocketAddr,
+    link: Arc<Link>,
+}
+
+impl Peer {
+    pub fn set_conditions(&self, conditions: Conditions) {
+        *self.link.conditions.lock().unwrap() = conditions;
+    }
+
+    /// Closes every connection this peer has. Unless it refuses
+    /// connections, the sessions reconnect on their own.
+    pub fn drop_connections(&self) {
+        for connection in self.link.connections.lock().unwrap().drain(..) {
+            connection.abort();
+        }
+    }
+
+    /// Hashes the torrent's pieces as this peer stores them and returns
+    /// the indices that don't match the metainfo.
+    pub async fn bad_pieces(&self, handle: &ManagedTorrent) -> Vec<u32> {
+        let info = handle.info();
+        let layout = PieceLayout::with_storage(&info, Arc::new(MemoryFiles(self.storage.files(&info.name))));
+        verify::check_all(&layout, &info, None).await.unwrap_or_default()
+    }
+}
+
+/// A handful of sessions that only know about each other.
+pub struct Swarm {
+    pub peers: Vec<Peer>,
+    routes: HashMap<(usize, usize), SocketAddr>, // (from, to) -> the proxy `from` dials
+    _dir: tempfile::TempDir, // sessions want a download folder; nothing is written there
+}
+
+impl Swarm {
+    pub async fn start(size: usize) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let mut peers = Vec::with_capacity(size);
+        for _ in 0..size {
+            let link = Arc::new(Link::default());
+            let storage = MemoryStorage::default();
+            let session = Session::new_with_opts(
+                dir.path().to_path_buf(),
+                SessionOptions {
+                    disable_dht: true,
+                    disable_dht_persistence: true,
+                    listen_port_range: Some(LISTEN_PORTS),
+                    default_storage_factory: Some(storage.clone().boxed()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+            let port = session.tcp_listen_port().ok_or_else(|| anyhow!("session isn't listening"))?;
+            peers.push(Peer { session, storage, addr: (Ipv4Addr::LOCALHOST, port).into(), link });
+        }
+        let mut routes = HashMap::new();
+        for (from, dialer) in peers.iter().enumerate() {
+            for (to, listener) in peers.iter().enumerate().filter(|(to, _)| *to != from) {
+                let addr = proxy(dialer.link.clone(), listener.link.clone(), listener.addr).await?;
+                routes.insert((from, to), addr);
+            }
+        }
+        Ok(Self { peers, routes, _dir: dir })
+    }
+
+    /// Adds the torrent to every peer with the others, through their
+    /// proxies, as its only peers.
+    /// `seeds` maps peer indices to the content they start with, put in
+    /// their storage before the add so the session's initial check finds
+    /// it; everyone else starts empty. Returns the handles in peer order.
+    pub async fn share(&self, torrent: &Path, seeds: &[(usize, Vec<Vec<u8>>)]) -> Result<Vec<Arc<ManagedTorrent>>> {
+        let bytes = std::fs::read(torrent)?;
+        let name = external::parse_torrent(&bytes)?.name.ok_or_else(|| anyhow!("torrent has no name"))?;
+        for (index, files) in seeds {
+            let peer = self.peers.get(*index).ok_or_else(|| anyhow!("no peer {}", index))?;
+            peer.storage.put(&name, files.clone());
+        }
+        let mut handles = Vec::with_capacity(self.peers.len());
+        for (from, peer) in self.peers.iter().enumerate() {
+            let initial_peers = self.routes.iter().filter(|((f, _), _)| *f == from).map(|(_, addr)| *addr).collect();
+            let options = AddTorrentOptions {
+                overwrite: true, // use the content already in storage
+                initial_peers: Some(initial_peers),
+                disable_trackers: true,
+                ..Default::default()
+            };
+            let added = peer.session.add_torrent(AddTorrent::from_bytes(bytes.clone()), Some(options)).await?;
+            handles.push(added.into_handle().ok_or_else(|| anyhow!("torrent was only listed"))?);
+        }
+        Ok(handles)
+    }
+
+    /// Waits until every handle reports finished.
+    pub async fn wait_finished(handles: &[Arc<ManagedTorrent>], timeout: Duration) -> Result<()> {
+        let started = Instant::now();
+        while !handles.iter().all(|h| h.stats().finished) {
+            if started.elapsed() > timeout {
+                return Err(anyhow!("transfer didn't finish within {:?}", timeout));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use crate::client::Client;
+use crate::error::EngineError;
+use crate::profiles::Profiles;
+use crate::webui;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, FromRef, Path, Query, Request, State,
+    },
+    body::Body,
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use bridge::{
+    ErrorKind, RpcError, SortOrder, SpeedSummary, StreamStatus, TorrentFilter, TorrentPage, TorrentSort, TorrentStatus,
+};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tracing::{error, info};
+
+// Streaming server for the player, plus a small session API and, when
+// configured, a web UI, on `http_listen_addr`. Requests go to the active
+// profile; ones from other hosts need `http_token`.
+pub fn spawn(profiles: Profiles) {
+    tokio::spawn(async move {
+        let addr = profiles.active().config().http_listen_addr;
+        let app = Router::new()
+            .route("/stream/:id/:file_idx", get(stream_handler))
+            .route("/stream-status/:id/:file_idx", get(stream_status_handler))
+            .route("/tracker-icon/:domain", get(tracker_icon_handler))
+            .route("/api/torrents", get(torrents_handler))
+            .route("/api/session", get(session_status_handler))
+            .route("/api/session/pause", post(pause_session_handler))
+            .route("/api/session/resume", post(resume_session_handler))
+            .route("/api/speed", get(speed_handler))
+            .route("/events", get(events_handler))
+            .route("/speed", get(follow_speed_handler))
+            .fallback(get(webui::handler))
+            .layer(middleware::from_fn_with_state(profiles.clone(), require_token))
+            .with_state(profiles);
+
+        let listener = match tokio::net::TcpListener::bind(&addr).await {
+            Ok(l) => l,
+            Err(e) => {
+                error!("Failed to bind streaming server to {}: {}", addr, e);
+                return;
+            }
+        };
+
+        info!("Streaming server listening on http://{}", addr);
+        if let Err(e) = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await {
+            error!("Streaming server failed: {}", e);
+        }
+    });
+}
+
+// How this machine reaches the server, e.g. for the player. A wildcard
+// address is reached on loopback.
+pub fn local_url(listen_addr: &str) -> String {
+    let Ok(mut addr) = listen_addr.parse::<SocketAddr>() else { return format!("http://{}", listen_addr) };
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr.ip() {
+            IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    format!("http://{}", addr)
+}
+
+// Clients on this machine get in as before. Others need the configured
+// token, as a Bearer Authorization header or, for players and sockets that
+// can't set one, a `token` query parameter; without one configured the
+// server doesn't listen off loopback at all (see Config::validate).
+async fn require_token(
+    State(client): State<Client>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if peer.ip().to_canonical().is_loopback() {
+        return next.run(request).await;
+    }
+    let token = client.config().http_token;
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let query = request.uri().query().unwrap_or_default().split('&').find_map(|pair| pair.strip_prefix("token="));
+    if token.is_empty() || bearer.or(query) != Some(token.as_str()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(request).await
+}
+
+impl FromRef<Profiles> for Client {
+    fn from_ref(profiles: &Profiles) -> Client {
+        profiles.active()
+    }
+}
+
+// Serves a file to the player with Range support so it can seek. A Range
+// that doesn't parse is ignored and the whole file is sent; one starting
+// past the end gets 416 with the file's length.
+async fn stream_handler(
+    Path((id, file_idx)): Path<(String, usize)>,
+    State(client): State<Client>,
+    headers: HeaderMap,
+) -> Result<Response, EngineError> {
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok()).and_then(parse_range);
+    let (first, last) = range.unwrap_or((0, None));
+    if range.is_some() {
+        let file_len = client.torrent_files(&id, file_idx, 1)?.files.first().map(|f| f.size);
+        if let Some(file_len) = file_len.filter(|&len| first >= len) {
+            let headers = [(header::CONTENT_RANGE, format!("bytes */{}", file_len))];
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response());
+        }
+    }
+    let reader = client.open_stream(&id, file_idx, first, last).await?;
+    let served = reader.range();
+    let file_len = reader.file_len();
+    let content_type = client
+        .file_path(&id, file_idx)
+        .map(|path| content_type(&path))
+        .unwrap_or("application/octet-stream");
+
+    let body = Body::from_stream(futures::stream::unfold(reader, |mut reader| async move {
+        match reader.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), reader)),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), reader)),
+        }
+    }));
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, served.end - served.start);
+    if range.is_some() {
+        response = response.status(StatusCode::PARTIAL_CONTENT).header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", served.start, served.end - 1, file_len),
+        );
+    }
+    response.body(body).map_err(|e| EngineError::Internal(e.into()))
+}
+
+#[derive(Deserialize)]
+struct StreamStatusQuery {
+    offset: Option<u64>,
+}
+
+// `?offset=<byte>` adds an estimate of when that position will be playable.
+async fn stream_status_handler(
+    Path((id, file_idx)): Path<(String, usize)>,
+    Query(query): Query<StreamStatusQuery>,
+    State(client): State<Client>,
+) -> Result<Json<StreamStatus>, EngineError> {
+    client.stream_status(&id, file_idx, query.offset).map(Json)
+}
+
+async fn tracker_icon_handler(
+    Path(domain): Path<String>,
+    State(client): State<Client>,
+) -> Result<Response, EngineError> {
+    let (bytes, content_type) = client.tracker_icon(&domain)?;
+    // The bytes come from the tracker's site; don't let them be sniffed or
+    // run as anything but an image
+    let headers = [
+        (header::CONTENT_TYPE, content_type),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        (header::CONTENT_SECURITY_POLICY, "default-src 'none'; sandbox".to_string()),
+    ];
+    Ok((headers, bytes).into_response())
+}
+
+// "bytes=<first>-[<last>]"; suffix ranges and multiple ranges aren't used
+// by players and aren't supported.
+fn parse_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (first, last) = value.strip_prefix("bytes=")?.split_once('-')?;
+    let first = first.trim().parse().ok()?;
+    let last = match last.trim() {
+        "" => None,
+        last => Some(last.parse().ok()?),
+    };
+    Some((first, last))
+}
+
+fn content_type(path: &std::path::Path) -> &'static str {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "mkv" => "video/x-matroska",
+        "webm" => "video/webm",
+        "avi" => "video/x-msvideo",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        "ogg" => "audio/ogg",
+        _ => "application/octet-stream",
+    }
+}
+
+#[derive(Deserialize)]
+struct TorrentsQuery {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_page_size")]
+    limit: usize,
+    #[serde(default)]
+    sort_by: TorrentSort,
+    #[serde(default)]
+    order: SortOrder,
+    status: Option<TorrentStatus>,
+    category: Option<String>,
+    tag: Option<String>,
+    search: Option<String>,
+}
+
+fn default_page_size() -> usize {
+    100
+}
+
+// `?offset=0&limit=50&sort_by=AddedOn&order=Descending&status=Seeding&search=linux`
+async fn torrents_handler(Query(query): Query<TorrentsQuery>, State(client): State<Client>) -> Json<TorrentPage> {
+    let filter = TorrentFilter {
+        status: query.status,
+        category: query.category,
+        tag: query.tag,
+        search: query.search,
+    };
+    Json(client.list_torrents_page(query.offset, query.limit, query.sort_by, query.order, &filter))
+}
+
+async fn session_status_handler(State(client): State<Client>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "paused": client.is_session_paused(),
+        "dht_nodes": client.dht_nodes(),
+        "write_buffer_bytes": client.write_buffer_bytes(),
+    }))
+}
+
+// Both need a JSON Content-Type: a page on another site can only send one
+// after a CORS preflight, which this server never answers, so together with
+// the Origin check other sites can't pause the session with a plain form.
+async fn pause_session_handler(State(client): State<Client>, headers: HeaderMap) -> Response {
+    if !json_from_same_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    client.pause_session().await;
+    Json(serde_json::json!({ "paused": true })).into_response()
+}
+
+async fn resume_session_handler(State(client): State<Client>, headers: HeaderMap) -> Response {
+    if !json_from_same_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    client.resume_session().await;
+    Json(serde_json::json!({ "paused": false })).into_response()
+}
+
+async fn speed_handler(State(client): State<Client>) -> Json<SpeedSummary> {
+    Json(client.speed_summary())
+}
+
+// The speed summary pushed as JSON text messages: the current one right
+// away, then each change.
+async fn follow_speed_handler(ws: WebSocketUpgrade, State(client): State<Client>, headers: HeaderMap) -> Response {
+    if !same_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    ws.on_upgrade(move |socket| push_speed(socket, client))
+}
+
+async fn push_speed(mut socket: WebSocket, client: Client) {
+    let mut speed = client.follow_speed();
+    loop {
+        let summary = *speed.borrow_and_update();
+        let Ok(text) = serde_json::to_string(&summary) else { return };
+        if socket.send(Message::Text(text)).await.is_err() {
+            return;
+        }
+        loop {
+            tokio::select! {
+                changed = speed.changed() => match changed {
+                    Ok(()) => break,
+                    Err(_) => return,
+                },
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    #[serde(default)]
+    since: u64,
+}
+
+// Live engine events, one JSON EventRecord per text message. `?since=<seq>`
+// replays what's still buffered after that event first, so a client that
+// reconnects with the last seq it saw doesn't miss anything.
+async fn events_handler(
+    ws: WebSocketUpgrade,
+    Query(query): Query<EventsQuery>,
+    State(client): State<Client>,
+    headers: HeaderMap,
+) -> Response {
+    if !same_origin(&headers) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    ws.on_upgrade(move |socket| push_events(socket, client, query.since))
+}
+
+async fn push_events(mut socket: WebSocket, client: Client, since: u64) {
+    let mut events = client.follow_events(since);
+    loop {
+        tokio::select! {
+            record = events.recv() => {
+                let Some(record) = record else { return };
+                let Ok(text) = serde_json::to_string(&record) else { continue };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    return;
+                }
+            }
+            // Nothing is expected from the client; this notices it leaving
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+// Browsers send Origin with every WebSocket handshake and cross-site POST,
+// and a page on any site may send those to 127.0.0.1. Only pages this server hosts (the web UI,
+// same host and port) get through; clients that aren't browsers send no
+// Origin at all.
+fn same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else { return true };
+    let authority = origin.to_str().ok().and_then(|o| o.strip_prefix("http://").or_else(|| o.strip_prefix("https://")));
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    matches!((authority, host), (Some(origin), Some(host)) if origin.eq_ignore_ascii_case(host))
+}
+
+fn json_from_same_origin(headers: &HeaderMap) -> bool {
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    let json = content_type.split(';').next().is_some_and(|t| t.trim().eq_ignore_ascii_case("application/json"));
+    json && same_origin(headers)
+}
+
+// Errors go out as the same {kind, message} object RPC uses, with a
+// matching status code.
+impl IntoResponse for EngineError {
+    fn into_response(self) -> Response {
+        let status = match self.kind() {
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+            ErrorKind::Unsupported => StatusCode::NOT_IMPLEMENTED,
+            ErrorKind::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorKind::Io | ErrorKind::Credentials | ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(RpcError::from(self))).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-"), Some((0, None)));
+        assert_eq!(parse_range("bytes=5-10"), Some((5, Some(10))));
+        assert_eq!(parse_range("bytes= 5 - 10 "), Some((5, Some(10))));
+        assert_eq!(parse_range("bytes=0-18446744073709551615"), Some((0, Some(u64::MAX))));
+    }
+
+    #[test]
+    fn only_lets_pages_it_hosts_in() {
+        let headers = |origin: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::HOST, "127.0.0.1:3000".parse().unwrap());
+            if let Some(origin) = origin {
+                headers.insert(header::ORIGIN, origin.parse().unwrap());
+            }
+            headers
+        };
+        assert!(same_origin(&headers(None)));
+        assert!(same_origin(&headers(Some("http://127.0.0.1:3000"))));
+        for origin in ["https://example.com", "http://127.0.0.1:8080", "http://127.0.0.1:3000.example.com", "null"] {
+            assert!(!same_origin(&headers(Some(origin))), "{}", origin);
+        }
+    }
+
+    #[test]
+    fn refuses_unsupported_ranges() {
+        let overflowing = "bytes=18446744073709551616-";
+        for value in ["bytes=-5", "items=0-1", "bytes=a-b", "bytes=0-1,4-5", "bytes=5", "0-1", overflowing] {
+            assert_eq!(parse_range(value), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Result};
+use librqbit::dht::Id20;
+use rand::Rng;
+
+const CLIENT_CODE: &str = "AT";
+const CLIENT_NAME: &str = "AuroraTorrent";
+
+// The Azureus-style peer id prefix for this build: "-AT0100-" for 0.1.0
+pub fn default_prefix() -> String {
+    let release = env!("CARGO_PKG_VERSION").split(['-', '+']).next().unwrap_or("");
+    let mut digits: String = release
+        .split('.')
+        .take(4)
+        .map(|part| std::char::from_digit(part.parse::<u32>().unwrap_or(0).min(35), 36).unwrap_or('0'))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    while digits.len() < 4 {
+        digits.push('0');
+    }
+    format!("-{}{}-", CLIENT_CODE, digits)
+}
+
+// Who we tell peers we are. Everything is derived from the one peer id
+// prefix, so the peer id and the user agent of the engine's own requests
+// always name the same client and version. The version digits can be
+// changed for interoperability testing; the client code can't, so a config
+// can't make us pass for another client.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Identity {
+    prefix: String,
+    version: String,
+}
+
+impl Identity {
+    pub fn new(prefix: &str) -> Result<Self> {
+        let well_formed = prefix.len() == 8
+            && prefix.is_ascii()
+            && prefix.starts_with('-')
+            && prefix.ends_with('-')
+            && prefix[3..7].chars().all(|c| c.is_ascii_alphanumeric());
+        if !well_formed {
+            return Err(anyhow!("peer_id_prefix must look like {}", default_prefix()));
+        }
+        if &prefix[1..3] != CLIENT_CODE {
+            return Err(anyhow!("peer_id_prefix must keep the {} client code; only the version can change", CLIENT_CODE));
+        }
+        // "0105" is 0.1.0.5, and a trailing zero build is left off: 0.1.0
+        let mut parts: Vec<u32> = prefix[3..7].chars().filter_map(|c| c.to_digit(36)).collect();
+        if parts.last() == Some(&0) {
+            parts.pop();
+        }
+        let version = parts.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(".");
+        Ok(Self { prefix: prefix.to_string(), version })
+    }
+
+    // A fresh peer id: the prefix and 12 random letters and digits
+    pub fn peer_id(&self) -> Id20 {
+        let mut id = [0u8; 20];
+        id[..8].copy_from_slice(self.prefix.as_bytes());
+        let mut rng = rand::thread_rng();
+        for byte in &mut id[8..] {
+            *byte = rng.sample(rand::distributions::Alphanumeric);
+        }
+        Id20::new(id)
+    }
+
+    // For scrapes and the engine's other HTTP requests; the session
+    // announces with its own
+    pub fn user_agent(&self) -> String {
+        format!("{}/{}", CLIENT_NAME, self.version)
+    }
+}
@@ -0,0 +1,138 @@
+use crate::events::EventBus;
+use crate::persist;
+use bridge::{EngineEvent, JobInfo, JobKind, JobState};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Finished jobs kept for ListJobs after they end
+const KEEP_FINISHED: usize = 50;
+
+// Long operations (data checks, torrent creation) with progress and
+// cancellation, listed for the UI. Each runs under a Job; JobFinished goes
+// out when it ends, however it ends.
+#[derive(Clone)]
+pub struct Jobs {
+    next_id: Arc<AtomicU64>,
+    entries: Arc<Mutex<Vec<Entry>>>, // oldest first
+    events: EventBus,
+}
+
+struct Entry {
+    info: JobInfo,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Jobs {
+    pub fn new(events: EventBus) -> Self {
+        Self { next_id: Arc::new(AtomicU64::new(1)), entries: Arc::default(), events }
+    }
+
+    // A queued job; call running() once it gets going and finish() at the end
+    pub fn start(&self, kind: JobKind, torrent_id: Option<&str>, name: &str) -> Job {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let info = JobInfo {
+            id,
+            kind,
+            torrent_id: torrent_id.map(String::from),
+            name: name.to_string(),
+            state: JobState::Queued,
+            progress: 0.0,
+            error: None,
+            result: None,
+            started_at: persist::now(),
+            finished_at: None,
+        };
+        self.entries.lock().unwrap().push(Entry { info, cancelled: cancelled.clone() });
+        Job { jobs: self.clone(), id, cancelled }
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        self.entries.lock().unwrap().iter().map(|e| e.info.clone()).collect()
+    }
+
+    // Asks a queued or running job to stop; it ends as Cancelled at its next
+    // progress point. Returns the job as it was, None if there is no such
+    // unfinished job.
+    pub fn cancel(&self, id: u64) -> Option<JobInfo> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.iter().find(|e| e.info.id == id && e.info.finished_at.is_none())?;
+        entry.cancelled.store(true, Ordering::Relaxed);
+        Some(entry.info.clone())
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut JobInfo)) {
+        if let Some(entry) = self.entries.lock().unwrap().iter_mut().find(|e| e.info.id == id) {
+            f(&mut entry.info);
+        }
+    }
+}
+
+// One job's handle for the code doing the work. Clones report to the same
+// job, so it can be handed to a blocking thread.
+#[derive(Clone)]
+pub struct Job {
+    jobs: Jobs,
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub fn running(&self) {
+        self.jobs.update(self.id, |info| info.state = JobState::Running);
+    }
+
+    pub fn progress(&self, done: u64, total: u64) {
+        let progress = if total == 0 { 1.0 } else { done as f64 / total as f64 };
+        self.jobs.update(self.id, |info| info.progress = progress.min(1.0));
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Ends the job: Done with an optional result, Failed with an error, or
+    // Cancelled if cancel() was called, whatever the outcome.
+    pub fn finish(&self, outcome: Result<Option<String>, String>) {
+        let mut finished = None;
+        {
+            let mut entries = self.jobs.entries.lock().unwrap();
+            if let Some(entry) = entries.iter_mut().find(|e| e.info.id == self.id && e.info.finished_at.is_none()) {
+                let info = &mut entry.info;
+                match outcome {
+                    _ if self.is_cancelled() => info.state = JobState::Cancelled,
+                    Ok(result) => {
+                        info.state = JobState::Done;
+                        info.progress = 1.0;
+                        info.result = result;
+                    }
+                    Err(error) => {
+                        info.state = JobState::Failed;
+                        info.error = Some(error);
+                    }
+                }
+                info.finished_at = Some(persist::now());
+                finished = Some(info.clone());
+            }
+            let done = entries.iter().filter(|e| e.info.finished_at.is_some()).count();
+            let mut excess = done.saturating_sub(KEEP_FINISHED);
+            entries.retain(|e| {
+                let drop = excess > 0 && e.info.finished_at.is_some();
+                if drop {
+                    excess -= 1;
+                }
+                !drop
+            });
+        }
+        if let Some(job) = finished {
+            self.jobs.events.emit(EngineEvent::JobFinished { job });
+        }
+    }
+
+    // Ends the job as Cancelled when it was stopped some other way (its
+    // waiting check was dropped from the check queue)
+    pub fn finish_cancelled(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.finish(Ok(None));
+    }
+}
@@ -1,216 +1,175 @@
+//! AuroraTorrent's BitTorrent engine.
+//!
+//! The desktop app runs it with [`run`], which also serves the RPC and HTTP
+//! APIs. To embed the engine in another Rust application, start a
+//! [`Client`] instead and call it directly.
+
 use anyhow::Result;
-use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
-    routing::get,
-    Router,
-};
-use bridge::{RpcCommand, RpcRequest, RpcResponse, TorrentState, FileInfo, PeerInfo, TrackerInfo, PORT};
-use librqbit::{Session, AddTorrentOptions, SessionOptions};
-use std::sync::{Arc, Mutex};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
-use tracing::{info, error};
+use bridge::PauseReason;
+use librqbit::Session;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::info;
 
+mod added;
+mod autosave;
+mod bans;
+mod bencode;
+mod checkqueue;
+mod client;
 mod config;
-use config::Config;
+mod configwatch;
+mod connection;
+mod control;
+mod create;
+mod credentials;
+mod datacap;
+mod dht;
+mod diagnostics;
+mod error;
+mod events;
+mod external;
+mod files;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "test-harness")]
+pub mod harness;
+mod http;
+mod identity;
+mod jobs;
+mod logs;
+mod maintenance;
+mod metainfo;
+mod modes;
+mod netwatch;
+mod ordering;
+mod peers;
+mod paths;
+mod persist;
+mod profiles;
+mod queue;
+mod ratelimit;
+mod recheck;
+mod resolve;
+mod resources;
+mod retention;
+mod roots;
+mod rpc;
+mod schedule;
+mod scrape;
+mod seedgoal;
+mod speed;
+mod stalled;
+mod storage;
+mod stream;
+mod supervisor;
+mod trackerlimits;
+mod trackersites;
+mod transcoder;
+mod tagging;
+mod torrent;
+mod transfer;
+mod updates;
+mod verify;
+mod webui;
+mod writeorder;
+use checkqueue::CheckQueue;
+use credentials::CredentialStore;
+use dht::DhtLookups;
+use events::EventBus;
+use files::FileCache;
+
+pub use bridge::{
+    AddParams, AddPreview, BannedPeer, CheckQueueEntry, CreatedTorrent, DailyUsage, DataCapAction, DataUsage, DhtStats,
+    EngineEvent, EventRecord, FileInfo, FilePage, FilePriority, FileTreeNode, JobInfo, JobKind, JobState,
+    PeerInfo, PieceEncoding, PieceStates, Profile, RetentionAction,
+    RetentionCandidate, RetentionRule, ShareLimitAction, SortOrder, StartupTimings, StreamStatus, TorrentPieces,
+    SwarmHealth, TagInfo, TagRule, TaskHealth, TaskState, TorrentFilter, TorrentMode, TorrentPage, TorrentSort,
+    TorrentState, TorrentStatus, TrackerInfo, TrackerLimit, TrackerScrape, TrackerSite, TranscoderInfo,
+};
+pub use client::Client;
+pub use config::{Config, ConnectionPreset, ConnectionSettings, QueueSettings};
+pub use error::EngineError;
+pub use external::handle_external_add;
+pub use profiles::Profiles;
 
 #[derive(Clone)]
 struct AppState {
     session: Arc<Session>,
     config: Arc<Mutex<Config>>,
+    events: EventBus,
+    corrupted: Arc<Mutex<HashSet<String>>>,
+    engine_paused: Arc<Mutex<HashMap<String, PauseReason>>>,
+    credentials: Arc<CredentialStore>,
+    trackers: Arc<Mutex<HashMap<String, Vec<String>>>>, // torrent id -> announce URLs
+    tracker_warnings: Arc<Mutex<HashMap<String, String>>>, // masked tracker URL -> last scrape warning message
+    udp_trackers: scrape::UdpTrackers,
+    session_paused: Arc<AtomicBool>,
+    // Torrents that were already engine-paused when the session was paused
+    session_paused_over: Arc<Mutex<HashMap<String, PauseReason>>>,
+    files: FileCache,
+    file_orders: ordering::FileOrders,
+    stalled: Arc<Mutex<HashSet<String>>>,
+    content_roots: roots::ContentRoots,
+    dht: DhtLookups,
+    prefetch: stream::Prefetcher,
+    checks: CheckQueue,
+    jobs: jobs::Jobs,
+    transcoder: transcoder::Transcoder,
+    schedules: schedule::Schedules, // torrent id -> unix time to start at
+    tagging: tagging::Tagging,
+    tracker_limits: trackerlimits::TrackerLimits,
+    bans: bans::Bans,
+    seed_goals: seedgoal::SeedGoals,
+    retention: retention::Retention, // per-category cleanup after seeding
+    transfers: transfer::Transfers, // per-torrent byte counts since start and in total
+    write_budget: writeorder::WriteBudget,
+    data_meter: datacap::DataMeter,
+    tracker_sites: trackersites::TrackerSites,
+    metainfo: metainfo::Metainfo, // stored .torrent files
+    pacer: maintenance::Pacer,
+    speed: speed::SpeedMeter,
+    updates: updates::Updates,
+    scrapes: Arc<Mutex<HashMap<String, Vec<bridge::TrackerScrape>>>>, // torrent id -> last bulk scrape results
+    added: added::AddedTimes,
+    autosave: autosave::Autosave,
+    queues: queue::Queues, // download and seed queue order
+    modes: modes::Modes,
+    identity: identity::Identity, // fixed for the session, like librqbit's peer id
+    tasks: supervisor::Tasks, // background loop health
+    startup: Arc<OnceLock<bridge::StartupTimings>>, // set once Client::start is done
 }
 
+/// Starts the engine and serves the RPC and HTTP APIs the desktop app
+/// uses. Only returns on error.
+///
+/// The config comes from the JSON file named by `AURORA_CONFIG`, if set,
+/// and edits to that file are applied while running; otherwise the
+/// defaults are used. That config is the "default" profile; RPC and HTTP
+/// requests go to whichever [`Profiles`] entry is active.
 pub async fn run() -> Result<()> {
-    tracing_subscriber::fmt::try_init().ok();
+    tracing_subscriber::fmt().with_writer(|| logs::Tee).try_init().ok();
     info!("Starting AuroraTorrent Engine with librqbit...");
 
-    // Ensure download directory exists
-    let config = Config::default();
-    tokio::fs::create_dir_all(&config.download_path).await?;
-
-    // Initialize librqbit session
-    let session = Session::new(config.download_path.clone()).await?;
-    let session = Arc::new(session);
-
-    let state = AppState {
-        session: session.clone(),
-        config: Arc::new(Mutex::new(config)),
+    let config_file = std::env::var_os("AURORA_CONFIG").map(PathBuf::from);
+    let config = match &config_file {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
     };
-
-    // Start Streaming Server (Placeholder for now, librqbit has its own stream handling usually, 
-    // but we might need to proxy it or expose it differently. 
-    // For now, we'll keep the structure but maybe point to librqbit's stream if possible)
-    let stream_state = state.clone();
-    tokio::spawn(async move {
-        let app = Router::new()
-            .route("/stream/:id/:file_idx", get(stream_handler))
-            .with_state(stream_state);
-            
-        let listener = match tokio::net::TcpListener::bind("127.0.0.1:3000").await {
-            Ok(l) => l,
-            Err(e) => {
-                error!("Failed to bind streaming server: {}", e);
-                return;
-            }
-        };
-        
-        info!("Streaming server listening on http://127.0.0.1:3000");
-        if let Err(e) = axum::serve(listener, app).await {
-            error!("Streaming server failed: {}", e);
-        }
-    });
-
-    // Start RPC Server
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", PORT)).await?;
-    info!("RPC server listening on 127.0.0.1:{}", PORT);
-
-    loop {
-        let (mut socket, _) = listener.accept().await?;
-        let state = state.clone();
-        tokio::spawn(async move {
-            let mut buf = [0; 4096];
-            loop {
-                let n = match socket.read(&mut buf).await {
-                    Ok(n) if n == 0 => return,
-                    Ok(n) => n,
-                    Err(_) => return,
-                };
-
-                let req_str = String::from_utf8_lossy(&buf[..n]);
-                // info!("RPC Raw: {}", req_str);
-                match serde_json::from_str::<RpcRequest>(&req_str) {
-                    Ok(req) => {
-                        let response = handle_rpc(req, &state).await;
-                        let resp_bytes = serde_json::to_vec(&response).unwrap();
-                        socket.write_all(&resp_bytes).await.ok();
-                    }
-                    Err(e) => {
-                        error!("RPC Parse Error: {}", e);
-                    }
-                }
-            }
-        });
+    let profiles = Profiles::start(config).await?;
+    let client = profiles.default_profile();
+    if let Some(path) = config_file {
+        client.watch_config_file(path);
     }
-}
-
-async fn handle_rpc(req: RpcRequest, state: &AppState) -> RpcResponse<serde_json::Value> {
-    info!("Received command: {:?}", req.command);
-    match req.command {
-        RpcCommand::AddTorrent { magnet } => {
-            match state.session.add_torrent(&magnet, None).await {
-                Ok(handle) => {
-                    let id = handle.info_hash().to_hex();
-                    RpcResponse {
-                        jsonrpc: "2.0".into(),
-                        id: req.id,
-                        result: Some(serde_json::json!({ "status": "added", "id": id })),
-                        error: None,
-                    }
-                }
-                Err(e) => RpcResponse {
-                    jsonrpc: "2.0".into(),
-                    id: req.id,
-                    result: None,
-                    error: Some(format!("Failed to add torrent: {}", e)),
-                }
-            }
-        }
-        RpcCommand::ListTorrents => {
-            let handles = state.session.torrents();
-            let mut torrents = Vec::new();
-            
-            for handle in handles {
-                let info = handle.info();
-                let stats = handle.stats();
-                
-                // Map librqbit state to our TorrentState
-                // Note: This is a best-effort mapping.
-                
-                let files = info.files().iter().map(|f| FileInfo {
-                    name: f.name.clone(),
-                    size: f.len,
-                    progress: 0.0, // TODO: Calculate per-file progress if possible
-                }).collect();
-
-                torrents.push(TorrentState {
-                    id: handle.info_hash().to_hex(),
-                    name: info.name.clone(),
-                    progress: stats.progress, // Assuming 0.0 to 1.0
-                    status: if stats.finished { "Seeding".into() } else { "Downloading".into() },
-                    download_speed: stats.download_speed,
-                    upload_speed: stats.upload_speed,
-                    total_size: info.total_size,
-                    files,
-                    peers: vec![], // TODO: Populate peers
-                    trackers: vec![], // TODO: Populate trackers
-                });
-            }
-
-            RpcResponse {
-                jsonrpc: "2.0".into(),
-                id: req.id,
-                result: Some(serde_json::to_value(&torrents).unwrap()),
-                error: None,
-            }
-        }
-        RpcCommand::StartTorrent { id } => {
-            // librqbit starts automatically, but maybe we can pause/resume?
-            // For now, just say started.
-             RpcResponse {
-                jsonrpc: "2.0".into(),
-                id: req.id,
-                result: Some(serde_json::json!({ "status": "started" })),
-                error: None,
-            }
+    http::spawn(profiles.clone());
+    #[cfg(feature = "grpc")]
+    {
+        let addr = client.config().grpc_listen_addr;
+        if !addr.is_empty() {
+            grpc::spawn(profiles.clone(), addr.parse()?);
         }
-        RpcCommand::StreamTorrent { id } => {
-             RpcResponse {
-                jsonrpc: "2.0".into(),
-                id: req.id,
-                result: Some(serde_json::json!({ 
-                    "status": "streaming",
-                    "url": format!("http://127.0.0.1:3000/stream/{}/0", id)
-                })),
-                error: None,
-            }
-        }
-        RpcCommand::GetConfig => {
-            let config = state.config.lock().unwrap();
-            RpcResponse {
-                jsonrpc: "2.0".into(),
-                id: req.id,
-                result: Some(serde_json::to_value(&*config).unwrap()),
-                error: None,
-            }
-        }
-        RpcCommand::SetConfig { download_path, max_download_speed, max_upload_speed } => {
-            {
-                let mut config = state.config.lock().unwrap();
-                if let Some(p) = download_path { config.download_path = p; }
-                // TODO: Apply speed limits to session
-            }
-            RpcResponse {
-                jsonrpc: "2.0".into(),
-                id: req.id,
-                result: Some(serde_json::json!({ "status": "updated" })),
-                error: None,
-            }
-        }
-        _ => RpcResponse {
-            jsonrpc: "2.0".into(),
-            id: req.id,
-            result: None,
-            error: Some("Method not implemented".into()),
-        },
     }
-}
-
-async fn stream_handler(Path((id, file_idx)): Path<(String, usize)>, State(state): State<AppState>) -> impl IntoResponse {
-    // TODO: Hook into librqbit's streaming capabilities
-    // For now, return a placeholder
-    axum::response::Response::builder()
-        .header("Content-Type", "text/plain")
-        .body(axum::body::Body::from(format!("Streaming for {} not yet implemented with librqbit", id)))
-        .unwrap()
+    rpc::serve(profiles).await
 }
@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+const KEEP: usize = 2000;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// Log output for the subscriber run() installs: goes to stdout as before and
+// the last lines are kept for diagnostics dumps. Embedders with their own
+// subscriber can route it through here too, otherwise dumps have no log.
+pub struct Tee;
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stdout().write_all(buf)?;
+        let text = strip_ansi(&String::from_utf8_lossy(buf));
+        let mut recent = RECENT.lock().unwrap();
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            if recent.len() == KEEP {
+                recent.pop_front();
+            }
+            recent.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+// Oldest first
+pub fn recent() -> Vec<String> {
+    RECENT.lock().unwrap().iter().cloned().collect()
+}
+
+// The terminal colors fmt adds, "\x1b[...m"
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,154 @@
+use crate::{credentials, scrape, AppState};
+use bridge::TrackerScrape;
+use futures::StreamExt;
+use librqbit::ManagedTorrent;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// Bulk runs send each tracker domain at most one request per gap
+const TRACKER_GAP: Duration = Duration::from_secs(1);
+const MAX_SCRAPES_IN_FLIGHT: usize = 16;
+
+// Spaces out requests from bulk re-announces and scrapes, so hundreds of
+// torrents on one tracker don't hit it all at once. Shared between runs:
+// a second run queues up behind one still going.
+#[derive(Clone, Default)]
+pub struct Pacer {
+    next: Arc<Mutex<HashMap<String, Instant>>>, // tracker domain -> earliest time of its next request
+}
+
+impl Pacer {
+    // Books the earliest time a request touching all of `domains` may go out.
+    fn reserve(&self, domains: &[String]) -> Instant {
+        let now = Instant::now();
+        let mut next = self.next.lock().unwrap();
+        next.retain(|_, at| *at > now);
+        let at = domains.iter().filter_map(|d| next.get(d).copied()).max().unwrap_or(now);
+        for domain in domains {
+            next.insert(domain.clone(), at + TRACKER_GAP);
+        }
+        at
+    }
+}
+
+// Torrents in `category`, or all of them
+fn selected(state: &AppState, category: Option<&str>) -> Vec<Arc<ManagedTorrent>> {
+    state
+        .torrents()
+        .into_iter()
+        .filter(|h| {
+            category.is_none_or(|c| state.tagging.labels(&h.info_hash().as_string()).category.as_deref() == Some(c))
+        })
+        .collect()
+}
+
+fn trackers(state: &AppState, id: &str) -> Vec<String> {
+    state.trackers.lock().unwrap().get(id).cloned().unwrap_or_default()
+}
+
+// Re-announces the running torrents in `category` (all with None) in the
+// background, paced per tracker. Each restart drops the torrent's peers
+// (see AppState::reannounce). Returns how many were queued.
+pub fn reannounce_all(state: &AppState, category: Option<&str>) -> usize {
+    let mut queue: Vec<(Instant, Arc<ManagedTorrent>)> = selected(state, category)
+        .into_iter()
+        .filter(|h| !state.engine_paused.lock().unwrap().contains_key(&h.info_hash().as_string()))
+        .map(|handle| {
+            let domains: Vec<String> = trackers(state, &handle.info_hash().as_string())
+                .iter()
+                .filter_map(|url| credentials::tracker_domain(url))
+                .collect();
+            (state.pacer.reserve(&domains), handle)
+        })
+        .collect();
+    queue.sort_by_key(|(at, _)| *at);
+    let count = queue.len();
+    info!("Re-announcing {} torrents", count);
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        for (at, handle) in queue {
+            tokio::time::sleep_until(at.into()).await;
+            if state.is_session_paused() {
+                info!("Stopping re-announce run: session paused");
+                return;
+            }
+            state.reannounce(&handle).await;
+        }
+    });
+    count
+}
+
+// Scrapes every tracker of the torrents in `category` (all with None) in
+// the background, paced per tracker. Results show up in the torrents'
+// tracker lists. Returns how many torrents were queued.
+pub fn scrape_all(state: &AppState, category: Option<&str>, user_agent: String) -> usize {
+    let torrents = selected(state, category);
+    let mut jobs: Vec<(Instant, String, [u8; 20], String)> = Vec::new();
+    for handle in &torrents {
+        let id = handle.info_hash().as_string();
+        for url in trackers(state, &id) {
+            let domains: Vec<String> = credentials::tracker_domain(&url).into_iter().collect();
+            jobs.push((state.pacer.reserve(&domains), id.clone(), handle.info_hash().0, url));
+        }
+    }
+    jobs.sort_by_key(|(at, ..)| *at);
+    info!("Scraping {} trackers of {} torrents", jobs.len(), torrents.len());
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        let state = &state;
+        let user_agent = &user_agent;
+        futures::stream::iter(jobs)
+            .for_each_concurrent(MAX_SCRAPES_IN_FLIGHT, |(at, id, info_hash, url)| async move {
+                tokio::time::sleep_until(at.into()).await;
+                let result = scrape_tracker(state, &url, &info_hash, user_agent).await;
+                let mut scrapes = state.scrapes.lock().unwrap();
+                let results = scrapes.entry(id).or_default();
+                results.retain(|r| r.url != result.url);
+                results.push(result);
+            })
+            .await;
+        info!("Scrape run finished");
+    });
+    torrents.len()
+}
+
+// One tracker's answer for one torrent, with its warning kept for the
+// torrent's tracker list.
+pub async fn scrape_tracker(
+    state: &AppState,
+    url: &str,
+    info_hash: &[u8; 20],
+    user_agent: &str,
+) -> TrackerScrape {
+    let result = scrape::scrape(&state.udp_trackers, &state.credentials.apply(url).await, info_hash, user_agent).await;
+    let url = state.credentials.mask(url);
+    match result {
+        Ok(counts) => {
+            let mut warnings = state.tracker_warnings.lock().unwrap();
+            match &counts.warning {
+                Some(warning) => warnings.insert(url.clone(), warning.clone()),
+                None => warnings.remove(&url),
+            };
+            TrackerScrape {
+                url,
+                seeders: Some(counts.seeders),
+                leechers: Some(counts.leechers),
+                completed: Some(counts.completed),
+                error: None,
+                warning: counts.warning,
+            }
+        }
+        Err(e) => TrackerScrape {
+            url,
+            seeders: None,
+            leechers: None,
+            completed: None,
+            error: Some(e.to_string()),
+            warning: None,
+        },
+    }
+}
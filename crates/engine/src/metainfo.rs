@@ -0,0 +1,188 @@
+use crate::bencode::{self, Value};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const DIR: &str = "torrents";
+
+// The descriptive root fields of a .torrent
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fields {
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+}
+
+// A copy of each torrent's .torrent file, one per id under the state dir,
+// so edits to its non-info fields outlive a restart. The engine adds its
+// torrents back from these on start. They have the trackers the torrent
+// was added with, so placeholders stay placeholders and passkeys from the
+// keyring never end up here.
+#[derive(Clone)]
+pub struct Metainfo {
+    dir: PathBuf,
+    fields: Arc<Mutex<HashMap<String, Fields>>>, // torrent id -> fields, read from the file on first use
+}
+
+impl Metainfo {
+    pub fn load(state_dir: &Path) -> Self {
+        Self { dir: state_dir.join(DIR), fields: Arc::default() }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.torrent", id))
+    }
+
+    pub fn get(&self, id: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.path(id)).ok()
+    }
+
+    // Ids of the torrents with a stored copy
+    pub fn ids(&self) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else { return Vec::new() };
+        entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                name.strip_suffix(".torrent").map(String::from)
+            })
+            .collect()
+    }
+
+    // Keeps the copy already there, so a torrent added again keeps its edits.
+    pub fn store(&self, id: &str, bytes: &[u8]) -> Result<()> {
+        let path = self.path(id);
+        if path.exists() {
+            return Ok(());
+        }
+        write(&path, bytes)?;
+        self.fields.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    pub fn fields(&self, id: &str) -> Fields {
+        if let Some(fields) = self.fields.lock().unwrap().get(id) {
+            return fields.clone();
+        }
+        let fields = self.get(id).map(|bytes| read_fields(&bytes)).unwrap_or_default();
+        self.fields.lock().unwrap().insert(id.to_string(), fields.clone());
+        fields
+    }
+
+    // Rewrites `bytes` (the stored copy, or one rebuilt for a torrent
+    // without one) with new fields and saves the result as the stored copy.
+    pub fn edit(&self, id: &str, bytes: &[u8], comment: Option<&str>, created_by: Option<&str>) -> Result<Fields> {
+        let edited = rewrite(bytes, comment, created_by)?;
+        write(&self.path(id), &edited)?;
+        let fields = read_fields(&edited);
+        self.fields.lock().unwrap().insert(id.to_string(), fields.clone());
+        Ok(fields)
+    }
+
+    pub fn remove(&self, id: &str) {
+        let _ = std::fs::remove_file(self.path(id));
+        self.fields.lock().unwrap().remove(id);
+    }
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("torrent.tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+pub fn read_fields(bytes: &[u8]) -> Fields {
+    let Ok(root) = bencode::decode(bytes) else { return Fields::default() };
+    // Readers prefer the UTF-8 variants when a torrent has both
+    let text = |key: &str| {
+        root.get(&format!("{}.utf-8", key)).or_else(|| root.get(key)).and_then(Value::as_str)
+    };
+    Fields { comment: text("comment"), created_by: text("created by") }
+}
+
+// Sets "comment" and "created by" (None leaves one as it is, an empty
+// string removes it). Every other entry, the info dict above all, is copied
+// byte for byte rather than re-encoded, so the info hash can't change even
+// if the original wasn't canonically encoded.
+pub fn rewrite(bytes: &[u8], comment: Option<&str>, created_by: Option<&str>) -> Result<Vec<u8>> {
+    let mut entries: Vec<(Vec<u8>, Vec<u8>)> =
+        bencode::raw_entries(bytes)?.into_iter().map(|(k, v)| (k, v.to_vec())).collect();
+    if !entries.iter().any(|(k, _)| k == b"info") {
+        return Err(anyhow!("torrent has no info dictionary"));
+    }
+    for (key, value) in [("comment", comment), ("created by", created_by)] {
+        let Some(value) = value else { continue };
+        // A stale UTF-8 variant would shadow the new value
+        let variant = format!("{}.utf-8", key);
+        entries.retain(|(k, _)| k != key.as_bytes() && k != variant.as_bytes());
+        if !value.is_empty() {
+            entries.push((key.as_bytes().to_vec(), bencode::encode(&Value::Bytes(value.as_bytes().to_vec()))));
+        }
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut out = vec![b'd'];
+    for (key, value) in entries {
+        out.extend_from_slice(&bencode::encode(&Value::Bytes(key)));
+        out.extend_from_slice(&value);
+    }
+    out.push(b'e');
+    Ok(out)
+}
+
+// A .torrent from an info dict as it was received, e.g. a magnet link's
+// from peers, and `trackers`, each its own tier. The info dict is copied
+// byte for byte, so the result hashes to the same id.
+pub fn from_info(info_bytes: &[u8], trackers: &[String]) -> Vec<u8> {
+    let url = |url: &String| Value::Bytes(url.clone().into_bytes());
+    let mut out = vec![b'd'];
+    if let Some(first) = trackers.first() {
+        out.extend_from_slice(&bencode::encode(&Value::Bytes(b"announce".to_vec())));
+        out.extend_from_slice(&bencode::encode(&url(first)));
+    }
+    if trackers.len() > 1 {
+        let tiers = trackers.iter().map(|t| Value::List(vec![url(t)])).collect();
+        out.extend_from_slice(&bencode::encode(&Value::Bytes(b"announce-list".to_vec())));
+        out.extend_from_slice(&bencode::encode(&Value::List(tiers)));
+    }
+    out.extend_from_slice(&bencode::encode(&Value::Bytes(b"info".to_vec())));
+    out.extend_from_slice(info_bytes);
+    out.push(b'e');
+    out
+}
+
+// Passes every announce URL ("announce" and the tiers of "announce-list")
+// through `f`, e.g. to fill in passkeys. Everything else is copied byte for
+// byte like in rewrite; the info hash doesn't cover the trackers anyway.
+pub fn map_trackers(bytes: &[u8], f: impl Fn(&str) -> String) -> Result<Vec<u8>> {
+    let map = |value: &Value| match value.as_str() {
+        Some(url) => Value::Bytes(f(&url).into_bytes()),
+        None => value.clone(),
+    };
+    let mut out = vec![b'd'];
+    for (key, raw) in bencode::raw_entries(bytes)? {
+        let value = match key.as_slice() {
+            b"announce" => bencode::encode(&map(&bencode::decode(raw)?)),
+            b"announce-list" => match bencode::decode(raw)? {
+                Value::List(tiers) => bencode::encode(&Value::List(
+                    tiers
+                        .iter()
+                        .map(|tier| match tier {
+                            Value::List(urls) => Value::List(urls.iter().map(map).collect()),
+                            other => other.clone(),
+                        })
+                        .collect(),
+                )),
+                _ => raw.to_vec(),
+            },
+            _ => raw.to_vec(),
+        };
+        out.extend_from_slice(&bencode::encode(&Value::Bytes(key)));
+        out.extend_from_slice(&value);
+    }
+    out.push(b'e');
+    Ok(out)
+}
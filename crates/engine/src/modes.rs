@@ -0,0 +1,49 @@
+use crate::persist;
+use anyhow::Result;
+use bridge::TorrentMode;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const FILE: &str = "torrent_modes.json";
+
+// Torrents that aren't auto managed, saved to the state dir
+#[derive(Clone)]
+pub struct Modes {
+    path: PathBuf,
+    modes: Arc<Mutex<HashMap<String, TorrentMode>>>,
+}
+
+impl Modes {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self { modes: Arc::new(Mutex::new(persist::load(&path))), path }
+    }
+
+    pub fn get(&self, id: &str) -> TorrentMode {
+        self.modes.lock().unwrap().get(id).copied().unwrap_or_default()
+    }
+
+    pub fn set(&self, id: &str, mode: TorrentMode) -> Result<()> {
+        let mut modes = self.modes.lock().unwrap();
+        let previous = if mode == TorrentMode::AutoManaged {
+            modes.remove(id)
+        } else {
+            modes.insert(id.to_string(), mode)
+        };
+        if previous.unwrap_or_default() != mode {
+            persist::save(&self.path, &*modes)?;
+        }
+        Ok(())
+    }
+
+    pub fn forget(&self, id: &str) {
+        let mut modes = self.modes.lock().unwrap();
+        if modes.remove(id).is_some() {
+            if let Err(e) = persist::save(&self.path, &*modes) {
+                warn!("Failed to save torrent modes: {}", e);
+            }
+        }
+    }
+}
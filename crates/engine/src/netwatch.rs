@@ -0,0 +1,120 @@
+use crate::AppState;
+use bridge::EngineEvent;
+use std::net::{IpAddr, Ipv6Addr, UdpSocket};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// The local address check is just a socket call, so it can run often.
+const TICK: Duration = Duration::from_secs(5);
+
+#[derive(Default, Clone, PartialEq)]
+struct NetworkState {
+    local_ip: Option<IpAddr>,
+    global_ipv6: Option<Ipv6Addr>,
+    external_ip: Option<String>,
+    listen_port: Option<u16>,
+}
+
+// Watches our outbound interface address, our public IP and the session's
+// listen port. When any of them changes, peers and trackers have a stale
+// address for us, so every torrent is re-announced (trackers and DHT).
+// A new global IPv6 address counts too, for dual-stack trackers.
+pub async fn run(state: AppState) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    let mut current = NetworkState::default();
+    let mut last_external_check: Option<Instant> = None;
+    let mut first = true;
+
+    loop {
+        tokio::time::sleep(TICK).await;
+        let config = state.config.lock().unwrap().clone();
+        if state.is_session_paused() {
+            continue;
+        }
+
+        let mut next = NetworkState {
+            local_ip: local_ip(),
+            global_ipv6: global_ipv6(),
+            external_ip: current.external_ip.clone(),
+            listen_port: state.session.tcp_listen_port(),
+        };
+
+        let external_due = last_external_check
+            .is_none_or(|t| t.elapsed() >= Duration::from_secs(config.external_ip_check_interval_secs));
+        // A new local address usually means a new public one too
+        if !config.external_ip_check_url.is_empty() && (external_due || next.local_ip != current.local_ip) {
+            last_external_check = Some(Instant::now());
+            match external_ip(&client, &config.external_ip_check_url).await {
+                Ok(ip) => next.external_ip = Some(ip),
+                Err(e) => warn!("External IP check failed: {}", e),
+            }
+        }
+
+        if next == current {
+            continue;
+        }
+        let changed = std::mem::replace(&mut current, next);
+        // Nothing to announce against on the very first sample
+        if std::mem::take(&mut first) || !config.reannounce_on_network_change {
+            continue;
+        }
+
+        info!(
+            "Network changed (local {:?} -> {:?}, IPv6 {:?} -> {:?}, external {:?} -> {:?}, port {:?} -> {:?}), re-announcing",
+            changed.local_ip, current.local_ip, changed.global_ipv6, current.global_ipv6,
+            changed.external_ip, current.external_ip, changed.listen_port, current.listen_port
+        );
+        for handle in state.torrents() {
+            state.reannounce(&handle).await;
+        }
+        state.events.emit(EngineEvent::NetworkChanged {
+            local_ip: current.local_ip.map(|ip| ip.to_string()),
+            external_ip: current.external_ip.clone(),
+            listen_port: current.listen_port,
+        });
+    }
+}
+
+// Address of the interface the OS would route internet traffic through.
+// Connecting a UDP socket sends nothing, it only resolves the route.
+fn local_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("8.8.8.8:80").ok()?;
+    socket.local_addr().ok().map(|a| a.ip())
+}
+
+// Our routable IPv6 address, if we have one: not loopback, link-local or
+// unique local, none of which a tracker could hand out.
+fn global_ipv6() -> Option<Ipv6Addr> {
+    let socket = UdpSocket::bind("[::]:0").ok()?;
+    socket.connect("[2001:4860:4860::8888]:80").ok()?;
+    let IpAddr::V6(ip) = socket.local_addr().ok()?.ip() else {
+        return None;
+    };
+    (!is_lan(IpAddr::V6(ip)) && !ip.is_unspecified()).then_some(ip)
+}
+
+// Private, link-local and loopback ranges.
+fn is_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_link_local() || v4.is_loopback(),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_lan(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                v6.is_loopback()
+                    || (first & 0xfe00) == 0xfc00 // unique local fc00::/7
+                    || (first & 0xffc0) == 0xfe80 // link-local fe80::/10
+            }
+        },
+    }
+}
+
+async fn external_ip(client: &reqwest::Client, url: &str) -> anyhow::Result<String> {
+    let body = client.get(url).send().await?.error_for_status()?.text().await?;
+    let ip: IpAddr = body.trim().parse()?;
+    Ok(ip.to_string())
+}
@@ -0,0 +1,114 @@
+use crate::files;
+use crate::{persist, AppState};
+use anyhow::Result;
+use bridge::{FileInfo, FilePriority};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+const TICK: Duration = Duration::from_secs(2);
+const FILE: &str = "file_orders.json";
+
+// Torrent id -> file indices in download order, for torrents downloading
+// file by file. Saved to the state dir on every change.
+#[derive(Clone)]
+pub struct FileOrders {
+    path: PathBuf,
+    orders: Arc<Mutex<HashMap<String, Vec<usize>>>>,
+}
+
+impl FileOrders {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self { orders: Arc::new(Mutex::new(persist::load(&path))), path }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Vec<usize>> {
+        self.orders.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn all(&self) -> HashMap<String, Vec<usize>> {
+        self.orders.lock().unwrap().clone()
+    }
+
+    // None downloads the files all at once again
+    pub fn set(&self, id: &str, order: Option<Vec<usize>>) -> Result<()> {
+        let mut orders = self.orders.lock().unwrap();
+        let changed = match order {
+            Some(order) => orders.insert(id.to_string(), order.clone()) != Some(order),
+            None => orders.remove(id).is_some(),
+        };
+        if changed {
+            persist::save(&self.path, &*orders)?;
+        }
+        Ok(())
+    }
+
+    pub fn forget(&self, id: &str) {
+        if let Err(e) = self.set(id, None) {
+            warn!("Failed to save file orders: {}", e);
+        }
+    }
+}
+
+// Files the session should be fetching right now. Without an order that is
+// every non-skipped file. With one, files are released one at a time (or
+// `window` at a time) in that order, so file 1 completes before file 2
+// starts; finished files stay selected so they keep seeding.
+pub fn selection(files: &[FileInfo], order: Option<&[usize]>, window: usize) -> HashSet<usize> {
+    let wanted = |i: usize| files.get(i).is_some_and(|f| f.priority != FilePriority::Skip);
+    let Some(order) = order else {
+        return (0..files.len()).filter(|&i| wanted(i)).collect();
+    };
+
+    let mut selected: HashSet<usize> = HashSet::new();
+    let mut pending = 0;
+    for &i in order.iter().filter(|&&i| wanted(i)) {
+        if files[i].downloaded >= files[i].size {
+            selected.insert(i);
+        } else if pending < window.max(1) {
+            selected.insert(i);
+            pending += 1;
+        }
+    }
+    selected
+}
+
+// Full order for a torrent: the given indices first, then anything they
+// left out in listed order.
+pub fn normalize(order: Option<Vec<usize>>, file_count: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = order.unwrap_or_default().into_iter().filter(|&i| i < file_count).collect();
+    let mut seen: HashSet<usize> = HashSet::new();
+    order.retain(|i| seen.insert(*i));
+    order.extend((0..file_count).filter(|i| !seen.contains(i)));
+    order
+}
+
+// Advances ordered torrents to their next file as each one completes.
+pub async fn run(state: AppState) {
+    let mut applied: HashMap<String, HashSet<usize>> = HashMap::new();
+    loop {
+        tokio::time::sleep(TICK).await;
+        let window = state.config.lock().unwrap().ordered_files_window;
+        let orders = state.file_orders.all();
+        applied.retain(|id, _| orders.contains_key(id));
+
+        for (id, order) in orders {
+            let Some(handle) = state.torrent(&id) else { continue };
+            let cached = state.files.get(&id, &handle);
+            let current = files::with_progress(&cached, 0..cached.len(), &handle.stats().file_progress);
+            let wanted = selection(&current, Some(&order), window);
+            if applied.get(&id) == Some(&wanted) {
+                continue;
+            }
+            match state.select_files(&handle, &wanted).await {
+                Ok(()) => {
+                    applied.insert(id, wanted);
+                }
+                Err(e) => warn!("Failed to advance file order for {}: {}", id, e),
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+// Names Windows keeps for devices, with or without an extension: "CON",
+// "aux.txt" and "com1.tar.gz" all open the device instead of a file.
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// Filesystems on macOS and Windows take names that differ only in case, or
+// only in Unicode normalization ("é" as one code point or as "e" and an
+// accent), as the same name
+const FOLDS: bool = cfg!(any(windows, target_os = "macos"));
+
+// Past this, Win32 calls fail on plain paths (260 for files, less the room
+// CreateDirectory wants for an 8.3 name)
+const MAX_PATH: usize = 248;
+
+// Where a torrent file's path ends up on this platform. On Windows, names
+// it can't hold are escaped (see escape) and long paths get the "\\?\"
+// prefix that lifts the MAX_PATH limit. Elsewhere paths are left as they
+// are, so torrents added before this keep finding their files.
+pub fn on_disk(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    let escaped: PathBuf = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(name) => escape(&name.to_string_lossy()).into(),
+            other => other.as_os_str().to_os_string(),
+        })
+        .collect();
+    long(escaped)
+}
+
+// "CON" becomes "CON_", "aux.txt" "aux_.txt"; characters Windows refuses
+// and trailing dots and spaces (which it silently drops) become "_".
+fn escape(name: &str) -> String {
+    let mut name: String = name.chars().map(|c| if c < ' ' || "<>:\"|?*\\".contains(c) { '_' } else { c }).collect();
+    let kept = name.trim_end_matches(['.', ' ']).len();
+    if kept < name.len() {
+        name.truncate(kept);
+        name.push('_');
+    }
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem.trim_end())) {
+        name.insert(stem.len(), '_');
+    }
+    name
+}
+
+fn long(path: PathBuf) -> PathBuf {
+    if path.as_os_str().len() < MAX_PATH {
+        return path;
+    }
+    let path = std::path::absolute(&path).unwrap_or(path);
+    let text = path.to_string_lossy();
+    if text.starts_with(r"\\?\") {
+        return path;
+    }
+    match text.strip_prefix(r"\\") {
+        Some(unc) => PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => PathBuf::from(format!(r"\\?\{}", text)),
+    }
+}
+
+// A name from a torrent that would put a file outside the torrent's folder
+#[derive(Debug, Error)]
+#[error("unsafe file name in torrent: {0:?}")]
+pub struct UnsafeName(pub String);
+
+// Every "/"-separated part of `name` has to be a plain name: no "..", ".",
+// empty parts (as in "/etc/x"), or drive and root prefixes.
+pub fn check(name: &str) -> Result<()> {
+    let safe = name.split('/').all(|part| {
+        let mut components = Path::new(part).components();
+        matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+    });
+    if !safe {
+        bail!(UnsafeName(name.to_string()));
+    }
+    Ok(())
+}
+
+// The torrent's file names ("dir/file.ext") as they go on disk: in NFC where
+// FOLDS, and with " (2)", " (3)"... added to a file whose name an earlier
+// file, or a folder, already takes there. It depends only on the names, so
+// every place that maps files to the disk gets the same answer. Fails on
+// the first name that isn't safe (see check).
+pub fn unique(names: &[&str]) -> Result<Vec<String>> {
+    for name in names {
+        check(name)?;
+    }
+    let folders: HashSet<String> =
+        names.iter().flat_map(|name| name.match_indices('/').map(|(i, _)| key(&name[..i]))).collect();
+    let mut taken = HashSet::new();
+    Ok(names
+        .iter()
+        .map(|name| {
+            let name = normalize(name);
+            let mut candidate = name.clone();
+            let mut n = 1;
+            while folders.contains(&key(&candidate)) || !taken.insert(key(&candidate)) {
+                n += 1;
+                candidate = suffixed(&name, n);
+            }
+            candidate
+        })
+        .collect())
+}
+
+// Whether unique gave `name` a suffix, as opposed to just normalizing it
+pub fn renamed(name: &str, disk_name: &str) -> bool {
+    normalize(name) != disk_name
+}
+
+fn normalize(name: &str) -> String {
+    if FOLDS {
+        name.nfc().collect()
+    } else {
+        name.to_string()
+    }
+}
+
+fn key(name: &str) -> String {
+    if FOLDS {
+        normalize(name).to_lowercase()
+    } else {
+        name.to_string()
+    }
+}
+
+// "dir/file.tar.gz" -> "dir/file.tar (2).gz"
+fn suffixed(name: &str, n: usize) -> String {
+    let file_start = name.rfind('/').map_or(0, |i| i + 1);
+    match name[file_start..].rfind('.').filter(|&i| i > 0) {
+        Some(dot) => {
+            let dot = file_start + dot;
+            format!("{} ({}){}", &name[..dot], n, &name[dot..])
+        }
+        None => format!("{} ({})", name, n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_names_outside_the_folder() {
+        for name in ["../x", "/etc/x", "a/../../x", "./x", "a//b", "a/", ""] {
+            let err = unique(&["ok", name]).unwrap_err();
+            assert!(err.downcast_ref::<UnsafeName>().is_some(), "{:?}", name);
+        }
+        assert!(unique(&["a/b.txt", "..a", "a..b/c"]).is_ok());
+    }
+
+    #[test]
+    fn renames_duplicates_with_a_suffix() {
+        let names = unique(&["a.txt", "a.txt", "a (2).txt", "dir.d/file", "dir.d/file", ".hidden", ".hidden"]).unwrap();
+        assert_eq!(
+            names,
+            ["a.txt", "a (2).txt", "a (2) (2).txt", "dir.d/file", "dir.d/file (2)", ".hidden", ".hidden (2)"]
+        );
+        assert_eq!(suffixed("dir/file.tar.gz", 3), "dir/file.tar (3).gz");
+    }
+
+    #[test]
+    fn renames_files_named_like_a_folder() {
+        assert_eq!(unique(&["readme", "readme/inner"]).unwrap(), ["readme (2)", "readme/inner"]);
+    }
+
+    #[test]
+    fn folds_case_and_normalization_where_the_filesystem_does() {
+        let names = unique(&["Read Me.txt", "read me.TXT", "caf\u{e9}", "cafe\u{301}"]).unwrap();
+        if FOLDS {
+            assert_eq!(names, ["Read Me.txt", "read me (2).TXT", "caf\u{e9}", "caf\u{e9} (2)"]);
+            assert!(!renamed("cafe\u{301}", "caf\u{e9}"));
+        } else {
+            assert_eq!(names, ["Read Me.txt", "read me.TXT", "caf\u{e9}", "cafe\u{301}"]);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use bridge::PeerInfo;
+use librqbit::ManagedTorrent;
+use std::net::SocketAddr;
+
+// The torrent's live peer connections. The session reports no client name,
+// speeds or discovery source per peer, so only the address is filled in.
+pub fn connected(handle: &ManagedTorrent) -> Vec<PeerInfo> {
+    let Some(live) = handle.live() else { return vec![] };
+    live.per_peer_stats_snapshot(Default::default())
+        .peers
+        .into_keys()
+        .map(|addr| PeerInfo {
+            ip: addr.parse::<SocketAddr>().map_or(addr, |a| a.ip().to_string()),
+            client: String::new(),
+            down_speed: 0,
+            up_speed: 0,
+        })
+        .collect()
+}
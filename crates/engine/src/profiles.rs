@@ -0,0 +1,157 @@
+use crate::client::Client;
+use crate::config::Config;
+use crate::error::EngineError;
+use crate::persist;
+use bridge::Profile;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+const FILE: &str = "profiles.json";
+const DEFAULT: &str = "default";
+
+#[derive(Serialize, Deserialize, Default)]
+struct Saved {
+    active: Option<String>,
+    profiles: Vec<Profile>,
+}
+
+/// Named engine contexts, each a [`Client`] with its own session, download
+/// folder, torrents, state dir and speed limits. `"default"` runs on the
+/// config the engine was started with; the others are saved to its state
+/// dir and keep their state under `profiles/<name>` there.
+///
+/// A profile keeps running once started, so switching only changes which
+/// one [`Profiles::active`] returns; its torrents go on downloading and
+/// seeding in the background.
+#[derive(Clone)]
+pub struct Profiles {
+    base: Config,
+    path: PathBuf,
+    saved: Arc<Mutex<Saved>>,
+    running: Arc<Mutex<HashMap<String, Client>>>,
+    active: Arc<Mutex<String>>,
+    starting: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Profiles {
+    /// Starts the default profile, then the profile that was active last
+    /// time if it was another one.
+    pub async fn start(config: Config) -> Result<Profiles, EngineError> {
+        let path = Path::new(&config.state_dir).join(FILE);
+        let saved: Saved = persist::load(&path);
+        let last = saved.active.clone();
+        let default = Client::start(config.clone()).await?;
+        let profiles = Profiles {
+            base: config,
+            path,
+            saved: Arc::new(Mutex::new(saved)),
+            running: Arc::new(Mutex::new(HashMap::from([(DEFAULT.to_string(), default)]))),
+            active: Arc::new(Mutex::new(DEFAULT.to_string())),
+            starting: Arc::default(),
+        };
+        if let Some(name) = last.filter(|n| n != DEFAULT) {
+            profiles.switch(&name).await?;
+        }
+        Ok(profiles)
+    }
+
+    /// The profile commands go to.
+    pub fn active(&self) -> Client {
+        let name = self.active.lock().unwrap().clone();
+        self.running.lock().unwrap()[&name].clone()
+    }
+
+    /// The profile running on the engine's own config.
+    pub fn default_profile(&self) -> Client {
+        self.running.lock().unwrap()[DEFAULT].clone()
+    }
+
+    /// Every profile, default first.
+    pub fn list(&self) -> Vec<Profile> {
+        let active = self.active.lock().unwrap().clone();
+        let default = Profile {
+            name: DEFAULT.to_string(),
+            download_path: self.base.download_path.clone(),
+            max_download_speed: self.base.max_download_speed,
+            max_upload_speed: self.base.max_upload_speed,
+            active: false,
+        };
+        let mut list = vec![default];
+        list.extend(self.saved.lock().unwrap().profiles.iter().cloned());
+        for profile in &mut list {
+            profile.active = profile.name == active;
+        }
+        list
+    }
+
+    /// Saves a new profile. It starts the first time it is switched to.
+    pub fn create(&self, mut profile: Profile) -> Result<(), EngineError> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if profile.name.is_empty() || !profile.name.chars().all(valid) {
+            return Err(EngineError::InvalidInput("profile names use letters, digits, '-' and '_'".into()));
+        }
+        if profile.download_path.trim().is_empty() {
+            return Err(EngineError::InvalidInput("download_path is empty".into()));
+        }
+        let mut saved = self.saved.lock().unwrap();
+        if profile.name == DEFAULT || saved.profiles.iter().any(|p| p.name == profile.name) {
+            return Err(EngineError::InvalidInput(format!("profile {} already exists", profile.name)));
+        }
+        profile.active = false;
+        saved.profiles.push(profile);
+        persist::save(&self.path, &*saved)?;
+        Ok(())
+    }
+
+    /// Makes `name` the active profile, starting it if it isn't running.
+    pub async fn switch(&self, name: &str) -> Result<(), EngineError> {
+        let _starting = self.starting.lock().await;
+        if !self.running.lock().unwrap().contains_key(name) {
+            let profile = self
+                .saved
+                .lock()
+                .unwrap()
+                .profiles
+                .iter()
+                .find(|p| p.name == name)
+                .cloned()
+                .ok_or_else(|| EngineError::NotFound(format!("Profile {}", name)))?;
+            info!("Starting profile {}", name);
+            let client = Client::start(self.config(&profile)).await?;
+            self.running.lock().unwrap().insert(name.to_string(), client);
+        }
+        *self.active.lock().unwrap() = name.to_string();
+
+        let mut saved = self.saved.lock().unwrap();
+        saved.active = Some(name.to_string());
+        persist::save(&self.path, &*saved)?;
+        Ok(())
+    }
+
+    /// Shuts every running profile down, see [`Client::shutdown`].
+    pub async fn shutdown(&self) {
+        let running: Vec<Client> = self.running.lock().unwrap().values().cloned().collect();
+        futures::future::join_all(running.iter().map(Client::shutdown)).await;
+    }
+
+    /// Tells every running profile the machine woke from sleep.
+    pub fn resumed_from_sleep(&self) {
+        for client in self.running.lock().unwrap().values() {
+            client.resumed_from_sleep();
+        }
+    }
+
+    // The engine's config with the profile's folders and limits
+    fn config(&self, profile: &Profile) -> Config {
+        let mut config = self.base.clone();
+        let state_dir = Path::new(&self.base.state_dir).join("profiles").join(&profile.name);
+        config.state_dir = state_dir.to_string_lossy().into_owned();
+        config.download_path = profile.download_path.clone();
+        config.max_download_speed = profile.max_download_speed;
+        config.max_upload_speed = profile.max_upload_speed;
+        config
+    }
+}
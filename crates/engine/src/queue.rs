@@ -0,0 +1,215 @@
+use crate::{persist, AppState};
+use anyhow::Result;
+use bridge::{EngineEvent, PauseReason, QueueMove, TorrentMode, TorrentQueues};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const TICK: Duration = Duration::from_secs(5);
+const FILE: &str = "queues.json";
+
+// Download and seed queue order, saved to the state dir. Torrents the queues
+// haven't seen yet go to the end of theirs; one that finishes moves to the
+// end of the seed queue.
+#[derive(Clone)]
+pub struct Queues {
+    path: PathBuf,
+    order: Arc<Mutex<TorrentQueues>>,
+}
+
+impl Queues {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self { order: Arc::new(Mutex::new(persist::load(&path))), path }
+    }
+
+    pub fn all(&self) -> TorrentQueues {
+        self.order.lock().unwrap().clone()
+    }
+
+    pub fn position(&self, id: &str) -> Option<usize> {
+        let order = self.order.lock().unwrap();
+        let position = |queue: &[String]| queue.iter().position(|q| q == id);
+        position(&order.downloads).or_else(|| position(&order.seeds))
+    }
+
+    // Puts each torrent in the queue for its state, keeping the order of
+    // those already there, and drops torrents no longer in the session.
+    // `torrents` is (id, finished) in session order.
+    fn sync(&self, torrents: &[(String, bool)]) {
+        let mut order = self.order.lock().unwrap();
+        let before = order.clone();
+        let live: HashSet<&str> = torrents.iter().map(|(id, _)| id.as_str()).collect();
+        let seeding: HashSet<&str> = torrents.iter().filter(|(_, f)| *f).map(|(id, _)| id.as_str()).collect();
+        order.downloads.retain(|id| live.contains(id.as_str()) && !seeding.contains(id.as_str()));
+        order.seeds.retain(|id| seeding.contains(id.as_str()));
+        for (id, finished) in torrents {
+            let queue = if *finished { &mut order.seeds } else { &mut order.downloads };
+            if !queue.contains(id) {
+                queue.push(id.clone());
+            }
+        }
+        if order.downloads != before.downloads || order.seeds != before.seeds {
+            if let Err(e) = persist::save(&self.path, &*order) {
+                warn!("Failed to save queues: {}", e);
+            }
+        }
+    }
+
+    // False if the torrent isn't in that queue
+    pub fn move_torrent(&self, id: &str, seeds: bool, direction: QueueMove) -> Result<bool> {
+        let mut order = self.order.lock().unwrap();
+        let queue = if seeds { &mut order.seeds } else { &mut order.downloads };
+        let Some(from) = queue.iter().position(|q| q == id) else {
+            return Ok(false);
+        };
+        let to = match direction {
+            QueueMove::Up => from.saturating_sub(1),
+            QueueMove::Down => (from + 1).min(queue.len() - 1),
+            QueueMove::Top => 0,
+            QueueMove::Bottom => queue.len() - 1,
+        };
+        let moved = queue.remove(from);
+        queue.insert(to, moved);
+        persist::save(&self.path, &*order)?;
+        Ok(true)
+    }
+
+    // Moves `into` to the front of the seed queue and `out` to the back.
+    // Applied to the queue as it is now rather than the snapshot they were
+    // picked from, so a move or removal in between isn't undone; ids no
+    // longer in the seed queue are skipped.
+    fn rotate_seeds(&self, into: &[&String], out: &[&String]) {
+        let mut order = self.order.lock().unwrap();
+        let seeds = std::mem::take(&mut order.seeds);
+        let present = |ids: &[&String]| -> Vec<String> {
+            ids.iter().filter(|id| seeds.contains(id)).map(|id| id.to_string()).collect()
+        };
+        let (front, back) = (present(into), present(out));
+        let rest = seeds.iter().filter(|id| !front.contains(id) && !back.contains(id)).cloned();
+        order.seeds = front.iter().cloned().chain(rest).chain(back.iter().cloned()).collect();
+        if let Err(e) = persist::save(&self.path, &*order) {
+            warn!("Failed to save queues: {}", e);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Rotation {
+    last: Option<Instant>,
+    uploaded: HashMap<String, u64>, // session upload total at the start of the window
+    waited: HashMap<String, u32>,   // rotations a waiting seed was passed over
+}
+
+// Gives rarely requested seeds a turn on a link that can only keep a few
+// active. Each rotation the active seeds that uploaded least since the last
+// one go to the end of the seed queue, and the waiting seeds with the most
+// demand take their slots: leechers from the last scrape, plus one for each
+// rotation they already waited, so every seed gets an announce and upload
+// window eventually.
+fn rotate(state: &AppState, count: usize, rotation: &mut Rotation) {
+    let seeds = state.queues.all().seeds;
+    let paused = state.engine_paused.lock().unwrap().clone();
+    let active: Vec<&String> = seeds.iter().filter(|id| !paused.contains_key(*id)).collect();
+    let waiting: Vec<&String> = seeds.iter().filter(|id| paused.get(*id) == Some(&PauseReason::Queued)).collect();
+    let uploaded = |id: &str| state.transfers.session_totals(id).1;
+    let count = count.min(waiting.len()).min(active.len());
+
+    let mut by_upload = active.clone();
+    by_upload.sort_by_key(|id| uploaded(id).saturating_sub(rotation.uploaded.get(*id).copied().unwrap_or(0)));
+    let out: Vec<&String> = by_upload.into_iter().take(count).collect();
+
+    let demand = |id: &str| {
+        let leechers = state.scrapes.lock().unwrap().get(id).into_iter().flatten().filter_map(|s| s.leechers).max();
+        leechers.unwrap_or(0) + rotation.waited.get(id).copied().unwrap_or(0)
+    };
+    let mut by_demand = waiting.clone();
+    by_demand.sort_by_key(|id| std::cmp::Reverse(demand(id)));
+    let into: Vec<&String> = by_demand.into_iter().take(count).collect();
+
+    if count > 0 {
+        info!("Rotating seeds: {} in, {} out", into.len(), out.len());
+        state.queues.rotate_seeds(&into, &out);
+    }
+    for id in &waiting {
+        if into.contains(id) {
+            rotation.waited.remove(*id);
+        } else {
+            *rotation.waited.entry((*id).clone()).or_default() += 1;
+        }
+    }
+    rotation.waited.retain(|id, _| seeds.contains(id));
+    rotation.uploaded = seeds.iter().map(|id| (id.clone(), uploaded(id))).collect();
+}
+
+// Lets the first `max_active_downloads` unfinished and `max_active_uploads`
+// finished torrents in queue order run while their queue is on; the rest
+// wait paused (PauseReason::Queued) until a slot frees up. Torrents paused
+// for any other reason, force started or paused through the API don't take
+// a slot.
+pub async fn run(state: AppState) {
+    let mut rotation = Rotation::default();
+    loop {
+        tokio::time::sleep(TICK).await;
+        if state.is_session_paused() {
+            continue;
+        }
+        let handles = state.torrents();
+        let torrents: Vec<(String, bool)> =
+            handles.iter().map(|h| (h.info_hash().as_string(), h.stats().finished)).collect();
+        state.queues.sync(&torrents);
+
+        let settings = state.config.lock().unwrap().queue.clone();
+        let every = Duration::from_secs(settings.seed_rotation_secs);
+        if settings.upload_queue_enabled && !every.is_zero() {
+            if rotation.last.is_some_and(|t| t.elapsed() >= every) {
+                rotate(&state, settings.seed_rotation_count, &mut rotation);
+            }
+            if rotation.last.is_none_or(|t| t.elapsed() >= every) {
+                rotation.last = Some(Instant::now());
+            }
+        }
+        let order = state.queues.all();
+        let queues = [
+            (order.downloads, settings.download_queue_enabled, settings.max_active_downloads),
+            (order.seeds, settings.upload_queue_enabled, settings.max_active_uploads),
+        ];
+        let reason = PauseReason::Queued;
+        for (ids, enabled, max_active) in queues {
+            let mut active = 0;
+            for id in ids {
+                let Some(handle) = handles.iter().find(|h| h.info_hash().as_string() == id) else { continue };
+                if state.modes.get(&id) != TorrentMode::AutoManaged {
+                    continue;
+                }
+                let current = state.engine_paused.lock().unwrap().get(&id).copied();
+                if current.is_some_and(|r| r != reason) {
+                    continue;
+                }
+                let allowed = !enabled || active < max_active;
+                if allowed {
+                    active += 1;
+                }
+                match (current, allowed) {
+                    (None, false) => {
+                        if let Err(e) = state.session.pause(handle).await {
+                            warn!("Failed to pause {}: {}", id, e);
+                            continue;
+                        }
+                        info!("Queueing {}: active torrent limit reached", id);
+                        state.engine_paused.lock().unwrap().insert(id.clone(), reason);
+                        state.events.emit(EngineEvent::TorrentPaused { id, reason });
+                    }
+                    (Some(_), true) => {
+                        if let Err(e) = state.release(handle, reason).await {
+                            warn!("Failed to resume {}: {}", id, e);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::config::Config;
+use crate::AppState;
+use bridge::DataCapAction;
+use librqbit::limits::LimitsConfig;
+use std::num::NonZeroU32;
+use std::time::Duration;
+use tracing::info;
+
+const TICK: Duration = Duration::from_secs(1);
+// The session takes a whole block from its limiter at once and drops the
+// peer if the limiter can't ever hold that much, so no limit goes lower.
+const MIN_RATE: u32 = 16 * 1024;
+
+// Session-wide speed limits as the session should have them now: the
+// alternative ones while `alt_speed_enabled` is set, or once the data cap
+// is reached with AltLimits. librqbit 8.1.1 only has this one limiter for
+// the whole session and counts every peer alike, so local-network peers
+// can't be exempted from it.
+pub fn limits(config: &Config, capped: bool) -> LimitsConfig {
+    let alt = config.alt_speed_enabled || (config.data_cap_action == DataCapAction::AltLimits && capped);
+    let (download, upload) = match alt {
+        false => (config.max_download_speed, config.max_upload_speed),
+        true => (config.alt_download_speed, config.alt_upload_speed),
+    };
+    LimitsConfig { download_bps: bps(download), upload_bps: bps(upload) }
+}
+
+// 0 is unlimited
+fn bps(rate: u64) -> Option<NonZeroU32> {
+    NonZeroU32::new(rate.min(u32::MAX as u64) as u32).map(|rate| rate.max(NonZeroU32::new(MIN_RATE).unwrap()))
+}
+
+// Hands changed limits to the session, so SetConfig, the alternative limits
+// and the data cap take effect within a second. Setting a limit restarts
+// the session's limiter, so it only happens on a change.
+pub async fn run(state: AppState) {
+    let mut applied = limits(&state.config.lock().unwrap(), state.data_meter.capped());
+    loop {
+        tokio::time::sleep(TICK).await;
+        let current = limits(&state.config.lock().unwrap(), state.data_meter.capped());
+        if current == applied {
+            continue;
+        }
+        info!("Speed limits: {:?} down, {:?} up (bytes per second)", current.download_bps, current.upload_bps);
+        if current.download_bps != applied.download_bps {
+            state.session.ratelimits.set_download_bps(current.download_bps);
+        }
+        if current.upload_bps != applied.upload_bps {
+            state.session.ratelimits.set_upload_bps(current.upload_bps);
+        }
+        applied = current;
+    }
+}
+
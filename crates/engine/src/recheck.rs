@@ -0,0 +1,169 @@
+use crate::torrent::{self, TorrentExt};
+use crate::verify::{verify_batch, PieceCheck, PieceLayout};
+use crate::AppState;
+use bridge::{EngineEvent, JobKind, PauseReason};
+use librqbit::ManagedTorrent;
+use rand::seq::index::sample;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// How often the task wakes up to look for seeds that are due a spot-check.
+const TICK: Duration = Duration::from_secs(60);
+// Pieces read before handing them to a hash worker in one go.
+const BATCH_SIZE: usize = 4;
+// How often an initial check's job picks up the session's progress.
+const INITIAL_POLL: Duration = Duration::from_secs(1);
+
+// Follows the session's own check of a torrent it's initializing: the data
+// already on disk for one just added over it, or the saved bitfield of a
+// restored one. The torrent is held paused (PauseReason::Checking) until the
+// check is done, then started. The session can't cancel a check, so
+// cancelling the job only stops reporting on it.
+pub fn initial(state: AppState, handle: Arc<ManagedTorrent>) {
+    tokio::spawn(async move {
+        let id = handle.info_hash().as_string();
+        let job = state.jobs.start(JobKind::InitialCheck, Some(&id), &handle.info().name);
+        job.running();
+        let checked = {
+            let initialized = handle.wait_until_initialized();
+            tokio::pin!(initialized);
+            loop {
+                tokio::select! {
+                    result = &mut initialized => break result,
+                    _ = tokio::time::sleep(INITIAL_POLL) => {
+                        let stats = handle.stats();
+                        job.progress(stats.progress_bytes, stats.total_bytes);
+                    }
+                }
+            }
+        };
+        if let Err(e) = checked {
+            warn!("Initial check of {} failed: {:#}", id, e);
+            job.finish(Err(format!("{:#}", e)));
+            state.engine_paused.lock().unwrap().remove(&id);
+            return;
+        }
+        let stats = handle.stats();
+        let found = format!("{:.0}% already there", torrent::progress(&stats) * 100.0);
+        info!("Initial check of {}: {}", id, found);
+        job.finish(Ok(Some(found)));
+        if let Err(e) = state.release(&handle, PauseReason::Checking).await {
+            warn!("Failed to start {} after checking: {}", id, e);
+        }
+    });
+}
+
+// Periodically re-reads a random sample of pieces from long-running seeds and
+// compares them against the metainfo hashes, so silent bit-rot is caught
+// before we serve bad data to the swarm.
+pub async fn run(state: AppState) {
+    // Torrent id -> when it was last checked (or first seen seeding)
+    let mut last_checked: HashMap<String, Instant> = HashMap::new();
+    let mut interval = tokio::time::interval(TICK);
+
+    loop {
+        interval.tick().await;
+
+        let config = state.config.lock().unwrap().clone();
+        if !config.auto_recheck_enabled {
+            continue;
+        }
+        let due_after = Duration::from_secs(config.auto_recheck_interval_secs);
+
+        for handle in state.torrents() {
+            if !handle.stats().finished {
+                continue;
+            }
+            let id = handle.info_hash().as_string();
+            let seen = *last_checked.entry(id.clone()).or_insert_with(Instant::now);
+            if seen.elapsed() < due_after {
+                continue;
+            }
+            last_checked.insert(id.clone(), Instant::now());
+
+            let info = handle.info();
+            let job = state.jobs.start(JobKind::SpotCheck, Some(&id), &info.name);
+            // Cancelled while queued: try again next interval
+            let Some(_slot) = state.checks.enter(&id, &info.name).await else {
+                job.finish_cancelled();
+                continue;
+            };
+            job.running();
+            let layout = match PieceLayout::new(&state.content_root(&id, &info), &info) {
+                Ok(layout) => layout,
+                Err(e) => {
+                    job.finish(Err(e.to_string()));
+                    continue;
+                }
+            };
+            let num_pieces = layout.num_pieces() as usize;
+            if num_pieces == 0 {
+                job.finish(Ok(None));
+                continue;
+            }
+            let picks: Vec<u32> = {
+                let mut rng = rand::thread_rng();
+                let amount = config.auto_recheck_pieces.min(num_pieces);
+                sample(&mut rng, num_pieces, amount).iter().map(|i| i as u32).collect()
+            };
+
+            let mut bad_pieces = Vec::new();
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            for (n, &index) in picks.iter().enumerate() {
+                if job.is_cancelled() {
+                    break;
+                }
+                job.progress(n as u64, picks.len() as u64);
+                if let Some(expected) = info.piece_hash(index) {
+                    match layout.read_piece(index).await {
+                        Ok(data) => batch.push(PieceCheck { index, data, expected }),
+                        Err(e) => {
+                            warn!("Recheck of {} piece {} failed to read: {:#}", id, index, e);
+                            bad_pieces.push(index);
+                        }
+                    }
+
+                    // Throttle disk reads so the check doesn't starve seeding
+                    if config.auto_recheck_read_limit > 0 {
+                        let secs = layout.piece_size(index) as f64 / config.auto_recheck_read_limit as f64;
+                        tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+                    }
+                }
+
+                if batch.len() == BATCH_SIZE || (n + 1 == picks.len() && !batch.is_empty()) {
+                    let checks = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                    for (index, ok) in verify_batch(checks).await {
+                        if !ok {
+                            bad_pieces.push(index);
+                        }
+                    }
+                }
+            }
+
+            if job.is_cancelled() {
+                job.finish(Ok(None));
+                continue;
+            }
+            if bad_pieces.is_empty() {
+                info!("Recheck of {} passed", id);
+                job.finish(Ok(Some(format!("{} pieces passed", picks.len()))));
+                continue;
+            }
+
+            warn!("Recheck of {} found {} corrupted pieces", id, bad_pieces.len());
+            job.finish(Ok(Some(format!("{} of {} pieces corrupted", bad_pieces.len(), picks.len()))));
+            state.corrupted.lock().unwrap().insert(id.clone());
+            state.events.emit(EngineEvent::TorrentCorrupted {
+                id,
+                name: info.name.clone(),
+                bad_pieces,
+            });
+        }
+
+        // Forget torrents that have been removed from the session
+        let live: Vec<String> = state.torrents().iter().map(|h| h.info_hash().as_string()).collect();
+        last_checked.retain(|id, _| live.contains(id));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::external;
+use anyhow::{anyhow, bail, Result};
+use regex::RegexBuilder;
+use reqwest::redirect::Policy;
+use reqwest::{header, Url};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+// Redirects and landing pages between the link and the torrent
+const MAX_HOPS: usize = 5;
+const MAX_BODY: usize = 10 * 1024 * 1024;
+
+pub enum Resolved {
+    Magnet(String),
+    Torrent(Vec<u8>),
+}
+
+// Turns a download link as indexers hand them out into something the
+// session can add. Follows redirects, including ones to a magnet link, and
+// for sites that put a page in front of the download, the page's magnet or
+// .torrent link. Links often carry an API key, so errors never include
+// the URL.
+pub async fn resolve(link: &str, user_agent: &str) -> Result<Resolved> {
+    let link = link.trim();
+    if link.starts_with("magnet:") {
+        return Ok(Resolved::Magnet(link.to_string()));
+    }
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(user_agent)
+        .gzip(true)
+        .redirect(Policy::none())
+        .build()?;
+
+    let mut url = Url::parse(link).map_err(|_| anyhow!("not a magnet link or URL"))?;
+    for _ in 0..MAX_HOPS {
+        let response = client.get(url.clone()).send().await.map_err(|e| e.without_url())?;
+        let next = if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| anyhow!("redirect without a location"))?;
+            if location.starts_with("magnet:") {
+                return Ok(Resolved::Magnet(location.to_string()));
+            }
+            url.join(location)?
+        } else {
+            let response = response.error_for_status().map_err(|e| e.without_url())?;
+            let body = read_body(response, MAX_BODY).await?;
+            if external::parse_torrent(&body).is_ok() {
+                return Ok(Resolved::Torrent(body));
+            }
+            let page = String::from_utf8_lossy(&body);
+            if let Some(magnet) = magnet_link(&page) {
+                return Ok(Resolved::Magnet(magnet));
+            }
+            torrent_link(&page, &url).ok_or_else(|| anyhow!("no magnet or .torrent link on the download page"))?
+        };
+        // Same rules as tracker redirects (see scrape.rs)
+        if !matches!(next.scheme(), "http" | "https") || (url.scheme() == "https" && next.scheme() == "http") {
+            bail!("refusing to follow a link to a {} URL", next.scheme());
+        }
+        url = next;
+    }
+    bail!("too many redirects or pages before the torrent")
+}
+
+// The whole body, or an error once it passes `max` bytes
+pub async fn read_body(mut response: reqwest::Response, max: usize) -> Result<Vec<u8>> {
+    if response.content_length().is_some_and(|len| len > max as u64) {
+        bail!("download is larger than {} bytes", max);
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.without_url())? {
+        body.extend_from_slice(&chunk);
+        if body.len() > max {
+            bail!("download is larger than {} bytes", max);
+        }
+    }
+    Ok(body)
+}
+
+fn magnet_link(page: &str) -> Option<String> {
+    let regex = RegexBuilder::new(r#"magnet:\?[^"'<>\s]+"#).case_insensitive(true).build().ok()?;
+    regex.find(page).map(|m| m.as_str().replace("&amp;", "&"))
+}
+
+// The first link on the page to a .torrent file
+fn torrent_link(page: &str, base: &Url) -> Option<Url> {
+    let regex = RegexBuilder::new(r#"href\s*=\s*["']([^"']+\.torrent(?:\?[^"']*)?)["']"#)
+        .case_insensitive(true)
+        .build()
+        .ok()?;
+    let href = regex.captures(page)?.get(1)?.as_str().replace("&amp;", "&");
+    base.join(&href).ok()
+}
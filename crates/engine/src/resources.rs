@@ -0,0 +1,116 @@
+use crate::AppState;
+use bridge::{EngineEvent, PauseReason};
+use std::time::Duration;
+use sysinfo::{Disks, System};
+use tracing::{info, warn};
+
+// Consecutive over-limit CPU samples before we react, so short spikes
+// (e.g. hashing a finished piece) don't pause anything.
+const CPU_SAMPLES: u32 = 3;
+// Resume once usage drops this far below the limit.
+const CPU_HYSTERESIS: f32 = 10.0;
+
+// Watches free space on the download volume and system CPU load, pausing
+// downloads while either is past its configured threshold and resuming them
+// once conditions recover. Only torrents paused here are ever resumed here.
+pub async fn run(state: AppState) {
+    let mut sys = System::new();
+    let mut disks = Disks::new_with_refreshed_list();
+    let mut cpu_over = 0u32;
+    let mut cpu_paused = false;
+
+    loop {
+        let config = state.config.lock().unwrap().clone();
+        tokio::time::sleep(Duration::from_secs(config.resource_check_interval_secs.max(1))).await;
+
+        // CPU pressure
+        sys.refresh_cpu();
+        let cpu = sys.global_cpu_info().cpu_usage();
+        if config.max_cpu_percent > 0.0 && cpu > config.max_cpu_percent {
+            cpu_over += 1;
+        } else {
+            cpu_over = 0;
+        }
+        if !cpu_paused && cpu_over >= CPU_SAMPLES {
+            warn!("CPU usage {:.0}% above limit, pausing downloads", cpu);
+            cpu_paused = true;
+        } else if cpu_paused && (config.max_cpu_percent <= 0.0 || cpu < config.max_cpu_percent - CPU_HYSTERESIS) {
+            info!("CPU usage back to {:.0}%, resuming downloads", cpu);
+            cpu_paused = false;
+        }
+
+        // Disk space
+        disks.refresh();
+        let free = free_space(&disks, &config.download_path);
+        let low_disk = config.min_free_disk_space > 0
+            && free.is_some_and(|f| f < config.min_free_disk_space);
+
+        for handle in state.torrents() {
+            let stats = handle.stats();
+            let id = handle.info_hash().as_string();
+
+            let short_of_space = || {
+                let (Some(free), true) = (free, config.min_free_disk_space > 0) else {
+                    return false;
+                };
+                let remaining = stats.total_bytes.saturating_sub(stats.progress_bytes);
+                remaining > free.saturating_sub(config.min_free_disk_space)
+            };
+
+            let wanted = if stats.finished {
+                None
+            } else if low_disk {
+                Some(PauseReason::LowDiskSpace)
+            } else if short_of_space() {
+                Some(PauseReason::InsufficientDiskSpace)
+            } else if cpu_paused {
+                Some(PauseReason::HighCpu)
+            } else {
+                None
+            };
+
+            let current = state.engine_paused.lock().unwrap().get(&id).copied();
+            let ours = |r: PauseReason| {
+                matches!(r, PauseReason::LowDiskSpace | PauseReason::InsufficientDiskSpace | PauseReason::HighCpu)
+            };
+            if state.is_session_paused() || current.is_some_and(|r| !ours(r)) {
+                // Owned by the user, the queue, the global pause or another task; only
+                // that one resumes it
+                continue;
+            }
+            match (current, wanted) {
+                (None, Some(reason)) => {
+                    if let Err(e) = state.session.pause(&handle).await {
+                        warn!("Failed to pause {}: {}", id, e);
+                        continue;
+                    }
+                    state.engine_paused.lock().unwrap().insert(id.clone(), reason);
+                    state.events.emit(EngineEvent::TorrentPaused { id, reason });
+                }
+                (Some(reason), None) => {
+                    if let Err(e) = state.release(&handle, reason).await {
+                        warn!("Failed to resume {}: {}", id, e);
+                    }
+                }
+                (Some(old), Some(new)) if old != new => {
+                    // Still paused, just for a different reason now
+                    state.engine_paused.lock().unwrap().insert(id.clone(), new);
+                    state.events.emit(EngineEvent::TorrentPaused { id, reason: new });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Free bytes on the volume holding `path`, picking the disk with the longest
+// matching mount point.
+fn free_space(disks: &Disks, path: &str) -> Option<u64> {
+    let path = std::fs::canonicalize(path).ok()?;
+    disks
+        .list()
+        .iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map(|d| d.available_space())
+}
@@ -0,0 +1,403 @@
+use crate::{files, persist, AppState};
+use crate::torrent::TorrentExt;
+use anyhow::{anyhow, bail, Result};
+use bridge::{EngineEvent, PauseReason, RetentionAction, RetentionCandidate, RetentionRule};
+use librqbit::ManagedTorrent;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const TICK: Duration = Duration::from_secs(600);
+const FILE: &str = "retention.json";
+const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Default)]
+struct Saved {
+    rules: Vec<RetentionRule>,
+    goal_met: HashMap<String, u64>, // torrent id -> unix time it first met its seeding goal
+}
+
+// Per-category rules for what happens to content some days after its
+// torrent met its seeding goal, and when each torrent met it. Saved to the
+// state dir so a restart doesn't start the count over.
+#[derive(Clone)]
+pub struct Retention {
+    path: PathBuf,
+    saved: Arc<Mutex<Saved>>,
+}
+
+impl Retention {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self { saved: Arc::new(Mutex::new(persist::load(&path))), path }
+    }
+
+    pub fn rules(&self) -> Vec<RetentionRule> {
+        self.saved.lock().unwrap().rules.clone()
+    }
+
+    // Adds the category's rule or replaces it.
+    pub fn set_rule(&self, mut rule: RetentionRule) -> Result<()> {
+        rule.category = rule.category.trim().to_string();
+        if rule.category.is_empty() {
+            return Err(anyhow!("category is empty"));
+        }
+        if rule.action == RetentionAction::Archive && rule.archive_dir.trim().is_empty() {
+            return Err(anyhow!("archive_dir is needed to archive"));
+        }
+        let mut saved = self.saved.lock().unwrap();
+        match saved.rules.iter_mut().find(|r| r.category == rule.category) {
+            Some(existing) => *existing = rule,
+            None => saved.rules.push(rule),
+        }
+        persist::save(&self.path, &*saved)
+    }
+
+    pub fn remove_rule(&self, category: &str) -> Result<bool> {
+        let mut saved = self.saved.lock().unwrap();
+        let before = saved.rules.len();
+        saved.rules.retain(|r| r.category != category);
+        if saved.rules.len() == before {
+            return Ok(false);
+        }
+        persist::save(&self.path, &*saved)?;
+        Ok(true)
+    }
+
+    // Keeps the first time; a torrent resumed and paused again by its
+    // goal doesn't get more days.
+    pub fn goal_met(&self, id: &str) {
+        let mut saved = self.saved.lock().unwrap();
+        if saved.goal_met.contains_key(id) {
+            return;
+        }
+        saved.goal_met.insert(id.to_string(), persist::now());
+        if let Err(e) = persist::save(&self.path, &*saved) {
+            warn!("Failed to save retention state: {}", e);
+        }
+    }
+
+    pub fn forget(&self, id: &str) {
+        let mut saved = self.saved.lock().unwrap();
+        if saved.goal_met.remove(id).is_some() {
+            if let Err(e) = persist::save(&self.path, &*saved) {
+                warn!("Failed to save retention state: {}", e);
+            }
+        }
+    }
+}
+
+// The torrent's own files as they are on disk, by name within the
+// content root. Content roots are saved with the torrent (see roots.rs);
+// one whose files aren't where the root says is left alone rather than
+// acted on elsewhere.
+struct Content {
+    root: PathBuf,
+    names: Vec<String>,
+    // Only a folder the engine named after the torrent is its own to
+    // remove; one it was added with belongs to the user (see storage.rs)
+    own_root: bool,
+}
+
+impl Content {
+    fn of(state: &AppState, id: &str, handle: &ManagedTorrent) -> Option<Self> {
+        let info = handle.info();
+        let root = state.content_root(id, &info);
+        let own_root = info.files().len() > 1 && state.content_roots.get(id).is_none();
+        let names: Vec<String> = files::disk_names(&info)
+            .ok()?
+            .into_iter()
+            .filter(|name| files::on_disk(&root, name).exists())
+            .collect();
+        (!names.is_empty()).then_some(Self { root, names, own_root })
+    }
+
+    // The torrent's folder, or its one file
+    fn path(&self) -> PathBuf {
+        match (self.own_root, self.names.as_slice()) {
+            (false, [name]) => files::on_disk(&self.root, name),
+            _ => self.root.clone(),
+        }
+    }
+
+    // Where archiving puts the files: a folder named like the torrent's
+    // own, or straight into the archive folder.
+    fn archive_root(&self, archive_dir: &Path) -> Option<PathBuf> {
+        match self.own_root {
+            true => self.root.file_name().map(|name| archive_dir.join(name)),
+            false => Some(archive_dir.to_path_buf()),
+        }
+    }
+
+    // Where archiving moves `path()` to
+    fn destination(&self, archive_dir: &Path) -> Option<PathBuf> {
+        let root = self.archive_root(archive_dir)?;
+        Some(match (self.own_root, self.names.as_slice()) {
+            (false, [name]) => files::on_disk(&root, name),
+            _ => root,
+        })
+    }
+}
+
+// What the rules would do to the torrents in the session, due now or later
+pub fn preview(state: &AppState) -> Vec<RetentionCandidate> {
+    let (rules, goal_met) = {
+        let saved = state.retention.saved.lock().unwrap();
+        (saved.rules.clone(), saved.goal_met.clone())
+    };
+    let now = persist::now();
+    let mut candidates = Vec::new();
+    for handle in state.torrents() {
+        let id = handle.info_hash().as_string();
+        let Some(&met_at) = goal_met.get(&id) else { continue };
+        let Some(category) = state.tagging.labels(&id).category else { continue };
+        let Some(rule) = rules.iter().find(|r| r.category == category) else { continue };
+        let Some(content) = Content::of(state, &id, &handle) else { continue };
+        let destination = match rule.action {
+            RetentionAction::Archive => content.destination(Path::new(&rule.archive_dir)),
+            RetentionAction::Delete => None,
+        };
+        let due_at = met_at + rule.after_days as u64 * DAY;
+        candidates.push(RetentionCandidate {
+            id,
+            name: handle.info().name,
+            category,
+            action: rule.action,
+            path: content.path().display().to_string(),
+            destination: destination.map(|d| d.display().to_string()),
+            goal_met_at: met_at,
+            due_at,
+            due: due_at <= now,
+        });
+    }
+    candidates.sort_by_key(|c| c.due_at);
+    candidates
+}
+
+// Deletes the torrent with its content, or archives the content. To
+// archive, the torrent is held paused while its files move and only taken
+// out of the session once they have, so a move that fails leaves it as it
+// was, to be tried again on the next pass. Only the torrent's own files
+// move, along with the folders that held nothing else.
+async fn apply(state: &AppState, handle: &Arc<ManagedTorrent>, candidate: &RetentionCandidate) -> Result<()> {
+    let archive_dir = match candidate.action {
+        RetentionAction::Delete => return state.remove(handle, true).await,
+        RetentionAction::Archive => {
+            let rules = state.retention.rules();
+            let Some(rule) = rules.iter().find(|r| r.category == candidate.category) else {
+                bail!("no rule for {}", candidate.category);
+            };
+            PathBuf::from(&rule.archive_dir)
+        }
+    };
+    let Some(content) = Content::of(state, &candidate.id, handle) else { bail!("no content to archive") };
+    let Some(to_root) = content.archive_root(&archive_dir) else { bail!("no file name to archive under") };
+    let moves: Vec<(PathBuf, PathBuf)> = content
+        .names
+        .iter()
+        .map(|name| (files::on_disk(&content.root, name), files::on_disk(&to_root, name)))
+        .collect();
+    if let Some((_, to)) = moves.iter().find(|(_, to)| to.exists()) {
+        bail!("{} already exists", to.display());
+    }
+    // One paused already, most likely by its seeding goal, stays as it is
+    let held = state.engine_paused.lock().unwrap().contains_key(&candidate.id);
+    if !held {
+        state.session.pause(handle).await?;
+        state.hold(&candidate.id, PauseReason::Retention);
+    }
+    let moved = tokio::task::spawn_blocking(move || {
+        move_files(&moves, &archive_dir)?;
+        let from: Vec<PathBuf> = moves.into_iter().map(|(from, _)| from).collect();
+        remove_empty_dirs(&from, &content.root);
+        if content.own_root {
+            let _ = std::fs::remove_dir(&content.root);
+        }
+        io::Result::Ok(())
+    })
+    .await?;
+    if let Err(e) = moved {
+        if !held {
+            state.release(handle, PauseReason::Retention).await?;
+        }
+        return Err(e.into());
+    }
+    state.remove(handle, false).await
+}
+
+// Moves every file, each with a rename when both are on the same
+// filesystem, otherwise a copy. If one fails the ones done so far go back
+// and the copies are removed, so the content stays whole where it was.
+// Once all are across, the originals of the copies are deleted; one that
+// can't be is only warned about.
+fn move_files(moves: &[(PathBuf, PathBuf)], archive_dir: &Path) -> io::Result<()> {
+    move_files_with(moves, archive_dir, |from, to| std::fs::rename(from, to))
+}
+
+// move_files with the rename to try first, so tests can take the copy path
+fn move_files_with(
+    moves: &[(PathBuf, PathBuf)],
+    archive_dir: &Path,
+    rename: impl Fn(&Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut done: Vec<(&Path, &Path, bool)> = Vec::new(); // from, to, renamed
+    for (from, to) in moves {
+        match move_file(from, to, &rename) {
+            Ok(renamed) => done.push((from, to, renamed)),
+            Err(e) => {
+                for &(from, to, renamed) in &done {
+                    let undone = match renamed {
+                        true => std::fs::rename(to, from),
+                        false => std::fs::remove_file(to),
+                    };
+                    if let Err(undo) = undone {
+                        warn!("Failed to undo the archive of {}: {}", from.display(), undo);
+                    }
+                }
+                let created: Vec<PathBuf> = moves.iter().map(|(_, to)| to.clone()).collect();
+                remove_empty_dirs(&created, archive_dir);
+                return Err(e);
+            }
+        }
+    }
+    for (from, _, _) in done.into_iter().filter(|&(_, _, renamed)| !renamed) {
+        if let Err(e) = std::fs::remove_file(from) {
+            warn!("Archived {} but couldn't delete it: {}", from.display(), e);
+        }
+    }
+    Ok(())
+}
+
+// Whether it was renamed; a copy that fails part way is removed again.
+fn move_file(from: &Path, to: &Path, rename: impl Fn(&Path, &Path) -> io::Result<()>) -> io::Result<bool> {
+    if let Some(dir) = to.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if rename(from, to).is_ok() {
+        return Ok(true);
+    }
+    if let Err(e) = std::fs::copy(from, to) {
+        if to.exists() {
+            if let Err(clean) = std::fs::remove_file(to) {
+                warn!("Failed to remove the partial copy at {}: {}", to.display(), clean);
+            }
+        }
+        return Err(e);
+    }
+    Ok(false)
+}
+
+// Removes the folders between each file and `root` that are left empty,
+// innermost first; `root` itself and anything outside it stay.
+fn remove_empty_dirs(files: &[PathBuf], root: &Path) {
+    for file in files {
+        for dir in file.ancestors().skip(1) {
+            if dir == root || !dir.starts_with(root) || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+// Applies the rules to the torrents that are due. One that fails, e.g.
+// because the archive already has something by that name, is tried again
+// on the next pass.
+pub async fn run(state: AppState) {
+    loop {
+        tokio::time::sleep(TICK).await;
+        apply_due(&state).await;
+    }
+}
+
+pub async fn apply_due(state: &AppState) {
+    for candidate in preview(state).into_iter().filter(|c| c.due) {
+        let Some(handle) = state.torrent(&candidate.id) else { continue };
+        if let Err(e) = apply(state, &handle, &candidate).await {
+            warn!("Retention {:?} of {} failed: {:#}", candidate.action, candidate.id, e);
+            continue;
+        }
+        info!("Retention: {:?} {} ({})", candidate.action, candidate.id, candidate.path);
+        state.events.emit(EngineEvent::RetentionApplied {
+            id: candidate.id,
+            name: candidate.name,
+            action: candidate.action,
+            destination: candidate.destination,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `names` under `dir`, each holding its own name
+    fn files(dir: &Path, names: &[&str]) -> Vec<PathBuf> {
+        names
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, name).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    fn moves(from: &[PathBuf], root: &Path, archive: &Path) -> Vec<(PathBuf, PathBuf)> {
+        from.iter().map(|f| (f.clone(), archive.join(f.strip_prefix(root).unwrap()))).collect()
+    }
+
+    fn no_rename(_: &Path, _: &Path) -> io::Result<()> {
+        Err(io::Error::other("other filesystem"))
+    }
+
+    #[test]
+    fn moves_files_and_clears_the_folders_they_leave() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, archive) = (dir.path().join("album"), dir.path().join("archive"));
+        let from = files(&root, &["cd1/1.flac", "cd1/2.flac", "cover.jpg"]);
+        let moves = moves(&from, &root, &archive);
+        move_files(&moves, &archive).unwrap();
+        remove_empty_dirs(&from, &root);
+
+        assert!(moves.iter().all(|(from, to)| !from.exists() && to.exists()));
+        assert_eq!(std::fs::read_to_string(archive.join("cd1/2.flac")).unwrap(), "cd1/2.flac");
+        assert!(!root.join("cd1").exists());
+        assert!(root.exists());
+    }
+
+    #[test]
+    fn copies_across_filesystems() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, archive) = (dir.path().join("album"), dir.path().join("archive"));
+        let from = files(&root, &["cd1/1.flac", "cover.jpg"]);
+        move_files_with(&moves(&from, &root, &archive), &archive, no_rename).unwrap();
+
+        assert!(from.iter().all(|f| !f.exists()));
+        assert_eq!(std::fs::read_to_string(archive.join("cd1/1.flac")).unwrap(), "cd1/1.flac");
+        assert_eq!(std::fs::read_to_string(archive.join("cover.jpg")).unwrap(), "cover.jpg");
+    }
+
+    #[test]
+    fn a_failed_move_puts_everything_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let (root, archive) = (dir.path().join("album"), dir.path().join("archive"));
+        let mut from = files(&root, &["cd1/1.flac", "cd2/2.flac"]);
+        from.push(root.join("cd3/missing.flac"));
+        let moves = moves(&from, &root, &archive);
+
+        assert!(move_files(&moves, &archive).is_err());
+        assert_eq!(std::fs::read_to_string(&from[0]).unwrap(), "cd1/1.flac");
+        assert_eq!(std::fs::read_to_string(&from[1]).unwrap(), "cd2/2.flac");
+        assert!(!archive.join("cd1").exists() && !archive.join("cd2").exists());
+
+        assert!(move_files_with(&moves, &archive, no_rename).is_err());
+        assert_eq!(std::fs::read_to_string(&from[0]).unwrap(), "cd1/1.flac");
+        assert_eq!(std::fs::read_to_string(&from[1]).unwrap(), "cd2/2.flac");
+        assert!(!archive.join("cd1").exists() && !archive.join("cd2").exists());
+    }
+}
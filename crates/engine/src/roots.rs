@@ -0,0 +1,54 @@
+use crate::files;
+use crate::persist;
+use crate::torrent::TorrentInfo;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const FILE: &str = "content_roots.json";
+
+// Torrents added with their own content folder instead of one under the
+// download directory, saved to the state dir so checks, streaming and
+// retention still find the files after a restart
+#[derive(Clone)]
+pub struct ContentRoots {
+    path: PathBuf,
+    roots: Arc<Mutex<HashMap<String, PathBuf>>>,
+}
+
+impl ContentRoots {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self { roots: Arc::new(Mutex::new(persist::load(&path))), path }
+    }
+
+    pub fn get(&self, id: &str) -> Option<PathBuf> {
+        self.roots.lock().unwrap().get(id).cloned()
+    }
+
+    // Where the torrent's files are: the folder it was added with, or the
+    // usual place under the download directory.
+    pub fn resolve(&self, id: &str, download_path: &str, info: &TorrentInfo) -> PathBuf {
+        self.get(id).unwrap_or_else(|| files::content_root(download_path, info))
+    }
+
+    pub fn set(&self, id: &str, root: &Path) -> Result<()> {
+        let mut roots = self.roots.lock().unwrap();
+        if roots.get(id).map(PathBuf::as_path) != Some(root) {
+            roots.insert(id.to_string(), root.to_path_buf());
+            persist::save(&self.path, &*roots)?;
+        }
+        Ok(())
+    }
+
+    pub fn forget(&self, id: &str) {
+        let mut roots = self.roots.lock().unwrap();
+        if roots.remove(id).is_some() {
+            if let Err(e) = persist::save(&self.path, &*roots) {
+                warn!("Failed to save content roots: {}", e);
+            }
+        }
+    }
+}
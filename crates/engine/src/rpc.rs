@@ -0,0 +1,275 @@
+use crate::config;
+use crate::error::EngineError;
+use crate::http;
+use crate::profiles::Profiles;
+use anyhow::Result;
+use bridge::{ErrorKind, RpcCommand, RpcError, RpcRequest, RpcResponse, TorrentMode, PORT};
+use serde::Serialize;
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+pub async fn serve(profiles: Profiles) -> Result<()> {
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", PORT)).await?;
+    info!("RPC server listening on 127.0.0.1:{}", PORT);
+
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let profiles = profiles.clone();
+        tokio::spawn(async move {
+            let mut buf = [0; 4096];
+            loop {
+                let n = match socket.read(&mut buf).await {
+                    Ok(0) => return,
+                    Ok(n) => n,
+                    Err(_) => return,
+                };
+
+                let req_str = String::from_utf8_lossy(&buf[..n]);
+                // info!("RPC Raw: {}", req_str);
+                match serde_json::from_str::<RpcRequest>(&req_str) {
+                    Ok(req) => {
+                        let response = handle_rpc(req, &profiles).await;
+                        let resp_bytes = serde_json::to_vec(&response).unwrap();
+                        socket.write_all(&resp_bytes).await.ok();
+                    }
+                    Err(e) => {
+                        error!("RPC Parse Error: {}", e);
+                        let response: RpcResponse<serde_json::Value> = RpcResponse {
+                            jsonrpc: "2.0".into(),
+                            id: 0,
+                            result: None,
+                            error: Some(RpcError::new(ErrorKind::InvalidInput, format!("Malformed request: {}", e))),
+                        };
+                        let resp_bytes = serde_json::to_vec(&response).unwrap();
+                        socket.write_all(&resp_bytes).await.ok();
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn handle_rpc(req: RpcRequest, profiles: &Profiles) -> RpcResponse<serde_json::Value> {
+    info!("Received command: {:?}", req.command);
+    let client = &profiles.active();
+    let result = match req.command {
+        RpcCommand::AddTorrent { magnet, seed_path, start_at, skip_checking } => match (seed_path, start_at) {
+            (Some(path), _) => client.add_torrent_for_seeding(&magnet, std::path::Path::new(&path)).await,
+            (None, Some(start_at)) => client.add_torrent_scheduled(&magnet, start_at).await,
+            (None, None) if skip_checking => client.add_torrent_skip_checking(&magnet).await,
+            (None, None) => client.add_torrent(&magnet).await,
+        }
+        .map(|id| serde_json::json!({ "status": "added", "id": id })),
+        RpcCommand::AddByInfoHash { info_hash } => client
+            .add_by_info_hash(&info_hash)
+            .await
+            .map(|id| serde_json::json!({ "status": "added", "id": id })),
+        RpcCommand::ListTorrents => to_value(client.list_torrents()),
+        RpcCommand::ListTorrentsPage { offset, limit, sort_by, order, filter } => {
+            to_value(client.list_torrents_page(offset, limit, sort_by, order, &filter))
+        }
+        RpcCommand::GetTorrentFiles { id, offset, limit } => {
+            client.torrent_files(&id, offset, limit).and_then(to_value)
+        }
+        RpcCommand::GetPieceStates { id, cells } => client.piece_states(&id, cells).and_then(to_value),
+        RpcCommand::GetFileTree { id } => client.file_tree(&id).and_then(to_value),
+        RpcCommand::SetFilePriority { id, file_indices, priority } => client
+            .set_file_priority(&id, file_indices, priority)
+            .await
+            .map(|n| serde_json::json!({ "status": "updated", "files": n })),
+        RpcCommand::SetFolderPriority { id, path, priority } => client
+            .set_folder_priority(&id, &path, priority)
+            .await
+            .map(|n| serde_json::json!({ "status": "updated", "files": n })),
+        RpcCommand::GetFilePath { id, file_index } => client
+            .file_path(&id, file_index)
+            .map(|path| serde_json::json!({ "path": path, "exists": path.exists() })),
+        RpcCommand::SetDownloadOrder { id, enabled, order } => client
+            .set_download_order(&id, enabled, order)
+            .await
+            .map(|_| serde_json::json!({ "status": "updated" })),
+        RpcCommand::StartTorrent { id } => client
+            .set_torrent_mode(&id, TorrentMode::AutoManaged)
+            .await
+            .map(|_| serde_json::json!({ "status": "started" })),
+        RpcCommand::PauseTorrent { id } => client
+            .set_torrent_mode(&id, TorrentMode::Paused)
+            .await
+            .map(|_| serde_json::json!({ "status": "paused" })),
+        RpcCommand::SetTorrentMode { id, mode } => client
+            .set_torrent_mode(&id, mode)
+            .await
+            .map(|_| serde_json::json!({ "status": "updated" })),
+        RpcCommand::StreamTorrent { id } => Ok(serde_json::json!({
+            "status": "streaming",
+            "url": format!("{}/stream/{}/0", http::local_url(&client.config().http_listen_addr), id)
+        })),
+        RpcCommand::GetConfig => to_value(client.config()),
+        RpcCommand::SetConfig { locale: Some(locale), .. } if !locale.is_empty() && !config::valid_locale(&locale) => {
+            Err(EngineError::InvalidInput(format!("{} is not a language tag", locale)))
+        }
+        RpcCommand::SetConfig { download_path, max_download_speed, max_upload_speed, ffmpeg_path, locale } => {
+            // Speed limits are read live by the session's rate limiter
+            client.update_config(|config| {
+                if let Some(p) = download_path { config.download_path = p; }
+                if let Some(v) = max_download_speed { config.max_download_speed = v; }
+                if let Some(v) = max_upload_speed { config.max_upload_speed = v; }
+                if let Some(p) = ffmpeg_path { config.ffmpeg_path = p; }
+                if let Some(l) = locale { config.locale = l; }
+            });
+            Ok(serde_json::json!({ "status": "updated" }))
+        }
+        RpcCommand::GetEvents { since } => to_value(client.events_since(since)),
+        RpcCommand::ListTrackerCredentials => to_value(client.tracker_credential_domains()),
+        RpcCommand::SetTrackerPasskey { domain, passkey } => client
+            .set_tracker_passkey(&domain, &passkey)
+            .await
+            .map(|updated| serde_json::json!({ "status": "updated", "torrents": updated })),
+        RpcCommand::RemoveTrackerPasskey { domain } => client
+            .remove_tracker_passkey(&domain)
+            .map(|_| serde_json::json!({ "status": "removed" })),
+        RpcCommand::PauseSession => {
+            client.pause_session().await;
+            Ok(serde_json::json!({ "status": "paused" }))
+        }
+        RpcCommand::ResumeSession => {
+            client.resume_session().await;
+            Ok(serde_json::json!({ "status": "resumed" }))
+        }
+        RpcCommand::Shutdown => {
+            profiles.shutdown().await;
+            Ok(serde_json::json!({ "status": "stopped" }))
+        }
+        RpcCommand::GetSessionSummary { recent } => to_value(client.session_summary(recent)),
+        RpcCommand::GetSpeedSummary => to_value(client.speed_summary()),
+        RpcCommand::GetVersionInfo => to_value(client.version_info()),
+        RpcCommand::CheckUpdates => client.check_updates().await.and_then(to_value),
+        RpcCommand::Handshake { locale } => to_value(client.handshake(locale.as_deref())),
+        RpcCommand::SetAltSpeed { enabled } => {
+            client.set_alt_speed(enabled);
+            Ok(serde_json::json!({ "status": "updated" }))
+        }
+        RpcCommand::ResumedFromSleep => {
+            profiles.resumed_from_sleep();
+            Ok(serde_json::json!({ "status": "resumed" }))
+        }
+        RpcCommand::ResolveMagnet { magnet } => client.resolve_magnet(&magnet).await.and_then(to_value),
+        RpcCommand::PreviewSwarm { source } => client.preview_swarm(&source).await.and_then(to_value),
+        RpcCommand::GetSessionStatus => Ok(serde_json::json!({
+            "paused": client.is_session_paused(),
+            "dht_nodes": client.dht_nodes(),
+            "write_buffer_bytes": client.write_buffer_bytes(),
+            "startup": client.startup_timings(),
+        })),
+        RpcCommand::GetDhtStats => to_value(client.dht_stats()),
+        RpcCommand::GetTaskHealth => to_value(client.task_health()),
+        RpcCommand::DumpDiagnostics { output } => client
+            .dump_diagnostics(Path::new(&output))
+            .map(|_| serde_json::json!({ "status": "written", "path": output })),
+        RpcCommand::GetTranscoderInfo { refresh } => to_value(client.transcoder_info(refresh).await),
+        RpcCommand::ScheduleStart { id, start_at } => client
+            .schedule_start(&id, start_at)
+            .await
+            .map(|_| serde_json::json!({ "status": "scheduled" })),
+        RpcCommand::CancelScheduledStart { id } => client
+            .cancel_scheduled_start(&id)
+            .await
+            .map(|_| serde_json::json!({ "status": "cancelled" })),
+        RpcCommand::ListScheduledStarts => to_value(client.scheduled_starts()),
+        RpcCommand::ListTagRules => to_value(client.tag_rules()),
+        RpcCommand::AddTagRule { rule } => client.add_tag_rule(rule).map(|id| serde_json::json!({ "status": "added", "id": id })),
+        RpcCommand::UpdateTagRule { rule } => client.update_tag_rule(rule).map(|_| serde_json::json!({ "status": "updated" })),
+        RpcCommand::RemoveTagRule { id } => client.remove_tag_rule(id).map(|_| serde_json::json!({ "status": "removed" })),
+        RpcCommand::SetTorrentTags { id, tags, category } => client
+            .set_torrent_tags(&id, tags, category)
+            .map(|_| serde_json::json!({ "status": "updated" })),
+        RpcCommand::ListTags => to_value(client.tags()),
+        RpcCommand::SetTag { tag } => client.set_tag(tag).map(|_| serde_json::json!({ "status": "updated" })),
+        RpcCommand::RemoveTag { name } => client.remove_tag(&name).map(|_| serde_json::json!({ "status": "removed" })),
+        RpcCommand::ListTrackerLimits => to_value(client.tracker_limits()),
+        RpcCommand::SetTrackerLimit { limit } => client.set_tracker_limit(limit).map(|_| serde_json::json!({ "status": "updated" })),
+        RpcCommand::ListRetentionRules => to_value(client.retention_rules()),
+        RpcCommand::SetRetentionRule { rule } => client.set_retention_rule(rule).map(|_| serde_json::json!({ "status": "updated" })),
+        RpcCommand::RemoveRetentionRule { category } => client
+            .remove_retention_rule(&category)
+            .map(|_| serde_json::json!({ "status": "removed" })),
+        RpcCommand::PreviewRetention => to_value(client.preview_retention()),
+        RpcCommand::RemoveTrackerLimit { domain } => client
+            .remove_tracker_limit(&domain)
+            .map(|_| serde_json::json!({ "status": "removed" })),
+        RpcCommand::BanPeer { ip, reason, duration_secs } => client
+            .ban_peer(&ip, &reason, duration_secs)
+            .map(|_| serde_json::json!({ "status": "banned", "takes_effect": "next_start" })),
+        RpcCommand::UnbanPeer { ip } => client
+            .unban_peer(&ip)
+            .map(|_| serde_json::json!({ "status": "unbanned", "takes_effect": "next_start" })),
+        RpcCommand::ListBannedPeers => to_value(client.banned_peers()),
+        RpcCommand::CreateTorrent { source, output, trackers, private, piece_size } => client
+            .create_torrent(Path::new(&source), Path::new(&output), trackers, private, piece_size)
+            .await
+            .and_then(to_value),
+        RpcCommand::ExportAsNewTorrent { id, output, trackers, private } => client
+            .export_as_new_torrent(&id, Path::new(&output), trackers, private)
+            .await
+            .and_then(to_value),
+        RpcCommand::GetCheckQueue => to_value(client.check_queue()),
+        RpcCommand::CancelCheck { id } => client.cancel_check(&id).map(|_| serde_json::json!({ "status": "cancelled" })),
+        RpcCommand::ListJobs => to_value(client.jobs()),
+        RpcCommand::CancelJob { id } => client.cancel_job(id).map(|_| serde_json::json!({ "status": "cancelling" })),
+        RpcCommand::GetTorrentQueues => to_value(client.torrent_queues()),
+        RpcCommand::MoveInDownloadQueue { id, direction } => client
+            .move_in_download_queue(&id, direction)
+            .map(|_| serde_json::json!({ "status": "moved" })),
+        RpcCommand::MoveInSeedQueue { id, direction } => client
+            .move_in_seed_queue(&id, direction)
+            .map(|_| serde_json::json!({ "status": "moved" })),
+        RpcCommand::MoveCheck { id, position } => client.move_check(&id, position).map(|_| serde_json::json!({ "status": "moved" })),
+        RpcCommand::SetRatioLimit { id, ratio } => {
+            client.set_ratio_limit(&id, ratio).map(|_| serde_json::json!({ "status": "updated" }))
+        }
+        RpcCommand::SetCategoryRatioLimit { category, ratio } => {
+            client.set_category_ratio_limit(&category, ratio).map(|_| serde_json::json!({ "status": "updated" }))
+        }
+        RpcCommand::GetCategoryRatioLimits => to_value(client.category_ratio_limits()),
+        RpcCommand::ReannounceAll { category } => {
+            client.reannounce_all(category.as_deref()).map(|queued| serde_json::json!({ "queued": queued }))
+        }
+        RpcCommand::ScrapeAll { category } => {
+            client.scrape_all(category.as_deref()).map(|queued| serde_json::json!({ "queued": queued }))
+        }
+        RpcCommand::EditTorrentMetadata { id, comment, created_by } => client
+            .edit_torrent_metadata(&id, comment, created_by)
+            .map(|(comment, created_by)| serde_json::json!({ "comment": comment, "created_by": created_by })),
+        RpcCommand::GetTrackerSites => to_value(client.tracker_sites()),
+        RpcCommand::GetDataUsage => to_value(client.data_usage()),
+        RpcCommand::AddFromLink { link, params } => client
+            .add_from_link(&link, params)
+            .await
+            .map(|id| serde_json::json!({ "status": "added", "id": id })),
+        RpcCommand::ListProfiles => to_value(profiles.list()),
+        RpcCommand::CreateProfile { profile } => profiles.create(profile).map(|_| serde_json::json!({ "status": "created" })),
+        RpcCommand::SwitchProfile { name } => profiles.switch(&name).await.map(|_| serde_json::json!({ "status": "switched" })),
+        command => Err(EngineError::Unsupported(format!("{:?}", command))),
+    };
+
+    match result {
+        Ok(value) => RpcResponse {
+            jsonrpc: "2.0".into(),
+            id: req.id,
+            result: Some(value),
+            error: None,
+        },
+        Err(e) => RpcResponse {
+            jsonrpc: "2.0".into(),
+            id: req.id,
+            result: None,
+            error: Some(e.into()),
+        },
+    }
+}
+
+fn to_value(value: impl Serialize) -> Result<serde_json::Value, EngineError> {
+    Ok(serde_json::to_value(value).map_err(anyhow::Error::from)?)
+}
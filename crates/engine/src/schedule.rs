@@ -0,0 +1,98 @@
+use crate::{persist, AppState};
+use anyhow::Result;
+use bridge::{EngineEvent, PauseReason};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const TICK: Duration = Duration::from_secs(10);
+const FILE: &str = "schedules.json";
+
+// Torrent id -> unix time to start at. Saved to the state dir on every
+// change so schedules survive a restart.
+#[derive(Clone)]
+pub struct Schedules {
+    path: PathBuf,
+    starts: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl Schedules {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        let starts = persist::load(&path);
+        Self { path, starts: Arc::new(Mutex::new(starts)) }
+    }
+
+    pub fn set(&self, id: &str, start_at: u64) -> Result<()> {
+        let mut starts = self.starts.lock().unwrap();
+        starts.insert(id.to_string(), start_at);
+        persist::save(&self.path, &*starts)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<bool> {
+        let mut starts = self.starts.lock().unwrap();
+        if starts.remove(id).is_none() {
+            return Ok(false);
+        }
+        persist::save(&self.path, &*starts)?;
+        Ok(true)
+    }
+
+    pub fn all(&self) -> HashMap<String, u64> {
+        self.starts.lock().unwrap().clone()
+    }
+
+    pub fn forget(&self, id: &str) {
+        if let Err(e) = self.remove(id) {
+            warn!("Failed to save schedules: {}", e);
+        }
+    }
+}
+
+// The next unix time at `minute` past midnight UTC, strictly after `now`
+pub fn next_at(minute: u32, now: u64) -> u64 {
+    let at = now - now % 86400 + minute as u64 * 60;
+    if at > now {
+        at
+    } else {
+        at + 86400
+    }
+}
+
+// Holds scheduled torrents paused until their start time, then starts them.
+// A scheduled torrent that was started by something else (a restart) is
+// paused again until it is due.
+pub async fn run(state: AppState) {
+    loop {
+        tokio::time::sleep(TICK).await;
+        if state.is_session_paused() {
+            continue;
+        }
+        let reason = PauseReason::Scheduled;
+        let now = persist::now();
+        for (id, start_at) in state.schedules.all() {
+            let Some(handle) = state.torrent(&id) else { continue };
+            let current = state.engine_paused.lock().unwrap().get(&id).copied();
+            if start_at > now {
+                if current.is_none() && state.session.pause(&handle).await.is_ok() {
+                    state.engine_paused.lock().unwrap().insert(id.clone(), reason);
+                    state.events.emit(EngineEvent::TorrentPaused { id, reason });
+                }
+                continue;
+            }
+
+            if let Err(e) = state.schedules.remove(&id) {
+                warn!("Failed to save schedules: {}", e);
+            }
+            if current != Some(reason) {
+                continue; // paused for something else; that owner resumes it
+            }
+            info!("Starting scheduled torrent {}", id);
+            if let Err(e) = state.release(&handle, reason).await {
+                warn!("Failed to start scheduled {}: {}", id, e);
+            }
+        }
+    }
+}
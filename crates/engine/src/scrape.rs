@@ -0,0 +1,276 @@
+use crate::bencode::{self, Value};
+use crate::resolve;
+use anyhow::{anyhow, bail, Result};
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const UDP_PROTOCOL_ID: u64 = 0x41727101980;
+const MAX_REDIRECTS: usize = 5;
+const HTTP_ATTEMPTS: u32 = 3;
+const RETRY_BASE: Duration = Duration::from_secs(1);
+const CONNECTION_ID_TTL: Duration = Duration::from_secs(60);
+// A scrape of one torrent is a few dozen bytes
+const MAX_BODY: usize = 1024 * 1024;
+
+pub struct ScrapeCounts {
+    pub seeders: u32,
+    pub leechers: u32,
+    pub completed: u32,
+    pub warning: Option<String>, // the tracker's "warning message"
+}
+
+// Asks one tracker about one torrent without announcing to it.
+pub async fn scrape(
+    udp: &UdpTrackers,
+    url: &str,
+    info_hash: &[u8; 20],
+    user_agent: &str,
+) -> Result<ScrapeCounts> {
+    let url = Url::parse(url)?;
+    match url.scheme() {
+        "http" | "https" => scrape_http(&url, info_hash, user_agent).await,
+        "udp" => udp.scrape(&url, info_hash).await,
+        scheme => bail!("unsupported tracker scheme {}", scheme),
+    }
+}
+
+// Trackers that support scrape serve it where the last path segment says
+// "announce" ("/announce.php" -> "/scrape.php").
+fn scrape_url(announce: &Url) -> Option<Url> {
+    let (dir, last) = announce.path().rsplit_once('/')?;
+    let rest = last.strip_prefix("announce")?;
+    let mut url = announce.clone();
+    url.set_path(&format!("{}/scrape{}", dir, rest));
+    Some(url)
+}
+
+// Follows redirects within http(s), but never from https down to http: the
+// URL may carry a passkey.
+fn http_client(user_agent: &str) -> Result<reqwest::Client> {
+    let redirects = Policy::custom(|attempt| {
+        let downgrade = attempt.url().scheme() == "http" && attempt.previous().iter().any(|u| u.scheme() == "https");
+        if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if !matches!(attempt.url().scheme(), "http" | "https") || downgrade {
+            let message = format!("refusing redirect to a {} URL", attempt.url().scheme());
+            attempt.error(message)
+        } else {
+            attempt.follow()
+        }
+    });
+    Ok(reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .user_agent(user_agent)
+        .gzip(true)
+        .redirect(redirects)
+        .build()?)
+}
+
+// Retries timeouts, connection failures and server errors with exponential
+// backoff plus up to 50% jitter, so trackers that hiccup aren't all hit
+// again at the same moment. Errors leave out the URL, which carries the
+// passkey, as they end up in tracker messages.
+async fn http_get(client: &reqwest::Client, url: Url) -> Result<Vec<u8>> {
+    let mut attempt = 0;
+    loop {
+        let result = client.get(url.clone()).send().await.and_then(|r| r.error_for_status());
+        let error = match result {
+            Ok(response) => return resolve::read_body(response, MAX_BODY).await,
+            Err(e) => e.without_url(),
+        };
+        let retryable = error.is_timeout()
+            || error.is_connect()
+            || error.status().is_some_and(|s| s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS);
+        attempt += 1;
+        if !retryable || attempt == HTTP_ATTEMPTS {
+            return Err(error.into());
+        }
+        let backoff = RETRY_BASE * 2u32.pow(attempt - 1);
+        tokio::time::sleep(backoff + backoff.mul_f64(rand::random::<f64>() * 0.5)).await;
+    }
+}
+
+async fn scrape_http(announce: &Url, info_hash: &[u8; 20], user_agent: &str) -> Result<ScrapeCounts> {
+    let mut url = scrape_url(announce).ok_or_else(|| anyhow!("tracker does not support scrape"))?;
+    // The raw hash bytes, percent-encoded by hand; Url would re-encode them
+    let encoded: String = info_hash.iter().map(|b| format!("%{:02X}", b)).collect();
+    let query = match url.query() {
+        Some(q) => format!("{}&info_hash={}", q, encoded),
+        None => format!("info_hash={}", encoded),
+    };
+    url.set_query(Some(&query));
+
+    let body = http_get(&http_client(user_agent)?, url).await?;
+    let root = bencode::decode(&body)?;
+    if let Some(reason) = root.get("failure reason").and_then(Value::as_str) {
+        bail!("{}", reason);
+    }
+    let Some(Value::Dict(files)) = root.get("files") else {
+        bail!("scrape response has no files");
+    };
+    let stats = files
+        .get(&info_hash[..])
+        .ok_or_else(|| anyhow!("tracker does not know this torrent"))?;
+    let count = |key| stats.get(key).and_then(Value::as_int).unwrap_or(0).max(0) as u32;
+    Ok(ScrapeCounts {
+        seeders: count("complete"),
+        leechers: count("incomplete"),
+        completed: count("downloaded"),
+        warning: root.get("warning message").and_then(Value::as_str),
+    })
+}
+
+// BEP 15 over one socket per local address, shared by every UDP tracker
+// request. A receive task hands each response to the request waiting on its
+// transaction id. Connection ids are kept for the minute trackers accept
+// them, so a scrape within that minute skips the connect round trip.
+#[derive(Clone, Default)]
+pub struct UdpTrackers {
+    sockets: Arc<Mutex<HashMap<SocketAddr, Arc<SharedSocket>>>>, // local address -> socket
+    connections: Arc<Mutex<HashMap<SocketAddr, (u64, Instant)>>>, // tracker -> connection id, when obtained
+}
+
+struct SharedSocket {
+    socket: UdpSocket,
+    waiting: Mutex<HashMap<u32, Waiter>>, // by transaction id
+}
+
+struct Waiter {
+    tracker: SocketAddr,
+    reply: oneshot::Sender<Vec<u8>>,
+}
+
+impl UdpTrackers {
+    async fn scrape(&self, announce: &Url, info_hash: &[u8; 20]) -> Result<ScrapeCounts> {
+        let host = announce.host_str().ok_or_else(|| anyhow!("tracker URL has no host"))?;
+        let port = announce.port().ok_or_else(|| anyhow!("UDP tracker URL has no port"))?;
+        // IPv4 first: plenty of hosts resolve IPv6 without having a route
+        // for it
+        let mut addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+        addrs.sort_by_key(|a| a.is_ipv6());
+        let addr = *addrs.first().ok_or_else(|| anyhow!("could not resolve {}", host))?;
+        let local: IpAddr = match addr {
+            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let socket = self.socket(SocketAddr::new(local, 0)).await?;
+
+        let (connection_id, cached) = self.connection_id(&socket, addr).await?;
+        let result = scrape_udp(&socket, addr, connection_id, info_hash).await;
+        if result.is_err() && cached {
+            // The tracker may have forgotten the id early, e.g. on a restart
+            self.connections.lock().unwrap().remove(&addr);
+            let (connection_id, _) = self.connection_id(&socket, addr).await?;
+            return scrape_udp(&socket, addr, connection_id, info_hash).await;
+        }
+        result
+    }
+
+    async fn socket(&self, local: SocketAddr) -> Result<Arc<SharedSocket>> {
+        if let Some(socket) = self.sockets.lock().unwrap().get(&local) {
+            return Ok(socket.clone());
+        }
+        let socket = Arc::new(SharedSocket { socket: UdpSocket::bind(local).await?, waiting: Mutex::default() });
+        // Another request may have bound one meanwhile; use that instead
+        match self.sockets.lock().unwrap().entry(local) {
+            Entry::Occupied(existing) => Ok(existing.get().clone()),
+            Entry::Vacant(slot) => {
+                slot.insert(socket.clone());
+                tokio::spawn(receive(socket.clone()));
+                Ok(socket)
+            }
+        }
+    }
+
+    // The cached id if it is still valid, otherwise a new one. True when
+    // it came from the cache.
+    async fn connection_id(&self, socket: &SharedSocket, addr: SocketAddr) -> Result<(u64, bool)> {
+        let cached = self.connections.lock().unwrap().get(&addr).copied();
+        if let Some((id, _)) = cached.filter(|(_, t)| t.elapsed() < CONNECTION_ID_TTL) {
+            return Ok((id, true));
+        }
+        let tx: u32 = rand::random();
+        let mut request = Vec::with_capacity(16);
+        request.extend(UDP_PROTOCOL_ID.to_be_bytes());
+        request.extend(0u32.to_be_bytes());
+        request.extend(tx.to_be_bytes());
+        let response = socket.exchange(addr, &request, tx, 0, 16).await?;
+        let id = u64::from_be_bytes(response[8..16].try_into()?);
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|_, (_, t)| t.elapsed() < CONNECTION_ID_TTL);
+        connections.insert(addr, (id, Instant::now()));
+        Ok((id, false))
+    }
+}
+
+async fn scrape_udp(
+    socket: &SharedSocket,
+    addr: SocketAddr,
+    connection_id: u64,
+    info_hash: &[u8; 20],
+) -> Result<ScrapeCounts> {
+    let tx: u32 = rand::random();
+    let mut request = Vec::with_capacity(36);
+    request.extend(connection_id.to_be_bytes());
+    request.extend(2u32.to_be_bytes());
+    request.extend(tx.to_be_bytes());
+    request.extend(info_hash);
+    let response = socket.exchange(addr, &request, tx, 2, 20).await?;
+    let word = |i: usize| u32::from_be_bytes([response[i], response[i + 1], response[i + 2], response[i + 3]]);
+    Ok(ScrapeCounts {
+        seeders: word(8),
+        completed: word(12),
+        leechers: word(16),
+        warning: None,
+    })
+}
+
+impl SharedSocket {
+    async fn exchange(&self, addr: SocketAddr, request: &[u8], tx: u32, action: u32, min_len: usize) -> Result<Vec<u8>> {
+        let (reply, response) = oneshot::channel();
+        self.waiting.lock().unwrap().insert(tx, Waiter { tracker: addr, reply });
+        let result = async {
+            self.socket.send_to(request, addr).await?;
+            let response = tokio::time::timeout(TIMEOUT, response).await.map_err(|_| anyhow!("tracker timed out"))?;
+            Ok::<_, anyhow::Error>(response?)
+        }
+        .await;
+        self.waiting.lock().unwrap().remove(&tx);
+        let response = result?;
+        match u32::from_be_bytes([response[0], response[1], response[2], response[3]]) {
+            3 => bail!("{}", String::from_utf8_lossy(&response[8..])),
+            a if a == action && response.len() >= min_len => Ok(response),
+            _ => bail!("malformed tracker response"),
+        }
+    }
+}
+
+// Stray and stale datagrams, and ones from anyone but the tracker a
+// request went to, are dropped.
+async fn receive(socket: Arc<SharedSocket>) {
+    let mut buf = [0u8; 2048];
+    loop {
+        let (n, from) = match socket.socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(_) => continue, // e.g. an ICMP unreachable for an earlier send
+        };
+        if n < 8 {
+            continue;
+        }
+        let tx = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        let mut waiting = socket.waiting.lock().unwrap();
+        if waiting.get(&tx).is_some_and(|w| w.tracker == from) {
+            if let Some(waiter) = waiting.remove(&tx) {
+                let _ = waiter.reply.send(buf[..n].to_vec());
+            }
+        }
+    }
+}
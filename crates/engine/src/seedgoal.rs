@@ -0,0 +1,218 @@
+use crate::{persist, AppState};
+use crate::torrent::TorrentExt;
+use anyhow::Result;
+use bridge::{EngineEvent, PauseReason, ShareLimitAction};
+use librqbit::ManagedTorrent;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const TICK: Duration = Duration::from_secs(10);
+const FILE: &str = "ratio_limits.json";
+const REACHED_FILE: &str = "share_limits_reached.json";
+
+// Ratio targets more specific than the global `ratio_limit`. 0 means no
+// limit at that level, overriding the less specific ones.
+#[derive(Serialize, Deserialize, Default)]
+struct RatioLimits {
+    torrents: HashMap<String, f64>,
+    categories: HashMap<String, f64>,
+}
+
+// Per-torrent and per-category ratio targets, and the torrents whose share
+// limit action was taken, both saved to the state dir. The action is taken
+// only once per torrent, so one the user resumes afterwards keeps seeding;
+// one it paused is held again when the engine restarts.
+#[derive(Clone)]
+pub struct SeedGoals {
+    path: PathBuf,
+    reached_path: PathBuf,
+    limits: Arc<Mutex<RatioLimits>>,
+    reached: Arc<Mutex<HashSet<String>>>,
+}
+
+impl SeedGoals {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        let reached_path = state_dir.join(REACHED_FILE);
+        Self {
+            limits: Arc::new(Mutex::new(persist::load(&path))),
+            reached: Arc::new(Mutex::new(persist::load(&reached_path))),
+            path,
+            reached_path,
+        }
+    }
+
+    // None goes back to the category's or the global target
+    pub fn set_torrent_limit(&self, id: &str, ratio: Option<f64>) -> Result<()> {
+        let mut limits = self.limits.lock().unwrap();
+        match ratio {
+            Some(ratio) => limits.torrents.insert(id.to_string(), ratio),
+            None => limits.torrents.remove(id),
+        };
+        self.unmark_reached(id);
+        persist::save(&self.path, &*limits)
+    }
+
+    pub fn set_category_limit(&self, category: &str, ratio: Option<f64>) -> Result<()> {
+        let mut limits = self.limits.lock().unwrap();
+        match ratio {
+            Some(ratio) => limits.categories.insert(category.to_string(), ratio),
+            None => limits.categories.remove(category),
+        };
+        persist::save(&self.path, &*limits)
+    }
+
+    pub fn category_limits(&self) -> HashMap<String, f64> {
+        self.limits.lock().unwrap().categories.clone()
+    }
+
+    // The most specific target that applies: the torrent's own, then its
+    // category's, then `global`.
+    pub fn ratio_limit(&self, id: &str, category: Option<&str>, global: f64) -> Option<f64> {
+        let limits = self.limits.lock().unwrap();
+        let limit = limits
+            .torrents
+            .get(id)
+            .or_else(|| category.and_then(|c| limits.categories.get(c)))
+            .copied()
+            .unwrap_or(global);
+        (limit > 0.0).then_some(limit)
+    }
+
+    pub fn is_reached(&self, id: &str) -> bool {
+        self.reached.lock().unwrap().contains(id)
+    }
+
+    fn mark_reached(&self, id: &str) -> bool {
+        let mut reached = self.reached.lock().unwrap();
+        if !reached.insert(id.to_string()) {
+            return false;
+        }
+        self.save_reached(&reached);
+        true
+    }
+
+    fn unmark_reached(&self, id: &str) {
+        let mut reached = self.reached.lock().unwrap();
+        if reached.remove(id) {
+            self.save_reached(&reached);
+        }
+    }
+
+    fn save_reached(&self, reached: &HashSet<String>) {
+        if let Err(e) = persist::save(&self.reached_path, reached) {
+            warn!("Failed to save reached share limits: {}", e);
+        }
+    }
+
+    pub fn forget(&self, id: &str) {
+        self.unmark_reached(id);
+        let mut limits = self.limits.lock().unwrap();
+        if limits.torrents.remove(id).is_some() {
+            if let Err(e) = persist::save(&self.path, &*limits) {
+                warn!("Failed to save ratio limits: {}", e);
+            }
+        }
+    }
+
+    fn retain(&self, live: &[String]) {
+        let mut reached = self.reached.lock().unwrap();
+        let before = reached.len();
+        reached.retain(|id| live.contains(id));
+        if reached.len() != before {
+            self.save_reached(&reached);
+        }
+    }
+}
+
+// Uploaded since the torrent was added over what it seeds
+pub fn ratio(state: &AppState, id: &str, handle: &ManagedTorrent) -> f64 {
+    match copy_size(state, handle) {
+        0 => 0.0,
+        size => state.transfers.totals(id).1 as f64 / size as f64,
+    }
+}
+
+// The pieces we have, which with skipped files is less than the torrent.
+fn copy_size(state: &AppState, handle: &ManagedTorrent) -> u64 {
+    let info = handle.info();
+    let have = state.have_pieces(handle).iter().filter(|h| **h).count() as u64;
+    (have * info.piece_length).min(info.total_size)
+}
+
+// Applies `share_limit_action` to finished torrents once they reach their
+// ratio target.
+pub async fn run(state: AppState) {
+    loop {
+        tokio::time::sleep(TICK).await;
+        let (global_ratio, action) = {
+            let config = state.config.lock().unwrap();
+            (config.ratio_limit, config.share_limit_action)
+        };
+        let mut live = Vec::new();
+        for handle in state.torrents() {
+            let id = handle.info_hash().as_string();
+            live.push(id.clone());
+            state.transfers.record(&id, &handle);
+            if !handle.stats().finished || state.is_session_paused() {
+                continue;
+            }
+            if state.engine_paused.lock().unwrap().contains_key(&id) {
+                continue;
+            }
+            let category = state.tagging.labels(&id).category;
+            let ratio_limit = state.seed_goals.ratio_limit(&id, category.as_deref(), global_ratio);
+            let ratio = ratio(&state, &id, &handle);
+            if !ratio_limit.is_some_and(|limit| ratio >= limit) || !state.seed_goals.mark_reached(&id) {
+                continue;
+            }
+
+            info!("{} reached its share limit (ratio {:.2}): {:?}", id, ratio, action);
+            state.retention.goal_met(&id);
+            let name = handle.info().name;
+            match action {
+                ShareLimitAction::Pause => {
+                    if let Err(e) = state.session.pause(&handle).await {
+                        warn!("Failed to pause {}: {}", id, e);
+                        continue;
+                    }
+                    let reason = PauseReason::ShareLimit;
+                    state.engine_paused.lock().unwrap().insert(id.clone(), reason);
+                    state.events.emit(EngineEvent::TorrentPaused { id: id.clone(), reason });
+                }
+                ShareLimitAction::Remove | ShareLimitAction::RemoveWithFiles => {
+                    let delete_files = action == ShareLimitAction::RemoveWithFiles;
+                    if let Err(e) = state.remove(&handle, delete_files).await {
+                        warn!("Failed to remove {}: {}", id, e);
+                        continue;
+                    }
+                }
+            }
+            state.events.emit(EngineEvent::ShareLimitReached { id, name, action });
+        }
+        state.seed_goals.retain(&live);
+        state.transfers.retain(&live);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reached_limits_outlive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let goals = SeedGoals::load(dir.path());
+        assert!(goals.mark_reached("a"));
+        assert!(!goals.mark_reached("a"));
+        assert!(SeedGoals::load(dir.path()).is_reached("a"));
+
+        // A new target for the torrent starts it over
+        goals.set_torrent_limit("a", Some(2.0)).unwrap();
+        assert!(!SeedGoals::load(dir.path()).is_reached("a"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::AppState;
+use bridge::SpeedSummary;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+const TICK: Duration = Duration::from_secs(1);
+
+// Session-wide rates and torrent counts, worked out once a second from the
+// session's byte counters. Reading the latest one is a copy, so the title
+// bar and tray can poll it as often as they like without going through
+// every torrent's stats.
+#[derive(Clone)]
+pub struct SpeedMeter {
+    latest: Arc<watch::Sender<SpeedSummary>>,
+}
+
+impl SpeedMeter {
+    pub fn new() -> Self {
+        Self { latest: Arc::new(watch::channel(SpeedSummary::default()).0) }
+    }
+
+    pub fn latest(&self) -> SpeedSummary {
+        *self.latest.borrow()
+    }
+
+    // Sees every new summary, once a second while something changes
+    pub fn subscribe(&self) -> watch::Receiver<SpeedSummary> {
+        self.latest.subscribe()
+    }
+}
+
+// Also hands each tick's bytes to the data meter.
+pub async fn run(state: AppState) {
+    let meter = state.speed.clone();
+    let mut last = Instant::now();
+    let stats = state.session.stats_snapshot();
+    let (mut fetched, mut uploaded) = (stats.fetched_bytes, stats.uploaded_bytes);
+    loop {
+        tokio::time::sleep(TICK).await;
+        let elapsed = last.elapsed().as_secs_f64().max(0.001);
+        last = Instant::now();
+        let stats = state.session.stats_snapshot();
+        let downloaded = stats.fetched_bytes.saturating_sub(fetched);
+        let sent = stats.uploaded_bytes.saturating_sub(uploaded);
+        (fetched, uploaded) = (stats.fetched_bytes, stats.uploaded_bytes);
+        state.data_meter.add(downloaded, sent);
+        let rate = |bytes: u64| (bytes as f64 / elapsed) as u64;
+        let mut summary = SpeedSummary {
+            download_speed: rate(downloaded),
+            upload_speed: rate(sent),
+            ..Default::default()
+        };
+        for handle in state.torrents() {
+            if state.engine_paused.lock().unwrap().contains_key(&handle.info_hash().as_string()) {
+                summary.paused += 1;
+            } else if handle.stats().finished {
+                summary.seeding += 1;
+            } else {
+                summary.downloading += 1;
+            }
+        }
+        meter.latest.send_if_modified(|current| std::mem::replace(current, summary) != summary);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::AppState;
+use crate::torrent::{self, TorrentExt};
+use bridge::EngineEvent;
+use librqbit::TorrentStats;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+const TICK: Duration = Duration::from_secs(10);
+
+struct Watch {
+    progress: f64,
+    since: Instant,       // last time progress moved
+    reannounced: Instant, // last recovery attempt while stalled
+}
+
+// Flags downloads that have no seeds to get pieces from and have made no
+// progress for `stall_timeout_secs`. The session doesn't tell seeds from
+// other peers, so a torrent counts as seedless when it has no live peer
+// connection or its trackers' last scrape reported no seeders. A stalled
+// torrent keeps running; it is re-announced (trackers and DHT) once per
+// timeout in case seeds come back, and clears itself as soon as it gets a
+// new piece or a peer.
+pub async fn run(state: AppState) {
+    let mut watches: HashMap<String, Watch> = HashMap::new();
+    loop {
+        tokio::time::sleep(TICK).await;
+        let config = state.config.lock().unwrap().clone();
+        let timeout = Duration::from_secs(config.stall_timeout_secs);
+        let mut live = Vec::new();
+
+        for handle in state.torrents() {
+            let id = handle.info_hash().as_string();
+            let stats = handle.stats();
+            live.push(id.clone());
+
+            let paused = state.engine_paused.lock().unwrap().contains_key(&id);
+            let now = Instant::now();
+            let progress = torrent::progress(&stats);
+            let watch = watches.entry(id.clone()).or_insert(Watch { progress, since: now, reannounced: now });
+            let moving = progress > watch.progress;
+            watch.progress = progress;
+            let seeded = !seedless(&state, &id, &stats);
+            if config.stall_timeout_secs == 0 || stats.finished || paused || moving || seeded {
+                watch.since = now;
+                if state.stalled.lock().unwrap().remove(&id) {
+                    info!("{} is no longer stalled", id);
+                    state.events.emit(EngineEvent::TorrentUnstalled { id });
+                }
+                continue;
+            }
+            if watch.since.elapsed() < timeout {
+                continue;
+            }
+
+            if state.stalled.lock().unwrap().insert(id.clone()) {
+                info!("{} stalled: no seeds and no progress for {:?}", id, timeout);
+                state.events.emit(EngineEvent::TorrentStalled { id: id.clone(), name: handle.info().name });
+                watch.reannounced = now;
+                if config.stall_reannounce && !state.is_session_paused() {
+                    state.reannounce(&handle).await;
+                }
+            } else if config.stall_reannounce && !state.is_session_paused() && watch.reannounced.elapsed() >= timeout {
+                watch.reannounced = now;
+                state.reannounce(&handle).await;
+            }
+        }
+
+        watches.retain(|id, _| live.contains(id));
+        state.stalled.lock().unwrap().retain(|id| live.contains(id));
+    }
+}
+
+fn seedless(state: &AppState, id: &str, stats: &TorrentStats) -> bool {
+    let peers = stats.live.as_ref().map_or(0, |live| live.snapshot.peer_stats.live);
+    let scrapes = state.scrapes.lock().unwrap();
+    let seeders = scrapes.get(id).and_then(|s| s.iter().filter_map(|s| s.seeders).max());
+    peers == 0 || seeders == Some(0)
+}
@@ -1,10 +1,162 @@
-// Storage scaffolding
-pub struct Storage {
-    // Sled db or file path
+use crate::config::Config;
+use crate::files;
+use crate::roots::ContentRoots;
+use crate::torrent::TorrentInfo;
+use anyhow::Context;
+use librqbit::storage::{BoxStorageFactory, StorageFactory, StorageFactoryExt, TorrentStorage};
+use librqbit::{ManagedTorrentShared, TorrentMetadata};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// The session's storage on disk, for every torrent it adds or restores.
+// Files are where files::disk_paths puts them under the torrent's content
+// root (see ContentRoots::resolve), so on Windows names like "aux.txt" are
+// escaped and long paths prefixed the same way for the session's writes as
+// for everything else that opens them. A torrent with names that would
+// leave its folder (see paths::check) gets no storage, so it can't be
+// added. Errors carry the file's path: the session stops the torrent with
+// it rather than with a bare OS error for some piece.
+#[derive(Clone)]
+pub struct DiskStorageFactory {
+    config: Arc<Mutex<Config>>,
+    roots: ContentRoots,
+}
+
+impl DiskStorageFactory {
+    pub fn new(config: Arc<Mutex<Config>>, roots: ContentRoots) -> Self {
+        Self { config, roots }
+    }
+}
+
+impl StorageFactory for DiskStorageFactory {
+    type Storage = DiskFiles;
+
+    fn create(&self, shared: &ManagedTorrentShared, metadata: &TorrentMetadata) -> anyhow::Result<DiskFiles> {
+        let info = TorrentInfo::new(metadata);
+        let id = shared.info_hash.as_string();
+        let download_path = self.config.lock().unwrap().download_path.clone();
+        let root = self.roots.resolve(&id, &download_path, &info);
+        // Only a folder the engine named after the torrent is its own to
+        // remove; one it was added with belongs to the user
+        let own_root = info.files().len() > 1 && self.roots.get(&id).is_none();
+        let paths = files::disk_paths(&root, &info)?;
+        let mut files = DiskFiles::open(root, paths);
+        files.own_root = own_root;
+        Ok(files)
+    }
+
+    fn clone_box(&self) -> BoxStorageFactory {
+        self.clone().boxed()
+    }
+}
+
+// Files are opened on first write, so ones that are skipped are never
+// created, and reads of ones that aren't there fail instead of creating
+// them (the session's initial check counts those pieces as missing).
+pub struct DiskFiles {
+    root: PathBuf,
+    own_root: bool,
+    files: Vec<(PathBuf, Mutex<Option<File>>)>,
+}
+
+impl DiskFiles {
+    // The files at `paths`, under `root`; also how the engine reads them
+    // for its own checks (see verify::PieceLayout)
+    pub fn open(root: PathBuf, paths: Vec<PathBuf>) -> Self {
+        Self { root, own_root: false, files: paths.into_iter().map(|p| (p, Mutex::new(None))).collect() }
+    }
+
+    fn with_file<T>(
+        &self,
+        file_id: usize,
+        what: &str,
+        create: bool,
+        f: impl FnOnce(&mut File) -> Result<T>,
+    ) -> anyhow::Result<T> {
+        let (path, file) = self.files.get(file_id).with_context(|| format!("no file {}", file_id))?;
+        let mut file = file.lock().unwrap();
+        let result = match &mut *file {
+            Some(file) => f(file),
+            None => open(path, create).and_then(|opened| f(file.insert(opened))),
+        };
+        result.with_context(|| format!("{} {}", what, path.display()))
+    }
+}
+
+fn open(path: &Path, create: bool) -> Result<File> {
+    if create {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+    OpenOptions::new().read(true).write(true).create(create).truncate(false).open(path)
 }
 
-impl Storage {
-    pub fn new() -> Self {
-        Self {}
+impl TorrentStorage for DiskFiles {
+    fn init(&mut self, _shared: &ManagedTorrentShared, _metadata: &TorrentMetadata) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn pread_exact(&self, file_id: usize, offset: u64, buf: &mut [u8]) -> anyhow::Result<()> {
+        self.with_file(file_id, "reading", false, |file| {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(buf)
+        })
+    }
+
+    fn pwrite_all(&self, file_id: usize, offset: u64, buf: &[u8]) -> anyhow::Result<()> {
+        self.with_file(file_id, "writing", true, |file| {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(buf)
+        })
+    }
+
+    fn remove_file(&self, file_id: usize, _filename: &Path) -> anyhow::Result<()> {
+        let Some((path, file)) = self.files.get(file_id) else { return Ok(()) };
+        file.lock().unwrap().take();
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e).with_context(|| format!("removing {}", path.display())),
+            _ => Ok(()),
+        }
+    }
+
+    // `path` is one of the folders the torrent's files are in, relative to
+    // the content root, or "" for the root itself. Folders that still hold
+    // anything are left alone.
+    fn remove_directory_if_empty(&self, path: &Path) -> anyhow::Result<()> {
+        let dir = if path.as_os_str().is_empty() {
+            if !self.own_root {
+                return Ok(());
+            }
+            self.root.clone()
+        } else {
+            let name = path.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/");
+            files::on_disk(&self.root, &name)
+        };
+        match std::fs::remove_dir(&dir) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound || dir.read_dir().is_ok_and(|mut d| d.next().is_some()) => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("removing {}", dir.display())),
+        }
+    }
+
+    fn ensure_file_length(&self, file_id: usize, length: u64) -> anyhow::Result<()> {
+        self.with_file(file_id, "sizing", true, |file| {
+            if file.metadata()?.len() != length {
+                file.set_len(length)?;
+            }
+            Ok(())
+        })
+    }
+
+    // Handles are reopened as needed by the new owner
+    fn take(&self) -> anyhow::Result<Box<dyn TorrentStorage>> {
+        Ok(Box::new(DiskFiles {
+            root: self.root.clone(),
+            own_root: self.own_root,
+            files: self.files.iter().map(|(p, _)| (p.clone(), Mutex::new(None))).collect(),
+        }))
     }
 }
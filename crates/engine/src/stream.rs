@@ -0,0 +1,199 @@
+use crate::error::EngineError;
+use crate::torrent::{self, TorrentExt, TorrentInfo};
+use bridge::{ByteRange, StreamStatus};
+use librqbit::ManagedTorrent;
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+const CHUNK: usize = 64 * 1024;
+// How far ahead of each open stream the session fetches pieces first
+const SESSION_WINDOW: u64 = 32 * 1024 * 1024;
+const IDLE: Duration = Duration::from_secs(60);
+
+// Where the player is in each file being played, keyed on torrent id and
+// file index. The session fetches the pieces under and after every open
+// stream before anything else; this keeps the position so the status can
+// show that window.
+#[derive(Clone, Default)]
+pub struct Prefetcher {
+    windows: Arc<Mutex<HashMap<(String, usize), Window>>>,
+}
+
+struct Window {
+    position: u64, // file offset the player last reached
+    at: Instant,
+}
+
+impl Prefetcher {
+    // Records that the player reached `offset` in the file
+    fn advance(&self, id: &str, file: &FileSpan, offset: u64) {
+        let mut windows = self.windows.lock().unwrap();
+        windows.retain(|_, w| w.at.elapsed() < IDLE);
+        windows.insert((id.to_string(), file.index), Window { position: offset, at: Instant::now() });
+    }
+}
+
+// What's buffered of a file and how long until `offset` can be played.
+// Playing `offset` needs everything from the player's current position (or
+// just the piece under `offset` if nothing is playing) up to it, so the
+// estimate is the missing bytes in that span over the torrent's rate.
+pub fn status(
+    prefetcher: &Prefetcher,
+    handle: &ManagedTorrent,
+    have: &[bool],
+    id: &str,
+    index: usize,
+    offset: Option<u64>,
+) -> Result<StreamStatus, EngineError> {
+    let file = FileSpan::new(&handle.info(), index)
+        .ok_or_else(|| EngineError::NotFound(format!("File {} of torrent {}", index, id)))?;
+    let has = |piece: u32| have.get(piece as usize).copied().unwrap_or(false);
+    let download_speed = torrent::download_speed(&handle.stats());
+
+    let mut buffered: Vec<ByteRange> = Vec::new();
+    if file.len > 0 {
+        for piece in file.piece(file.start)..=file.piece(file.start + file.len - 1) {
+            if !has(piece) {
+                continue;
+            }
+            let range = file.piece_range(piece);
+            match buffered.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => buffered.push(range),
+            }
+        }
+    }
+
+    let position = {
+        let windows = prefetcher.windows.lock().unwrap();
+        windows.get(&(id.to_string(), index)).filter(|w| w.at.elapsed() < IDLE).map(|w| w.position)
+    };
+    let prefetch = position.filter(|&p| p < file.len).map(|start| ByteRange {
+        start,
+        end: start.saturating_add(SESSION_WINDOW).min(file.len),
+    });
+
+    let eta_secs = offset.filter(|&o| o < file.len).and_then(|offset| {
+        let from = position.map_or(offset, |position| position.min(offset));
+        let missing: u64 = (file.piece(file.start + from)..=file.piece(file.start + offset))
+            .filter(|&p| !has(p))
+            .map(|p| {
+                let r = file.piece_range(p);
+                r.end - r.start
+            })
+            .sum();
+        match (missing, download_speed) {
+            (0, _) => Some(0.0),
+            (_, 0) => None,
+            (missing, speed) => Some(missing as f64 / speed as f64),
+        }
+    });
+
+    Ok(StreamStatus { file_size: file.len, buffered, prefetch, download_speed, offset, eta_secs })
+}
+
+// Where a file sits in the torrent's byte stream.
+struct FileSpan {
+    index: usize,
+    start: u64,
+    len: u64,
+    piece_length: u64,
+}
+
+impl FileSpan {
+    fn new(info: &TorrentInfo, index: usize) -> Option<Self> {
+        let files = info.files();
+        let len = files.get(index)?.len;
+        let start = files[..index].iter().map(|f| f.len).sum();
+        Some(Self { index, start, len, piece_length: info.piece_length.max(1) })
+    }
+
+    fn piece(&self, offset: u64) -> u32 {
+        (offset / self.piece_length) as u32
+    }
+
+    // The part of a piece that falls inside the file, in file offsets
+    fn piece_range(&self, piece: u32) -> ByteRange {
+        let start = piece as u64 * self.piece_length;
+        let end = start + self.piece_length;
+        ByteRange {
+            start: start.max(self.start) - self.start,
+            end: end.min(self.start + self.len) - self.start,
+        }
+    }
+}
+
+// A player's read of one file from some offset, chunk by chunk. Reads wait
+// for pieces that haven't arrived yet.
+pub struct FileReader {
+    stream: Pin<Box<dyn ReadSeek>>,
+    prefetcher: Prefetcher,
+    id: String,
+    file: FileSpan,
+    position: u64,
+    end: u64,
+}
+
+// The session's file stream, whose type it doesn't export
+trait ReadSeek: AsyncRead + AsyncSeek + Send {}
+impl<T: AsyncRead + AsyncSeek + Send> ReadSeek for T {}
+
+impl FileReader {
+    // `last` is inclusive, as in an HTTP Range header, and open-ended if None.
+    pub async fn open(
+        prefetcher: Prefetcher,
+        handle: Arc<ManagedTorrent>,
+        id: &str,
+        index: usize,
+        first: u64,
+        last: Option<u64>,
+    ) -> Result<Self, EngineError> {
+        let file = FileSpan::new(&handle.info(), index)
+            .ok_or_else(|| EngineError::NotFound(format!("File {} of torrent {}", index, id)))?;
+        if first >= file.len || last.is_some_and(|last| last < first) {
+            return Err(EngineError::InvalidInput(format!("Range {}-{:?} outside file of {} bytes", first, last, file.len)));
+        }
+        let range = first..last.map_or(file.len, |last| last.saturating_add(1).min(file.len));
+        let mut stream = Box::pin(handle.stream(index)?);
+        stream.seek(SeekFrom::Start(range.start)).await?;
+        let reader = Self {
+            stream,
+            prefetcher,
+            id: id.to_string(),
+            file,
+            position: range.start,
+            end: range.end,
+        };
+        reader.prefetcher.advance(&reader.id, &reader.file, reader.position);
+        Ok(reader)
+    }
+
+    pub fn file_len(&self) -> u64 {
+        self.file.len
+    }
+
+    pub fn range(&self) -> Range<u64> {
+        self.position..self.end
+    }
+
+    pub async fn next_chunk(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let want = (self.end - self.position).min(CHUNK as u64) as usize;
+        if want == 0 {
+            return Ok(None);
+        }
+        let mut buf = vec![0; want];
+        let n = self.stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.truncate(n);
+        self.position += n as u64;
+        self.prefetcher.advance(&self.id, &self.file, self.position);
+        Ok(Some(buf))
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{persist, AppState};
+use bridge::{TaskHealth, TaskState};
+use std::any::Any;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+// Running this long after a restart counts as recovered; the backoff starts over
+const STABLE: Duration = Duration::from_secs(600);
+
+// Health of the engine's background loops, in the order they were started
+#[derive(Clone, Default)]
+pub struct Tasks {
+    health: Arc<Mutex<Vec<TaskHealth>>>,
+}
+
+impl Tasks {
+    pub fn list(&self) -> Vec<TaskHealth> {
+        self.health.lock().unwrap().clone()
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut TaskHealth)) {
+        if let Some(health) = self.health.lock().unwrap().iter_mut().find(|h| h.name == name) {
+            f(health);
+        }
+    }
+}
+
+// Runs a background loop so a panic in it is logged and shows in the task
+// health instead of the loop just going quiet. A critical loop is started
+// again after a pause that doubles with each panic in a row, the rest stay
+// down until the next start. A loop that panicked holding one of the
+// shared locks leaves it poisoned and will most likely keep failing; the
+// backoff keeps that from spinning.
+pub fn spawn<F, Fut>(state: &AppState, name: &'static str, critical: bool, task: F)
+where
+    F: Fn(AppState) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let tasks = state.tasks.clone();
+    tasks.health.lock().unwrap().push(TaskHealth {
+        name: name.to_string(),
+        critical,
+        state: TaskState::Running,
+        restarts: 0,
+        last_panic: None,
+        last_panic_at: None,
+    });
+    let state = state.clone();
+    tokio::spawn(async move {
+        let mut backoff = FIRST_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let panic = match tokio::spawn(task(state.clone())).await {
+                Ok(()) => {
+                    tasks.update(name, |h| h.state = TaskState::Stopped);
+                    return;
+                }
+                Err(e) if e.is_panic() => panic_message(e.into_panic()),
+                Err(_) => return, // runtime shutting down
+            };
+            error!("Background task {} panicked: {}", name, panic);
+            tasks.update(name, |h| {
+                h.state = if critical { TaskState::Restarting } else { TaskState::Failed };
+                h.last_panic = Some(panic);
+                h.last_panic_at = Some(persist::now());
+            });
+            if !critical {
+                return;
+            }
+            if started.elapsed() >= STABLE {
+                backoff = FIRST_BACKOFF;
+            }
+            warn!("Restarting {} in {}s", name, backoff.as_secs());
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            tasks.update(name, |h| {
+                h.state = TaskState::Running;
+                h.restarts += 1;
+            });
+        }
+    });
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("unknown panic", |s| s).to_string(),
+    }
+}
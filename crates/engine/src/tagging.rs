@@ -0,0 +1,373 @@
+use crate::persist;
+use anyhow::{anyhow, Result};
+use bridge::{TagInfo, TagRule};
+use regex::RegexBuilder;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+const RULES_FILE: &str = "tag_rules.json";
+const TAGS_FILE: &str = "torrent_tags.json";
+const DEFS_FILE: &str = "tags.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Labels {
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+}
+
+// What a rule is matched against
+pub struct Candidate<'a> {
+    pub name: &'a str,
+    pub total_size: u64,
+    pub files: Vec<&'a str>,
+    pub trackers: &'a [String],
+}
+
+// Tagging rules, the labels each torrent ended up with, and the tags' colors
+// and nesting, all saved to the state dir.
+#[derive(Clone)]
+pub struct Tagging {
+    rules_path: PathBuf,
+    labels_path: PathBuf,
+    defs_path: PathBuf,
+    rules: Arc<Mutex<Vec<TagRule>>>,
+    labels: Arc<Mutex<HashMap<String, Labels>>>, // torrent id -> labels
+    defs: Arc<Mutex<Vec<TagInfo>>>,              // colors and nesting; other tags are only names on torrents
+}
+
+impl Tagging {
+    pub fn load(state_dir: &Path) -> Self {
+        let rules_path = state_dir.join(RULES_FILE);
+        let labels_path = state_dir.join(TAGS_FILE);
+        let defs_path = state_dir.join(DEFS_FILE);
+        let labels: HashMap<String, Labels> = persist::load(&labels_path);
+        let defs = if defs_path.exists() { persist::load(&defs_path) } else { migrate(&labels, &defs_path) };
+        Self {
+            rules: Arc::new(Mutex::new(persist::load(&rules_path))),
+            labels: Arc::new(Mutex::new(labels)),
+            defs: Arc::new(Mutex::new(defs)),
+            rules_path,
+            labels_path,
+            defs_path,
+        }
+    }
+
+    // Every tag, defined or only on torrents; the latter as top-level tags
+    // without a color. Sorted by name.
+    pub fn tags(&self) -> Vec<TagInfo> {
+        let mut tags = self.defs.lock().unwrap().clone();
+        for tag in self.labels.lock().unwrap().values().flat_map(|l| &l.tags) {
+            if !tags.iter().any(|t| &t.name == tag) {
+                tags.push(TagInfo { name: tag.clone(), color: None, parent: None });
+            }
+        }
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        tags
+    }
+
+    // Adds the tag's color and parent or replaces them. The parent has to
+    // be a tag already and can't be the tag itself or below it.
+    pub fn set_tag(&self, mut tag: TagInfo) -> Result<()> {
+        tag.name = tag.name.trim().to_string();
+        if tag.name.is_empty() {
+            return Err(anyhow!("tag name is empty"));
+        }
+        if let Some(color) = &tag.color {
+            let hex = color.strip_prefix('#').unwrap_or_default();
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(anyhow!("color {:?} is not #rrggbb", color));
+            }
+        }
+        let known = self.tags();
+        let mut parent = tag.parent.clone();
+        while let Some(name) = parent {
+            if name == tag.name {
+                return Err(anyhow!("{} can't be nested under itself", tag.name));
+            }
+            let Some(above) = known.iter().find(|t| t.name == name) else {
+                return Err(anyhow!("parent tag {} doesn't exist", name));
+            };
+            parent = above.parent.clone();
+        }
+        let mut defs = self.defs.lock().unwrap();
+        match defs.iter_mut().find(|t| t.name == tag.name) {
+            Some(existing) => *existing = tag,
+            None => defs.push(tag),
+        }
+        persist::save(&self.defs_path, &*defs)
+    }
+
+    // Deletes the tag and takes it off every torrent; tags nested under it
+    // move up to its parent. False if there is no such tag.
+    pub fn remove_tag(&self, name: &str) -> Result<bool> {
+        let mut defs = self.defs.lock().unwrap();
+        let removed = defs.iter().position(|t| t.name == name).map(|i| defs.remove(i));
+        let mut labels = self.labels.lock().unwrap();
+        let mut untagged = false;
+        for l in labels.values_mut() {
+            let before = l.tags.len();
+            l.tags.retain(|t| t != name);
+            untagged |= l.tags.len() != before;
+        }
+        if removed.is_none() && !untagged {
+            return Ok(false);
+        }
+        let parent = removed.and_then(|t| t.parent);
+        for child in defs.iter_mut().filter(|t| t.parent.as_deref() == Some(name)) {
+            child.parent = parent.clone();
+        }
+        labels.retain(|_, l| *l != Labels::default());
+        persist::save(&self.labels_path, &*labels)?;
+        persist::save(&self.defs_path, &*defs)?;
+        Ok(true)
+    }
+
+    pub fn rules(&self) -> Vec<TagRule> {
+        self.rules.lock().unwrap().clone()
+    }
+
+    // Stores a new rule and returns its id; the id on `rule` is ignored.
+    pub fn add_rule(&self, mut rule: TagRule) -> Result<u64> {
+        validate(&rule)?;
+        let mut rules = self.rules.lock().unwrap();
+        rule.id = rules.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+        let id = rule.id;
+        rules.push(rule);
+        persist::save(&self.rules_path, &*rules)?;
+        Ok(id)
+    }
+
+    // Replaces the rule with the same id. False if there is none.
+    pub fn update_rule(&self, rule: TagRule) -> Result<bool> {
+        validate(&rule)?;
+        let mut rules = self.rules.lock().unwrap();
+        let Some(slot) = rules.iter_mut().find(|r| r.id == rule.id) else {
+            return Ok(false);
+        };
+        *slot = rule;
+        persist::save(&self.rules_path, &*rules)?;
+        Ok(true)
+    }
+
+    pub fn remove_rule(&self, id: u64) -> Result<bool> {
+        let mut rules = self.rules.lock().unwrap();
+        let before = rules.len();
+        rules.retain(|r| r.id != id);
+        if rules.len() == before {
+            return Ok(false);
+        }
+        persist::save(&self.rules_path, &*rules)?;
+        Ok(true)
+    }
+
+    pub fn labels(&self, id: &str) -> Labels {
+        self.labels.lock().unwrap().get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn set_labels(&self, id: &str, labels: Labels) -> Result<()> {
+        let mut all = self.labels.lock().unwrap();
+        if labels == Labels::default() {
+            all.remove(id);
+        } else {
+            all.insert(id.to_string(), labels);
+        }
+        persist::save(&self.labels_path, &*all)
+    }
+
+    pub fn forget(&self, id: &str) {
+        let mut all = self.labels.lock().unwrap();
+        if all.remove(id).is_some() {
+            if let Err(e) = persist::save(&self.labels_path, &*all) {
+                warn!("Failed to save torrent tags: {}", e);
+            }
+        }
+    }
+
+    // Labels from every enabled rule the torrent matches: the union of
+    // their tags, and the category of the first one that sets one.
+    pub fn evaluate(&self, torrent: &Candidate) -> Labels {
+        let mut labels = Labels::default();
+        for rule in self.rules.lock().unwrap().iter().filter(|r| r.enabled && matches(r, torrent)) {
+            for tag in &rule.tags {
+                if !labels.tags.contains(tag) {
+                    labels.tags.push(tag.clone());
+                }
+            }
+            if labels.category.is_none() {
+                labels.category = rule.category.clone();
+            }
+        }
+        labels
+    }
+
+    // When a new torrent should start, as minutes past midnight UTC: the
+    // start time of the first enabled rule it matches that sets one.
+    pub fn start_time(&self, torrent: &Candidate) -> Option<u32> {
+        self.rules
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.enabled && matches(r, torrent))
+            .find_map(|r| r.start_time.as_deref().and_then(parse_time))
+    }
+}
+
+// Before tags had definitions they were just names on torrents, with "/"
+// as the usual way to nest them. The first start with definitions turns
+// each "a/b" into tag "a/b" under "a", adding "a" if no torrent has it.
+fn migrate(labels: &HashMap<String, Labels>, path: &Path) -> Vec<TagInfo> {
+    let mut defs: Vec<TagInfo> = Vec::new();
+    for tag in labels.values().flat_map(|l| &l.tags) {
+        let mut name = tag.as_str();
+        loop {
+            let parent = name.rsplit_once('/').map(|(p, _)| p).filter(|p| !p.is_empty());
+            if !defs.iter().any(|t| t.name == name) {
+                defs.push(TagInfo { name: name.to_string(), color: None, parent: parent.map(String::from) });
+            }
+            let Some(parent) = parent else { break };
+            name = parent;
+        }
+    }
+    if defs.is_empty() {
+        return defs;
+    }
+    defs.sort_by(|a, b| a.name.cmp(&b.name));
+    info!("Migrated {} tags to tag definitions", defs.len());
+    if let Err(e) = persist::save(path, &defs) {
+        warn!("Failed to save tag definitions: {}", e);
+    }
+    defs
+}
+
+fn validate(rule: &TagRule) -> Result<()> {
+    if let Some(pattern) = &rule.name_regex {
+        RegexBuilder::new(pattern).case_insensitive(true).build()?;
+    }
+    if let Some(time) = &rule.start_time {
+        parse_time(time).ok_or_else(|| anyhow!("start time {:?} is not HH:MM", time))?;
+    }
+    if rule.tags.is_empty() && rule.category.is_none() && rule.start_time.is_none() {
+        return Err(anyhow!("rule assigns no tags, category or start time"));
+    }
+    Ok(())
+}
+
+// "HH:MM" -> minutes past midnight
+fn parse_time(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+// Every condition the rule sets has to hold.
+fn matches(rule: &TagRule, torrent: &Candidate) -> bool {
+    if let Some(domain) = &rule.tracker_domain {
+        let domain = domain.to_ascii_lowercase();
+        let on_tracker = torrent.trackers.iter().any(|url| {
+            Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_ascii_lowercase)).is_some_and(|host| {
+                host == domain || host.ends_with(&format!(".{}", domain))
+            })
+        });
+        if !on_tracker {
+            return false;
+        }
+    }
+    if let Some(pattern) = &rule.name_regex {
+        let Ok(regex) = RegexBuilder::new(pattern).case_insensitive(true).build() else {
+            return false;
+        };
+        if !regex.is_match(torrent.name) {
+            return false;
+        }
+    }
+    if !rule.extensions.is_empty() {
+        let has_extension = torrent.files.iter().any(|name| {
+            let ext = name.rsplit_once('.').map(|(_, e)| e).unwrap_or("");
+            rule.extensions.iter().any(|want| want.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        });
+        if !has_extension {
+            return false;
+        }
+    }
+    rule.min_size.is_none_or(|min| torrent.total_size >= min) && rule.max_size.is_none_or(|max| torrent.total_size <= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str) -> TagRule {
+        TagRule {
+            id: 0,
+            name: name.to_string(),
+            enabled: true,
+            tracker_domain: None,
+            name_regex: None,
+            extensions: Vec::new(),
+            min_size: None,
+            max_size: None,
+            tags: vec![name.to_string()],
+            category: None,
+            start_time: None,
+        }
+    }
+
+    fn tagging(rules: Vec<TagRule>) -> (tempfile::TempDir, Tagging) {
+        let dir = tempfile::tempdir().unwrap();
+        let tagging = Tagging::load(dir.path());
+        for rule in rules {
+            tagging.add_rule(rule).unwrap();
+        }
+        (dir, tagging)
+    }
+
+    #[test]
+    fn rules_match_on_every_condition_they_set() {
+        let trackers = ["https://announce.Tracker.example/a/announce".to_string()];
+        let album = Candidate {
+            name: "Some Band - Live (2001) [FLAC]",
+            total_size: 400 << 20,
+            files: vec!["01 - Intro.FLAC", "cover.jpg"],
+            trackers: &trackers,
+        };
+        let (_dir, tagging) = tagging(vec![
+            TagRule { tracker_domain: Some("tracker.example".into()), ..rule("tracker") },
+            TagRule { tracker_domain: Some("other.example".into()), ..rule("other tracker") },
+            TagRule { name_regex: Some(r"\[flac\]".into()), ..rule("regex") },
+            TagRule { extensions: vec![".flac".into()], ..rule("extension") },
+            TagRule { extensions: vec!["mkv".into()], ..rule("video") },
+            TagRule { min_size: Some(100 << 20), max_size: Some(1 << 30), ..rule("size") },
+            TagRule { min_size: Some(1 << 30), ..rule("too small") },
+            TagRule { tracker_domain: Some("tracker.example".into()), extensions: vec!["mkv".into()], ..rule("both") },
+            TagRule { enabled: false, ..rule("disabled") },
+        ]);
+        assert_eq!(tagging.evaluate(&album).tags, ["tracker", "regex", "extension", "size"]);
+    }
+
+    #[test]
+    fn first_category_and_start_time_win() {
+        let candidate = Candidate { name: "x", total_size: 1, files: vec!["x"], trackers: &[] };
+        let (_dir, tagging) = tagging(vec![
+            TagRule { enabled: false, category: Some("off".into()), start_time: Some("01:00".into()), ..rule("a") },
+            TagRule { category: Some("music".into()), start_time: Some("02:30".into()), ..rule("b") },
+            TagRule { category: Some("other".into()), start_time: Some("03:00".into()), ..rule("b") },
+        ]);
+        let labels = tagging.evaluate(&candidate);
+        assert_eq!(labels.tags, ["b"]);
+        assert_eq!(labels.category.as_deref(), Some("music"));
+        assert_eq!(tagging.start_time(&candidate), Some(150));
+    }
+
+    #[test]
+    fn refuses_rules_that_cant_apply() {
+        let (_dir, tagging) = tagging(Vec::new());
+        assert!(tagging.add_rule(TagRule { name_regex: Some("(".into()), ..rule("a") }).is_err());
+        assert!(tagging.add_rule(TagRule { start_time: Some("24:00".into()), ..rule("a") }).is_err());
+        assert!(tagging.add_rule(TagRule { tags: Vec::new(), ..rule("a") }).is_err());
+        assert!(tagging.rules().is_empty());
+    }
+}
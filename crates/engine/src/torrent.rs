@@ -0,0 +1,141 @@
+use librqbit::{ByteBufOwned, ManagedTorrent, TorrentMetaV1Info, TorrentMetadata, TorrentStats};
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+pub struct FileEntry {
+    pub name: String, // path within the torrent, "/"-separated
+    pub len: u64,
+}
+
+// The parts of a torrent's metainfo the engine works with, copied out of
+// the session's parsed metadata.
+pub struct TorrentInfo {
+    pub name: String,
+    pub total_size: u64,
+    pub piece_length: u64,
+    files: Vec<FileEntry>,
+    pieces: Vec<u8>, // concatenated SHA-1 piece hashes
+}
+
+impl TorrentInfo {
+    pub fn new(metadata: &TorrentMetadata) -> Self {
+        let files = metadata
+            .file_infos
+            .iter()
+            .map(|f| FileEntry {
+                name: f.relative_filename.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/"),
+                len: f.len,
+            })
+            .collect();
+        Self {
+            name: metadata.name.clone().unwrap_or_default(),
+            total_size: metadata.lengths.total_length(),
+            piece_length: metadata.info.piece_length as u64,
+            files,
+            pieces: metadata.info.pieces.as_ref().to_vec(),
+        }
+    }
+
+    // From metainfo the session has parsed without adding (list_only)
+    pub fn from_info(info: &TorrentMetaV1Info<ByteBufOwned>) -> anyhow::Result<Self> {
+        let files = info
+            .iter_file_details()?
+            .map(|f| Ok(FileEntry { name: f.filename.to_vec()?.join("/"), len: f.len }))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            name: info.name.as_ref().map(|n| String::from_utf8_lossy(n.as_ref()).into_owned()).unwrap_or_default(),
+            total_size: files.iter().map(|f| f.len).sum(),
+            piece_length: info.piece_length as u64,
+            files,
+            pieces: info.pieces.as_ref().to_vec(),
+        })
+    }
+
+    pub fn files(&self) -> &[FileEntry] {
+        &self.files
+    }
+
+    pub fn num_pieces(&self) -> usize {
+        self.pieces.len() / 20
+    }
+
+    pub fn piece_hash(&self, index: u32) -> Option<[u8; 20]> {
+        let start = index as usize * 20;
+        self.pieces.get(start..start + 20)?.try_into().ok()
+    }
+}
+
+pub trait TorrentExt {
+    fn info(&self) -> TorrentInfo;
+}
+
+impl TorrentExt for ManagedTorrent {
+    // Torrents are only in the session once their metadata is known; until
+    // a handle has it, it looks like an empty torrent.
+    fn info(&self) -> TorrentInfo {
+        match self.metadata.load().as_deref() {
+            Some(metadata) => TorrentInfo::new(metadata),
+            None => TorrentInfo {
+                name: self.name().unwrap_or_default(),
+                total_size: 0,
+                piece_length: 0,
+                files: vec![],
+                pieces: vec![],
+            },
+        }
+    }
+}
+
+// 0.0 to 1.0 of the selected files
+pub fn progress(stats: &TorrentStats) -> f64 {
+    if stats.total_bytes == 0 {
+        return if stats.finished { 1.0 } else { 0.0 };
+    }
+    stats.progress_bytes as f64 / stats.total_bytes as f64
+}
+
+// Bytes per second, 0 unless the torrent is running
+pub fn download_speed(stats: &TorrentStats) -> u64 {
+    stats.live.as_ref().map_or(0, |live| (live.download_speed.mbps * MIB) as u64)
+}
+
+pub fn upload_speed(stats: &TorrentStats) -> u64 {
+    stats.live.as_ref().map_or(0, |live| (live.upload_speed.mbps * MIB) as u64)
+}
+
+// The session only hands out its have-bitfield as the Debug output of a
+// bit slice ("BitSlice<u8, Msb0> { .. } [1, 0, ...]"), padded to a whole
+// number of bytes.
+pub fn parse_haves(dump: &str, num_pieces: usize) -> Vec<bool> {
+    let mut have = vec![false; num_pieces];
+    let Some(list) = dump.rfind('[').map(|start| dump[start + 1..].trim_end_matches(']')) else {
+        return have;
+    };
+    for (slot, bit) in have.iter_mut().zip(list.split(',')) {
+        *slot = bit.trim() == "1";
+    }
+    have
+}
+
+// Groups the pieces into `cells` runs of about equal length for a bar
+// narrower than the piece count. A run is had if all its pieces are.
+pub fn cells(have: &[bool], cells: usize) -> Vec<bool> {
+    let len = have.len();
+    if cells == 0 || cells >= len {
+        return have.to_vec();
+    }
+    (0..cells).map(|cell| have[cell * len / cells..(cell + 1) * len / cells].iter().all(|h| *h)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_have_dump() {
+        let dump = "BitSlice<u8, bitvec::order::Msb0> { addr: 0x1, head: 000, bits: 8 } [1, 0, 1, 1, 0, 0, 0, 0]";
+        assert_eq!(parse_haves(dump, 4), vec![true, false, true, true]);
+        assert_eq!(parse_haves("", 2), vec![false, false]);
+        assert_eq!(parse_haves("[]", 0), Vec::<bool>::new());
+    }
+}
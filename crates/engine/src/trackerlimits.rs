@@ -0,0 +1,147 @@
+use crate::{credentials, persist, AppState};
+use anyhow::{anyhow, Result};
+use bridge::{EngineEvent, PauseReason, TrackerLimit};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const TICK: Duration = Duration::from_secs(10);
+const FILE: &str = "tracker_limits.json";
+
+// Per-tracker-domain settings, applied to every torrent announcing to that
+// domain or a subdomain of it. Saved to the state dir.
+#[derive(Clone)]
+pub struct TrackerLimits {
+    path: PathBuf,
+    limits: Arc<Mutex<Vec<TrackerLimit>>>,
+    announced: Arc<Mutex<HashMap<String, Instant>>>, // domain -> our last re-announce there
+}
+
+impl TrackerLimits {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self {
+            limits: Arc::new(Mutex::new(persist::load(&path))),
+            path,
+            announced: Arc::default(),
+        }
+    }
+
+    pub fn all(&self) -> Vec<TrackerLimit> {
+        self.limits.lock().unwrap().clone()
+    }
+
+    // Adds the domain's settings or replaces them.
+    pub fn set(&self, mut limit: TrackerLimit) -> Result<()> {
+        limit.domain = limit.domain.trim().trim_start_matches('.').to_lowercase();
+        if limit.domain.is_empty() {
+            return Err(anyhow!("tracker domain is empty"));
+        }
+        let mut limits = self.limits.lock().unwrap();
+        match limits.iter_mut().find(|l| l.domain == limit.domain) {
+            Some(existing) => *existing = limit,
+            None => limits.push(limit),
+        }
+        persist::save(&self.path, &*limits)
+    }
+
+    pub fn remove(&self, domain: &str) -> Result<bool> {
+        let domain = domain.trim().to_lowercase();
+        let mut limits = self.limits.lock().unwrap();
+        let before = limits.len();
+        limits.retain(|l| l.domain != domain);
+        if limits.len() == before {
+            return Ok(false);
+        }
+        persist::save(&self.path, &*limits)?;
+        Ok(true)
+    }
+
+    // Settings that apply to a torrent with these announce URLs
+    pub fn matching(&self, trackers: &[String]) -> Vec<TrackerLimit> {
+        let hosts: Vec<String> = trackers.iter().filter_map(|url| credentials::tracker_domain(url)).collect();
+        self.limits
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|l| hosts.iter().any(|h| *h == l.domain || h.ends_with(&format!(".{}", l.domain))))
+            .cloned()
+            .collect()
+    }
+
+    pub fn default_category(&self, trackers: &[String]) -> Option<String> {
+        self.matching(trackers).into_iter().find_map(|l| l.default_category)
+    }
+
+    // Whether the engine may re-announce a torrent on these trackers now.
+    // Records the announce when it may.
+    pub fn may_announce(&self, trackers: &[String]) -> bool {
+        let limits: Vec<_> = self
+            .matching(trackers)
+            .into_iter()
+            .filter_map(|l| Some((l.domain, Duration::from_secs(l.min_announce_interval_secs?))))
+            .collect();
+        let mut announced = self.announced.lock().unwrap();
+        if limits.iter().any(|(domain, min)| announced.get(domain).is_some_and(|t| t.elapsed() < *min)) {
+            return false;
+        }
+        for (domain, _) in limits {
+            announced.insert(domain, Instant::now());
+        }
+        true
+    }
+}
+
+// Keeps at most `max_active` torrents running per tracker domain. Torrents
+// are let through in session order; the rest wait paused until a slot
+// frees up. Torrents paused for any other reason don't take a slot.
+pub async fn run(state: AppState) {
+    loop {
+        tokio::time::sleep(TICK).await;
+        if state.is_session_paused() {
+            continue;
+        }
+        let reason = PauseReason::TrackerLimit;
+        let mut active: HashMap<String, usize> = HashMap::new();
+        for handle in state.torrents() {
+            let id = handle.info_hash().as_string();
+            let current = state.engine_paused.lock().unwrap().get(&id).copied();
+            if current.is_some_and(|r| r != reason) {
+                continue;
+            }
+            let trackers = state.trackers.lock().unwrap().get(&id).cloned().unwrap_or_default();
+            let capped: Vec<(String, usize)> = state
+                .tracker_limits
+                .matching(&trackers)
+                .into_iter()
+                .filter_map(|l| Some((l.domain, l.max_active?)))
+                .collect();
+            let allowed = capped.iter().all(|(domain, max)| active.get(domain).copied().unwrap_or(0) < *max);
+            if allowed {
+                for (domain, _) in &capped {
+                    *active.entry(domain.clone()).or_default() += 1;
+                }
+            }
+
+            match (current, allowed) {
+                (None, false) => {
+                    if let Err(e) = state.session.pause(&handle).await {
+                        warn!("Failed to pause {}: {}", id, e);
+                        continue;
+                    }
+                    info!("Pausing {}: tracker active torrent limit reached", id);
+                    state.engine_paused.lock().unwrap().insert(id.clone(), reason);
+                    state.events.emit(EngineEvent::TorrentPaused { id, reason });
+                }
+                (Some(_), true) => {
+                    if let Err(e) = state.release(&handle, reason).await {
+                        warn!("Failed to resume {}: {}", id, e);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
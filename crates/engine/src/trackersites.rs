@@ -0,0 +1,207 @@
+use crate::{persist, resolve};
+use anyhow::{anyhow, bail, Result};
+use bridge::TrackerSite;
+use regex::RegexBuilder;
+use reqwest::{header, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const FILE: &str = "tracker_sites.json";
+const ICON_DIR: &str = "tracker_icons";
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_PAGE: usize = 512 * 1024;
+const MAX_ICON: usize = 256 * 1024;
+const REFRESH_AFTER: u64 = 7 * 24 * 60 * 60;
+const RETRY_AFTER: u64 = 24 * 60 * 60; // after a failed fetch
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct Site {
+    title: Option<String>,
+    icon_type: Option<String>, // content type of the cached icon, None = no icon
+    fetched_at: u64,
+}
+
+// Titles and favicons of tracker sites, for showing trackers by something
+// recognizable. Fetched in the background the first time a domain is asked
+// about and refreshed weekly; what was fetched last is kept in the state
+// dir, so it still shows when the site is down or we're offline.
+#[derive(Clone)]
+pub struct TrackerSites {
+    path: PathBuf,
+    icons: PathBuf,
+    sites: Arc<Mutex<HashMap<String, Site>>>,
+    fetching: Arc<Mutex<HashSet<String>>>,
+}
+
+impl TrackerSites {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self {
+            sites: Arc::new(Mutex::new(persist::load(&path))),
+            path,
+            icons: state_dir.join(ICON_DIR),
+            fetching: Arc::default(),
+        }
+    }
+
+    // What is cached for each domain. With `fetch`, missing or stale ones
+    // are fetched in the background and show up on a later call.
+    pub fn get(&self, domains: &[String], fetch: Option<&str>) -> Vec<TrackerSite> {
+        let now = persist::now();
+        let sites = self.sites.lock().unwrap();
+        let mut result = Vec::new();
+        for domain in domains.iter().filter(|d| valid_domain(d)) {
+            let site = sites.get(domain);
+            let due = site.is_none_or(|s| {
+                let wait = if s.title.is_none() && s.icon_type.is_none() { RETRY_AFTER } else { REFRESH_AFTER };
+                now.saturating_sub(s.fetched_at) > wait
+            });
+            if let Some(user_agent) = fetch.filter(|_| due) {
+                if self.fetching.lock().unwrap().insert(domain.clone()) {
+                    self.spawn_fetch(domain.clone(), user_agent.to_string());
+                }
+            }
+            result.push(TrackerSite {
+                domain: domain.clone(),
+                title: site.and_then(|s| s.title.clone()),
+                has_icon: site.is_some_and(|s| s.icon_type.is_some()),
+            });
+        }
+        result
+    }
+
+    pub fn icon(&self, domain: &str) -> Option<Icon> {
+        if !valid_domain(domain) {
+            return None;
+        }
+        let icon_type = self.sites.lock().unwrap().get(domain)?.icon_type.clone()?;
+        let bytes = std::fs::read(self.icons.join(domain)).ok()?;
+        Some((bytes, icon_type))
+    }
+
+    fn spawn_fetch(&self, domain: String, user_agent: String) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let fetched = fetch(&domain, &user_agent).await;
+            this.fetching.lock().unwrap().remove(&domain);
+            let mut site = Site { fetched_at: persist::now(), ..Default::default() };
+            match fetched {
+                Ok((title, icon)) => {
+                    info!("Fetched site details of {}", domain);
+                    site.title = title;
+                    if let Some((bytes, content_type)) = icon {
+                        let saved = std::fs::create_dir_all(&this.icons)
+                            .and_then(|_| std::fs::write(this.icons.join(&domain), bytes));
+                        match saved {
+                            Ok(()) => site.icon_type = Some(content_type),
+                            Err(e) => warn!("Failed to save icon of {}: {}", domain, e),
+                        }
+                    }
+                }
+                Err(e) => {
+                    // Keep what we had and try again later
+                    warn!("Couldn't fetch site details of {}: {:#}", domain, e);
+                    if let Some(old) = this.sites.lock().unwrap().get(&domain) {
+                        site = Site { fetched_at: site.fetched_at, ..old.clone() };
+                    }
+                }
+            }
+            let mut sites = this.sites.lock().unwrap();
+            sites.insert(domain, site);
+            if let Err(e) = persist::save(&this.path, &*sites) {
+                warn!("Failed to save tracker sites: {}", e);
+            }
+        });
+    }
+}
+
+// Host names only, so a domain is also a safe file name
+fn valid_domain(domain: &str) -> bool {
+    !domain.is_empty()
+        && !domain.starts_with('.')
+        && domain.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+type Icon = (Vec<u8>, String); // bytes, content type
+
+// The site's title and icon, from its home page and the icon it links to
+// or /favicon.ico. Links and redirects only go as far as the site's own
+// host and the hosts under it, so a page can't point the engine at another
+// server, such as one on the local network.
+async fn fetch(domain: &str, user_agent: &str) -> Result<(Option<String>, Option<Icon>)> {
+    let site = domain.to_ascii_lowercase();
+    let redirect_site = site.clone();
+    let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() < 5 && on_site(attempt.url(), &redirect_site) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    });
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(redirects)
+        .user_agent(user_agent)
+        .gzip(true)
+        .build()?;
+    let home = Url::parse(&format!("https://{}/", domain))?;
+
+    let page = match client.get(home.clone()).send().await.and_then(|r| r.error_for_status()) {
+        Ok(response) => resolve::read_body(response, MAX_PAGE).await.ok(),
+        Err(_) => None,
+    };
+    let page = page.map(|p| String::from_utf8_lossy(&p).into_owned()).unwrap_or_default();
+    let title = capture(&page, r"<title[^>]*>([^<]+)</title>").map(|t| unescape(t.trim())).filter(|t| !t.is_empty());
+
+    let linked = capture(&page, r#"<link[^>]+rel=["'](?:shortcut )?icon["'][^>]*href=["']([^"']+)["']"#)
+        .or_else(|| capture(&page, r#"<link[^>]+href=["']([^"']+)["'][^>]*rel=["'](?:shortcut )?icon["']"#))
+        .and_then(|href| home.join(&unescape(&href)).ok())
+        .filter(|u| matches!(u.scheme(), "http" | "https") && on_site(u, &site));
+    let mut icon = None;
+    for url in linked.into_iter().chain(home.join("/favicon.ico").ok()) {
+        if let Ok(found) = fetch_icon(&client, url).await {
+            icon = Some(found);
+            break;
+        }
+    }
+    if title.is_none() && icon.is_none() {
+        bail!("no title or icon");
+    }
+    Ok((title, icon))
+}
+
+async fn fetch_icon(client: &reqwest::Client, url: Url) -> Result<Icon> {
+    let response = client.get(url).send().await.and_then(|r| r.error_for_status()).map_err(|e| e.without_url())?;
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|t| t.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    // Sites answer missing icons with their HTML error page. SVG can carry
+    // scripts, which would run on the engine's origin if opened directly.
+    if !content_type.starts_with("image/") || content_type == "image/svg+xml" {
+        return Err(anyhow!("not an image ({})", content_type));
+    }
+    Ok((resolve::read_body(response, MAX_ICON).await?, content_type))
+}
+
+fn on_site(url: &Url, domain: &str) -> bool {
+    url.host_str().is_some_and(|host| {
+        let host = host.to_ascii_lowercase();
+        host == domain || host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.'))
+    })
+}
+
+fn capture(page: &str, pattern: &str) -> Option<String> {
+    let regex = RegexBuilder::new(pattern).case_insensitive(true).build().ok()?;
+    Some(regex.captures(page)?.get(1)?.as_str().to_string())
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&#39;", "'")
+}
@@ -0,0 +1,98 @@
+use bridge::{CodecInfo, TranscoderInfo};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+// FFmpeg can be installed, removed or upgraded while we run, so a detection
+// result is only trusted for this long.
+const RECHECK_AFTER: Duration = Duration::from_secs(5 * 60);
+
+// Last detection result, per configured binary so changing `ffmpeg_path`
+// takes effect on the next call.
+#[derive(Clone, Default)]
+pub struct Transcoder {
+    cached: Arc<Mutex<Option<(String, Instant, TranscoderInfo)>>>,
+}
+
+impl Transcoder {
+    pub async fn info(&self, configured: &str, refresh: bool) -> TranscoderInfo {
+        if !refresh {
+            if let Some((path, at, info)) = &*self.cached.lock().unwrap() {
+                if path == configured && at.elapsed() < RECHECK_AFTER {
+                    return info.clone();
+                }
+            }
+        }
+        let info = detect(configured).await;
+        *self.cached.lock().unwrap() = Some((configured.to_string(), Instant::now(), info.clone()));
+        info
+    }
+}
+
+// Empty path = whatever "ffmpeg" resolves to on PATH.
+async fn detect(configured: &str) -> TranscoderInfo {
+    let binary = if configured.is_empty() { "ffmpeg" } else { configured };
+    let version = match run(binary, "-version").await {
+        Ok(out) => out.lines().next().map(|l| l.trim().to_string()),
+        Err(e) => {
+            return TranscoderInfo { path: binary.to_string(), error: Some(e), ..Default::default() };
+        }
+    };
+    let codecs = run(binary, "-codecs").await.map(|out| parse_codecs(&out)).unwrap_or_default();
+    let hwaccels = run(binary, "-hwaccels").await.map(|out| parse_hwaccels(&out)).unwrap_or_default();
+    TranscoderInfo { available: true, path: binary.to_string(), version, codecs, hwaccels, error: None }
+}
+
+async fn run(binary: &str, arg: &str) -> Result<String, String> {
+    let output = Command::new(binary)
+        .args(["-hide_banner", arg])
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("{}: {}", binary, e))?;
+    if !output.status.success() {
+        return Err(format!("{} {} exited with {}", binary, arg, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// `ffmpeg -codecs` lists one codec per line after a "-------" separator:
+// " DEV.LS h264    H.264 / AVC / MPEG-4 AVC ..." where the flags are
+// decode, encode and media type.
+fn parse_codecs(out: &str) -> Vec<CodecInfo> {
+    out.lines()
+        .skip_while(|l| !l.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let flags = parts.next()?.as_bytes();
+            let name = parts.next()?;
+            if flags.len() < 3 {
+                return None;
+            }
+            let kind = match flags[2] {
+                b'V' => "video",
+                b'A' => "audio",
+                b'S' => "subtitle",
+                _ => "data",
+            };
+            Some(CodecInfo {
+                name: name.to_string(),
+                kind: kind.to_string(),
+                decode: flags[0] == b'D',
+                encode: flags[1] == b'E',
+            })
+        })
+        .collect()
+}
+
+// `ffmpeg -hwaccels` prints a header line, then one method per line.
+fn parse_hwaccels(out: &str) -> Vec<String> {
+    out.lines()
+        .skip_while(|l| !l.starts_with("Hardware acceleration methods"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,117 @@
+use crate::autosave::Dirty;
+use crate::persist;
+use librqbit::ManagedTorrent;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+const FILE: &str = "transfers.json";
+
+#[derive(Default)]
+struct Counters {
+    live: (u64, u64), // (down, up) the running torrent had reported at the last call
+    downloaded: u64,
+    uploaded: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+struct Totals {
+    downloaded: u64,
+    uploaded: u64,
+}
+
+// Per-torrent byte counts since the engine started, built from the running
+// torrent's counters so they survive pauses, which start those over.
+// Sampled, so bytes that move between the last sample and a pause are
+// missed. The same bytes also go into all-time totals, saved to the state
+// dir by the autosave task.
+#[derive(Clone)]
+pub struct Transfers {
+    path: PathBuf,
+    torrents: Arc<Mutex<HashMap<String, Counters>>>,
+    totals: Arc<Mutex<HashMap<String, Totals>>>,
+    dirty: Dirty,
+}
+
+impl Transfers {
+    pub fn load(state_dir: &Path, dirty: Dirty) -> Self {
+        let path = state_dir.join(FILE);
+        Self { totals: Arc::new(Mutex::new(persist::load(&path))), torrents: Arc::default(), path, dirty }
+    }
+
+    // Adds what the torrent moved since the last call. A counter that went
+    // down belongs to a restarted torrent.
+    pub fn record(&self, id: &str, handle: &ManagedTorrent) {
+        let mut torrents = self.torrents.lock().unwrap();
+        let counters = torrents.entry(id.to_string()).or_default();
+        let Some(live) = handle.stats().live else {
+            counters.live = (0, 0);
+            return;
+        };
+        let (down, up) = (live.snapshot.fetched_bytes, live.snapshot.uploaded_bytes);
+        let (down_before, up_before) = counters.live;
+        let downloaded = down.checked_sub(down_before).unwrap_or(down);
+        let uploaded = up.checked_sub(up_before).unwrap_or(up);
+        counters.downloaded += downloaded;
+        counters.uploaded += uploaded;
+        counters.live = (down, up);
+        if downloaded > 0 || uploaded > 0 {
+            let mut totals = self.totals.lock().unwrap();
+            let total = totals.entry(id.to_string()).or_default();
+            total.downloaded += downloaded;
+            total.uploaded += uploaded;
+            self.dirty.mark_periodic();
+        }
+    }
+
+    // (downloaded, uploaded)
+    pub fn session_totals(&self, id: &str) -> (u64, u64) {
+        self.torrents.lock().unwrap().get(id).map_or((0, 0), |c| (c.downloaded, c.uploaded))
+    }
+
+    // (downloaded, uploaded) since the torrent was added
+    pub fn totals(&self, id: &str) -> (u64, u64) {
+        self.totals.lock().unwrap().get(id).map_or((0, 0), |t| (t.downloaded, t.uploaded))
+    }
+
+    pub fn forget(&self, id: &str) {
+        self.torrents.lock().unwrap().remove(id);
+        if self.totals.lock().unwrap().remove(id).is_some() {
+            self.dirty.mark();
+        }
+    }
+
+    pub fn retain(&self, live: &[String]) {
+        self.torrents.lock().unwrap().retain(|id, _| live.contains(id));
+    }
+
+    pub fn save_if_dirty(&self) {
+        if !self.dirty.take() {
+            return;
+        }
+        if let Err(e) = persist::save(&self.path, &*self.totals.lock().unwrap()) {
+            warn!("Failed to save transfer totals: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autosave::Autosave;
+
+    #[test]
+    fn totals_outlive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(FILE), r#"{"a": {"downloaded": 100, "uploaded": 250}}"#).unwrap();
+        let transfers = Transfers::load(dir.path(), Autosave::default().dirty());
+        assert_eq!(transfers.totals("a"), (100, 250));
+        assert_eq!(transfers.session_totals("a"), (0, 0));
+
+        transfers.forget("a");
+        transfers.save_if_dirty();
+        assert_eq!(Transfers::load(dir.path(), Autosave::default().dirty()).totals("a"), (0, 0));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{persist, resolve, AppState};
+use anyhow::{anyhow, Result};
+use bridge::{EngineEvent, UpdateInfo};
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const FILE: &str = "update_check.json";
+const TICK: Duration = Duration::from_secs(60 * 60);
+const CHECK_EVERY: u64 = 24 * 60 * 60;
+const TIMEOUT: Duration = Duration::from_secs(15);
+const MAX_FEED: usize = 1024 * 1024;
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Serialize, Deserialize, Default)]
+struct Saved {
+    last: Option<UpdateInfo>,
+    notified: Option<String>, // version the UpdateAvailable event went out for
+}
+
+// The newest release as of the last check, saved to the state dir so the
+// daily check survives restarts and a version is only announced once.
+#[derive(Clone)]
+pub struct Updates {
+    path: PathBuf,
+    saved: Arc<Mutex<Saved>>,
+}
+
+impl Updates {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(FILE);
+        Self { saved: Arc::new(Mutex::new(persist::load(&path))), path }
+    }
+
+    // What the last check found, or just the running version before any
+    pub fn info(&self) -> UpdateInfo {
+        self.saved.lock().unwrap().last.clone().unwrap_or_else(|| UpdateInfo {
+            current_version: CURRENT_VERSION.to_string(),
+            latest_version: None,
+            update_available: false,
+            release_url: None,
+            notes: None,
+            checked_at: 0,
+        })
+    }
+
+    pub async fn check(&self, feed: &str, user_agent: &str) -> Result<UpdateInfo> {
+        let release = fetch(feed, user_agent).await?;
+        let info = UpdateInfo {
+            current_version: CURRENT_VERSION.to_string(),
+            update_available: newer(&release.version, CURRENT_VERSION),
+            latest_version: Some(release.version),
+            release_url: release.url,
+            notes: release.notes,
+            checked_at: persist::now(),
+        };
+        let mut saved = self.saved.lock().unwrap();
+        saved.last = Some(info.clone());
+        persist::save(&self.path, &*saved)?;
+        Ok(info)
+    }
+
+    // True the first time it's asked about `version`
+    fn first_notice(&self, version: &str) -> bool {
+        let mut saved = self.saved.lock().unwrap();
+        if saved.notified.as_deref() == Some(version) {
+            return false;
+        }
+        saved.notified = Some(version.to_string());
+        if let Err(e) = persist::save(&self.path, &*saved) {
+            warn!("Failed to save update check: {}", e);
+        }
+        true
+    }
+}
+
+struct Release {
+    version: String,
+    url: Option<String>,
+    notes: Option<String>,
+}
+
+// A GitHub "latest release" response, or a plain {version, url, notes}
+// document for self-hosted feeds.
+#[derive(Deserialize)]
+struct Feed {
+    #[serde(alias = "version")]
+    tag_name: String,
+    #[serde(alias = "url")]
+    html_url: Option<String>,
+    #[serde(alias = "notes")]
+    body: Option<String>,
+}
+
+async fn fetch(feed: &str, user_agent: &str) -> Result<Release> {
+    let client = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .timeout(TIMEOUT)
+        .build()?;
+    let response = client.get(feed).header(header::ACCEPT, "application/json").send().await?.error_for_status()?;
+    let body = resolve::read_body(response, MAX_FEED).await?;
+    let feed: Feed = serde_json::from_slice(&body)?;
+    let version = feed.tag_name.trim().trim_start_matches('v').to_string();
+    if version.is_empty() {
+        return Err(anyhow!("release feed has no version"));
+    }
+    Ok(Release { version, url: feed.html_url, notes: feed.body })
+}
+
+// Dotted numeric versions, compared part by part; a pre-release suffix
+// ("1.2.0-beta") is ignored.
+fn newer(candidate: &str, current: &str) -> bool {
+    let parts = |v: &str| -> Vec<u64> {
+        v.split(['-', '+']).next().unwrap_or("").split('.').map(|p| p.parse().unwrap_or(0)).collect()
+    };
+    let (mut a, mut b) = (parts(candidate), parts(current));
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    a > b
+}
+
+// Checks the feed once a day while `check_for_updates` is on and emits
+// UpdateAvailable the first time a newer version shows up.
+pub async fn run(state: AppState) {
+    loop {
+        let (enabled, feed) = {
+            let config = state.config.lock().unwrap();
+            (config.check_for_updates, config.update_feed_url.clone())
+        };
+        let due = persist::now().saturating_sub(state.updates.info().checked_at) >= CHECK_EVERY;
+        if enabled && due && !feed.is_empty() {
+            match state.updates.check(&feed, &state.user_agent()).await {
+                Ok(info) => {
+                    let latest = info.latest_version.clone().unwrap_or_default();
+                    if info.update_available && state.updates.first_notice(&latest) {
+                        info!("AuroraTorrent {} is available (running {})", latest, CURRENT_VERSION);
+                        state.events.emit(EngineEvent::UpdateAvailable {
+                            version: latest,
+                            url: info.release_url,
+                        });
+                    }
+                }
+                Err(e) => warn!("Update check failed: {:#}", e),
+            }
+        }
+        tokio::time::sleep(TICK).await;
+    }
+}
@@ -0,0 +1,161 @@
+use crate::files;
+use crate::jobs::Job;
+use crate::storage;
+use crate::torrent::TorrentInfo;
+use anyhow::{Context, Result};
+use librqbit::storage::TorrentStorage;
+use sha1::{Digest, Sha1};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Semaphore;
+
+// Maps the torrent's flat piece space onto its files.
+pub struct PieceLayout {
+    piece_length: u64,
+    total_size: u64,
+    file_lengths: Vec<u64>,
+    storage: Arc<dyn TorrentStorage>,
+}
+
+impl PieceLayout {
+    // The files on disk under `root`, the torrent's content root (see
+    // AppState::content_root), read the way the session reads them
+    pub fn new(root: &Path, info: &TorrentInfo) -> Result<Self> {
+        let files = storage::DiskFiles::open(root.to_path_buf(), files::disk_paths(root, info)?);
+        Ok(Self::with_storage(info, Arc::new(files)))
+    }
+
+    pub fn with_storage(info: &TorrentInfo, storage: Arc<dyn TorrentStorage>) -> Self {
+        Self {
+            piece_length: info.piece_length,
+            total_size: info.total_size,
+            file_lengths: info.files().iter().map(|f| f.len).collect(),
+            storage,
+        }
+    }
+
+    pub fn num_pieces(&self) -> u32 {
+        if self.piece_length == 0 {
+            return 0;
+        }
+        self.total_size.div_ceil(self.piece_length) as u32
+    }
+
+    pub fn piece_size(&self, index: u32) -> u64 {
+        let start = index as u64 * self.piece_length;
+        self.piece_length.min(self.total_size.saturating_sub(start))
+    }
+
+    // Storage reads block, so the piece is read on a blocking thread
+    pub async fn read_piece(&self, index: u32) -> Result<Vec<u8>> {
+        let size = self.piece_size(index);
+        let offset = index as u64 * self.piece_length;
+        let file_lengths = self.file_lengths.clone();
+        let storage = self.storage.clone();
+        tokio::task::spawn_blocking(move || read_range(&*storage, &file_lengths, offset, size))
+            .await
+            .context("piece read panicked")?
+            .with_context(|| format!("reading piece {}", index))
+    }
+}
+
+// Reads `size` bytes at `offset` in the torrent's flat byte space, across
+// as many files as they span.
+fn read_range(storage: &dyn TorrentStorage, file_lengths: &[u64], mut offset: u64, size: u64) -> Result<Vec<u8>> {
+    let mut remaining = size;
+    let mut data = Vec::with_capacity(size as usize);
+    let mut file_start = 0u64;
+    for (file, len) in file_lengths.iter().enumerate() {
+        let file_end = file_start + len;
+        if remaining > 0 && offset < file_end && *len > 0 {
+            let in_file = offset - file_start;
+            let chunk = remaining.min(len - in_file);
+
+            let mut buf = vec![0u8; chunk as usize];
+            storage.pread_exact(file, in_file, &mut buf)?;
+            data.extend_from_slice(&buf);
+
+            offset += chunk;
+            remaining -= chunk;
+        }
+        file_start = file_end;
+    }
+    if remaining > 0 {
+        anyhow::bail!("extends past the last file");
+    }
+    Ok(data)
+}
+
+pub struct PieceCheck {
+    pub index: u32,
+    pub data: Vec<u8>,
+    pub expected: [u8; 20],
+}
+
+pub fn verify_piece(data: &[u8], expected: &[u8; 20]) -> bool {
+    let digest = Sha1::digest(data);
+    digest.as_slice() == expected
+}
+
+// Caps the number of blocking threads busy hashing at once, so a large batch
+// can't crowd out other spawn_blocking users (file I/O).
+fn hash_workers() -> Arc<Semaphore> {
+    static WORKERS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+    WORKERS
+        .get_or_init(|| {
+            let n = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2);
+            Arc::new(Semaphore::new(n))
+        })
+        .clone()
+}
+
+// Hashes a batch of pieces on a dedicated blocking worker instead of the
+// async threads. Returns (index, ok) in the same order as the input.
+pub async fn verify_batch(batch: Vec<PieceCheck>) -> Vec<(u32, bool)> {
+    if batch.is_empty() {
+        return Vec::new();
+    }
+    let indices: Vec<u32> = batch.iter().map(|c| c.index).collect();
+
+    let _permit = hash_workers().acquire_owned().await;
+    let result = tokio::task::spawn_blocking(move || {
+        batch
+            .iter()
+            .map(|c| (c.index, verify_piece(&c.data, &c.expected)))
+            .collect::<Vec<_>>()
+    })
+    .await;
+
+    // A panicked worker counts as a failed check rather than a pass
+    result.unwrap_or_else(|_| indices.into_iter().map(|i| (i, false)).collect())
+}
+
+// Pieces hashed per blocking job during a full check
+const CHECK_BATCH: usize = 16;
+
+// Full check of every piece against the metainfo. Returns the indices that
+// don't match; pieces that can't be read (missing or short files) count as
+// bad. Reports progress to `job`, and returns None if it was cancelled.
+pub async fn check_all(layout: &PieceLayout, info: &TorrentInfo, job: Option<&Job>) -> Option<Vec<u32>> {
+    let num_pieces = layout.num_pieces();
+    let mut bad = Vec::new();
+    let mut batch = Vec::with_capacity(CHECK_BATCH);
+    for index in 0..num_pieces {
+        if let Some(job) = job {
+            if job.is_cancelled() {
+                return None;
+            }
+            job.progress(index as u64, num_pieces as u64);
+        }
+        match (info.piece_hash(index), layout.read_piece(index).await) {
+            (Some(expected), Ok(data)) => batch.push(PieceCheck { index, data, expected }),
+            _ => bad.push(index),
+        }
+        if batch.len() == CHECK_BATCH || index + 1 == num_pieces {
+            let checks = std::mem::replace(&mut batch, Vec::with_capacity(CHECK_BATCH));
+            bad.extend(verify_batch(checks).await.into_iter().filter(|&(_, ok)| !ok).map(|(i, _)| i));
+        }
+    }
+    bad.sort_unstable();
+    Some(bad)
+}
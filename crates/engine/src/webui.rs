@@ -0,0 +1,143 @@
+use crate::client::Client;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const INDEX: &str = "index.html";
+// Bundlers put a content hash in asset names, so these never change
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "no-cache";
+
+// Serves the folder in `web_ui_path` (the built UI, or another WebUI such
+// as VueTorrent) for every path no API route took. Paths without a file
+// extension that match nothing get index.html, so client-side routes
+// survive a reload.
+pub async fn handler(State(client): State<Client>, uri: Uri, headers: HeaderMap) -> Response {
+    let folder = client.config().web_ui_path;
+    if folder.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Ok(root) = tokio::fs::canonicalize(&folder).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(relative) = relative_path(uri.path()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let file = match resolve(&root, &relative).await {
+        Some(file) => Some(file),
+        None if relative.extension().is_none() => resolve(&root, Path::new("")).await,
+        None => None,
+    };
+    let Some(file) = file else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Ok(metadata) = tokio::fs::metadata(&file).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let modified = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_secs());
+    let etag = format!("\"{:x}-{:x}\"", modified, metadata.len());
+    let cache = if fingerprinted(&file) { IMMUTABLE } else { REVALIDATE };
+    let common = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, cache.to_string()),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+    ];
+    if headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()).is_some_and(|v| v.contains(&etag)) {
+        return (StatusCode::NOT_MODIFIED, common).into_response();
+    }
+    match tokio::fs::read(&file).await {
+        Ok(bytes) => (common, [(header::CONTENT_TYPE, content_type(&file))], bytes).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// The URL path as a relative path with only normal segments. Anything that
+// could climb out of the folder ("..", a drive or root, a backslash
+// separator) is refused rather than cleaned up.
+fn relative_path(path: &str) -> Option<PathBuf> {
+    let decoded = urlencoding::decode(path).ok()?;
+    let mut relative = PathBuf::new();
+    for segment in decoded.split('/').filter(|s| !s.is_empty()) {
+        if segment.contains('\\') || segment.contains('\0') {
+            return None;
+        }
+        let mut components = Path::new(segment).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(part)), None) => relative.push(part),
+            _ => return None,
+        }
+    }
+    Some(relative)
+}
+
+// A file inside `root`, with "dir/" meaning "dir/index.html". Symlinks
+// pointing out of the folder don't count.
+async fn resolve(root: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut path = tokio::fs::canonicalize(root.join(relative)).await.ok()?;
+    if !path.starts_with(root) {
+        return None;
+    }
+    if path.is_dir() {
+        path = tokio::fs::canonicalize(path.join(INDEX)).await.ok()?;
+    }
+    (path.starts_with(root) && path.is_file()).then_some(path)
+}
+
+// "index-4f3a2b1c.js", "app.8d9e0f1a.css": after the first separator, a
+// run of 8+ letters and digits with at least one digit.
+fn fingerprinted(path: &Path) -> bool {
+    let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else { return false };
+    stem.split(['-', '.', '_']).skip(1).any(|part| {
+        part.len() >= 8
+            && part.chars().all(|c| c.is_ascii_alphanumeric())
+            && part.chars().any(|c| c.is_ascii_digit())
+    })
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "wasm" => "application/wasm",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_path_keeps_plain_segments() {
+        assert_eq!(relative_path("assets/app.js"), Some(PathBuf::from("assets/app.js")));
+        assert_eq!(relative_path("/dir//index.html"), Some(PathBuf::from("dir/index.html")));
+        assert_eq!(relative_path("my%20file.txt"), Some(PathBuf::from("my file.txt")));
+        assert_eq!(relative_path(""), Some(PathBuf::new()));
+    }
+
+    #[test]
+    fn relative_path_refuses_escapes() {
+        for path in ["../secret", "a/../../b", "%2e%2e/secret", "a%2f..%2f..%2fb", "a\\b", "a%5cb", "a%00b", "./a"] {
+            assert_eq!(relative_path(path), None, "{}", path);
+        }
+    }
+}
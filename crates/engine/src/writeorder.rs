@@ -0,0 +1,327 @@
+use crate::config::Config;
+use crate::storage::DiskStorageFactory;
+use crate::AppState;
+use anyhow::Result;
+use librqbit::storage::{BoxStorageFactory, StorageFactory, StorageFactoryExt, TorrentStorage};
+use librqbit::{ManagedTorrentShared, TorrentMetadata};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// Nothing sits in memory longer than this, so a slow torrent still reaches
+// the disk regularly: buffers are written out on the next write past it,
+// or by run() if none comes.
+const MAX_AGE: Duration = Duration::from_secs(10);
+const TICK: Duration = Duration::from_secs(1);
+
+// Bytes held in write buffers across all torrents. Once it reaches the
+// budget, each write flushes its torrent's buffer before returning, so the
+// session waits on the disk instead of piling up more blocks in memory.
+#[derive(Clone, Default)]
+pub struct WriteBudget {
+    used: Arc<AtomicU64>,
+    buffers: Arc<Mutex<Vec<Weak<Buffer>>>>, // every live torrent's, for run()
+}
+
+impl WriteBudget {
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+}
+
+// The session's storage: the disk as is, or with `ordered_writes` set,
+// the disk behind a buffer that writes in disk order. The setting is read
+// whenever the session opens a torrent's storage, so a change applies to
+// torrents added or restarted from then on. The session's own persistence
+// only takes librqbit's filesystem storage, so the engine restores
+// torrents itself (see Client::restore); blocks still buffered when the
+// engine stopped are found missing by the check a restored torrent gets.
+pub fn storage_factory(disk: DiskStorageFactory, config: Arc<Mutex<Config>>, budget: WriteBudget) -> BoxStorageFactory {
+    SessionStorage { disk, config, budget }.boxed()
+}
+
+#[derive(Clone)]
+struct SessionStorage {
+    disk: DiskStorageFactory,
+    config: Arc<Mutex<Config>>,
+    budget: WriteBudget,
+}
+
+impl StorageFactory for SessionStorage {
+    type Storage = Box<dyn TorrentStorage>;
+
+    fn create(&self, shared: &ManagedTorrentShared, metadata: &TorrentMetadata) -> Result<Self::Storage> {
+        let mut disk = self.disk.create(shared, metadata)?;
+        let config = self.config.lock().unwrap();
+        if !config.ordered_writes {
+            return Ok(Box::new(disk));
+        }
+        let limits = Limits {
+            torrent: config.ordered_write_buffer.max(1),
+            total: config.ordered_write_budget.max(1),
+            budget: self.budget.clone(),
+        };
+        disk.init(shared, metadata)?;
+        Ok(Box::new(OrderedWrites::new(Box::new(disk), limits)))
+    }
+
+    fn clone_box(&self) -> BoxStorageFactory {
+        self.clone().boxed()
+    }
+}
+
+// Writes out buffers that have held blocks for MAX_AGE, for torrents that
+// went quiet after their last blocks arrived. Until then the session counts
+// those pieces as verified while a crash would lose them.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(TICK);
+    loop {
+        interval.tick().await;
+        let due: Vec<Arc<Buffer>> = {
+            let mut buffers = state.write_budget.buffers.lock().unwrap();
+            buffers.retain(|b| b.strong_count() > 0);
+            buffers.iter().filter_map(Weak::upgrade).filter(|b| b.is_due()).collect()
+        };
+        if due.is_empty() {
+            continue;
+        }
+        let flushed = tokio::task::spawn_blocking(move || {
+            for buffer in due {
+                let mut pending = buffer.pending.lock().unwrap();
+                if let Err(e) = buffer.flush(&mut pending) {
+                    warn!("Failed to write buffered blocks: {:#}", e);
+                }
+            }
+        });
+        if let Err(e) = flushed.await {
+            warn!("Write buffer flush failed: {}", e);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Limits {
+    torrent: u64,
+    total: u64,
+    budget: WriteBudget,
+}
+
+// Blocks arrive in whatever order peers deliver them, which on a spinning
+// disk means a seek per write. This holds them (up to the torrent's limit,
+// the global budget, or MAX_AGE) and writes them out sorted by file and
+// offset. Reads, including the session's hash checks, see buffered data as
+// if it were on disk.
+struct OrderedWrites {
+    buffer: Arc<Buffer>,
+}
+
+// Shared with run() through the budget, which only holds it weakly
+struct Buffer {
+    inner: Box<dyn TorrentStorage>,
+    limits: Limits,
+    pending: Mutex<Pending>,
+}
+
+#[derive(Default)]
+struct Pending {
+    blocks: BTreeMap<(usize, u64), Vec<u8>>, // (file, offset) -> data
+    bytes: u64,
+    oldest: Option<Instant>,
+}
+
+impl OrderedWrites {
+    fn new(inner: Box<dyn TorrentStorage>, limits: Limits) -> Self {
+        let buffer = Arc::new(Buffer { inner, limits, pending: Mutex::new(Pending::default()) });
+        buffer.limits.budget.buffers.lock().unwrap().push(Arc::downgrade(&buffer));
+        Self { buffer }
+    }
+}
+
+impl Buffer {
+    fn is_due(&self) -> bool {
+        self.pending.lock().unwrap().oldest.is_some_and(|t| t.elapsed() >= MAX_AGE)
+    }
+
+    // Blocks that can't be written stay buffered, with the ones after them,
+    // for the next flush to try again
+    fn flush(&self, pending: &mut Pending) -> Result<()> {
+        let mut blocks = std::mem::take(&mut pending.blocks).into_iter();
+        while let Some(((file_id, offset), data)) = blocks.next() {
+            if let Err(e) = self.inner.pwrite_all(file_id, offset, &data) {
+                pending.blocks.insert((file_id, offset), data);
+                pending.blocks.extend(blocks);
+                return Err(e);
+            }
+            pending.bytes -= data.len() as u64;
+            self.limits.budget.used.fetch_sub(data.len() as u64, Ordering::Relaxed);
+        }
+        pending.oldest = None;
+        Ok(())
+    }
+}
+
+impl TorrentStorage for OrderedWrites {
+    // The storage underneath is initialized before it's wrapped
+    fn init(&mut self, _shared: &ManagedTorrentShared, _metadata: &TorrentMetadata) -> Result<()> {
+        Ok(())
+    }
+
+    fn pread_exact(&self, file_id: usize, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let pending = self.buffer.pending.lock().unwrap();
+        self.buffer.inner.pread_exact(file_id, offset, buf)?;
+        let end = offset + buf.len() as u64;
+        for (&(_, start), data) in pending.blocks.range((file_id, 0)..(file_id, end)) {
+            let block_end = start + data.len() as u64;
+            if block_end <= offset {
+                continue;
+            }
+            let (from, to) = (start.max(offset), block_end.min(end));
+            buf[(from - offset) as usize..(to - offset) as usize]
+                .copy_from_slice(&data[(from - start) as usize..(to - start) as usize]);
+        }
+        Ok(())
+    }
+
+    fn pwrite_all(&self, file_id: usize, offset: u64, buf: &[u8]) -> Result<()> {
+        let Buffer { limits, pending, .. } = &*self.buffer;
+        let mut pending = pending.lock().unwrap();
+        if let Some(replaced) = pending.blocks.insert((file_id, offset), buf.to_vec()) {
+            pending.bytes -= replaced.len() as u64;
+            limits.budget.used.fetch_sub(replaced.len() as u64, Ordering::Relaxed);
+        }
+        pending.bytes += buf.len() as u64;
+        let used = limits.budget.used.fetch_add(buf.len() as u64, Ordering::Relaxed) + buf.len() as u64;
+        let oldest = *pending.oldest.get_or_insert_with(Instant::now);
+        if pending.bytes >= limits.torrent || used >= limits.total || oldest.elapsed() >= MAX_AGE {
+            self.buffer.flush(&mut pending)?;
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, file_id: usize, filename: &Path) -> Result<()> {
+        let mut pending = self.buffer.pending.lock().unwrap();
+        let dropped: Vec<_> = pending.blocks.range((file_id, 0)..=(file_id, u64::MAX)).map(|(&k, _)| k).collect();
+        for key in dropped {
+            if let Some(data) = pending.blocks.remove(&key) {
+                pending.bytes -= data.len() as u64;
+                self.buffer.limits.budget.used.fetch_sub(data.len() as u64, Ordering::Relaxed);
+            }
+        }
+        self.buffer.inner.remove_file(file_id, filename)
+    }
+
+    fn remove_directory_if_empty(&self, path: &Path) -> Result<()> {
+        self.buffer.inner.remove_directory_if_empty(path)
+    }
+
+    fn ensure_file_length(&self, file_id: usize, length: u64) -> Result<()> {
+        self.buffer.inner.ensure_file_length(file_id, length)
+    }
+
+    fn take(&self) -> Result<Box<dyn TorrentStorage>> {
+        self.buffer.flush(&mut self.buffer.pending.lock().unwrap())?;
+        Ok(Box::new(OrderedWrites::new(self.buffer.inner.take()?, self.buffer.limits.clone())))
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(self.pending.get_mut().unwrap());
+        if let Err(e) = self.flush(&mut pending) {
+            warn!("Failed to write buffered blocks: {}", e);
+        }
+        // What couldn't be written is gone with the buffer
+        self.limits.budget.used.fetch_sub(pending.bytes, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Files in memory; writes to `broken` fail
+    struct Files {
+        data: Mutex<BTreeMap<(usize, u64), Vec<u8>>>,
+        broken: usize,
+    }
+
+    impl TorrentStorage for Files {
+        fn init(&mut self, _shared: &ManagedTorrentShared, _metadata: &TorrentMetadata) -> Result<()> {
+            Ok(())
+        }
+
+        fn pread_exact(&self, _file_id: usize, _offset: u64, buf: &mut [u8]) -> Result<()> {
+            buf.fill(0);
+            Ok(())
+        }
+
+        fn pwrite_all(&self, file_id: usize, offset: u64, buf: &[u8]) -> Result<()> {
+            if file_id == self.broken {
+                anyhow::bail!("disk full");
+            }
+            self.data.lock().unwrap().insert((file_id, offset), buf.to_vec());
+            Ok(())
+        }
+
+        fn remove_file(&self, _file_id: usize, _filename: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        fn remove_directory_if_empty(&self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        fn ensure_file_length(&self, _file_id: usize, _length: u64) -> Result<()> {
+            Ok(())
+        }
+
+        fn take(&self) -> Result<Box<dyn TorrentStorage>> {
+            unimplemented!()
+        }
+    }
+
+    fn buffered(broken: usize) -> (OrderedWrites, WriteBudget) {
+        let budget = WriteBudget::default();
+        let limits = Limits { torrent: 1 << 20, total: 1 << 20, budget: budget.clone() };
+        let files = Files { data: Mutex::new(BTreeMap::new()), broken };
+        (OrderedWrites::new(Box::new(files), limits), budget)
+    }
+
+    #[test]
+    fn reads_see_buffered_blocks() {
+        let (storage, budget) = buffered(usize::MAX);
+        storage.pwrite_all(0, 2, b"abc").unwrap();
+        let mut buf = [1u8; 6];
+        storage.pread_exact(0, 0, &mut buf).unwrap();
+        assert_eq!(&buf, b"\0\0abc\0");
+        assert_eq!(budget.used(), 3);
+    }
+
+    #[test]
+    fn failed_writes_stay_buffered() {
+        let (storage, budget) = buffered(1);
+        storage.pwrite_all(0, 0, b"ab").unwrap();
+        storage.pwrite_all(1, 0, b"cd").unwrap();
+        storage.pwrite_all(1, 2, b"ef").unwrap();
+        let buffer = &storage.buffer;
+        assert!(buffer.flush(&mut buffer.pending.lock().unwrap()).is_err());
+        let pending = buffer.pending.lock().unwrap();
+        assert_eq!(pending.blocks.keys().copied().collect::<Vec<_>>(), vec![(1, 0), (1, 2)]);
+        assert_eq!(pending.bytes, 4);
+        assert!(pending.oldest.is_some());
+        assert_eq!(budget.used(), 4);
+    }
+
+    #[test]
+    fn due_after_max_age() {
+        let (storage, _) = buffered(usize::MAX);
+        assert!(!storage.buffer.is_due());
+        storage.pwrite_all(0, 0, b"ab").unwrap();
+        assert!(!storage.buffer.is_due());
+        storage.buffer.pending.lock().unwrap().oldest = Instant::now().checked_sub(MAX_AGE);
+        assert!(storage.buffer.is_due());
+    }
+}
@@ -0,0 +1,109 @@
+// End-to-end transfers between in-memory sessions (see src/harness.rs)
+
+use aurora_engine::harness::{write_torrent, Conditions, Swarm};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const PIECE_SIZE: u64 = 16 * 1024;
+
+// Not all zeroes, so a piece left unwritten can't pass its hash
+fn pattern(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect()
+}
+
+fn torrent(dir: &tempfile::TempDir, content: &[Vec<u8>]) -> PathBuf {
+    let path = dir.path().join("shared.torrent");
+    write_torrent(&path, "shared", content, PIECE_SIZE).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn seeder_to_leecher() {
+    let swarm = Swarm::start(2).await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let content = vec![pattern(100_000, 1), pattern(70_001, 2)];
+    let torrent = torrent(&dir, &content);
+
+    let handles = swarm.share(&torrent, &[(0, content.clone())]).await.unwrap();
+    Swarm::wait_finished(&handles, Duration::from_secs(30)).await.unwrap();
+
+    assert_eq!(swarm.peers[1].storage.get("shared"), Some(content));
+    assert!(swarm.peers[1].bad_pieces(&handles[1]).await.is_empty());
+}
+
+#[tokio::test]
+async fn max_speed_throttles_transfers() {
+    let swarm = Swarm::start(2).await.unwrap();
+    swarm.peers[0].set_conditions(Conditions { max_speed: 64 * 1024, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let content = vec![pattern(256 * 1024, 3)];
+    let torrent = torrent(&dir, &content);
+
+    let started = Instant::now();
+    let handles = swarm.share(&torrent, &[(0, content.clone())]).await.unwrap();
+    Swarm::wait_finished(&handles, Duration::from_secs(60)).await.unwrap();
+
+    // 256 KiB at 64 KiB/s takes 4 s; the proxy paces from the first byte
+    assert!(started.elapsed() >= Duration::from_secs(3), "{:?}", started.elapsed());
+    assert_eq!(swarm.peers[1].storage.get("shared"), Some(content));
+}
+
+// How long sharing `content` from peer 0 to peer 1 takes with `conditions`
+// on peer 1
+async fn transfer_time(content: &[Vec<u8>], conditions: Conditions) -> Duration {
+    let swarm = Swarm::start(2).await.unwrap();
+    swarm.peers[1].set_conditions(conditions);
+    let dir = tempfile::tempdir().unwrap();
+    let torrent = torrent(&dir, content);
+
+    let started = Instant::now();
+    let handles = swarm.share(&torrent, &[(0, content.to_vec())]).await.unwrap();
+    Swarm::wait_finished(&handles, Duration::from_secs(30)).await.unwrap();
+    assert_eq!(swarm.peers[1].storage.get("shared").as_deref(), Some(content));
+    started.elapsed()
+}
+
+#[tokio::test]
+async fn latency_delays_blocks() {
+    let content = vec![pattern(3 * PIECE_SIZE as usize, 4)];
+    let latency = Duration::from_millis(300);
+    let baseline = transfer_time(&content, Conditions::default()).await;
+    let delayed = transfer_time(&content, Conditions { latency, ..Default::default() }).await;
+
+    // At least the handshake and one request go there and back
+    assert!(delayed >= baseline + 2 * latency, "{:?} vs {:?}", delayed, baseline);
+}
+
+#[tokio::test]
+async fn refused_connections_hold_transfers_until_allowed() {
+    let swarm = Swarm::start(2).await.unwrap();
+    swarm.peers[1].set_conditions(Conditions { refuse_connections: true, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let content = vec![pattern(50_000, 5)];
+    let torrent = torrent(&dir, &content);
+
+    let handles = swarm.share(&torrent, &[(0, content.clone())]).await.unwrap();
+    assert!(Swarm::wait_finished(&handles, Duration::from_secs(3)).await.is_err());
+    assert_ne!(swarm.peers[1].storage.get("shared"), Some(content.clone()));
+
+    swarm.peers[1].set_conditions(Conditions::default());
+    Swarm::wait_finished(&handles, Duration::from_secs(60)).await.unwrap();
+    assert_eq!(swarm.peers[1].storage.get("shared"), Some(content));
+}
+
+#[tokio::test]
+async fn dropped_connections_come_back() {
+    let swarm = Swarm::start(2).await.unwrap();
+    swarm.peers[0].set_conditions(Conditions { max_speed: 64 * 1024, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let content = vec![pattern(256 * 1024, 6)];
+    let torrent = torrent(&dir, &content);
+
+    let handles = swarm.share(&torrent, &[(0, content.clone())]).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    swarm.peers[0].drop_connections();
+    swarm.peers[0].set_conditions(Conditions::default());
+
+    Swarm::wait_finished(&handles, Duration::from_secs(60)).await.unwrap();
+    assert_eq!(swarm.peers[1].storage.get("shared"), Some(content));
+}